@@ -0,0 +1,169 @@
+// Criterion suite: AEAD, key exchange, framing and the full per-frame path.
+//
+//   cargo bench --bench suite -- --save-baseline pi5
+//   cargo bench --bench suite -- --baseline pi5          (compare against it)
+//   Group C's bench-compare reads the same baselines (see src/bin/README.md)
+//
+// Baselines are stored by criterion under target/criterion/<bench>/<baseline>/.
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use std::hint::black_box;
+use std::time::Duration;
+
+use bytes::Bytes;
+use rpi_secure_stream::crypto::{ecdh_derive, generate_ephemeral, generate_rsa_keypair, rsa_unwrap, rsa_wrap};
+use rpi_secure_stream::net::{Aes128GcmStream, WireMsg, FLAG_FRAME};
+
+/// Payload sizes: small control messages up to a 720p I420 frame
+const PAYLOAD_SIZES: &[(&str, usize)] = &[
+    ("1KiB", 1024),
+    ("16KiB", 16 * 1024),
+    ("64KiB", 64 * 1024),
+    ("480p-I420", 640 * 480 * 3 / 2),
+    ("720p-I420", 1280 * 720 * 3 / 2),
+];
+
+const FRAME_SIZES: &[(&str, usize)] = &[
+    ("480p-I420", 640 * 480 * 3 / 2),
+    ("720p-I420", 1280 * 720 * 3 / 2),
+];
+
+const RSA_BITS: &[usize] = &[2048, 3072];
+const CONTEXT: &[u8] = b"ECE4301-midterm-2025";
+
+fn random_bytes(len: usize) -> Vec<u8> {
+    let mut v = vec![0u8; len];
+    OsRng.fill_bytes(&mut v);
+    v
+}
+
+fn aead_stream() -> Aes128GcmStream {
+    let mut key = [0u8; 16];
+    let mut nonce_base = [0u8; 12];
+    OsRng.fill_bytes(&mut key);
+    OsRng.fill_bytes(&mut nonce_base);
+    Aes128GcmStream::new(key, nonce_base).expect("aes-gcm init")
+}
+
+fn bench_aead(c: &mut Criterion) {
+    let aead = aead_stream();
+    let mut group = c.benchmark_group("aead/aes128gcm");
+
+    for &(label, size) in PAYLOAD_SIZES {
+        let pt = random_bytes(size);
+        let ct = aead.encrypt_frame(7, &pt, size as u32).unwrap();
+        group.throughput(Throughput::Bytes(size as u64));
+
+        group.bench_with_input(BenchmarkId::new("encrypt", label), &pt, |b, pt| {
+            let mut seq = 0u64;
+            b.iter(|| {
+                seq += 1;
+                black_box(aead.encrypt_frame(seq, pt, pt.len() as u32).unwrap())
+            })
+        });
+
+        group.bench_with_input(BenchmarkId::new("decrypt", label), &ct, |b, ct| {
+            b.iter(|| black_box(aead.decrypt_frame(7, ct, size as u32).unwrap()))
+        });
+    }
+    group.finish();
+}
+
+fn bench_kex(c: &mut Criterion) {
+    let mut group = c.benchmark_group("kex/ecdh-p256");
+    let salt = random_bytes(32);
+
+    group.bench_function("keygen", |b| b.iter(|| black_box(generate_ephemeral())));
+    group.bench_function("derive", |b| {
+        b.iter_batched(
+            || {
+                let (secret, _) = generate_ephemeral();
+                let (_, peer_pub) = generate_ephemeral();
+                (secret, peer_pub)
+            },
+            |(secret, peer_pub)| black_box(ecdh_derive(&secret, &peer_pub, &salt, CONTEXT).unwrap()),
+            BatchSize::SmallInput,
+        )
+    });
+    group.finish();
+
+    let mut group = c.benchmark_group("kex/rsa-oaep");
+    // RSA keygen takes hundreds of ms on a Pi; keep the sample count at the minimum
+    group.sample_size(10).measurement_time(Duration::from_secs(20));
+
+    for &bits in RSA_BITS {
+        group.bench_with_input(BenchmarkId::new("keygen", bits), &bits, |b, &bits| {
+            b.iter(|| black_box(generate_rsa_keypair(bits).unwrap()))
+        });
+
+        let (sk, pk) = generate_rsa_keypair(bits).unwrap();
+        let (wrapped, _) = rsa_wrap(&pk).unwrap();
+        group.bench_with_input(BenchmarkId::new("wrap", bits), &pk, |b, pk| {
+            b.iter(|| black_box(rsa_wrap(pk).unwrap()))
+        });
+        group.bench_with_input(BenchmarkId::new("unwrap", bits), &wrapped, |b, wrapped| {
+            b.iter(|| black_box(rsa_unwrap(&sk, wrapped).unwrap()))
+        });
+    }
+    group.finish();
+}
+
+fn bench_framing(c: &mut Criterion) {
+    let mut group = c.benchmark_group("framing/wiremsg");
+
+    for &(label, size) in FRAME_SIZES {
+        let msg = WireMsg {
+            flags: FLAG_FRAME,
+            ts_ns: 1,
+            seq: 42,
+            pt_len: size as u32,
+            payload: Bytes::from(random_bytes(size + 16)),
+        };
+        let encoded = msg.encode();
+        group.throughput(Throughput::Bytes(encoded.len() as u64));
+
+        group.bench_with_input(BenchmarkId::new("encode", label), &msg, |b, msg| {
+            b.iter(|| black_box(msg.encode()))
+        });
+        group.bench_with_input(BenchmarkId::new("decode", label), &encoded, |b, encoded| {
+            b.iter(|| black_box(WireMsg::decode(&encoded[4..]).unwrap()))
+        });
+    }
+    group.finish();
+}
+
+/// Everything the sender/receiver do per frame, minus the socket
+fn bench_pipeline(c: &mut Criterion) {
+    let aead = aead_stream();
+    let mut group = c.benchmark_group("pipeline");
+
+    for &(label, size) in FRAME_SIZES {
+        let frame = random_bytes(size);
+        group.throughput(Throughput::Bytes(size as u64));
+
+        group.bench_with_input(BenchmarkId::new("encrypt-frame", label), &frame, |b, frame| {
+            let mut seq = 0u64;
+            b.iter(|| {
+                seq += 1;
+                let ct = aead.encrypt_frame(seq, frame, frame.len() as u32).unwrap();
+                let msg = WireMsg { flags: FLAG_FRAME, ts_ns: seq, seq, pt_len: frame.len() as u32, payload: Bytes::from(ct) };
+                black_box(msg.encode())
+            })
+        });
+
+        let ct = aead.encrypt_frame(1, &frame, size as u32).unwrap();
+        let wire = WireMsg { flags: FLAG_FRAME, ts_ns: 1, seq: 1, pt_len: size as u32, payload: Bytes::from(ct) }.encode();
+        group.bench_with_input(BenchmarkId::new("decrypt-frame", label), &wire, |b, wire| {
+            b.iter(|| {
+                let msg = WireMsg::decode(&wire[4..]).unwrap();
+                black_box(aead.decrypt_frame(msg.seq, &msg.payload, msg.pt_len).unwrap())
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_aead, bench_kex, bench_framing, bench_pipeline);
+criterion_main!(benches);
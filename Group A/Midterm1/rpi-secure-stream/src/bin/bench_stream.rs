@@ -35,30 +35,14 @@ struct Args {
 fn mib(bytes: usize) -> f64 { bytes as f64 / (1024.0 * 1024.0) }
 
 fn main() -> Result<()> {
+    let args = Args::parse();
+
     // Init GStreamer
     video::gst_init_once()?;
 
-    // Build the camera -> I420 -> appsink pipeline
+    // Build the camera -> I420 -> appsink pipeline at the requested caps
     let (pipeline, sink): (gst::Pipeline, AppSink) =
-        video::make_sender_pipeline(
-            &std::env::args().find(|a| a.starts_with("--device=")).map(|s| s.replace("--device=","")).unwrap_or("/dev/video0".into()),
-            // We pass the parsed args below; this string is unused by the helper once we set it properly.
-            640, 480, 15
-        )?;
-
-    // The helper already set caps to I420,width,height,fps — but we want CLI values.
-    // Recreate with the real device/caps to be explicit.
-    let pipeline_desc = format!(
-        "v4l2src device={} ! videoconvert ! \
-         video/x-raw,format=I420,width={},height={},framerate={}/1 ! \
-         queue max-size-buffers=4 ! \
-         appsink name=sink emit-signals=true sync=false drop=true",
-        // we use args passed via clap in a second; this block is kept simple
-        "",
-        0, 0, 1
-    );
-    // Instead of rebuilding, set the pipeline we already got to Playing and just pull samples.
-    let _ = pipeline_desc; // (kept to show prior approach; not used now)
+        video::make_sender_pipeline(&args.device, args.width, args.height, args.fps)?;
 
     // Make an AEAD stream
     let mut key = [0u8; 16];
@@ -71,8 +55,6 @@ fn main() -> Result<()> {
     pipeline.set_state(gst::State::Playing)?;
 
     // Run for the requested duration
-    // NOTE: we use clap-parsed args; no hard-coded 60s
-    let args = Args::parse();
     let t_end = Instant::now() + Duration::from_secs(args.seconds);
 
     let mut frames = 0usize;
@@ -53,7 +53,16 @@ impl WireMsg {
         let mut body = vec![0u8; len];
         s.read_exact(&mut body).await?;
 
-        let mut rd = &body[..];
+        Self::decode(&body)
+    }
+
+    /// Parse a message body (everything after the u32 length prefix)
+    pub fn decode(body: &[u8]) -> Result<WireMsg> {
+        if body.len() < (1 + 8 + 8 + 4) {
+            return Err(anyhow!("short frame: {}", body.len()));
+        }
+
+        let mut rd = body;
         let flags = rd.get_u8();
         let ts_ns = rd.get_u64();
         let seq    = rd.get_u64();
@@ -0,0 +1,189 @@
+// Criterion benchmark suite
+// crates/bench/benches/suite.rs
+//
+//   cargo bench -p bench --bench suite -- --save-baseline pi5
+//   cargo run --release -p bench --bin bench-compare -- --base pi4 --new pi5
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use crypto::{ecdh_kex, rsa_kex, AsconVariant, CipherSuite, FrameCipher, FrameHeader, SessionKeyMaterial, HEADER_SIZE};
+use rand::{rngs::OsRng, RngCore};
+use std::hint::black_box;
+use std::time::Duration;
+
+/// Payload sizes from a control message up to a 720p I420 frame
+const PAYLOAD_SIZES: &[(&str, usize)] = &[
+    ("1KiB", 1024),
+    ("16KiB", 16 * 1024),
+    ("64KiB", 64 * 1024),
+    ("480p", 640 * 480 * 3 / 2),
+    ("720p", 1280 * 720 * 3 / 2),
+];
+
+const SUITES: &[CipherSuite] = &[
+    CipherSuite::Aes128Gcm,
+    CipherSuite::Ascon(AsconVariant::Ascon128),
+    CipherSuite::Ascon(AsconVariant::Ascon128a),
+];
+
+const RSA_BITS: &[usize] = &[2048, 3072];
+const CONTEXT: &[u8] = b"ECE4301-midterm-2025";
+
+fn random_bytes(len: usize) -> Vec<u8> {
+    let mut v = vec![0u8; len];
+    OsRng.fill_bytes(&mut v);
+    v
+}
+
+// Ciphers refuse to encrypt past MAX_COUNTER frames, which long criterion
+// runs on small payloads can reach; callers swap in a new key when asked to rekey
+fn fresh_cipher(suite: CipherSuite) -> FrameCipher {
+    FrameCipher::new(suite, SessionKeyMaterial::generate_random(), None)
+}
+
+fn bench_aead(c: &mut Criterion) {
+    for &suite in SUITES {
+        let mut group = c.benchmark_group(format!("aead/{}", suite));
+        
+        for &(label, size) in PAYLOAD_SIZES {
+            let pt = random_bytes(size);
+            group.throughput(Throughput::Bytes(size as u64));
+            
+            group.bench_with_input(BenchmarkId::new("encrypt", label), &pt, |b, pt| {
+                let mut cipher = fresh_cipher(suite);
+                b.iter(|| {
+                    if cipher.should_rekey() {
+                        cipher = fresh_cipher(suite);
+                    }
+                    black_box(cipher.encrypt(pt, b"aad").unwrap())
+                })
+            });
+            
+            let cipher = fresh_cipher(suite);
+            let ct = cipher.encrypt(&pt, b"aad").unwrap();
+            group.bench_with_input(BenchmarkId::new("decrypt", label), &ct, |b, ct| {
+                b.iter(|| black_box(cipher.decrypt(ct, b"aad", 0).unwrap()))
+            });
+        }
+        group.finish();
+    }
+}
+
+fn bench_kex(c: &mut Criterion) {
+    let mut group = c.benchmark_group("kex/ecdh-p256");
+    
+    group.bench_function("keygen", |b| b.iter(|| black_box(ecdh_kex::EcdhKeyPair::generate())));
+    // The transcript-bound derivation the handshake runs
+    group.bench_function("derive", |b| {
+        b.iter_batched(
+            || (ecdh_kex::EcdhKeyPair::generate(), ecdh_kex::EcdhKeyPair::generate().public_key_bytes()),
+            |(keypair, peer)| {
+                black_box(keypair.derive_with_transcript(&peer, ecdh_kex::Role::Initiator, "ECDH-P256", "AES-128-GCM", CONTEXT).unwrap())
+            },
+            BatchSize::SmallInput,
+        )
+    });
+    group.finish();
+    
+    let mut group = c.benchmark_group("kex/rsa-oaep");
+    // Keygen takes hundreds of ms on a Pi; keep the sample count at criterion's minimum
+    group.sample_size(10).measurement_time(Duration::from_secs(20));
+    
+    let session_key = random_bytes(24);
+    for &bits in RSA_BITS {
+        group.bench_with_input(BenchmarkId::new("keygen", bits), &bits, |b, &bits| {
+            b.iter(|| black_box(rsa_kex::RsaKeyPair::generate(bits).unwrap()))
+        });
+        
+        let keypair = rsa_kex::RsaKeyPair::generate(bits).unwrap();
+        let wrapped = keypair.wrap_session_key(&session_key).unwrap();
+        group.bench_function(BenchmarkId::new("wrap", bits), |b| {
+            b.iter(|| black_box(keypair.wrap_session_key(&session_key).unwrap()))
+        });
+        group.bench_function(BenchmarkId::new("unwrap", bits), |b| {
+            b.iter(|| black_box(keypair.unwrap_session_key(&wrapped).unwrap()))
+        });
+    }
+    group.finish();
+}
+
+fn bench_framing(c: &mut Criterion) {
+    let mut group = c.benchmark_group("framing/header");
+    
+    let header = FrameHeader {
+        flags: 0,
+        timestamp_us: 1_700_000_000_000_000,
+        counter: 42,
+        nonce_counter: 42,
+        payload_len: (1280 * 720 * 3 / 2) as u32,
+    };
+    let encoded = header.encode();
+    group.throughput(Throughput::Bytes(HEADER_SIZE as u64));
+    
+    group.bench_function("encode", |b| b.iter(|| black_box(black_box(&header).encode())));
+    group.bench_function("decode", |b| {
+        b.iter(|| black_box(FrameHeader::decode(black_box(&encoded)).unwrap()))
+    });
+    group.finish();
+}
+
+fn frame_header(cipher: &FrameCipher, counter: u32, len: usize) -> FrameHeader {
+    FrameHeader {
+        flags: 0,
+        timestamp_us: counter as u64,
+        counter,
+        nonce_counter: cipher.get_counter(),
+        payload_len: len as u32,
+    }
+}
+
+/// Everything the sender/receiver do per frame, minus the socket
+fn bench_pipeline(c: &mut Criterion) {
+    let mut group = c.benchmark_group("pipeline");
+    
+    for &suite in SUITES {
+        for &(label, size) in &PAYLOAD_SIZES[3..] {
+            let frame = random_bytes(size);
+            group.throughput(Throughput::Bytes(size as u64));
+            
+            // Header build + encrypt + header/ciphertext on the wire
+            let id = BenchmarkId::new(format!("encrypt-frame/{}", suite), label);
+            group.bench_with_input(id, &frame, |b, frame| {
+                let mut cipher = fresh_cipher(suite);
+                let mut counter = 0u32;
+                let mut wire = Vec::with_capacity(HEADER_SIZE + size + 16);
+                b.iter(|| {
+                    if cipher.should_rekey() {
+                        cipher = fresh_cipher(suite);
+                    }
+                    counter = counter.wrapping_add(1);
+                    
+                    let header = frame_header(&cipher, counter, frame.len()).encode();
+                    let ct = cipher.encrypt(frame, &header).unwrap();
+                    
+                    wire.clear();
+                    wire.extend_from_slice(&header);
+                    wire.extend_from_slice(&ct);
+                    black_box(&wire);
+                })
+            });
+            
+            // Header decode + decrypt, as the receiver does per frame
+            let cipher = fresh_cipher(suite);
+            let header = frame_header(&cipher, 1, size).encode();
+            let mut wire = header.to_vec();
+            wire.extend_from_slice(&cipher.encrypt(&frame, &header).unwrap());
+            let id = BenchmarkId::new(format!("decrypt-frame/{}", suite), label);
+            group.bench_with_input(id, &wire, |b, wire| {
+                b.iter(|| {
+                    let (header_buf, ct) = wire.split_at(HEADER_SIZE);
+                    let header = FrameHeader::decode(header_buf).unwrap();
+                    black_box(cipher.decrypt(ct, header_buf, header.nonce_counter).unwrap())
+                })
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_aead, bench_kex, bench_framing, bench_pipeline);
+criterion_main!(benches);
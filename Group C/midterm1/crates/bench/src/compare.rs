@@ -0,0 +1,252 @@
+// Baseline comparison for the criterion suite
+// crates/bench/src/compare.rs
+//
+// Reads target/criterion/**/<baseline>/estimates.json for two saved baselines
+// and writes a JSON regression report. Exit status: 0 = no regressions,
+// 2 = at least one benchmark regressed, 1 = error.
+
+use anyhow::{anyhow, Context, Result};
+use clap::Parser;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+const EXIT_REGRESSION: i32 = 2;
+
+#[derive(Parser, Debug)]
+#[command(about = "Compare two saved criterion baselines and flag regressions")]
+struct Args {
+    /// Reference baseline (e.g. pi4, or the previous commit)
+    #[arg(long)]
+    base: String,
+    
+    /// Baseline under test (e.g. pi5, or this commit)
+    #[arg(long)]
+    new: String,
+    
+    /// Slowdown in percent of mean time that counts as a regression
+    #[arg(long, default_value = "5.0")]
+    threshold: f64,
+    
+    /// Criterion output directory
+    #[arg(long, default_value = "target/criterion")]
+    criterion_dir: PathBuf,
+    
+    /// Write the report here instead of stdout
+    #[arg(long)]
+    out: Option<PathBuf>,
+}
+
+/// Mean time per iteration in ns with its confidence interval
+#[derive(Debug, Clone, Copy)]
+struct Estimate {
+    mean: f64,
+    lower: f64,
+    upper: f64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Status {
+    Regressed,
+    Improved,
+    Unchanged,
+    Missing,
+}
+
+#[derive(Debug, Serialize)]
+struct Row {
+    id: String,
+    status: Status,
+    base_ns: Option<f64>,
+    new_ns: Option<f64>,
+    change_pct: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+struct Report {
+    base: String,
+    new: String,
+    threshold_pct: f64,
+    regressions: usize,
+    benchmarks: Vec<Row>,
+}
+
+fn read_json(path: &Path) -> Result<serde_json::Value> {
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    serde_json::from_str(&text).with_context(|| format!("Failed to parse {}", path.display()))
+}
+
+fn read_estimate(dir: &Path) -> Result<Estimate> {
+    let estimates = read_json(&dir.join("estimates.json"))?;
+    let mean = &estimates["mean"];
+    let field = |v: &serde_json::Value| {
+        v.as_f64().ok_or_else(|| anyhow!("Malformed estimates.json in {}", dir.display()))
+    };
+    
+    Ok(Estimate {
+        mean: field(&mean["point_estimate"])?,
+        lower: field(&mean["confidence_interval"]["lower_bound"])?,
+        upper: field(&mean["confidence_interval"]["upper_bound"])?,
+    })
+}
+
+/// Every `<benchmark>/<baseline>/benchmark.json` in the tree is one saved run
+fn collect(dir: &Path, out: &mut BTreeMap<String, BTreeMap<String, Estimate>>) -> Result<()> {
+    for entry in std::fs::read_dir(dir).with_context(|| format!("Failed to list {}", dir.display()))? {
+        let path = entry?.path();
+        if !path.is_dir() || path.file_name().is_some_and(|n| n == "report") {
+            continue;
+        }
+        
+        let meta = path.join("benchmark.json");
+        if meta.exists() && path.join("estimates.json").exists() {
+            let id = read_json(&meta)?["full_id"]
+                .as_str()
+                .map(str::to_owned)
+                .ok_or_else(|| anyhow!("No full_id in {}", meta.display()))?;
+            let baseline = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+            out.entry(id).or_default().insert(baseline, read_estimate(&path)?);
+        } else {
+            collect(&path, out)?;
+        }
+    }
+    Ok(())
+}
+
+fn compare(args: &Args, all: &BTreeMap<String, BTreeMap<String, Estimate>>) -> Report {
+    let mut rows = Vec::new();
+    let mut regressions = 0;
+    
+    for (id, baselines) in all {
+        let (base, new) = match (baselines.get(&args.base), baselines.get(&args.new)) {
+            (Some(base), Some(new)) => (*base, *new),
+            (None, None) => continue,
+            (base, new) => {
+                rows.push(Row {
+                    id: id.clone(),
+                    status: Status::Missing,
+                    base_ns: base.map(|e| e.mean),
+                    new_ns: new.map(|e| e.mean),
+                    change_pct: None,
+                });
+                continue;
+            }
+        };
+        
+        // A change only counts when the confidence intervals are disjoint, so
+        // run-to-run noise on a loaded Pi does not fail the comparison
+        let change_pct = (new.mean / base.mean - 1.0) * 100.0;
+        let status = if change_pct > args.threshold && new.lower > base.upper {
+            regressions += 1;
+            Status::Regressed
+        } else if change_pct < -args.threshold && new.upper < base.lower {
+            Status::Improved
+        } else {
+            Status::Unchanged
+        };
+        
+        eprintln!(
+            "{:<10} {:<50} {:>12.1} ns -> {:>12.1} ns  ({:+.2}%)",
+            format!("{:?}", status).to_lowercase(), id, base.mean, new.mean, change_pct
+        );
+        rows.push(Row {
+            id: id.clone(),
+            status,
+            base_ns: Some(base.mean),
+            new_ns: Some(new.mean),
+            change_pct: Some(change_pct),
+        });
+    }
+    
+    Report {
+        base: args.base.clone(),
+        new: args.new.clone(),
+        threshold_pct: args.threshold,
+        regressions,
+        benchmarks: rows,
+    }
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+    
+    let mut all = BTreeMap::new();
+    collect(&args.criterion_dir, &mut all)?;
+    
+    let report = compare(&args, &all);
+    if report.benchmarks.is_empty() {
+        return Err(anyhow!(
+            "No benchmarks saved under baseline '{}' or '{}' in {}",
+            args.base, args.new, args.criterion_dir.display()
+        ));
+    }
+    
+    let json = serde_json::to_string_pretty(&report)?;
+    match &args.out {
+        Some(path) => std::fs::write(path, json + "\n")
+            .with_context(|| format!("Failed to write {}", path.display()))?,
+        None => println!("{}", json),
+    }
+    
+    if report.regressions > 0 {
+        eprintln!("{} benchmark(s) regressed by more than {}%", report.regressions, args.threshold);
+        std::process::exit(EXIT_REGRESSION);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    /// One saved run the way criterion lays it out: <bench dir>/<baseline>/{benchmark,estimates}.json
+    fn save(root: &Path, bench: &str, baseline: &str, mean: f64, half_width: f64) {
+        let dir = root.join(bench.replace('/', "_")).join(baseline);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("benchmark.json"), serde_json::json!({ "full_id": bench }).to_string()).unwrap();
+        let estimates = serde_json::json!({
+            "mean": {
+                "point_estimate": mean,
+                "confidence_interval": { "lower_bound": mean - half_width, "upper_bound": mean + half_width },
+            }
+        });
+        std::fs::write(dir.join("estimates.json"), estimates.to_string()).unwrap();
+    }
+    
+    #[test]
+    fn test_compares_two_saved_baselines() {
+        let root = std::env::temp_dir().join(format!("bench_compare_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        save(&root, "aead/encrypt/1KiB", "pi4", 1000.0, 10.0);
+        save(&root, "aead/encrypt/1KiB", "pi5", 1200.0, 10.0);
+        save(&root, "aead/decrypt/1KiB", "pi4", 1000.0, 10.0);
+        save(&root, "aead/decrypt/1KiB", "pi5", 800.0, 10.0);
+        // 10% slower, but the intervals overlap: noise, not a regression
+        save(&root, "kex/derive", "pi4", 1000.0, 80.0);
+        save(&root, "kex/derive", "pi5", 1100.0, 80.0);
+        save(&root, "kex/keygen", "pi4", 1000.0, 10.0);
+        std::fs::create_dir_all(root.join("report")).unwrap();
+        
+        let mut all = BTreeMap::new();
+        collect(&root, &mut all).unwrap();
+        let args = Args::parse_from(["bench-compare", "--base", "pi4", "--new", "pi5"]);
+        let report = compare(&args, &all);
+        std::fs::remove_dir_all(&root).unwrap();
+        
+        let status: BTreeMap<&str, String> = report
+            .benchmarks
+            .iter()
+            .map(|r| (r.id.as_str(), format!("{:?}", r.status)))
+            .collect();
+        assert_eq!(status["aead/encrypt/1KiB"], "Regressed");
+        assert_eq!(status["aead/decrypt/1KiB"], "Improved");
+        assert_eq!(status["kex/derive"], "Unchanged");
+        assert_eq!(status["kex/keygen"], "Missing");
+        assert_eq!(report.regressions, 1);
+        
+        let row = report.benchmarks.iter().find(|r| r.id == "aead/encrypt/1KiB").unwrap();
+        assert!((row.change_pct.unwrap() - 20.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,361 @@
+// ASCON-128 / ASCON-128a AEAD (v1.2, NIST LWC finalist) for video frames
+// crates/crypto/src/ascon.rs
+
+use crate::{ct_eq, NonceBase, SessionKeyMaterial, AesKey};
+use anyhow::{bail, Result};
+use std::sync::atomic::{AtomicU32, Ordering};
+
+pub const KEY_LEN: usize = 16;
+pub const NONCE_LEN: usize = 16;
+pub const TAG_LEN: usize = 16;
+
+const ROUND_CONSTANTS: [u64; 12] = [
+    0xf0, 0xe1, 0xd2, 0xc3, 0xb4, 0xa5, 0x96, 0x87, 0x78, 0x69, 0x5a, 0x4b,
+];
+
+/// ASCON parameter set
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AsconVariant {
+    /// 64-bit rate, 6 intermediate rounds (primary recommendation)
+    Ascon128,
+    /// 128-bit rate, 8 intermediate rounds (faster on bulk data)
+    Ascon128a,
+}
+
+impl AsconVariant {
+    fn rate(self) -> usize {
+        match self {
+            AsconVariant::Ascon128 => 8,
+            AsconVariant::Ascon128a => 16,
+        }
+    }
+
+    fn rounds_b(self) -> usize {
+        match self {
+            AsconVariant::Ascon128 => 6,
+            AsconVariant::Ascon128a => 8,
+        }
+    }
+
+    fn iv(self) -> u64 {
+        // k || r || a || b || 0*
+        match self {
+            AsconVariant::Ascon128 => 0x80400c0600000000,
+            AsconVariant::Ascon128a => 0x80800c0800000000,
+        }
+    }
+}
+
+impl std::fmt::Display for AsconVariant {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AsconVariant::Ascon128 => write!(f, "ASCON-128"),
+            AsconVariant::Ascon128a => write!(f, "ASCON-128a"),
+        }
+    }
+}
+
+struct State([u64; 5]);
+
+impl State {
+    fn permute(&mut self, rounds: usize) {
+        let x = &mut self.0;
+        for &c in &ROUND_CONSTANTS[12 - rounds..] {
+            // Constant addition
+            x[2] ^= c;
+
+            // Substitution layer (bitsliced 5-bit S-box)
+            x[0] ^= x[4];
+            x[4] ^= x[3];
+            x[2] ^= x[1];
+            let t = [
+                !x[0] & x[1],
+                !x[1] & x[2],
+                !x[2] & x[3],
+                !x[3] & x[4],
+                !x[4] & x[0],
+            ];
+            x[0] ^= t[1];
+            x[1] ^= t[2];
+            x[2] ^= t[3];
+            x[3] ^= t[4];
+            x[4] ^= t[0];
+            x[1] ^= x[0];
+            x[0] ^= x[4];
+            x[3] ^= x[2];
+            x[2] = !x[2];
+
+            // Linear diffusion layer
+            x[0] ^= x[0].rotate_right(19) ^ x[0].rotate_right(28);
+            x[1] ^= x[1].rotate_right(61) ^ x[1].rotate_right(39);
+            x[2] ^= x[2].rotate_right(1) ^ x[2].rotate_right(6);
+            x[3] ^= x[3].rotate_right(10) ^ x[3].rotate_right(17);
+            x[4] ^= x[4].rotate_right(7) ^ x[4].rotate_right(41);
+        }
+    }
+
+    /// XOR `bytes` into the rate, starting at byte 0 of word 0 (big-endian)
+    fn absorb(&mut self, bytes: &[u8]) {
+        for (i, b) in bytes.iter().enumerate() {
+            self.0[i / 8] ^= (*b as u64) << (56 - 8 * (i % 8));
+        }
+    }
+
+    fn byte(&self, i: usize) -> u8 {
+        (self.0[i / 8] >> (56 - 8 * (i % 8))) as u8
+    }
+
+    fn set_byte(&mut self, i: usize, b: u8) {
+        let shift = 56 - 8 * (i % 8);
+        self.0[i / 8] = (self.0[i / 8] & !(0xff << shift)) | ((b as u64) << shift);
+    }
+
+    fn pad(&mut self, offset: usize) {
+        self.0[offset / 8] ^= 0x80 << (56 - 8 * (offset % 8));
+    }
+}
+
+fn words(bytes: &[u8; 16]) -> (u64, u64) {
+    (
+        u64::from_be_bytes(bytes[..8].try_into().unwrap()),
+        u64::from_be_bytes(bytes[8..].try_into().unwrap()),
+    )
+}
+
+/// Initialization and associated-data processing shared by both directions
+fn start(variant: AsconVariant, key: &[u8; KEY_LEN], nonce: &[u8; NONCE_LEN], aad: &[u8]) -> State {
+    let (k0, k1) = words(key);
+    let (n0, n1) = words(nonce);
+    let rate = variant.rate();
+
+    let mut s = State([variant.iv(), k0, k1, n0, n1]);
+    s.permute(12);
+    s.0[3] ^= k0;
+    s.0[4] ^= k1;
+
+    if !aad.is_empty() {
+        let mut blocks = aad.chunks_exact(rate);
+        for block in &mut blocks {
+            s.absorb(block);
+            s.permute(variant.rounds_b());
+        }
+        let last = blocks.remainder();
+        s.absorb(last);
+        s.pad(last.len());
+        s.permute(variant.rounds_b());
+    }
+
+    // Domain separation between AD and message
+    s.0[4] ^= 1;
+    s
+}
+
+fn finish(variant: AsconVariant, mut s: State, key: &[u8; KEY_LEN]) -> [u8; TAG_LEN] {
+    let (k0, k1) = words(key);
+    let first = variant.rate() / 8;
+    s.0[first] ^= k0;
+    s.0[first + 1] ^= k1;
+    s.permute(12);
+
+    let mut tag = [0u8; TAG_LEN];
+    tag[..8].copy_from_slice(&(s.0[3] ^ k0).to_be_bytes());
+    tag[8..].copy_from_slice(&(s.0[4] ^ k1).to_be_bytes());
+    tag
+}
+
+/// Encrypt `plaintext`, returning ciphertext || 16-byte tag
+pub fn encrypt(
+    variant: AsconVariant,
+    key: &[u8; KEY_LEN],
+    nonce: &[u8; NONCE_LEN],
+    aad: &[u8],
+    plaintext: &[u8],
+) -> Vec<u8> {
+    let rate = variant.rate();
+    let mut s = start(variant, key, nonce, aad);
+    let mut out = Vec::with_capacity(plaintext.len() + TAG_LEN);
+
+    let mut blocks = plaintext.chunks_exact(rate);
+    for block in &mut blocks {
+        s.absorb(block);
+        out.extend((0..rate).map(|i| s.byte(i)));
+        s.permute(variant.rounds_b());
+    }
+    let last = blocks.remainder();
+    s.absorb(last);
+    out.extend((0..last.len()).map(|i| s.byte(i)));
+    s.pad(last.len());
+
+    out.extend_from_slice(&finish(variant, s, key));
+    out
+}
+
+/// Verify and decrypt ciphertext || tag; `None` if authentication fails
+pub fn decrypt(
+    variant: AsconVariant,
+    key: &[u8; KEY_LEN],
+    nonce: &[u8; NONCE_LEN],
+    aad: &[u8],
+    ciphertext: &[u8],
+) -> Option<Vec<u8>> {
+    if ciphertext.len() < TAG_LEN {
+        return None;
+    }
+    let (ciphertext, tag) = ciphertext.split_at(ciphertext.len() - TAG_LEN);
+
+    let rate = variant.rate();
+    let mut s = start(variant, key, nonce, aad);
+    let mut out = Vec::with_capacity(ciphertext.len());
+
+    let mut blocks = ciphertext.chunks_exact(rate);
+    for block in &mut blocks {
+        for (i, c) in block.iter().enumerate() {
+            out.push(s.byte(i) ^ c);
+            s.set_byte(i, *c);
+        }
+        s.permute(variant.rounds_b());
+    }
+    let last = blocks.remainder();
+    for (i, c) in last.iter().enumerate() {
+        out.push(s.byte(i) ^ c);
+        s.set_byte(i, *c);
+    }
+    s.pad(last.len());
+
+    if ct_eq(&finish(variant, s, key), tag) {
+        Some(out)
+    } else {
+        None
+    }
+}
+
+/// ASCON cipher with the same nonce management as `AesGcmCipher`
+pub struct AsconCipher {
+    variant: AsconVariant,
+    key: AesKey,
+    nonce_base: NonceBase,
+    counter: AtomicU32,
+    rekey_threshold: u32,
+}
+
+impl AsconCipher {
+    pub fn new(key_material: SessionKeyMaterial, variant: AsconVariant, rekey_threshold: Option<u32>) -> Self {
+        let (key, nonce_base) = key_material.into_parts();
+
+        Self {
+            variant,
+            key,
+            nonce_base,
+            counter: AtomicU32::new(0),
+            rekey_threshold: rekey_threshold.unwrap_or(crate::MAX_COUNTER),
+        }
+    }
+
+    pub fn variant(&self) -> AsconVariant {
+        self.variant
+    }
+
+    pub fn should_rekey(&self) -> bool {
+        self.counter.load(Ordering::Relaxed) >= self.rekey_threshold
+    }
+
+    pub fn encrypt(&self, plaintext: &[u8], aad: &[u8]) -> Result<Vec<u8>> {
+        let ctr = self.counter.fetch_add(1, Ordering::SeqCst);
+
+        if ctr >= crate::MAX_COUNTER {
+            bail!("Nonce counter exhausted - MUST rekey");
+        }
+
+        let nonce = self.build_nonce(ctr);
+        Ok(encrypt(self.variant, self.key.expose_secret(), &nonce, aad, plaintext))
+    }
+
+    pub fn decrypt(&self, ciphertext: &[u8], aad: &[u8], nonce_ctr: u32) -> Result<Vec<u8>> {
+        let nonce = self.build_nonce(nonce_ctr);
+
+        decrypt(self.variant, self.key.expose_secret(), &nonce, aad, ciphertext)
+            .ok_or_else(|| anyhow::anyhow!("Decryption/Authentication failed: {} tag mismatch", self.variant))
+    }
+
+    // 128-bit nonce: nonce base || 0u32 || counter, same layout as the GCM
+    // nonce with a zero word in the middle
+    fn build_nonce(&self, counter: u32) -> [u8; NONCE_LEN] {
+        let mut nonce = [0u8; NONCE_LEN];
+        nonce[..8].copy_from_slice(self.nonce_base.expose_secret());
+        nonce[12..].copy_from_slice(&counter.to_be_bytes());
+        nonce
+    }
+
+    pub fn get_counter(&self) -> u32 {
+        self.counter.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Entries from the NIST LWC submission KAT files (LWC_AEAD_KAT_128_128.txt
+    // for ascon128v12 and ascon128av12). Key and nonce are 00..0F; PT and AD
+    // are the byte sequence 00, 01, 02, ... of the given length.
+    const KAT: &[(AsconVariant, u32, usize, usize, &str)] = &[
+        (AsconVariant::Ascon128, 1, 0, 0, "E355159F292911F794CB1432A0103A8A"),
+        (AsconVariant::Ascon128, 2, 0, 1, "944DF887CD4901614C5DEDBC42FC0DA0"),
+        (AsconVariant::Ascon128, 34, 1, 0, "BC18C3F4E39ECA7222490D967C79BFFC92"),
+        (
+            AsconVariant::Ascon128, 1057, 32, 0,
+            "BC820DBDF7A4631C5B29884AD69175C3389655CA8135C9E6E8FE7467276F8977\
+             0D975EFAB2EBAA41C0F3ABEEE425E784",
+        ),
+        (AsconVariant::Ascon128a, 1, 0, 0, "7A834E6F09210957067B10FD831F0078"),
+        (AsconVariant::Ascon128a, 2, 0, 1, "AF3031B07B129EC84153373DDCABA528"),
+        (AsconVariant::Ascon128a, 34, 1, 0, "6E652B55BFDC8CAD2EC43815B1666B1A3A"),
+        (AsconVariant::Ascon128a, 35, 1, 1, "E9C2813CC8C6DD2F245F3BB976DA566E9D"),
+        (
+            AsconVariant::Ascon128a, 545, 16, 16,
+            "52499AC9C84323A4AE24EAECCF45C137316D7AB17724BA67A85ECD3C0457C459",
+        ),
+        (
+            AsconVariant::Ascon128a, 1057, 32, 0,
+            "6E490CFED5B3546767350CD83C4ACFBD4CFB4BD07ABF5BC24D4B104645717C1E\
+             513ABFD1335ACFD296C49A35E0D54B73",
+        ),
+    ];
+
+    fn seq(len: usize) -> Vec<u8> {
+        (0..len as u8).collect()
+    }
+
+    #[test]
+    fn test_nist_lwc_kat() {
+        let key: [u8; 16] = seq(16).try_into().unwrap();
+        let nonce: [u8; 16] = seq(16).try_into().unwrap();
+
+        for &(variant, count, pt_len, ad_len, expected) in KAT {
+            let (pt, ad) = (seq(pt_len), seq(ad_len));
+            let ct = encrypt(variant, &key, &nonce, &ad, &pt);
+            assert_eq!(hex::encode_upper(&ct), expected, "{} Count = {}", variant, count);
+            assert_eq!(decrypt(variant, &key, &nonce, &ad, &ct).unwrap(), pt);
+        }
+    }
+
+    #[test]
+    fn test_ascon_cipher_roundtrip_and_tamper() {
+        let key_material = SessionKeyMaterial::generate_random();
+        let cipher = AsconCipher::new(key_material, AsconVariant::Ascon128a, Some(2));
+
+        let ciphertext = cipher.encrypt(b"frame payload", b"header").unwrap();
+        let ctr = cipher.get_counter() - 1;
+        assert_eq!(cipher.decrypt(&ciphertext, b"header", ctr).unwrap(), b"frame payload");
+
+        let mut tampered = ciphertext.clone();
+        tampered[0] ^= 1;
+        assert!(cipher.decrypt(&tampered, b"header", ctr).is_err());
+        assert!(cipher.decrypt(&ciphertext, b"other", ctr).is_err());
+        assert!(cipher.decrypt(&ciphertext, b"header", ctr + 1).is_err());
+
+        assert!(!cipher.should_rekey());
+        cipher.encrypt(b"", b"").unwrap();
+        assert!(cipher.should_rekey());
+    }
+}
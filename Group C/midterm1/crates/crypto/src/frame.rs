@@ -0,0 +1,46 @@
+// Video frame header shared by the stream binary and the benchmarks
+// crates/crypto/src/frame.rs
+
+use anyhow::{bail, Result};
+
+/// Frame header: [flags:1][timestamp_us:8][counter:4][nonce_counter:4][payload_len:4]
+pub const HEADER_SIZE: usize = 21;
+
+/// Header flag: first frame under the next epoch's key (`SessionKeyMaterial::ratchet`)
+pub const FLAG_REKEY: u8 = 0x01;
+
+/// Sent in the clear ahead of each encrypted frame and used as its AAD
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameHeader {
+    pub flags: u8,
+    pub timestamp_us: u64,
+    pub counter: u32,
+    pub nonce_counter: u32,
+    pub payload_len: u32,
+}
+
+impl FrameHeader {
+    pub fn encode(&self) -> [u8; HEADER_SIZE] {
+        let mut buf = [0u8; HEADER_SIZE];
+        buf[0] = self.flags;
+        buf[1..9].copy_from_slice(&self.timestamp_us.to_be_bytes());
+        buf[9..13].copy_from_slice(&self.counter.to_be_bytes());
+        buf[13..17].copy_from_slice(&self.nonce_counter.to_be_bytes());
+        buf[17..21].copy_from_slice(&self.payload_len.to_be_bytes());
+        buf
+    }
+    
+    pub fn decode(buf: &[u8]) -> Result<Self> {
+        if buf.len() < HEADER_SIZE {
+            bail!("Header too short");
+        }
+        
+        let flags = buf[0];
+        let timestamp_us = u64::from_be_bytes(buf[1..9].try_into()?);
+        let counter = u32::from_be_bytes(buf[9..13].try_into()?);
+        let nonce_counter = u32::from_be_bytes(buf[13..17].try_into()?);
+        let payload_len = u32::from_be_bytes(buf[17..21].try_into()?);
+        
+        Ok(Self { flags, timestamp_us, counter, nonce_counter, payload_len })
+    }
+}
@@ -4,13 +4,27 @@ use aes_gcm::{
 };
 use aes_gcm::aead::consts::U12;
 use hkdf::Hkdf;
+use hmac::{Hmac, Mac};
 use p256::ecdh::EphemeralSecret;
 use p256::{EncodedPoint, PublicKey};
-use rand::{RngCore, rngs::OsRng};
+use rand::rngs::OsRng;
 use rsa::{Oaep, RsaPrivateKey, RsaPublicKey};
-use sha2::Sha256;
+use sha2::{Digest, Sha256};
 use std::sync::atomic::{AtomicU32, Ordering};
 use anyhow::{Result, Context, bail};
+use zeroize::Zeroizing;
+
+pub mod secret;
+pub use secret::{AesKey, NonceBase, Secret, SharedSecret, ct_eq};
+
+pub mod ascon;
+pub use ascon::{AsconCipher, AsconVariant};
+
+pub mod frame;
+pub use frame::{FrameHeader, FLAG_REKEY, HEADER_SIZE};
+
+/// Frames per key before a frame cipher refuses to encrypt (2^20)
+pub const MAX_COUNTER: u32 = 1 << 20;
 
 /// Log ARM crypto support at runtime
 pub fn log_arm_crypto_support() {
@@ -54,6 +68,9 @@ pub fn log_arm_crypto_support() {
 /// RSA key establishment with OAEP-SHA256
 pub mod rsa_kex {
     use super::*;
+    use std::collections::VecDeque;
+    use std::sync::{Arc, Condvar, Mutex};
+    use std::thread::JoinHandle;
     
     pub struct RsaKeyPair {
         private_key: RsaPrivateKey,
@@ -82,8 +99,9 @@ pub mod rsa_kex {
                 .context("Failed to wrap session key")
         }
         
-        pub fn unwrap_session_key(&self, wrapped: &[u8]) -> Result<Vec<u8>> {
+        pub fn unwrap_session_key(&self, wrapped: &[u8]) -> Result<Zeroizing<Vec<u8>>> {
             self.private_key.decrypt(Oaep::new::<Sha256>(), wrapped)
+                .map(Zeroizing::new)
                 .context("Failed to unwrap session key")
         }
     }
@@ -94,11 +112,181 @@ pub mod rsa_kex {
             // Note: rsa crate uses zeroize internally
         }
     }
+    
+    /// Where a handshake's RSA keypair came from
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum KeySource {
+        /// Pre-generated by the background pool
+        Pool,
+        /// Generated on the handshake critical path
+        Inline,
+    }
+    
+    impl std::fmt::Display for KeySource {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            match self {
+                KeySource::Pool => write!(f, "pool"),
+                KeySource::Inline => write!(f, "inline"),
+            }
+        }
+    }
+    
+    /// When the pool worker generates replacement keys
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum RefillPolicy {
+        /// Top the pool back up to full depth after every take
+        Eager,
+        /// Wait until the pool drops to this many keys, then refill to full depth
+        LowWater(usize),
+    }
+    
+    struct PoolState {
+        keys: VecDeque<RsaKeyPair>,
+        /// Outstanding `hold_refill` guards; no keygen starts while non-zero
+        holds: usize,
+        shutdown: bool,
+    }
+    
+    struct PoolShared {
+        state: Mutex<PoolState>,
+        wake: Condvar,
+    }
+    
+    /// Ephemeral RSA keypairs pre-generated on a worker thread so handshakes
+    /// don't pay for keygen (seconds for RSA-3072 on a Pi 5)
+    pub struct RsaKeyPool {
+        bits: usize,
+        shared: Arc<PoolShared>,
+        worker: Option<JoinHandle<()>>,
+    }
+    
+    impl RsaKeyPool {
+        pub fn new(bits: usize, depth: usize, policy: RefillPolicy) -> Self {
+            let shared = Arc::new(PoolShared {
+                state: Mutex::new(PoolState { keys: VecDeque::with_capacity(depth), holds: 0, shutdown: false }),
+                wake: Condvar::new(),
+            });
+            
+            let worker_shared = shared.clone();
+            let worker = std::thread::Builder::new()
+                .name(format!("rsa-pool-{}", bits))
+                .spawn(move || Self::refill_loop(worker_shared, bits, depth, policy))
+                .expect("Failed to spawn RSA key pool worker");
+            
+            Self { bits, shared, worker: Some(worker) }
+        }
+        
+        fn refill_loop(shared: Arc<PoolShared>, bits: usize, depth: usize, policy: RefillPolicy) {
+            let mut refilling = true;
+            loop {
+                {
+                    let mut state = shared.state.lock().unwrap();
+                    loop {
+                        if state.shutdown {
+                            return;
+                        }
+                        let len = state.keys.len();
+                        if len >= depth {
+                            refilling = false;
+                        } else if policy == RefillPolicy::Eager {
+                            refilling = true;
+                        } else if let RefillPolicy::LowWater(low) = policy {
+                            if len <= low {
+                                refilling = true;
+                            }
+                        }
+                        if refilling && state.holds == 0 {
+                            break;
+                        }
+                        state = shared.wake.wait(state).unwrap();
+                    }
+                }
+                
+                // Generate outside the lock so takers never wait on keygen
+                match RsaKeyPair::generate(bits) {
+                    Ok(keypair) => {
+                        let mut state = shared.state.lock().unwrap();
+                        if state.shutdown {
+                            return;
+                        }
+                        state.keys.push_back(keypair);
+                        shared.wake.notify_all();
+                    }
+                    Err(_) => std::thread::sleep(std::time::Duration::from_millis(100)),
+                }
+            }
+        }
+        
+        /// Take a pooled keypair, generating inline if the pool is empty
+        pub fn take(&self) -> Result<(RsaKeyPair, KeySource)> {
+            match self.try_take() {
+                Some(keypair) => Ok((keypair, KeySource::Pool)),
+                None => Ok((RsaKeyPair::generate(self.bits)?, KeySource::Inline)),
+            }
+        }
+        
+        /// Take a pooled keypair without generating; async callers run the
+        /// keygen fallback off the runtime thread themselves
+        pub fn try_take(&self) -> Option<RsaKeyPair> {
+            let mut state = self.shared.state.lock().unwrap();
+            let key = state.keys.pop_front();
+            self.shared.wake.notify_all();
+            key
+        }
+        
+        /// Keep the worker from starting keygen until the guard is dropped,
+        /// so refills don't land inside a profiled handshake
+        pub fn hold_refill(&self) -> RefillHold {
+            self.shared.state.lock().unwrap().holds += 1;
+            RefillHold { shared: self.shared.clone() }
+        }
+        
+        /// Block until at least `count` keys are ready or `timeout` elapses
+        pub fn wait_ready(&self, count: usize, timeout: std::time::Duration) -> bool {
+            let state = self.shared.state.lock().unwrap();
+            let (state, _) = self.shared.wake
+                .wait_timeout_while(state, timeout, |s| s.keys.len() < count)
+                .unwrap();
+            state.keys.len() >= count
+        }
+        
+        /// Number of keys currently ready
+        pub fn available(&self) -> usize {
+            self.shared.state.lock().unwrap().keys.len()
+        }
+        
+        pub fn bits(&self) -> usize {
+            self.bits
+        }
+    }
+    
+    /// Guard returned by `RsaKeyPool::hold_refill`
+    pub struct RefillHold {
+        shared: Arc<PoolShared>,
+    }
+    
+    impl Drop for RefillHold {
+        fn drop(&mut self) {
+            self.shared.state.lock().unwrap().holds -= 1;
+            self.shared.wake.notify_all();
+        }
+    }
+    
+    impl Drop for RsaKeyPool {
+        fn drop(&mut self) {
+            self.shared.state.lock().unwrap().shutdown = true;
+            self.shared.wake.notify_all();
+            if let Some(worker) = self.worker.take() {
+                let _ = worker.join();
+            }
+        }
+    }
 }
 
 /// ECDH key establishment with P-256 + HKDF
 pub mod ecdh_kex {
     use super::*;
+    pub use super::handshake::{KeyConfirmation, Role};
     
     pub struct EcdhKeyPair {
         secret: EphemeralSecret,
@@ -124,90 +312,245 @@ pub mod ecdh_kex {
             let peer_public = PublicKey::from_sec1_bytes(peer_public_bytes)
                 .context("Invalid peer public key")?;
             
-            let shared_secret = self.secret.diffie_hellman(&peer_public);
+            let shared_secret = SharedSecret::from_slice(
+                self.secret.diffie_hellman(&peer_public).raw_secret_bytes()
+            )?;
             
             // HKDF-SHA256: shared_secret -> AES key + nonce base
-            let hk = Hkdf::<Sha256>::new(None, shared_secret.raw_secret_bytes());
+            let hk = Hkdf::<Sha256>::new(None, shared_secret.expose_secret());
             
-            let mut aes_key = [0u8; 16];
-            hk.expand(context, &mut aes_key)
+            let aes_key = AesKey::try_fill(|k| hk.expand(context, k))
                 .map_err(|_| anyhow::anyhow!("HKDF expand failed for AES key"))?;
             
             // Derive nonce_base from HKDF too, not random!
-            let mut nonce_base = [0u8; 8];
-            let mut combined = [0u8; 24];
-            hk.expand(b"nonce-base", &mut combined)
+            let mut combined = Zeroizing::new([0u8; 24]);
+            hk.expand(b"nonce-base", &mut combined[..])
                 .map_err(|_| anyhow::anyhow!("HKDF expand failed for nonce base"))?;
-            nonce_base.copy_from_slice(&combined[16..24]); // Use bytes after AES key
+            let nonce_base = NonceBase::from_slice(&combined[16..24])?; // Use bytes after AES key
             
             Ok(SessionKeyMaterial { aes_key, nonce_base })
         }
+        
+        /// Derive session keys bound to the full handshake transcript.
+        ///
+        /// See `handshake::transcript_hash`; the public keys are the
+        /// initiator and responder messages. Also returns the confirmation
+        /// keys for the "finished" exchange.
+        pub fn derive_with_transcript(
+            self,
+            peer_public_bytes: &[u8],
+            role: Role,
+            mechanism: &str,
+            cipher: &str,
+            context: &[u8],
+        ) -> Result<(SessionKeyMaterial, KeyConfirmation), HandshakeError> {
+            let peer_public = PublicKey::from_sec1_bytes(peer_public_bytes)
+                .map_err(|_| HandshakeError::InvalidPeerKey)?;
+            
+            let my_public = self.public_key_bytes();
+            let (initiator_pub, responder_pub) = match role {
+                Role::Initiator => (my_public.as_slice(), peer_public_bytes),
+                Role::Responder => (peer_public_bytes, my_public.as_slice()),
+            };
+            let transcript_hash = handshake::transcript_hash(mechanism, cipher, context, initiator_pub, responder_pub);
+            
+            let shared_secret = SharedSecret::from_slice(
+                self.secret.diffie_hellman(&peer_public).raw_secret_bytes()
+            ).map_err(|_| HandshakeError::InvalidPeerKey)?;
+            
+            handshake::derive_keys(shared_secret.expose_secret(), role, transcript_hash)
+        }
+    }
+}
+
+/// Transcript-bound key schedule and key confirmation shared by the
+/// ECDH, RSA and group handshakes
+pub mod handshake {
+    use super::*;
+    
+    /// Which side of the handshake we are
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Role {
+        Initiator,
+        Responder,
+    }
+    
+    /// Hash of everything both sides must agree on: mechanism, cipher suite,
+    /// context and the initiator/responder messages, each length-prefixed.
+    /// A peer talked into another mechanism or cipher gets a different hash,
+    /// so its keys and finished messages don't match.
+    pub fn transcript_hash(
+        mechanism: &str,
+        cipher: &str,
+        context: &[u8],
+        initiator_msg: &[u8],
+        responder_msg: &[u8],
+    ) -> [u8; 32] {
+        let mut transcript = Sha256::new();
+        for part in [b"ECE4301-transcript-v2".as_slice(), mechanism.as_bytes(), cipher.as_bytes(), context, initiator_msg, responder_msg] {
+            transcript.update((part.len() as u32).to_be_bytes());
+            transcript.update(part);
+        }
+        transcript.finalize().into()
+    }
+    
+    /// Expand a handshake secret into session keys and finished-message keys,
+    /// with the transcript hash as the HKDF salt
+    pub fn derive_keys(
+        secret: &[u8],
+        role: Role,
+        transcript_hash: [u8; 32],
+    ) -> Result<(SessionKeyMaterial, KeyConfirmation), HandshakeError> {
+        let hk = Hkdf::<Sha256>::new(Some(&transcript_hash), secret);
+        let expand = |label: &[u8], out: &mut [u8]| {
+            hk.expand(label, out).map_err(|_| HandshakeError::KeyDerivation)
+        };
+        
+        let aes_key = AesKey::try_fill(|k| expand(b"aes-key", k))?;
+        let nonce_base = NonceBase::try_fill(|n| expand(b"nonce-base", n))?;
+        let initiator_key = Secret::<32>::try_fill(|k| expand(b"initiator-finished", k))?;
+        let responder_key = Secret::<32>::try_fill(|k| expand(b"responder-finished", k))?;
+        
+        let (own_key, peer_key) = match role {
+            Role::Initiator => (initiator_key, responder_key),
+            Role::Responder => (responder_key, initiator_key),
+        };
+        
+        Ok((
+            SessionKeyMaterial { aes_key, nonce_base },
+            KeyConfirmation { transcript_hash, own_key, peer_key },
+        ))
+    }
+    
+    /// Keys for the HMAC "finished" messages that prove both sides derived
+    /// the same session key before any video is accepted
+    pub struct KeyConfirmation {
+        transcript_hash: [u8; 32],
+        own_key: Secret<32>,
+        peer_key: Secret<32>,
+    }
+    
+    impl KeyConfirmation {
+        pub const FINISHED_LEN: usize = 32;
+        
+        fn mac(key: &Secret<32>, transcript_hash: &[u8; 32]) -> Hmac<Sha256> {
+            let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(key.expose_secret())
+                .expect("HMAC accepts any key length");
+            mac.update(b"finished");
+            mac.update(transcript_hash);
+            mac
+        }
+        
+        /// Our finished message to send to the peer
+        pub fn finished(&self) -> [u8; 32] {
+            Self::mac(&self.own_key, &self.transcript_hash).finalize().into_bytes().into()
+        }
+        
+        /// Check the peer's finished message (constant time)
+        pub fn verify_peer(&self, finished: &[u8]) -> Result<(), HandshakeError> {
+            Self::mac(&self.peer_key, &self.transcript_hash)
+                .verify_slice(finished)
+                .map_err(|_| HandshakeError::KeyConfirmationFailed)
+        }
+        
+        pub fn transcript_hash(&self) -> &[u8; 32] {
+            &self.transcript_hash
+        }
     }
 }
 
+/// Handshake failures callers may want to tell apart
+#[derive(Debug, thiserror::Error)]
+pub enum HandshakeError {
+    #[error("invalid peer public key")]
+    InvalidPeerKey,
+    #[error("HKDF key derivation failed")]
+    KeyDerivation,
+    #[error("key confirmation failed: peer derived a different session key")]
+    KeyConfirmationFailed,
+}
+
 /// Session key material derived from key establishment
-#[derive(Debug, Clone)]
+///
+/// Zeroized on drop via the `Secret` fields; `Debug` is redacted.
+#[derive(Debug, PartialEq, Eq)]
 pub struct SessionKeyMaterial {
-    aes_key: [u8; 16],
-    nonce_base: [u8; 8],
+    aes_key: AesKey,
+    nonce_base: NonceBase,
 }
 
 impl SessionKeyMaterial {
     pub fn generate_random() -> Self {
-        let mut aes_key = [0u8; 16];
-        let mut nonce_base = [0u8; 8];
-        OsRng.fill_bytes(&mut aes_key);
-        OsRng.fill_bytes(&mut nonce_base);
-        Self { aes_key, nonce_base }
+        Self {
+            aes_key: AesKey::random(),
+            nonce_base: NonceBase::random(),
+        }
+    }
+    
+    pub fn aes_key(&self) -> &AesKey {
+        &self.aes_key
+    }
+    
+    pub fn nonce_base(&self) -> &NonceBase {
+        &self.nonce_base
     }
     
-    pub fn as_bytes(&self) -> Vec<u8> {
-        let mut bytes = Vec::with_capacity(24);
-        bytes.extend_from_slice(&self.aes_key);
-        bytes.extend_from_slice(&self.nonce_base);
+    /// Serialized key || nonce base, wiped when the returned buffer drops
+    pub fn as_bytes(&self) -> Zeroizing<Vec<u8>> {
+        let mut bytes = Zeroizing::new(Vec::with_capacity(24));
+        bytes.extend_from_slice(self.aes_key.expose_secret());
+        bytes.extend_from_slice(self.nonce_base.expose_secret());
         bytes
     }
     
+    pub(crate) fn into_parts(self) -> (AesKey, NonceBase) {
+        (self.aes_key, self.nonce_base)
+    }
+    
     pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
         if bytes.len() != 24 {
             bail!("Invalid session key material length");
         }
-        let mut aes_key = [0u8; 16];
-        let mut nonce_base = [0u8; 8];
-        aes_key.copy_from_slice(&bytes[0..16]);
-        nonce_base.copy_from_slice(&bytes[16..24]);
-        Ok(Self { aes_key, nonce_base })
+        Ok(Self {
+            aes_key: AesKey::from_slice(&bytes[0..16])?,
+            nonce_base: NonceBase::from_slice(&bytes[16..24])?,
+        })
     }
-}
-
-impl Drop for SessionKeyMaterial {
-    fn drop(&mut self) {
-        // Zero out sensitive material
-        self.aes_key.iter_mut().for_each(|b| *b = 0);
-        self.nonce_base.iter_mut().for_each(|b| *b = 0);
+    
+    /// Key material for the next epoch, derived from this one.
+    ///
+    /// Both ends can step forward without another handshake, and the old
+    /// key cannot be recovered from the new one.
+    pub fn ratchet(&self, epoch: u32) -> Result<Self> {
+        let ikm = self.as_bytes();
+        let hk = Hkdf::<Sha256>::new(Some(&epoch.to_be_bytes()), &ikm);
+        let aes_key = AesKey::try_fill(|k| hk.expand(b"ratchet-aes-key", k))
+            .map_err(|_| anyhow::anyhow!("HKDF expand failed for AES key"))?;
+        let nonce_base = NonceBase::try_fill(|n| hk.expand(b"ratchet-nonce-base", n))
+            .map_err(|_| anyhow::anyhow!("HKDF expand failed for nonce base"))?;
+        Ok(Self { aes_key, nonce_base })
     }
 }
 
 /// AES-128-GCM cipher with nonce management
 pub struct AesGcmCipher {
     cipher: Aes128Gcm,
-    nonce_base: [u8; 8],
+    nonce_base: NonceBase,
     counter: AtomicU32,
     rekey_threshold: u32,
 }
 
 impl AesGcmCipher {
-    const MAX_COUNTER: u32 = 1 << 20; // 2^20 frames max per key
-    
     pub fn new(key_material: SessionKeyMaterial, rekey_threshold: Option<u32>) -> Self {
-        let cipher = Aes128Gcm::new_from_slice(&key_material.aes_key)
+        let SessionKeyMaterial { aes_key, nonce_base } = key_material;
+        let cipher = Aes128Gcm::new_from_slice(aes_key.expose_secret())
             .expect("Invalid key length");
         
         Self {
             cipher,
-            nonce_base: key_material.nonce_base,
+            nonce_base,
             counter: AtomicU32::new(0),
-            rekey_threshold: rekey_threshold.unwrap_or(Self::MAX_COUNTER),
+            rekey_threshold: rekey_threshold.unwrap_or(MAX_COUNTER),
         }
     }
     
@@ -218,7 +561,7 @@ impl AesGcmCipher {
     pub fn encrypt(&self, plaintext: &[u8], aad: &[u8]) -> Result<Vec<u8>> {
         let ctr = self.counter.fetch_add(1, Ordering::SeqCst);
         
-        if ctr >= Self::MAX_COUNTER {
+        if ctr >= MAX_COUNTER {
             bail!("Nonce counter exhausted - MUST rekey");
         }
         
@@ -239,7 +582,7 @@ impl AesGcmCipher {
     
     fn build_nonce(&self, counter: u32) -> Nonce<U12> {
         let mut nonce = [0u8; 12];
-        nonce[..8].copy_from_slice(&self.nonce_base);
+        nonce[..8].copy_from_slice(self.nonce_base.expose_secret());
         nonce[8..].copy_from_slice(&counter.to_be_bytes());
         Nonce::from(nonce)
     }
@@ -249,6 +592,74 @@ impl AesGcmCipher {
     }
 }
 
+/// AEAD used for video frames
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CipherSuite {
+    Aes128Gcm,
+    Ascon(AsconVariant),
+}
+
+impl std::fmt::Display for CipherSuite {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CipherSuite::Aes128Gcm => write!(f, "AES-128-GCM"),
+            CipherSuite::Ascon(variant) => write!(f, "{}", variant),
+        }
+    }
+}
+
+/// Frame cipher selected at runtime; every suite shares the nonce layout,
+/// counter semantics and `MAX_COUNTER` limit of `AesGcmCipher`
+pub enum FrameCipher {
+    // Boxed: the expanded AES key schedule dwarfs the ASCON state
+    AesGcm(Box<AesGcmCipher>),
+    Ascon(AsconCipher),
+}
+
+impl FrameCipher {
+    pub fn new(suite: CipherSuite, key_material: SessionKeyMaterial, rekey_threshold: Option<u32>) -> Self {
+        match suite {
+            CipherSuite::Aes128Gcm => FrameCipher::AesGcm(Box::new(AesGcmCipher::new(key_material, rekey_threshold))),
+            CipherSuite::Ascon(variant) => FrameCipher::Ascon(AsconCipher::new(key_material, variant, rekey_threshold)),
+        }
+    }
+    
+    pub fn suite(&self) -> CipherSuite {
+        match self {
+            FrameCipher::AesGcm(_) => CipherSuite::Aes128Gcm,
+            FrameCipher::Ascon(c) => CipherSuite::Ascon(c.variant()),
+        }
+    }
+    
+    pub fn should_rekey(&self) -> bool {
+        match self {
+            FrameCipher::AesGcm(c) => c.should_rekey(),
+            FrameCipher::Ascon(c) => c.should_rekey(),
+        }
+    }
+    
+    pub fn encrypt(&self, plaintext: &[u8], aad: &[u8]) -> Result<Vec<u8>> {
+        match self {
+            FrameCipher::AesGcm(c) => c.encrypt(plaintext, aad),
+            FrameCipher::Ascon(c) => c.encrypt(plaintext, aad),
+        }
+    }
+    
+    pub fn decrypt(&self, ciphertext: &[u8], aad: &[u8], nonce_ctr: u32) -> Result<Vec<u8>> {
+        match self {
+            FrameCipher::AesGcm(c) => c.decrypt(ciphertext, aad, nonce_ctr),
+            FrameCipher::Ascon(c) => c.decrypt(ciphertext, aad, nonce_ctr),
+        }
+    }
+    
+    pub fn get_counter(&self) -> u32 {
+        match self {
+            FrameCipher::AesGcm(c) => c.get_counter(),
+            FrameCipher::Ascon(c) => c.get_counter(),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -264,6 +675,29 @@ mod tests {
         assert_eq!(session_key, unwrapped.as_slice());
     }
     
+    #[test]
+    fn test_rsa_key_pool() {
+        let pool = rsa_kex::RsaKeyPool::new(1024, 2, rsa_kex::RefillPolicy::Eager);
+        assert!(pool.wait_ready(2, std::time::Duration::from_secs(60)));
+        
+        let (keypair, source) = pool.take().unwrap();
+        assert_eq!(source, rsa_kex::KeySource::Pool);
+        
+        let wrapped = keypair.wrap_session_key(b"0123456789abcdef").unwrap();
+        assert_eq!(keypair.unwrap_session_key(&wrapped).unwrap().as_slice(), b"0123456789abcdef");
+        
+        // No refill while held, then the worker tops the pool back up
+        let hold = pool.hold_refill();
+        assert!(pool.try_take().is_some());
+        assert!(!pool.wait_ready(1, std::time::Duration::from_millis(500)));
+        drop(hold);
+        assert!(pool.wait_ready(2, std::time::Duration::from_secs(60)));
+        
+        let empty = rsa_kex::RsaKeyPool::new(1024, 0, rsa_kex::RefillPolicy::Eager);
+        let (_, source) = empty.take().unwrap();
+        assert_eq!(source, rsa_kex::KeySource::Inline);
+    }
+    
     #[test]
     fn test_ecdh_derive() {
         let alice = ecdh_kex::EcdhKeyPair::generate();
@@ -279,6 +713,61 @@ mod tests {
         assert_eq!(alice_key.nonce_base, bob_key.nonce_base);
     }
     
+    #[test]
+    fn test_ecdh_transcript_confirmation() {
+        use ecdh_kex::{EcdhKeyPair, Role};
+        
+        let alice = EcdhKeyPair::generate();
+        let bob = EcdhKeyPair::generate();
+        let (alice_pub, bob_pub) = (alice.public_key_bytes(), bob.public_key_bytes());
+        
+        let (alice_key, alice_confirm) = alice
+            .derive_with_transcript(&bob_pub, Role::Initiator, "ECDH-P256", "AES-128-GCM", b"ctx").unwrap();
+        let (bob_key, bob_confirm) = bob
+            .derive_with_transcript(&alice_pub, Role::Responder, "ECDH-P256", "AES-128-GCM", b"ctx").unwrap();
+        
+        assert_eq!(alice_key, bob_key);
+        assert_eq!(alice_confirm.transcript_hash(), bob_confirm.transcript_hash());
+        bob_confirm.verify_peer(&alice_confirm.finished()).unwrap();
+        alice_confirm.verify_peer(&bob_confirm.finished()).unwrap();
+        
+        // Directions are not interchangeable: a reflected finished must fail
+        assert!(matches!(
+            alice_confirm.verify_peer(&alice_confirm.finished()),
+            Err(HandshakeError::KeyConfirmationFailed)
+        ));
+        
+        // A peer that disagrees on the mechanism ends up with different keys
+        let carol = EcdhKeyPair::generate();
+        let dave = EcdhKeyPair::generate();
+        let (carol_pub, dave_pub) = (carol.public_key_bytes(), dave.public_key_bytes());
+        let (carol_key, carol_confirm) = carol
+            .derive_with_transcript(&dave_pub, Role::Initiator, "ECDH-P256", "AES-128-GCM", b"ctx").unwrap();
+        let (dave_key, dave_confirm) = dave
+            .derive_with_transcript(&carol_pub, Role::Responder, "RSA-2048", "AES-128-GCM", b"ctx").unwrap();
+        assert_ne!(carol_key, dave_key);
+        assert!(dave_confirm.verify_peer(&carol_confirm.finished()).is_err());
+    }
+    
+    #[test]
+    fn test_transcript_binds_cipher() {
+        use handshake::{derive_keys, transcript_hash, Role};
+        
+        // Same secret and messages, but the responder was downgraded to Ascon
+        let secret = [0x5a; 32];
+        let honest = transcript_hash("RSA-2048", "AES-128-GCM", b"ctx", b"pub", b"wrapped");
+        let downgraded = transcript_hash("RSA-2048", "ASCON-128", b"ctx", b"pub", b"wrapped");
+        let (_, initiator) = derive_keys(&secret, Role::Initiator, honest).unwrap();
+        let (_, responder) = derive_keys(&secret, Role::Responder, downgraded).unwrap();
+        assert!(matches!(
+            responder.verify_peer(&initiator.finished()),
+            Err(HandshakeError::KeyConfirmationFailed)
+        ));
+        
+        let (_, responder) = derive_keys(&secret, Role::Responder, honest).unwrap();
+        responder.verify_peer(&initiator.finished()).unwrap();
+    }
+    
     #[test]
     fn test_aes_gcm_roundtrip() {
         let key_material = SessionKeyMaterial::generate_random();
@@ -294,6 +783,36 @@ mod tests {
         assert_eq!(plaintext, decrypted.as_slice());
     }
     
+    #[test]
+    fn test_frame_cipher_suites() {
+        let suites = [
+            CipherSuite::Aes128Gcm,
+            CipherSuite::Ascon(AsconVariant::Ascon128),
+            CipherSuite::Ascon(AsconVariant::Ascon128a),
+        ];
+        for suite in suites {
+            let key_bytes = SessionKeyMaterial::generate_random().as_bytes();
+            let sender = FrameCipher::new(suite, SessionKeyMaterial::from_bytes(&key_bytes).unwrap(), None);
+            let receiver = FrameCipher::new(suite, SessionKeyMaterial::from_bytes(&key_bytes).unwrap(), None);
+            assert_eq!(sender.suite(), suite);
+            
+            let ciphertext = sender.encrypt(b"frame", b"hdr").unwrap();
+            assert_eq!(receiver.decrypt(&ciphertext, b"hdr", 0).unwrap(), b"frame", "{}", suite);
+        }
+    }
+    
+    #[test]
+    fn test_session_key_ratchet() {
+        let key = SessionKeyMaterial::generate_random();
+        let peer = SessionKeyMaterial::from_bytes(&key.as_bytes()).unwrap();
+        
+        let next = key.ratchet(1).unwrap();
+        assert_eq!(next, peer.ratchet(1).unwrap());
+        assert_ne!(next, key);
+        assert_ne!(next, key.ratchet(2).unwrap());
+        assert_ne!(next.ratchet(2).unwrap(), key.ratchet(2).unwrap());
+    }
+    
     #[test]
     fn test_nonce_uniqueness() {
         let key_material = SessionKeyMaterial::generate_random();
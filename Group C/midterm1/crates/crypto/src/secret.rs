@@ -0,0 +1,238 @@
+// Secret wrapper types for key material
+// crates/crypto/src/secret.rs
+
+use anyhow::{bail, Result};
+use rand::{rngs::OsRng, RngCore};
+use subtle::ConstantTimeEq;
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+/// Fixed-size secret bytes.
+///
+/// - `Debug` never prints the contents
+/// - bytes are zeroized on drop
+/// - no `Clone`: copies must go through `expose_secret()` explicitly
+/// - equality is constant-time
+/// - with the `mlock` feature the backing page is locked out of swap
+pub struct Secret<const N: usize> {
+    // Boxed so the address stays put for mlock and moves don't leave copies behind
+    bytes: Box<[u8; N]>,
+    locked: bool,
+}
+
+/// AES-128 key
+pub type AesKey = Secret<16>;
+/// Per-session nonce prefix
+pub type NonceBase = Secret<8>;
+/// Raw ECDH shared secret (P-256 x-coordinate)
+pub type SharedSecret = Secret<32>;
+
+impl<const N: usize> Secret<N> {
+    /// Move `bytes` into a secret and wipe the argument.
+    ///
+    /// Arrays are `Copy`, so the caller's own binding is not wiped; prefer
+    /// `try_fill` when deriving key material.
+    pub fn new(mut bytes: [u8; N]) -> Self {
+        let mut secret = Self::zeroed();
+        secret.bytes.copy_from_slice(&bytes);
+        bytes.zeroize();
+        secret
+    }
+
+    /// Fill the secret in place (e.g. from HKDF expand) without a stack copy
+    pub fn try_fill<E>(fill: impl FnOnce(&mut [u8]) -> std::result::Result<(), E>) -> std::result::Result<Self, E> {
+        let mut secret = Self::zeroed();
+        fill(&mut secret.bytes[..])?;
+        Ok(secret)
+    }
+
+    pub fn random() -> Self {
+        let mut secret = Self::zeroed();
+        OsRng.fill_bytes(&mut secret.bytes[..]);
+        secret
+    }
+
+    pub fn from_slice(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() != N {
+            bail!("Invalid secret length: expected {} bytes, got {}", N, bytes.len());
+        }
+        let mut secret = Self::zeroed();
+        secret.bytes.copy_from_slice(bytes);
+        Ok(secret)
+    }
+
+    fn zeroed() -> Self {
+        let mut secret = Self {
+            bytes: Box::new([0u8; N]),
+            locked: false,
+        };
+        secret.locked = lock_memory(&secret.bytes[..]);
+        secret
+    }
+
+    /// Borrow the raw key bytes. Keep the borrow as short as possible.
+    pub fn expose_secret(&self) -> &[u8; N] {
+        &self.bytes
+    }
+
+    /// Whether the backing memory is locked out of swap
+    pub fn is_locked(&self) -> bool {
+        self.locked
+    }
+}
+
+impl<const N: usize> Zeroize for Secret<N> {
+    fn zeroize(&mut self) {
+        self.bytes.zeroize();
+    }
+}
+
+impl<const N: usize> Drop for Secret<N> {
+    fn drop(&mut self) {
+        self.bytes.zeroize();
+        if self.locked {
+            unlock_memory(&self.bytes[..]);
+        }
+    }
+}
+
+impl<const N: usize> ZeroizeOnDrop for Secret<N> {}
+
+impl<const N: usize> ConstantTimeEq for Secret<N> {
+    fn ct_eq(&self, other: &Self) -> subtle::Choice {
+        self.bytes[..].ct_eq(&other.bytes[..])
+    }
+}
+
+impl<const N: usize> PartialEq for Secret<N> {
+    fn eq(&self, other: &Self) -> bool {
+        self.ct_eq(other).into()
+    }
+}
+
+impl<const N: usize> Eq for Secret<N> {}
+
+impl<const N: usize> std::fmt::Debug for Secret<N> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Secret<{}>([REDACTED])", N)
+    }
+}
+
+/// Constant-time comparison for digests, MACs and other secret-derived bytes
+pub fn ct_eq(a: &[u8], b: &[u8]) -> bool {
+    a.ct_eq(b).into()
+}
+
+/// Secrets locked on each page, by page address. mlock does not nest, so a
+/// page is only unlocked once the last secret on it is dropped.
+#[cfg(feature = "mlock")]
+static LOCKED_PAGES: std::sync::Mutex<std::collections::BTreeMap<usize, usize>> =
+    std::sync::Mutex::new(std::collections::BTreeMap::new());
+
+#[cfg(feature = "mlock")]
+fn page_size() -> usize {
+    // SAFETY: sysconf has no preconditions
+    unsafe { libc::sysconf(libc::_SC_PAGESIZE) }.max(1) as usize
+}
+
+/// Start addresses of the pages spanned by `bytes`
+#[cfg(feature = "mlock")]
+fn pages(bytes: &[u8]) -> impl Iterator<Item = usize> {
+    let page = page_size();
+    let start = bytes.as_ptr() as usize & !(page - 1);
+    let end = bytes.as_ptr() as usize + bytes.len().max(1);
+    (start..end).step_by(page)
+}
+
+#[cfg(feature = "mlock")]
+fn lock_memory(bytes: &[u8]) -> bool {
+    let page = page_size();
+    let mut locked = LOCKED_PAGES.lock().unwrap_or_else(|e| e.into_inner());
+    for (i, addr) in pages(bytes).enumerate() {
+        let count = locked.entry(addr).or_insert(0);
+        // SAFETY: the page contains part of a live heap allocation
+        if *count == 0 && unsafe { libc::mlock(addr as *const libc::c_void, page) } != 0 {
+            locked.remove(&addr);
+            // Roll back the pages taken so far so the counts stay exact
+            for addr in pages(bytes).take(i) {
+                release_page(&mut locked, addr, page);
+            }
+            return false;
+        }
+        *count += 1;
+    }
+    true
+}
+
+#[cfg(feature = "mlock")]
+fn unlock_memory(bytes: &[u8]) {
+    let page = page_size();
+    let mut locked = LOCKED_PAGES.lock().unwrap_or_else(|e| e.into_inner());
+    for addr in pages(bytes) {
+        release_page(&mut locked, addr, page);
+    }
+}
+
+#[cfg(feature = "mlock")]
+fn release_page(locked: &mut std::collections::BTreeMap<usize, usize>, addr: usize, page: usize) {
+    if let Some(count) = locked.get_mut(&addr) {
+        *count -= 1;
+        if *count == 0 {
+            locked.remove(&addr);
+            // SAFETY: same page that was passed to mlock
+            unsafe {
+                libc::munlock(addr as *const libc::c_void, page);
+            }
+        }
+    }
+}
+
+#[cfg(not(feature = "mlock"))]
+fn lock_memory(_bytes: &[u8]) -> bool {
+    false
+}
+
+#[cfg(not(feature = "mlock"))]
+fn unlock_memory(_bytes: &[u8]) {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_debug_is_redacted() {
+        let key = AesKey::new([0x42; 16]);
+        let printed = format!("{:?}", key);
+        assert_eq!(printed, "Secret<16>([REDACTED])");
+        assert!(!printed.contains("42"));
+    }
+
+    #[test]
+    fn test_equality_and_zeroize() {
+        let a = NonceBase::new([7; 8]);
+        let b = NonceBase::from_slice(&[7; 8]).unwrap();
+        let mut c = NonceBase::random();
+        assert_eq!(a, b);
+        assert!(NonceBase::from_slice(&[0; 9]).is_err());
+
+        c.zeroize();
+        assert_eq!(c.expose_secret(), &[0u8; 8]);
+        assert!(ct_eq(a.expose_secret(), b.expose_secret()));
+        assert!(!ct_eq(a.expose_secret(), c.expose_secret()));
+    }
+
+    #[cfg(feature = "mlock")]
+    #[test]
+    fn test_shared_page_stays_locked() {
+        let a = AesKey::random();
+        let b = AesKey::random();
+        if !(a.is_locked() && b.is_locked()) {
+            return; // no RLIMIT_MEMLOCK headroom here
+        }
+        let page_b = pages(&b.expose_secret()[..]).collect::<Vec<_>>();
+        drop(a);
+
+        // b's pages are still counted (and so still locked) after a is gone
+        let locked = LOCKED_PAGES.lock().unwrap();
+        assert!(page_b.iter().all(|p| locked.get(p).is_some_and(|&n| n >= 1)));
+    }
+}
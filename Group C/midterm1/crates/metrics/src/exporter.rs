@@ -0,0 +1,276 @@
+// OpenMetrics exporter for live scraping of MetricsCollector state
+// crates/metrics/src/exporter.rs
+
+use crate::MetricsCollector;
+use anyhow::{Context, Result};
+use std::fmt::Write as _;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+const CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+const MAX_REQUEST_SIZE: usize = 8192;
+
+/// Labels attached to every exported sample
+#[derive(Debug, Clone)]
+pub struct ExporterLabels {
+    pub mechanism: String,
+    pub role: String,
+}
+
+impl MetricsCollector {
+    /// Serve `/metrics` in OpenMetrics text format on `addr`.
+    ///
+    /// Returns the bound address (useful with port 0) and the server task.
+    pub async fn serve_openmetrics(
+        self: Arc<Self>,
+        addr: &str,
+        labels: ExporterLabels,
+    ) -> Result<(SocketAddr, tokio::task::JoinHandle<()>)> {
+        let listener = TcpListener::bind(addr)
+            .await
+            .with_context(|| format!("Failed to bind metrics endpoint on {}", addr))?;
+        let local_addr = listener.local_addr()?;
+
+        let handle = tokio::spawn(async move {
+            loop {
+                let (stream, _) = match listener.accept().await {
+                    Ok(conn) => conn,
+                    Err(_) => continue,
+                };
+
+                let collector = self.clone();
+                let labels = labels.clone();
+                tokio::spawn(async move {
+                    let _ = handle_scrape(stream, &collector, &labels).await;
+                });
+            }
+        });
+
+        Ok((local_addr, handle))
+    }
+
+    /// Render the current collector state in OpenMetrics text format
+    pub async fn render_openmetrics(&self, labels: &ExporterLabels) -> String {
+        let label_str = format!(
+            "node_id=\"{}\",mechanism=\"{}\",role=\"{}\"",
+            escape_label(&self.node_id),
+            escape_label(&labels.mechanism),
+            escape_label(&labels.role),
+        );
+
+        let mut out = String::new();
+
+        {
+            let stream = self.stream_metrics.read().await;
+            let latest = stream.last();
+            let drops: u64 = stream.iter().map(|m| m.drops).sum();
+            let tag_failures: u64 = stream.iter().map(|m| m.tag_failures).sum();
+
+            let gauge = |out: &mut String, name: &str, help: &str, value: f64| {
+                let _ = writeln!(out, "# TYPE {} gauge", name);
+                let _ = writeln!(out, "# HELP {} {}", name, help);
+                let _ = writeln!(out, "{}{{{}}} {}", name, label_str, value);
+            };
+
+            // The background sampler pushes zeroed rows between stream updates,
+            // so stream gauges come from the last row the stream handler filled
+            if let Some(m) = latest {
+                let active = stream.iter().rev().find(|m| m.fps > 0.0);
+                let measured = stream.iter().rev().find(|m| m.latency_ms > 0.0);
+                gauge(&mut out, "stream_fps", "Frames per second.", active.map_or(0.0, |a| a.fps as f64));
+                gauge(&mut out, "stream_goodput_mbps", "Application goodput in Mbit/s.", active.map_or(0.0, |a| a.goodput_mbps as f64));
+                gauge(&mut out, "stream_latency_ms", "Most recent end-to-end frame latency in ms.", measured.map_or(0.0, |l| l.latency_ms as f64));
+                gauge(&mut out, "system_cpu_percent", "Average CPU usage across all cores.", m.cpu_pct as f64);
+                gauge(&mut out, "system_memory_mb", "Used system memory in MB.", m.mem_mb);
+                gauge(&mut out, "system_temperature_celsius", "SoC temperature.", m.temp_c as f64);
+            }
+
+            let _ = writeln!(out, "# TYPE stream_drops counter");
+            let _ = writeln!(out, "# HELP stream_drops Frames dropped before encryption.");
+            let _ = writeln!(out, "stream_drops_total{{{}}} {}", label_str, drops);
+
+            let _ = writeln!(out, "# TYPE stream_tag_failures counter");
+            let _ = writeln!(out, "# HELP stream_tag_failures AEAD authentication failures.");
+            let _ = writeln!(out, "stream_tag_failures_total{{{}}} {}", label_str, tag_failures);
+        }
+
+        let rekeys = *self.rekeys.read().await;
+        let _ = writeln!(out, "# TYPE session_rekeys counter");
+        let _ = writeln!(out, "# HELP session_rekeys Session rekey events.");
+        let _ = writeln!(out, "session_rekeys_total{{{}}} {}", label_str, rekeys);
+
+        {
+            let handshakes = self.handshakes.read().await;
+            let ok = handshakes.iter().filter(|h| h.success).count();
+            let failed = handshakes.len() - ok;
+
+            let _ = writeln!(out, "# TYPE handshakes counter");
+            let _ = writeln!(out, "# HELP handshakes Completed key establishment handshakes.");
+            let _ = writeln!(out, "handshakes_total{{{},result=\"success\"}} {}", label_str, ok);
+            let _ = writeln!(out, "handshakes_total{{{},result=\"failure\"}} {}", label_str, failed);
+
+            let confirm_failures: u64 = handshakes.iter().map(|h| h.confirm_failures as u64).sum();
+            let _ = writeln!(out, "# TYPE handshake_confirm_failures counter");
+            let _ = writeln!(out, "# HELP handshake_confirm_failures Key confirmation (finished message) failures.");
+            let _ = writeln!(out, "handshake_confirm_failures_total{{{}}} {}", label_str, confirm_failures);
+
+            if let Some(h) = handshakes.last() {
+                let secs = (h.ts_end - h.ts_start).num_microseconds().unwrap_or(0) as f64 / 1e6;
+                let _ = writeln!(out, "# TYPE handshake_duration_seconds gauge");
+                let _ = writeln!(out, "# HELP handshake_duration_seconds Duration of the last handshake.");
+                let _ = writeln!(out, "handshake_duration_seconds{{{}}} {}", label_str, secs);
+
+                let _ = writeln!(out, "# TYPE handshake_bytes gauge");
+                let _ = writeln!(out, "# HELP handshake_bytes Bytes exchanged in the last handshake.");
+                let _ = writeln!(out, "handshake_bytes{{{},direction=\"tx\"}} {}", label_str, h.bytes_tx);
+                let _ = writeln!(out, "handshake_bytes{{{},direction=\"rx\"}} {}", label_str, h.bytes_rx);
+            }
+        }
+
+        out.push_str("# EOF\n");
+        out
+    }
+}
+
+async fn handle_scrape(
+    mut stream: TcpStream,
+    collector: &MetricsCollector,
+    labels: &ExporterLabels,
+) -> Result<()> {
+    // Read until end of request headers; the body (if any) is ignored
+    let mut buf = Vec::with_capacity(1024);
+    let mut chunk = [0u8; 1024];
+    while !buf.windows(4).any(|w| w == b"\r\n\r\n") {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 || buf.len() + n > MAX_REQUEST_SIZE {
+            return Ok(());
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    }
+
+    let request_line = buf.split(|&b| b == b'\r').next().unwrap_or_default();
+    let request_line = String::from_utf8_lossy(request_line);
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default();
+    let path = parts.next().unwrap_or_default();
+
+    let response = if method == "GET" && (path == "/metrics" || path.starts_with("/metrics?")) {
+        let body = collector.render_openmetrics(labels).await;
+        format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            CONTENT_TYPE,
+            body.len(),
+            body
+        )
+    } else {
+        "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string()
+    };
+
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+/// Escape a label value per the OpenMetrics text format
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{HandshakeMetrics, StreamMetrics};
+    use chrono::Utc;
+
+    async fn scrape(addr: SocketAddr, path: &str) -> String {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let request = format!("GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path);
+        stream.write_all(request.as_bytes()).await.unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    #[tokio::test]
+    async fn test_scrape_openmetrics() {
+        let collector = Arc::new(MetricsCollector::new("pi-\"a\"".to_string()));
+        let labels = ExporterLabels {
+            mechanism: "ECDH-P256".to_string(),
+            role: "sender".to_string(),
+        };
+
+        let now = Utc::now();
+        collector.record_handshake(HandshakeMetrics {
+            ts_start: now,
+            ts_end: now,
+            mechanism: "ECDH-P256".to_string(),
+            bytes_tx: 69,
+            bytes_rx: 69,
+            cpu_avg: 0.0,
+            mem_mb: 0.0,
+            energy_j: 0.0,
+            success: true,
+            key_source: "ephemeral".to_string(),
+            confirm_failures: 0,
+        }).await;
+        collector.record_rekey().await;
+        collector.record_rekey().await;
+
+        let (addr, server) = collector.clone()
+            .serve_openmetrics("127.0.0.1:0", labels)
+            .await
+            .unwrap();
+
+        let response = scrape(addr, "/metrics").await;
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains(CONTENT_TYPE));
+        assert!(response.contains(
+            "session_rekeys_total{node_id=\"pi-\\\"a\\\"\",mechanism=\"ECDH-P256\",role=\"sender\"} 2"
+        ));
+        assert!(response.contains("result=\"success\"} 1"));
+        assert!(response.contains("direction=\"tx\"} 69"));
+        assert!(response.contains("handshake_confirm_failures_total{node_id=\"pi-\\\"a\\\"\",mechanism=\"ECDH-P256\",role=\"sender\"} 0"));
+        assert!(response.ends_with("# EOF\n"));
+
+        let response = scrape(addr, "/other").await;
+        assert!(response.starts_with("HTTP/1.1 404"));
+
+        server.abort();
+    }
+
+    #[tokio::test]
+    async fn test_stream_gauges_skip_sampler_rows() {
+        let collector = MetricsCollector::new("pi-a".to_string());
+        let labels = ExporterLabels {
+            mechanism: "ECDH-P256".to_string(),
+            role: "receiver".to_string(),
+        };
+
+        // A frame update followed by a row from the 250 ms sampler
+        for (fps, latency) in [(30.0, 12.5), (0.0, 0.0)] {
+            collector.stream_metrics.write().await.push(StreamMetrics {
+                ts: Utc::now(),
+                fps,
+                goodput_mbps: fps / 10.0,
+                latency_ms: latency,
+                cpu_pct: 40.0,
+                mem_mb: 100.0,
+                temp_c: 55.0,
+                drops: 0,
+                tag_failures: 0,
+            });
+        }
+
+        let body = collector.render_openmetrics(&labels).await;
+        let suffix = "{node_id=\"pi-a\",mechanism=\"ECDH-P256\",role=\"receiver\"}";
+        assert!(body.contains(&format!("stream_fps{} 30\n", suffix)));
+        assert!(body.contains(&format!("stream_goodput_mbps{} 3\n", suffix)));
+        assert!(body.contains(&format!("stream_latency_ms{} 12.5\n", suffix)));
+    }
+}
@@ -8,6 +8,12 @@ use sysinfo::System;
 use tokio::sync::RwLock;
 use tokio::time::{interval, Duration};
 
+#[cfg(feature = "prometheus")]
+pub mod exporter;
+
+pub mod profiler;
+pub use profiler::{HandshakeProfiler, PowerSource, ResourceUsage};
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HandshakeMetrics {
     pub ts_start: DateTime<Utc>,
@@ -19,6 +25,10 @@ pub struct HandshakeMetrics {
     pub mem_mb: f64,
     pub energy_j: f64,
     pub success: bool,
+    /// Origin of the key pair: "pool"/"inline" (RSA responder), "peer", "ephemeral" or "file"
+    pub key_source: String,
+    /// Finished messages that failed to verify (the handshake is aborted on the first)
+    pub confirm_failures: u32,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -44,11 +54,30 @@ pub struct PowerSample {
     pub node_id: String,
 }
 
+/// Current values for live displays (overlay, status lines)
+#[derive(Debug, Clone, Default)]
+pub struct LiveStats {
+    pub fps: f32,
+    pub goodput_mbps: f32,
+    pub latency_p95_ms: f32,
+    pub cpu_pct: f32,
+    pub temp_c: f32,
+    pub drops: u64,
+    pub tag_failures: u64,
+    pub rekeys: u64,
+    /// When the key in use was established (last handshake or rekey)
+    pub key_since: Option<DateTime<Utc>>,
+    pub last_rekey: Option<DateTime<Utc>>,
+}
+
 pub struct MetricsCollector {
     system: Arc<RwLock<System>>,
     node_id: String,
     power_samples: Arc<RwLock<Vec<PowerSample>>>,
     stream_metrics: Arc<RwLock<Vec<StreamMetrics>>>,
+    handshakes: Arc<RwLock<Vec<HandshakeMetrics>>>,
+    rekeys: Arc<RwLock<u64>>,
+    last_rekey: Arc<RwLock<Option<DateTime<Utc>>>>,
 }
 
 impl MetricsCollector {
@@ -58,6 +87,9 @@ impl MetricsCollector {
             node_id,
             power_samples: Arc::new(RwLock::new(Vec::new())),
             stream_metrics: Arc::new(RwLock::new(Vec::new())),
+            handshakes: Arc::new(RwLock::new(Vec::new())),
+            rekeys: Arc::new(RwLock::new(0)),
+            last_rekey: Arc::new(RwLock::new(None)),
         }
     }
     
@@ -139,6 +171,56 @@ impl MetricsCollector {
         }
     }
     
+    /// Record a completed handshake
+    pub async fn record_handshake(&self, metrics: HandshakeMetrics) {
+        self.handshakes.write().await.push(metrics);
+    }
+    
+    /// Record a session rekey event
+    pub async fn record_rekey(&self) {
+        *self.rekeys.write().await += 1;
+        *self.last_rekey.write().await = Some(Utc::now());
+    }
+    
+    /// Latest stream values for live display
+    ///
+    /// fps/goodput come from the last sample the stream handler updated (the
+    /// background sampler adds zeroed rows in between); p95 latency is over
+    /// all measured frames so far.
+    pub async fn live_stats(&self) -> LiveStats {
+        let mut stats = LiveStats::default();
+        
+        {
+            let stream = self.stream_metrics.read().await;
+            if let Some(m) = stream.last() {
+                stats.cpu_pct = m.cpu_pct;
+                stats.temp_c = m.temp_c;
+            }
+            if let Some(m) = stream.iter().rev().find(|m| m.fps > 0.0) {
+                stats.fps = m.fps;
+                stats.goodput_mbps = m.goodput_mbps;
+            }
+            stats.drops = stream.iter().map(|m| m.drops).sum();
+            stats.tag_failures = stream.iter().map(|m| m.tag_failures).sum();
+            
+            let mut latencies: Vec<f32> = stream.iter().map(|m| m.latency_ms).filter(|l| *l > 0.0).collect();
+            if !latencies.is_empty() {
+                latencies.sort_by(|a, b| a.partial_cmp(b).unwrap());
+                stats.latency_p95_ms = latencies[(latencies.len() * 95) / 100];
+            }
+        }
+        
+        stats.rekeys = *self.rekeys.read().await;
+        stats.last_rekey = *self.last_rekey.read().await;
+        let handshake_end = self.handshakes.read().await.iter()
+            .filter(|h| h.success)
+            .map(|h| h.ts_end)
+            .max();
+        stats.key_since = stats.last_rekey.max(handshake_end);
+        
+        stats
+    }
+    
     /// Write handshake metrics to CSV
     pub fn write_handshake_csv<P: AsRef<Path>>(
         metrics: &[HandshakeMetrics],
@@ -249,4 +331,32 @@ mod tests {
         let energy = collector.calculate_energy(Some("test")).await;
         assert!(energy > 0.0);
     }
+    
+    #[tokio::test]
+    async fn test_live_stats() {
+        let collector = MetricsCollector::new("test".to_string());
+        assert_eq!(collector.live_stats().await.key_since, None);
+        
+        for (fps, latency) in [(30.0, 10.0), (29.0, 20.0), (0.0, 0.0)] {
+            collector.stream_metrics.write().await.push(StreamMetrics {
+                ts: Utc::now(),
+                fps,
+                goodput_mbps: fps * 2.0,
+                latency_ms: latency,
+                cpu_pct: 50.0,
+                mem_mb: 100.0,
+                temp_c: 60.0,
+                drops: 0,
+                tag_failures: 1,
+            });
+        }
+        collector.record_rekey().await;
+        
+        let stats = collector.live_stats().await;
+        assert_eq!((stats.fps, stats.goodput_mbps), (29.0, 58.0));
+        assert_eq!(stats.latency_p95_ms, 20.0);
+        assert_eq!((stats.tag_failures, stats.rekeys), (3, 1));
+        assert_eq!(stats.key_since, stats.last_rekey);
+        assert!(stats.key_since.is_some());
+    }
 }
\ No newline at end of file
@@ -0,0 +1,236 @@
+// Scoped resource accounting for handshakes
+// crates/metrics/src/profiler.rs
+
+use crate::HandshakeMetrics;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+const SAMPLE_INTERVAL: Duration = Duration::from_millis(5);
+
+/// Where power readings come from while profiling
+#[derive(Debug, Clone)]
+pub enum PowerSource {
+    /// Fixed voltage/current when no meter is attached
+    Simulated { volts: f32, amps: f32 },
+    /// hwmon directory of a power sensor (e.g. INA219 via the ina2xx driver)
+    Hwmon(PathBuf),
+}
+
+impl Default for PowerSource {
+    fn default() -> Self {
+        // Matches the handshake phase figure used in the power CSV
+        PowerSource::Simulated { volts: 5.0, amps: 2.5 }
+    }
+}
+
+impl PowerSource {
+    /// Current power draw in watts
+    pub fn read_watts(&self) -> Option<f32> {
+        match self {
+            PowerSource::Simulated { volts, amps } => Some(volts * amps),
+            PowerSource::Hwmon(dir) => {
+                // power1_input is reported in microwatts
+                let raw = std::fs::read_to_string(dir.join("power1_input")).ok()?;
+                let micro_watts: f64 = raw.trim().parse().ok()?;
+                Some((micro_watts / 1_000_000.0) as f32)
+            }
+        }
+    }
+}
+
+/// Resources consumed over a profiled interval
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ResourceUsage {
+    /// Process CPU time over wall time, averaged across all cores (0-100)
+    pub cpu_avg: f32,
+    /// Peak resident set size observed during the interval
+    pub mem_mb: f64,
+    /// Energy integrated from the power source
+    pub energy_j: f64,
+    pub wall: Duration,
+}
+
+struct SamplerResult {
+    peak_rss_kb: u64,
+    energy_j: f64,
+}
+
+/// Guard that samples process CPU time, RSS and power while alive.
+///
+/// Start it at the top of a handshake and call `finish` (or `apply`) once
+/// the key material is ready. Dropping it without finishing just stops the
+/// sampler thread.
+pub struct HandshakeProfiler {
+    start_wall: Instant,
+    start_cpu: Duration,
+    stop: Arc<AtomicBool>,
+    sampler: Option<JoinHandle<SamplerResult>>,
+}
+
+impl HandshakeProfiler {
+    pub fn start(power: PowerSource) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let stop_flag = stop.clone();
+
+        let start_wall = Instant::now();
+        let start_cpu = process_cpu_time();
+
+        let sampler = std::thread::spawn(move || {
+            let mut peak_rss_kb = current_rss_kb().unwrap_or(0);
+            let mut energy_j = 0.0_f64;
+            let mut last_ts = Instant::now();
+            let mut last_watts = power.read_watts().unwrap_or(0.0);
+
+            loop {
+                let stopping = stop_flag.load(Ordering::Acquire);
+                if !stopping {
+                    std::thread::sleep(SAMPLE_INTERVAL);
+                }
+
+                let now = Instant::now();
+                let watts = power.read_watts().unwrap_or(last_watts);
+
+                // Trapezoidal integration, same as MetricsCollector::calculate_energy
+                let dt = (now - last_ts).as_secs_f64();
+                energy_j += (last_watts + watts) as f64 / 2.0 * dt;
+                last_ts = now;
+                last_watts = watts;
+
+                if let Some(rss) = current_rss_kb() {
+                    peak_rss_kb = peak_rss_kb.max(rss);
+                }
+
+                if stopping {
+                    break;
+                }
+            }
+
+            SamplerResult { peak_rss_kb, energy_j }
+        });
+
+        Self {
+            start_wall,
+            start_cpu,
+            stop,
+            sampler: Some(sampler),
+        }
+    }
+
+    /// Stop sampling and return the resources used since `start`
+    pub fn finish(mut self) -> ResourceUsage {
+        let wall = self.start_wall.elapsed();
+        let cpu = process_cpu_time().saturating_sub(self.start_cpu);
+
+        self.stop.store(true, Ordering::Release);
+        let result = self.sampler.take()
+            .and_then(|h| h.join().ok())
+            .unwrap_or(SamplerResult { peak_rss_kb: 0, energy_j: 0.0 });
+
+        let cores = std::thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1) as f64;
+        let cpu_avg = if wall.is_zero() {
+            0.0
+        } else {
+            (cpu.as_secs_f64() / wall.as_secs_f64() / cores * 100.0).min(100.0) as f32
+        };
+
+        ResourceUsage {
+            cpu_avg,
+            mem_mb: result.peak_rss_kb as f64 / 1024.0,
+            energy_j: result.energy_j,
+            wall,
+        }
+    }
+
+    /// Stop sampling and fill `cpu_avg`, `mem_mb` and `energy_j` of `metrics`
+    pub fn apply(self, metrics: &mut HandshakeMetrics) -> ResourceUsage {
+        let usage = self.finish();
+        metrics.cpu_avg = usage.cpu_avg;
+        metrics.mem_mb = usage.mem_mb;
+        metrics.energy_j = usage.energy_j;
+        usage
+    }
+}
+
+impl Drop for HandshakeProfiler {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Release);
+        if let Some(handle) = self.sampler.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// CPU time consumed by this process, summed over its threads.
+///
+/// Uses the nanosecond `schedstat` counters when the kernel exposes them and
+/// falls back to utime+stime from /proc/self/stat (10 ms resolution).
+fn process_cpu_time() -> Duration {
+    let from_schedstat = || -> Option<Duration> {
+        let mut total_ns = 0u64;
+        for entry in std::fs::read_dir("/proc/self/task").ok()? {
+            let path = entry.ok()?.path().join("schedstat");
+            // Threads can exit between read_dir and read; skip those
+            if let Ok(stat) = std::fs::read_to_string(path) {
+                total_ns += stat.split_whitespace().next()?.parse::<u64>().ok()?;
+            }
+        }
+        Some(Duration::from_nanos(total_ns))
+    };
+
+    let from_stat = || -> Option<Duration> {
+        const USER_HZ: u64 = 100;
+        let stat = std::fs::read_to_string("/proc/self/stat").ok()?;
+        // Fields after the parenthesised comm; utime and stime are fields 14 and 15
+        let rest = &stat[stat.rfind(')')? + 2..];
+        let mut fields = rest.split_whitespace().skip(11);
+        let utime: u64 = fields.next()?.parse().ok()?;
+        let stime: u64 = fields.next()?.parse().ok()?;
+        Some(Duration::from_millis((utime + stime) * 1000 / USER_HZ))
+    };
+
+    from_schedstat().or_else(from_stat).unwrap_or(Duration::ZERO)
+}
+
+/// Current resident set size from /proc/self/status
+fn current_rss_kb() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    status
+        .lines()
+        .find(|l| l.starts_with("VmRSS:"))?
+        .split_whitespace()
+        .nth(1)?
+        .parse()
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profiler_accounts_busy_work() {
+        let profiler = HandshakeProfiler::start(PowerSource::Simulated { volts: 5.0, amps: 2.0 });
+
+        // Burn some CPU so the counters move
+        let start = Instant::now();
+        let mut acc = 0u64;
+        while start.elapsed() < Duration::from_millis(50) {
+            acc = acc.wrapping_mul(6364136223846793005).wrapping_add(1);
+        }
+        std::hint::black_box(acc);
+
+        let usage = profiler.finish();
+        assert!(usage.wall >= Duration::from_millis(50));
+        assert!(usage.cpu_avg > 0.0);
+        // 10 W for at least 50 ms
+        assert!(usage.energy_j >= 0.5 * 0.9);
+        if cfg!(target_os = "linux") {
+            assert!(usage.mem_mb > 0.0);
+        }
+    }
+}
@@ -0,0 +1,132 @@
+// Control API client
+// crates/stream/src/bin/streamctl.rs
+//
+// Sends one command to a running `stream --control-socket <path>` and prints
+// the JSON response. Exit status: 0 = ok, 1 = the stream rejected the command
+// or could not be reached.
+//
+//   streamctl -s /tmp/stream.sock status
+//   streamctl -s /tmp/stream.sock set-rate --fps 10
+//   printf '{"cmd":"rekey"}\n{"cmd":"status"}\n' | streamctl -s /tmp/stream.sock batch
+
+use anyhow::{bail, Context, Result};
+use clap::{Parser, Subcommand, ValueEnum};
+use serde_json::{json, Value};
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+
+#[derive(Parser, Debug)]
+#[command(about = "Send runtime commands to a running stream")]
+struct Args {
+    /// Control socket of the stream (its --control-socket)
+    #[arg(short, long)]
+    socket: PathBuf,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Role, key epoch, frame count, rate targets, peers and recording
+    Status,
+    /// Move every session to the next key epoch now
+    Rekey,
+    /// Change the sender's frame rate and/or bitrate cap (0 = no cap)
+    SetRate {
+        #[arg(long)]
+        fps: Option<u32>,
+        #[arg(long)]
+        bitrate_kbps: Option<u32>,
+    },
+    /// Start streaming to another receiver: node_id:host:port
+    AddMember { member: String },
+    /// Stop streaming to a member
+    RemoveMember { node_id: String },
+    /// Append frames to a raw I420 file (default: recording_<node>_<time>.i420)
+    StartRecording {
+        #[arg(long)]
+        path: Option<String>,
+    },
+    /// Close the current recording
+    StopRecording,
+    /// Show or hide the receiver's statistics overlay (toggles if omitted)
+    Overlay {
+        #[arg(value_enum)]
+        state: Option<OnOff>,
+    },
+    /// Send JSON request lines from stdin, printing one response per line
+    Batch,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum OnOff {
+    On,
+    Off,
+}
+
+impl Command {
+    fn request(&self) -> Value {
+        match self {
+            Command::Status => json!({ "cmd": "status" }),
+            Command::Rekey => json!({ "cmd": "rekey" }),
+            Command::SetRate { fps, bitrate_kbps } => json!({ "cmd": "set-rate", "fps": fps, "bitrate_kbps": bitrate_kbps }),
+            Command::AddMember { member } => json!({ "cmd": "add-member", "member": member }),
+            Command::RemoveMember { node_id } => json!({ "cmd": "remove-member", "node_id": node_id }),
+            Command::StartRecording { path } => json!({ "cmd": "start-recording", "path": path }),
+            Command::StopRecording => json!({ "cmd": "stop-recording" }),
+            Command::Overlay { state } => json!({ "cmd": "overlay", "visible": state.map(|s| matches!(s, OnOff::On)) }),
+            Command::Batch => unreachable!("batch requests come from stdin"),
+        }
+    }
+}
+
+/// Send one request line and return the response line
+fn call(conn: &mut UnixStream, responses: &mut impl BufRead, request: &str) -> Result<String> {
+    conn.write_all(format!("{}\n", request.trim()).as_bytes())?;
+    let mut line = String::new();
+    if responses.read_line(&mut line)? == 0 {
+        bail!("Stream closed the control connection");
+    }
+    Ok(line.trim_end().to_string())
+}
+
+fn is_ok(response: &str) -> bool {
+    serde_json::from_str::<Value>(response).is_ok_and(|v| v["ok"] == true)
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+
+    let mut conn = UnixStream::connect(&args.socket)
+        .with_context(|| format!("Failed to connect to {}", args.socket.display()))?;
+    let mut responses = BufReader::new(conn.try_clone()?);
+
+    let all_ok = match args.command {
+        Command::Batch => {
+            let mut all_ok = true;
+            for line in std::io::stdin().lock().lines() {
+                let line = line?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let response = call(&mut conn, &mut responses, &line)?;
+                all_ok &= is_ok(&response);
+                println!("{}", response);
+            }
+            all_ok
+        }
+        ref command => {
+            let response = call(&mut conn, &mut responses, &command.request().to_string())?;
+            let parsed: Value = serde_json::from_str(&response).context("Invalid response")?;
+            println!("{}", serde_json::to_string_pretty(&parsed)?);
+            is_ok(&response)
+        }
+    };
+
+    if !all_ok {
+        std::process::exit(1);
+    }
+    Ok(())
+}
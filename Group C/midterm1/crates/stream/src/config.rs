@@ -0,0 +1,465 @@
+// Declarative TOML configuration profiles
+// crates/stream/src/config.rs
+//
+// A config file holds named profiles whose keys are the long flag names:
+//
+//   [defaults]                  # optional, applied under every profile
+//   node-id = "pi-1"
+//
+//   [profiles.leader]
+//   mode = "group-leader"
+//   members = ["pi-2:192.168.1.102:8443", "pi-3:192.168.1.103:8443"]
+//
+// Profile values are turned into flags and placed before the real command
+// line, so clap validates them and anything given on the CLI wins. Every
+// boolean flag also gets a `--no-<flag>` form so the CLI can switch off
+// something a profile switched on.
+
+use anyhow::{anyhow, bail, Context, Result};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+/// Flags that only make sense on the command line
+const CLI_ONLY: &[&str] = &["config", "profile", "print-config", "print-effective-config", "help", "version"];
+
+/// One profile entry: a boolean flag switched on or off, or an option value
+#[derive(Debug, Clone, PartialEq)]
+enum ProfileValue {
+    Flag(bool),
+    Value(String),
+}
+
+impl ProfileValue {
+    /// The flags that set this value; `false` goes through `--no-<flag>`
+    fn to_args(&self, key: &str) -> Vec<OsString> {
+        match self {
+            ProfileValue::Flag(true) => vec![format!("--{}", key).into()],
+            ProfileValue::Flag(false) => vec![format!("--no-{}", key).into()],
+            ProfileValue::Value(v) => vec![format!("--{}", key).into(), v.into()],
+        }
+    }
+}
+
+/// Profile values resolved from a config file
+#[derive(Debug, Clone)]
+pub struct Profile {
+    pub path: PathBuf,
+    pub name: String,
+    /// (long flag name, value)
+    values: Vec<(String, ProfileValue)>,
+}
+
+impl Profile {
+    fn to_args(&self) -> Vec<OsString> {
+        self.values.iter().flat_map(|(key, value)| value.to_args(key)).collect()
+    }
+
+    fn contains(&self, key: &str) -> bool {
+        self.values.iter().any(|(k, _)| k == key)
+    }
+}
+
+/// Arguments after layering CLI flags over the selected profile
+pub struct Parsed<T> {
+    pub args: T,
+    matches: ArgMatches,
+    command: Command,
+    profile: Option<Profile>,
+    cli_keys: Vec<String>,
+    /// `--no-<flag>` names added by `with_negations`
+    negations: Vec<String>,
+}
+
+/// Parse `T` from `argv`, applying `--config` / `--profile` underneath the CLI.
+///
+/// Errors in the command line itself exit through clap as usual; problems in
+/// the config file are returned with the file, profile and key in the message.
+pub fn parse<T: clap::Parser>(argv: Vec<OsString>) -> Result<Parsed<T>> {
+    let command = with_negations(T::command().args_override_self(true));
+
+    let config_path = flag_value(&argv, "config");
+    let profile_name = flag_value(&argv, "profile");
+    if profile_name.is_some() && config_path.is_none() {
+        bail!("--profile requires --config");
+    }
+
+    let profile = match &config_path {
+        Some(path) => Some(load_profile(&command, Path::new(path), profile_name.as_deref())?),
+        None => None,
+    };
+
+    let mut full_argv: Vec<OsString> = argv.iter().take(1).cloned().collect();
+    if let Some(p) = &profile {
+        full_argv.extend(p.to_args());
+    }
+    full_argv.extend(argv.iter().skip(1).cloned());
+
+    let matches = command.clone()
+        .try_get_matches_from(&full_argv)
+        .unwrap_or_else(|e| e.exit());
+    let args = T::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    let negations = command.get_arguments()
+        .filter(|a| matches!(a.get_action(), ArgAction::SetFalse))
+        .filter_map(|a| a.get_long().map(str::to_owned))
+        .collect();
+
+    Ok(Parsed {
+        args,
+        matches,
+        command,
+        profile,
+        cli_keys: cli_keys(&argv),
+        negations,
+    })
+}
+
+/// Add `--no-<flag>` for every boolean flag; whichever of the pair comes
+/// last wins, so `--no-display` on the CLI beats `display = true` in a profile
+fn with_negations(mut command: Command) -> Command {
+    let flags: Vec<(String, String)> = command.get_arguments()
+        .filter(|a| matches!(a.get_action(), ArgAction::SetTrue))
+        .filter_map(|a| Some((a.get_id().to_string(), a.get_long()?.to_string())))
+        .filter(|(_, long)| !CLI_ONLY.contains(&long.as_str()))
+        .collect();
+
+    for (id, long) in flags {
+        let negated = format!("no-{}", long);
+        command = command
+            .mut_arg(&id, |a| a.overrides_with(negated.clone()))
+            .arg(Arg::new(negated.clone())
+                .long(negated)
+                .action(ArgAction::SetFalse)
+                .overrides_with(id)
+                .help(format!("Turn off --{}", long)));
+    }
+    command
+}
+
+impl<T> Parsed<T> {
+    /// Every option with its final value and where it came from, as TOML
+    /// that can itself be loaded back as a profile
+    pub fn effective_toml(&self) -> String {
+        let mut out = String::from("# Effective stream configuration\n");
+        match &self.profile {
+            Some(p) => out.push_str(&format!("# config: {}, profile: {}\n", p.path.display(), p.name)),
+            None => out.push_str("# config: none\n"),
+        }
+        out.push_str(&format!("# generated: {}\n\n", chrono::Utc::now().to_rfc3339()));
+
+        for arg in self.command.get_arguments() {
+            let Some(key) = arg.get_long() else { continue };
+            if CLI_ONLY.contains(&key) {
+                continue;
+            }
+
+            if self.negations.iter().any(|n| n == key) {
+                continue;
+            }
+
+            let negated = format!("no-{}", key);
+            let source = if self.cli_keys.iter().any(|k| k == key || *k == negated) {
+                "cli"
+            } else if self.profile.as_ref().is_some_and(|p| p.contains(key)) {
+                "profile"
+            } else {
+                "default"
+            };
+
+            let raw = self.matches
+                .get_raw(arg.get_id().as_str())
+                .and_then(|mut v| v.next_back())
+                .map(|v| v.to_string_lossy().into_owned());
+
+            let line = match raw {
+                Some(v) => format!("{} = {}", key, toml_literal(&v)),
+                None => format!("# {} (unset)", key),
+            };
+            out.push_str(&format!("{:<48} # {}\n", line, source));
+        }
+        out
+    }
+}
+
+/// Read `path` and resolve the named profile (or the only one) against `command`
+pub fn load_profile(command: &Command, path: &Path, name: Option<&str>) -> Result<Profile> {
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read config file {}", path.display()))?;
+    let doc: toml::Table = text.parse()
+        .map_err(|e| anyhow!("{}: invalid TOML: {}", path.display(), e))?;
+
+    for key in doc.keys() {
+        if key != "defaults" && key != "profiles" {
+            bail!("{}: unexpected top-level key `{}` (expected [defaults] or [profiles.<name>])", path.display(), key);
+        }
+    }
+
+    let table = |key: &str, v: &toml::Value| -> Result<toml::Table> {
+        v.as_table()
+            .cloned()
+            .ok_or_else(|| anyhow!("{}: `{}` must be a table", path.display(), key))
+    };
+
+    let defaults = match doc.get("defaults") {
+        Some(v) => table("defaults", v)?,
+        None => toml::Table::new(),
+    };
+    let profiles = match doc.get("profiles") {
+        Some(v) => table("profiles", v)?,
+        None => toml::Table::new(),
+    };
+
+    let available = || profiles.keys().cloned().collect::<Vec<_>>().join(", ");
+    let (name, body) = match name {
+        Some(n) => match profiles.get(n) {
+            Some(body) => (n.to_string(), table(&format!("profiles.{}", n), body)?),
+            None => bail!("{}: no profile `{}` (available: {})", path.display(), n, available()),
+        },
+        None if profiles.len() == 1 => {
+            let (n, body) = profiles.iter().next().unwrap();
+            (n.clone(), table(&format!("profiles.{}", n), body)?)
+        }
+        None if profiles.is_empty() => ("defaults".to_string(), toml::Table::new()),
+        None => bail!("{}: several profiles defined, pick one with --profile ({})", path.display(), available()),
+    };
+
+    // Profile keys replace defaults with the same name
+    let mut merged: BTreeMap<String, (String, toml::Value)> = BTreeMap::new();
+    for (section, entries) in [("defaults", &defaults), (&format!("profiles.{}", name)[..], &body)] {
+        for (key, value) in entries {
+            merged.insert(key.replace('_', "-"), (section.to_string(), value.clone()));
+        }
+    }
+
+    let mut values = Vec::new();
+    for (key, (section, value)) in merged {
+        let at = || format!("{} [{}] `{}`", path.display(), section, key);
+
+        if CLI_ONLY.contains(&key.as_str()) {
+            bail!("{}: only valid on the command line", at());
+        }
+        let arg = command.get_arguments()
+            .find(|a| a.get_long() == Some(key.as_str()))
+            .ok_or_else(|| anyhow!("{}: unknown option (see `stream --help` for valid names)", at()))?;
+
+        let is_flag = !arg.get_action().takes_values();
+        let value = match (&value, is_flag) {
+            (toml::Value::Boolean(b), true) => ProfileValue::Flag(*b),
+            (_, true) => bail!("{}: expected true or false", at()),
+            (toml::Value::String(s), false) => ProfileValue::Value(s.clone()),
+            (toml::Value::Integer(i), false) => ProfileValue::Value(i.to_string()),
+            (toml::Value::Float(f), false) => ProfileValue::Value(f.to_string()),
+            (toml::Value::Boolean(b), false) => ProfileValue::Value(b.to_string()),
+            (toml::Value::Array(items), false) => {
+                let parts = items.iter()
+                    .map(|item| match item {
+                        toml::Value::String(s) => Ok(s.clone()),
+                        toml::Value::Integer(i) => Ok(i.to_string()),
+                        _ => Err(anyhow!("{}: list entries must be strings or integers", at())),
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                ProfileValue::Value(parts.join(","))
+            }
+            (_, false) => bail!("{}: unsupported value type", at()),
+        };
+
+        // Run the value through clap on its own so a bad value is reported
+        // against the file rather than as a confusing command-line error
+        let mut probe: Vec<OsString> = vec!["stream".into()];
+        for required in command.get_arguments().filter(|a| a.is_required_set()) {
+            if required.get_long() != Some(key.as_str()) {
+                let id = required.get_id().as_str();
+                let placeholder = required.get_possible_values().first()
+                    .map(|v| v.get_name().to_string())
+                    .unwrap_or_else(|| "x".to_string());
+                probe.push(format!("--{}", required.get_long().unwrap_or(id)).into());
+                probe.push(placeholder.into());
+            }
+        }
+        probe.extend(value.to_args(&key));
+        if let Err(e) = command.clone().try_get_matches_from(probe) {
+            let rendered = e.render().to_string();
+            let first = rendered.lines().next().unwrap_or_default().trim_start_matches("error: ");
+            bail!("{}: {}", at(), first);
+        }
+
+        values.push((key, value));
+    }
+
+    Ok(Profile {
+        path: path.to_path_buf(),
+        name,
+        values,
+    })
+}
+
+/// Value of `--name X` or `--name=X` in raw argv (last occurrence wins)
+fn flag_value(argv: &[OsString], name: &str) -> Option<String> {
+    let flag = format!("--{}", name);
+    let prefix = format!("--{}=", name);
+    let mut found = None;
+    let mut iter = argv.iter().skip(1).map(|a| a.to_string_lossy());
+    while let Some(arg) = iter.next() {
+        if arg == flag {
+            found = iter.next().map(|v| v.into_owned());
+        } else if let Some(v) = arg.strip_prefix(&prefix) {
+            found = Some(v.to_string());
+        }
+    }
+    found
+}
+
+/// Long flag names given explicitly on the command line
+fn cli_keys(argv: &[OsString]) -> Vec<String> {
+    argv.iter()
+        .skip(1)
+        .filter_map(|a| a.to_str()?.strip_prefix("--").map(|s| s.split('=').next().unwrap_or(s).to_string()))
+        .collect()
+}
+
+fn toml_literal(raw: &str) -> String {
+    if raw == "true" || raw == "false" || raw.parse::<i64>().is_ok() {
+        raw.to_string()
+    } else {
+        toml::Value::String(raw.to_string()).to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[derive(Parser, Debug)]
+    struct TestArgs {
+        #[arg(long)]
+        mode: String,
+        #[arg(long, default_value = "8443")]
+        port: u16,
+        #[arg(long)]
+        members: Option<String>,
+        #[arg(long)]
+        display: bool,
+        #[arg(long)]
+        config: Option<String>,
+        #[arg(long)]
+        profile: Option<String>,
+    }
+
+    fn write_config(name: &str, contents: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("stream-config-{}-{}.toml", name, std::process::id()));
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    fn argv(items: &[&str]) -> Vec<OsString> {
+        std::iter::once("stream").chain(items.iter().copied()).map(OsString::from).collect()
+    }
+
+    const LAB: &str = r#"
+        [defaults]
+        port = 9000
+
+        [profiles.leader]
+        mode = "group-leader"
+        members = ["pi-2:10.0.0.2:8443", "pi-3:10.0.0.3:8443"]
+        display = true
+
+        [profiles.member]
+        mode = "group-member"
+    "#;
+
+    #[test]
+    fn test_profile_with_cli_override() {
+        let path = write_config("override", LAB);
+        let path_str = path.to_str().unwrap();
+
+        let parsed = parse::<TestArgs>(argv(&["--config", path_str, "--profile", "leader", "--port", "7000"])).unwrap();
+        assert_eq!(parsed.args.mode, "group-leader");
+        assert_eq!(parsed.args.port, 7000);
+        assert_eq!(parsed.args.members.as_deref(), Some("pi-2:10.0.0.2:8443,pi-3:10.0.0.3:8443"));
+        assert!(parsed.args.display);
+
+        let dump = parsed.effective_toml();
+        assert!(dump.contains("port = 7000"));
+        assert!(dump.contains("mode = \"group-leader\""));
+        assert!(dump.lines().any(|l| l.starts_with("port = 7000") && l.ends_with("# cli")));
+        assert!(dump.lines().any(|l| l.starts_with("mode =") && l.ends_with("# profile")));
+
+        let parsed = parse::<TestArgs>(argv(&[&format!("--config={}", path_str), "--profile=member"])).unwrap();
+        assert_eq!(parsed.args.port, 9000);
+        assert!(!parsed.args.display);
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_cli_negates_profile_boolean() {
+        let path = write_config("negate", LAB);
+        let path_str = path.to_str().unwrap();
+
+        // `display = true` in the profile, switched off on the command line
+        let parsed = parse::<TestArgs>(argv(&["--config", path_str, "--profile", "leader", "--no-display"])).unwrap();
+        assert!(!parsed.args.display);
+        let dump = parsed.effective_toml();
+        assert!(dump.lines().any(|l| l.starts_with("display = false") && l.ends_with("# cli")));
+        assert!(!dump.contains("no-display"));
+
+        // Last one wins on the command line itself
+        let parsed = parse::<TestArgs>(argv(&["--config", path_str, "--profile", "member", "--no-display", "--display"])).unwrap();
+        assert!(parsed.args.display);
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_profile_false_overrides_defaults() {
+        let path = write_config("false", r#"
+            [defaults]
+            display = true
+
+            [profiles.headless]
+            mode = "receiver"
+            display = false
+        "#);
+        let path_str = path.to_str().unwrap();
+
+        let parsed = parse::<TestArgs>(argv(&["--config", path_str])).unwrap();
+        assert!(!parsed.args.display);
+        let dump = parsed.effective_toml();
+        assert!(dump.lines().any(|l| l.starts_with("display = false") && l.ends_with("# profile")), "{}", dump);
+
+        // and the CLI still wins over the profile
+        let parsed = parse::<TestArgs>(argv(&["--config", path_str, "--display"])).unwrap();
+        assert!(parsed.args.display);
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_invalid_config_is_reported() {
+        let cases = [
+            ("unknown", "[profiles.a]\nmode = \"x\"\nrsa-bit = 2048\n", "unknown option"),
+            ("value", "[profiles.a]\nmode = \"x\"\nport = \"eighty\"\n", "`port`"),
+            ("flag", "[profiles.a]\nmode = \"x\"\ndisplay = 1\n", "expected true or false"),
+            ("top", "mode = \"x\"\n", "unexpected top-level key"),
+            ("cli-only", "[profiles.a]\nconfig = \"other.toml\"\n", "only valid on the command line"),
+        ];
+
+        for (name, contents, expected) in cases {
+            let path = write_config(name, contents);
+            let err = parse::<TestArgs>(argv(&["--config", path.to_str().unwrap()])).err()
+                .unwrap_or_else(|| panic!("{} should fail", name));
+            assert!(err.to_string().contains(expected), "{}: {}", name, err);
+            std::fs::remove_file(path).unwrap();
+        }
+
+        let path = write_config("choose", LAB);
+        let err = parse::<TestArgs>(argv(&["--config", path.to_str().unwrap()])).err().unwrap();
+        assert!(err.to_string().contains("leader, member"));
+        let err = parse::<TestArgs>(argv(&["--config", path.to_str().unwrap(), "--profile", "relay"])).err().unwrap();
+        assert!(err.to_string().contains("no profile `relay`"));
+        std::fs::remove_file(path).unwrap();
+    }
+}
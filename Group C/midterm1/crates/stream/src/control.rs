@@ -0,0 +1,543 @@
+// Local Control API
+// crates/stream/src/control.rs
+//
+// JSON-lines over a Unix-domain socket: one request object per line, one
+// response object per line. Requests are tagged by "cmd":
+//
+//   {"cmd":"status"}
+//   {"cmd":"rekey"}
+//   {"cmd":"set-rate","fps":10,"bitrate_kbps":20000}
+//   {"cmd":"add-member","member":"pi-4:192.168.1.104:8443"}
+//   {"cmd":"remove-member","node_id":"pi-4"}
+//   {"cmd":"start-recording","path":"run1.i420"}
+//   {"cmd":"stop-recording"}
+//   {"cmd":"overlay","visible":true}      (omit "visible" to toggle)
+//
+// Responses are {"ok":true,...} or {"ok":false,"error":"..."}. The socket is
+// bound inside a private 0700 directory and only moved into place once it is
+// mode 0600, so only the user running the stream can ever connect to it.
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::{mpsc, oneshot, watch};
+use tracing::{info, warn};
+
+use crate::group_key::GroupMember;
+
+/// How long `rekey` and member changes wait for the stream loop to act
+const APPLY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Frames queued for the recording writer before new ones are dropped
+const RECORDING_QUEUE: usize = 32;
+
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(tag = "cmd", rename_all = "kebab-case", deny_unknown_fields)]
+pub enum Request {
+    Status,
+    Rekey,
+    SetRate { fps: Option<u32>, bitrate_kbps: Option<u32> },
+    AddMember { member: String },
+    RemoveMember { node_id: String },
+    StartRecording { path: Option<String> },
+    StopRecording,
+    Overlay { visible: Option<bool> },
+}
+
+/// Which loop owns this control handle; decides the commands it accepts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    Sender,
+    Receiver,
+    Relay,
+}
+
+impl std::fmt::Display for Role {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Role::Sender => write!(f, "sender"),
+            Role::Receiver => write!(f, "receiver"),
+            Role::Relay => write!(f, "relay"),
+        }
+    }
+}
+
+/// Membership change handed to the sender loop, answered once applied
+pub enum MemberChange {
+    Add(GroupMember, oneshot::Sender<Result<(), String>>),
+    Remove(String, oneshot::Sender<Result<(), String>>),
+}
+
+/// A recording in progress; frames are written on their own thread so a slow
+/// disk never stalls the stream loop
+struct Recording {
+    path: PathBuf,
+    queue: std::sync::mpsc::SyncSender<Vec<u8>>,
+    writer: std::thread::JoinHandle<std::io::Result<()>>,
+    frames: u64,
+    bytes: u64,
+    /// Frames dropped because the writer fell behind
+    dropped: u64,
+}
+
+impl Recording {
+    fn start(path: PathBuf, file: File) -> Self {
+        let (queue, frames) = std::sync::mpsc::sync_channel::<Vec<u8>>(RECORDING_QUEUE);
+        let writer = std::thread::spawn(move || {
+            let mut out = BufWriter::new(file);
+            for frame in frames {
+                out.write_all(&frame)?;
+            }
+            out.flush()
+        });
+        Self { path, queue, writer, frames: 0, bytes: 0, dropped: 0 }
+    }
+
+    /// Close the queue, wait for everything queued to reach the file and
+    /// summarise the recording
+    fn finish(self) -> Result<Value> {
+        let Recording { path, queue, writer, frames, bytes, dropped } = self;
+        drop(queue);
+        match writer.join() {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => return Err(anyhow::Error::new(e).context(format!("Failed to write {}", path.display()))),
+            Err(_) => bail!("Recording writer for {} panicked", path.display()),
+        }
+        info!("Recording stopped: {} frames in {} ({} dropped)", frames, path.display(), dropped);
+        Ok(json!({ "path": path, "frames": frames, "bytes": bytes, "dropped": dropped }))
+    }
+}
+
+/// Shared state between the stream loop and the control socket
+pub struct Control {
+    role: Role,
+    node_id: String,
+    mechanism: String,
+    cipher: String,
+    started: Instant,
+    frames: AtomicU64,
+    rekey_requested: AtomicBool,
+    fps: AtomicU32,
+    /// 0 = no cap
+    bitrate_kbps: AtomicU32,
+    overlay: AtomicBool,
+    /// Highest key epoch across this node's sessions
+    epoch: watch::Sender<u32>,
+    peers: Mutex<Vec<(String, u32)>>,
+    recording: Mutex<Option<Recording>>,
+    member_tx: mpsc::Sender<MemberChange>,
+    member_rx: Mutex<mpsc::Receiver<MemberChange>>,
+}
+
+impl Control {
+    pub fn new(role: Role, node_id: String, mechanism: String, cipher: String, fps: u32) -> Self {
+        let (member_tx, member_rx) = mpsc::channel(8);
+        Self {
+            role,
+            node_id,
+            mechanism,
+            cipher,
+            started: Instant::now(),
+            frames: AtomicU64::new(0),
+            rekey_requested: AtomicBool::new(false),
+            fps: AtomicU32::new(fps.max(1)),
+            bitrate_kbps: AtomicU32::new(0),
+            overlay: AtomicBool::new(false),
+            epoch: watch::channel(0).0,
+            peers: Mutex::new(Vec::new()),
+            recording: Mutex::new(None),
+            member_tx,
+            member_rx: Mutex::new(member_rx),
+        }
+    }
+
+    /// Bind the control socket and answer requests in the background
+    pub fn serve(self: Arc<Self>, path: &Path) -> Result<()> {
+        if path.exists() {
+            if std::os::unix::net::UnixStream::connect(path).is_ok() {
+                bail!("Control socket {} is already in use", path.display());
+            }
+            // Left behind by a previous run
+            std::fs::remove_file(path)
+                .with_context(|| format!("Failed to remove stale socket {}", path.display()))?;
+        }
+
+        let listener = bind_private(path)
+            .with_context(|| format!("Failed to bind control socket {}", path.display()))?;
+        info!("Control API listening on {}", path.display());
+
+        tokio::spawn(async move {
+            loop {
+                match listener.accept().await {
+                    Ok((conn, _)) => {
+                        tokio::spawn(self.clone().handle_connection(conn));
+                    }
+                    Err(e) => {
+                        warn!("Control socket accept failed: {}", e);
+                        break;
+                    }
+                }
+            }
+        });
+        Ok(())
+    }
+
+    async fn handle_connection(self: Arc<Self>, conn: UnixStream) {
+        let (read, mut write) = conn.into_split();
+        let mut lines = BufReader::new(read).lines();
+
+        while let Ok(Some(line)) = lines.next_line().await {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let response = self.handle_line(&line).await;
+            let mut out = response.to_string();
+            out.push('\n');
+            if write.write_all(out.as_bytes()).await.is_err() {
+                break;
+            }
+        }
+    }
+
+    /// Parse and run one request line; never fails, errors become `ok: false`
+    pub async fn handle_line(&self, line: &str) -> Value {
+        let result = match serde_json::from_str::<Request>(line) {
+            Ok(request) => self.handle(request).await,
+            Err(e) => Err(anyhow::anyhow!("Invalid request: {}", e)),
+        };
+        match result {
+            Ok(Value::Object(mut fields)) => {
+                fields.insert("ok".to_string(), Value::Bool(true));
+                Value::Object(fields)
+            }
+            Ok(other) => json!({ "ok": true, "result": other }),
+            Err(e) => json!({ "ok": false, "error": format!("{:#}", e) }),
+        }
+    }
+
+    async fn handle(&self, request: Request) -> Result<Value> {
+        match request {
+            Request::Status => Ok(self.status()),
+            Request::Rekey => {
+                if self.role == Role::Receiver {
+                    bail!("The receiver follows the sender's epochs; send rekey to the sender or relay");
+                }
+                let mut epoch = self.epoch.subscribe();
+                epoch.mark_unchanged();
+                self.rekey_requested.store(true, Ordering::SeqCst);
+                tokio::time::timeout(APPLY_TIMEOUT, epoch.changed())
+                    .await
+                    .context("Rekey not applied (is the stream still sending?)")??;
+                let epoch = *epoch.borrow();
+                Ok(json!({ "epoch": epoch }))
+            }
+            Request::SetRate { fps, bitrate_kbps } => {
+                if self.role != Role::Sender {
+                    bail!("Only the sender paces frames");
+                }
+                if fps.is_none() && bitrate_kbps.is_none() {
+                    bail!("set-rate needs fps and/or bitrate_kbps");
+                }
+                if let Some(fps) = fps {
+                    if fps == 0 {
+                        bail!("fps must be at least 1");
+                    }
+                    self.fps.store(fps, Ordering::Relaxed);
+                }
+                if let Some(kbps) = bitrate_kbps {
+                    self.bitrate_kbps.store(kbps, Ordering::Relaxed);
+                }
+                info!("Target rate set to {} fps, bitrate cap {:?} kbps", self.fps(), self.bitrate_cap());
+                Ok(json!({ "fps": self.fps(), "bitrate_kbps": self.bitrate_cap() }))
+            }
+            Request::AddMember { member } => {
+                let member = GroupMember::parse(&member)?;
+                let node_id = member.node_id.clone();
+                self.change_members(|reply| MemberChange::Add(member, reply)).await?;
+                Ok(json!({ "added": node_id }))
+            }
+            Request::RemoveMember { node_id } => {
+                self.change_members(|reply| MemberChange::Remove(node_id.clone(), reply)).await?;
+                Ok(json!({ "removed": node_id }))
+            }
+            Request::StartRecording { path } => {
+                let path = PathBuf::from(path.unwrap_or_else(|| {
+                    format!("recording_{}_{}.i420", self.node_id, chrono::Utc::now().format("%Y%m%dT%H%M%S"))
+                }));
+                let mut recording = self.recording.lock().unwrap();
+                if let Some(active) = recording.as_ref() {
+                    bail!("Already recording to {}", active.path.display());
+                }
+                let file = File::create(&path)
+                    .with_context(|| format!("Failed to create {}", path.display()))?;
+                info!("Recording frames to {}", path.display());
+                *recording = Some(Recording::start(path.clone(), file));
+                Ok(json!({ "path": path }))
+            }
+            Request::StopRecording => {
+                let active = self.recording.lock().unwrap().take()
+                    .context("Not recording")?;
+                active.finish()
+            }
+            Request::Overlay { visible } => {
+                if self.role != Role::Receiver {
+                    bail!("The overlay is drawn by the receiver's display");
+                }
+                let visible = visible.unwrap_or(!self.overlay_visible());
+                self.set_overlay_visible(visible);
+                Ok(json!({ "visible": visible }))
+            }
+        }
+    }
+
+    async fn change_members(
+        &self,
+        change: impl FnOnce(oneshot::Sender<Result<(), String>>) -> MemberChange,
+    ) -> Result<()> {
+        if self.role != Role::Sender {
+            bail!("Members can only be changed on the sender");
+        }
+        let (reply_tx, reply_rx) = oneshot::channel();
+        self.member_tx.send(change(reply_tx)).await
+            .context("Stream loop has stopped")?;
+        tokio::time::timeout(APPLY_TIMEOUT, reply_rx)
+            .await
+            .context("Member change not applied in time")?
+            .context("Stream loop has stopped")?
+            .map_err(anyhow::Error::msg)
+    }
+
+    fn status(&self) -> Value {
+        let recording = self.recording.lock().unwrap().as_ref()
+            .map(|r| json!({ "path": r.path, "frames": r.frames }));
+        let peers: Vec<Value> = self.peers.lock().unwrap().iter()
+            .map(|(node_id, epoch)| json!({ "node_id": node_id, "epoch": epoch }))
+            .collect();
+        json!({
+            "role": self.role.to_string(),
+            "node_id": self.node_id,
+            "mechanism": self.mechanism,
+            "cipher": self.cipher,
+            "epoch": *self.epoch.borrow(),
+            "frames": self.frames.load(Ordering::Relaxed),
+            "uptime_s": self.started.elapsed().as_secs_f64(),
+            "fps": self.fps(),
+            "bitrate_kbps": self.bitrate_cap(),
+            "recording": recording,
+            "overlay": self.overlay_visible(),
+            "peers": peers,
+        })
+    }
+
+    /// True once per `rekey` request; the stream loop ratchets when it sees it
+    pub fn take_rekey_request(&self) -> bool {
+        self.rekey_requested.swap(false, Ordering::SeqCst)
+    }
+
+    /// Next pending member change for the sender loop, if any
+    pub fn next_member_change(&self) -> Option<MemberChange> {
+        self.member_rx.lock().unwrap().try_recv().ok()
+    }
+
+    /// Publish the current sessions (node id, key epoch) for `status`
+    pub fn set_peers(&self, peers: Vec<(String, u32)>) {
+        let epoch = peers.iter().map(|(_, e)| *e).max().unwrap_or(0);
+        *self.peers.lock().unwrap() = peers;
+        self.epoch.send_if_modified(|current| {
+            let changed = *current != epoch;
+            *current = epoch;
+            changed
+        });
+    }
+
+    /// Count a frame and queue it for the recording, if one is running. Never
+    /// blocks: when the writer falls behind the frame is left out of the file.
+    pub fn frame(&self, data: &[u8]) {
+        self.frames.fetch_add(1, Ordering::Relaxed);
+
+        let mut recording = self.recording.lock().unwrap();
+        if let Some(active) = recording.as_mut() {
+            match active.queue.try_send(data.to_vec()) {
+                Ok(()) => {
+                    active.frames += 1;
+                    active.bytes += data.len() as u64;
+                }
+                Err(std::sync::mpsc::TrySendError::Full(_)) => active.dropped += 1,
+                Err(std::sync::mpsc::TrySendError::Disconnected(_)) => {
+                    // The writer only hangs up on an error; collect it
+                    if let Some(Err(e)) = recording.take().map(Recording::finish) {
+                        warn!("Recording stopped: {:#}", e);
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn overlay_visible(&self) -> bool {
+        self.overlay.load(Ordering::Relaxed)
+    }
+
+    pub fn set_overlay_visible(&self, visible: bool) {
+        self.overlay.store(visible, Ordering::Relaxed);
+    }
+
+    pub fn fps(&self) -> u32 {
+        self.fps.load(Ordering::Relaxed)
+    }
+
+    fn bitrate_cap(&self) -> Option<u32> {
+        match self.bitrate_kbps.load(Ordering::Relaxed) {
+            0 => None,
+            kbps => Some(kbps),
+        }
+    }
+
+    /// Delay before the next frame: the target fps, stretched if a frame of
+    /// `frame_bytes` would exceed the bitrate cap
+    pub fn frame_interval(&self, frame_bytes: usize) -> Duration {
+        let by_fps = Duration::from_secs_f64(1.0 / self.fps() as f64);
+        match self.bitrate_cap() {
+            Some(kbps) => by_fps.max(Duration::from_secs_f64(frame_bytes as f64 * 8.0 / (kbps as f64 * 1000.0))),
+            None => by_fps,
+        }
+    }
+}
+
+/// Bind `path` without a window in which other users could connect: the socket
+/// is created in a fresh 0700 directory next to it, set to 0600, then renamed
+/// into place
+fn bind_private(path: &Path) -> Result<UnixListener> {
+    let parent = path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let name = path.file_name().context("Control socket path has no file name")?;
+    let private = parent.join(format!(".{}.{}", name.to_string_lossy(), std::process::id()));
+    std::fs::DirBuilder::new().mode(0o700).create(&private)
+        .with_context(|| format!("Failed to create {}", private.display()))?;
+
+    let staged = private.join("sock");
+    let bound = UnixListener::bind(&staged)
+        .map_err(anyhow::Error::from)
+        .and_then(|listener| {
+            std::fs::set_permissions(&staged, std::fs::Permissions::from_mode(0o600))?;
+            std::fs::rename(&staged, path)?;
+            Ok(listener)
+        });
+    let _ = std::fs::remove_file(&staged);
+    let _ = std::fs::remove_dir(&private);
+    bound
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sender() -> Arc<Control> {
+        Arc::new(Control::new(Role::Sender, "pi-1".into(), "ECDH-P256".into(), "AES-128-GCM".into(), 30))
+    }
+
+    #[test]
+    fn test_parse_requests() {
+        let set_rate: Request = serde_json::from_str(r#"{"cmd":"set-rate","fps":10}"#).unwrap();
+        assert_eq!(set_rate, Request::SetRate { fps: Some(10), bitrate_kbps: None });
+
+        let add: Request = serde_json::from_str(r#"{"cmd":"add-member","member":"pi-4:10.0.0.4:8443"}"#).unwrap();
+        assert_eq!(add, Request::AddMember { member: "pi-4:10.0.0.4:8443".into() });
+
+        let toggle: Request = serde_json::from_str(r#"{"cmd":"overlay"}"#).unwrap();
+        assert_eq!(toggle, Request::Overlay { visible: None });
+
+        assert!(serde_json::from_str::<Request>(r#"{"cmd":"reboot"}"#).is_err());
+        assert!(serde_json::from_str::<Request>(r#"{"cmd":"set-rate","rate":5}"#).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_control_socket() {
+        let path = std::env::temp_dir().join(format!("stream-control-{}.sock", std::process::id()));
+        let control = sender();
+        control.clone().serve(&path).unwrap();
+
+        // Stand-in for the sender loop: apply rekeys and member changes
+        let looped = control.clone();
+        tokio::spawn(async move {
+            let mut epoch = 0;
+            loop {
+                if looped.take_rekey_request() {
+                    epoch += 1;
+                    looped.set_peers(vec![("rx".into(), epoch)]);
+                }
+                while let Some(change) = looped.next_member_change() {
+                    match change {
+                        MemberChange::Add(_, reply) => { let _ = reply.send(Ok(())); }
+                        MemberChange::Remove(id, reply) => { let _ = reply.send(Err(format!("No member {}", id))); }
+                    }
+                }
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        });
+
+        // Owner-only from the moment it appears, with no staging directory left behind
+        assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        let staging = format!(".{}.", path.file_name().unwrap().to_string_lossy());
+        assert!(!std::fs::read_dir(path.parent().unwrap()).unwrap()
+            .any(|e| e.unwrap().file_name().to_string_lossy().starts_with(&staging)));
+
+        let mut conn = BufReader::new(UnixStream::connect(&path).await.unwrap());
+        let mut call = async |req: &str| {
+            conn.get_mut().write_all(format!("{}\n", req).as_bytes()).await.unwrap();
+            let mut line = String::new();
+            conn.read_line(&mut line).await.unwrap();
+            serde_json::from_str::<Value>(&line).unwrap()
+        };
+
+        let rekeyed = call(r#"{"cmd":"rekey"}"#).await;
+        assert_eq!(rekeyed["ok"], true);
+        assert_eq!(rekeyed["epoch"], 1);
+
+        let rate = call(r#"{"cmd":"set-rate","fps":10,"bitrate_kbps":8000}"#).await;
+        assert_eq!(rate["fps"], 10);
+        // 115200-byte frames at 8 Mbit/s take longer than 1/10 s
+        assert_eq!(control.frame_interval(115_200), Duration::from_secs_f64(0.1152));
+
+        assert_eq!(call(r#"{"cmd":"add-member","member":"pi-4:10.0.0.4:8443"}"#).await["added"], "pi-4");
+        let removed = call(r#"{"cmd":"remove-member","node_id":"pi-9"}"#).await;
+        assert_eq!(removed["ok"], false);
+        assert_eq!(removed["error"], "No member pi-9");
+
+        let status = call(r#"{"cmd":"status"}"#).await;
+        assert_eq!(status["epoch"], 1);
+        assert_eq!(status["fps"], 10);
+        assert_eq!(status["peers"][0]["node_id"], "rx");
+
+        assert_eq!(call("not json").await["ok"], false);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_recording() {
+        let path = std::env::temp_dir().join(format!("stream-recording-{}.i420", std::process::id()));
+        let control = sender();
+        let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        let start = format!(r#"{{"cmd":"start-recording","path":{:?}}}"#, path.to_str().unwrap());
+
+        control.frame(&[1; 8]);
+        assert_eq!(rt.block_on(control.handle_line(&start))["ok"], true);
+        control.frame(&[2; 8]);
+        control.frame(&[3; 8]);
+        assert_eq!(rt.block_on(control.handle_line(&start))["ok"], false);
+
+        let stopped = rt.block_on(control.handle_line(r#"{"cmd":"stop-recording"}"#));
+        assert_eq!(stopped["frames"], 2);
+        assert_eq!(stopped["dropped"], 0);
+        assert_eq!(std::fs::read(&path).unwrap(), [[2u8; 8], [3u8; 8]].concat());
+        assert_eq!(control.status()["frames"], 3);
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -1,12 +1,30 @@
 use anyhow::{Result, Context};
+use chrono::{DateTime, Utc};
 use gstreamer::prelude::*;
 use gstreamer_app::AppSrc;
 use gstreamer as gst;
+use metrics::{LiveStats, MetricsCollector};
+use std::sync::Arc;
+use std::time::Duration;
 use tracing::info;
 
+/// Video sink used by `VideoDisplay::new`
+const WINDOW_SINK: &str = "autovideosink sync=false";
+
+/// How often the overlay is refreshed from the metrics collector
+const OVERLAY_REFRESH: Duration = Duration::from_millis(250);
+
+/// How long the overlay highlights a rekey
+const REKEY_FLASH_MS: i64 = 2000;
+
+/// textoverlay colours (ARGB)
+const OVERLAY_COLOR: u32 = 0xFFFF_FFFF;
+const FLASH_COLOR: u32 = 0xFFFF_D700;
+
 pub struct VideoDisplay {
     pipeline: gst::Pipeline,
     appsrc: AppSrc,
+    overlay: gst::Element,
     width: i32,
     height: i32,
     fps: i32,
@@ -14,18 +32,26 @@ pub struct VideoDisplay {
 
 impl VideoDisplay {
     pub fn new(width: i32, height: i32, fps: i32) -> Result<Self> {
+        Self::with_sink(width, height, fps, WINDOW_SINK)
+    }
+    
+    /// Display pipeline ending in `sink`, a gst-launch fragment
+    /// (e.g. "fakesink" to exercise the pipeline without a screen)
+    pub fn with_sink(width: i32, height: i32, fps: i32, sink: &str) -> Result<Self> {
         gst::init().context("Failed to initialize GStreamer")?;
         
         info!("Creating display pipeline: {}x{} @ {} fps", width, height, fps);
         
-        // Create pipeline: appsrc -> videoconvert -> autovideosink
+        // Create pipeline: appsrc -> textoverlay (hidden until enabled) -> videoconvert -> sink
         let pipeline_str = format!(
             "appsrc name=src format=time is-live=true do-timestamp=true \
              caps=video/x-raw,format=I420,width={},height={},framerate={}/1 ! \
              queue max-size-buffers=2 leaky=downstream ! \
+             textoverlay name=overlay silent=true valignment=top halignment=left \
+             font-desc=\"Monospace 11\" shaded-background=true ! \
              videoconvert ! \
-             autovideosink sync=false",
-            width, height, fps
+             {}",
+            width, height, fps, sink
         );
         
         info!("Display pipeline: {}", pipeline_str);
@@ -41,6 +67,10 @@ impl VideoDisplay {
             .downcast::<AppSrc>()
             .map_err(|_| anyhow::anyhow!("Failed to downcast to AppSrc"))?;
         
+        let overlay = pipeline
+            .by_name("overlay")
+            .context("Failed to find textoverlay element")?;
+        
         // Configure appsrc for streaming
         appsrc.set_property("format", gst::Format::Time);
         appsrc.set_property("is-live", true);
@@ -50,6 +80,7 @@ impl VideoDisplay {
         Ok(Self {
             pipeline,
             appsrc,
+            overlay,
             width,
             height,
             fps,
@@ -100,6 +131,11 @@ impl VideoDisplay {
         Ok(())
     }
     
+    /// Handle to the statistics overlay
+    pub fn overlay(&self) -> Overlay {
+        Overlay { element: self.overlay.clone() }
+    }
+    
     pub fn stop(&self) -> Result<()> {
         // Send EOS
         let _ = self.appsrc.end_of_stream();
@@ -118,4 +154,155 @@ impl Drop for VideoDisplay {
     fn drop(&mut self) {
         let _ = self.stop();
     }
-}
\ No newline at end of file
+}
+
+/// The on-screen statistics overlay (a `textoverlay` in the display pipeline)
+#[derive(Clone)]
+pub struct Overlay {
+    element: gst::Element,
+}
+
+impl Overlay {
+    pub fn set_visible(&self, visible: bool) {
+        self.element.set_property("silent", !visible);
+    }
+    
+    pub fn is_visible(&self) -> bool {
+        !self.element.property::<bool>("silent")
+    }
+    
+    pub fn show(&self, text: &OverlayText) {
+        self.element.set_property("text", text.text.as_str());
+        self.element.set_property("color", if text.flash { FLASH_COLOR } else { OVERLAY_COLOR });
+    }
+    
+    /// Refresh from the collector until aborted; `visible` is polled on every
+    /// refresh so the overlay can be toggled while streaming
+    pub fn spawn_updates(
+        self,
+        collector: Arc<MetricsCollector>,
+        mechanism: String,
+        cipher: String,
+        visible: impl Fn() -> bool + Send + 'static,
+    ) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(OVERLAY_REFRESH);
+            loop {
+                interval.tick().await;
+                
+                let visible = visible();
+                if visible != self.is_visible() {
+                    self.set_visible(visible);
+                }
+                if visible {
+                    let stats = collector.live_stats().await;
+                    self.show(&render_overlay(&mechanism, &cipher, &stats, Utc::now()));
+                }
+            }
+        })
+    }
+}
+
+/// Overlay contents for one refresh
+#[derive(Debug, Clone, PartialEq)]
+pub struct OverlayText {
+    pub text: String,
+    /// A rekey happened within the last `REKEY_FLASH_MS`
+    pub flash: bool,
+}
+
+/// Lay out the live stats; the receiver ratchets once per sender epoch, so
+/// the rekey count is the current epoch
+pub fn render_overlay(mechanism: &str, cipher: &str, stats: &LiveStats, now: DateTime<Utc>) -> OverlayText {
+    let key_age = stats.key_since
+        .map(|since| (now - since).num_seconds().max(0))
+        .map(|secs| format!("{:02}:{:02}", secs / 60, secs % 60))
+        .unwrap_or_else(|| "--:--".to_string());
+    let flash = stats.last_rekey
+        .is_some_and(|at| (now - at).num_milliseconds() < REKEY_FLASH_MS);
+    
+    let mut text = format!(
+        "{} / {}\n\
+         epoch {}  key age {}\n\
+         {:.1} fps  {:.1} Mbps\n\
+         p95 {:.1} ms  tag failures {}\n\
+         SoC {:.1}°C",
+        mechanism, cipher,
+        stats.rekeys, key_age,
+        stats.fps, stats.goodput_mbps,
+        stats.latency_p95_ms, stats.tag_failures,
+        stats.temp_c,
+    );
+    if flash {
+        text.push_str("\n** REKEY **");
+    }
+    
+    OverlayText { text, flash }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn stats(now: DateTime<Utc>) -> LiveStats {
+        LiveStats {
+            fps: 29.97,
+            goodput_mbps: 110.4,
+            latency_p95_ms: 12.34,
+            temp_c: 61.2,
+            tag_failures: 1,
+            rekeys: 3,
+            key_since: Some(now - chrono::Duration::seconds(75)),
+            last_rekey: Some(now - chrono::Duration::seconds(75)),
+            ..Default::default()
+        }
+    }
+    
+    #[test]
+    fn test_render_overlay() {
+        let now = Utc::now();
+        let overlay = render_overlay("ECDH-P256", "AES-128-GCM", &stats(now), now);
+        assert_eq!(
+            overlay.text,
+            "ECDH-P256 / AES-128-GCM\nepoch 3  key age 01:15\n30.0 fps  110.4 Mbps\np95 12.3 ms  tag failures 1\nSoC 61.2°C"
+        );
+        assert!(!overlay.flash);
+        
+        // Fresh rekey flashes
+        let rekeyed = LiveStats { last_rekey: Some(now), key_since: Some(now), ..stats(now) };
+        let overlay = render_overlay("ECDH-P256", "AES-128-GCM", &rekeyed, now);
+        assert!(overlay.flash);
+        assert!(overlay.text.contains("key age 00:00") && overlay.text.ends_with("** REKEY **"));
+        
+        let idle = render_overlay("GROUP-PSK", "ASCON-128", &LiveStats::default(), now);
+        assert!(idle.text.contains("key age --:--"));
+    }
+    
+    #[test]
+    #[ignore = "needs GStreamer with textoverlay (gst-plugins-base pango); run with --ignored"]
+    fn test_overlay_with_fake_sink() {
+        gst::init().unwrap();
+        // textoverlay lives in gst-plugins-base's pango plugin
+        assert!(
+            gst::ElementFactory::find("textoverlay").is_some(),
+            "textoverlay is not installed (gst-plugins-base pango plugin)"
+        );
+        
+        let display = VideoDisplay::with_sink(64, 48, 30, "fakesink sync=false").unwrap();
+        display.start().unwrap();
+        let overlay = display.overlay();
+        assert!(!overlay.is_visible());
+        
+        let now = Utc::now();
+        let text = render_overlay("ECDH-P256", "AES-128-GCM", &stats(now), now);
+        overlay.set_visible(true);
+        overlay.show(&text);
+        display.push_frame(&vec![0u8; 64 * 48 * 3 / 2]).unwrap();
+        
+        assert!(overlay.is_visible());
+        assert_eq!(overlay.element.property::<String>("text"), text.text);
+        overlay.set_visible(false);
+        assert!(!overlay.is_visible());
+        display.stop().unwrap();
+    }
+}
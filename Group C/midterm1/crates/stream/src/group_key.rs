@@ -2,11 +2,24 @@
 // crates/stream/src/group_key.rs
 
 use anyhow::{Result, bail};
-use crypto::{SessionKeyMaterial, ecdh_kex};
+use crypto::{SessionKeyMaterial, ecdh_kex, ct_eq};
 use sha2::{Sha256, Digest};
 use tokio::net::TcpStream;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tracing::{info, warn};
+use zeroize::Zeroizing;
+use std::path::PathBuf;
+use crate::keystore::{self, KekSource, KeystoreHeader};
+
+/// Mechanism bound into the leader/member pairwise transcript
+const PAIRWISE_MECHANISM: &str = "GROUP-ECDH-P256";
+
+/// Nonce for the single group-key message under a fresh pairwise key
+fn pairwise_nonce(pairwise_key: &SessionKeyMaterial) -> [u8; 12] {
+    let mut nonce = [0u8; 12];
+    nonce[..8].copy_from_slice(pairwise_key.nonce_base().expose_secret());
+    nonce
+}
 
 #[derive(Debug, Clone)]
 pub struct GroupMember {
@@ -14,11 +27,13 @@ pub struct GroupMember {
     pub address: String,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug)]
 pub struct GroupKeyContext {
     pub group_key: SessionKeyMaterial,
     pub members: Vec<GroupMember>,
     pub key_hash: [u8; 32],
+    pub epoch: u64,
+    pub members_hash: [u8; 32],
 }
 
 impl GroupMember {
@@ -39,16 +54,31 @@ impl GroupMember {
 pub struct LeaderNode {
     node_id: String,
     members: Vec<GroupMember>,
+    epoch: u64,
+    /// Stream cipher the group key will be used with; members must agree
+    cipher: String,
 }
 
 impl LeaderNode {
-    pub fn new(node_id: String, members: Vec<GroupMember>) -> Self {
-        Self { node_id, members }
+    pub fn new(node_id: String, members: Vec<GroupMember>, epoch: u64, cipher: String) -> Self {
+        Self { node_id, members, epoch, cipher }
+    }
+    
+    /// Hash of the member IDs this leader distributes to
+    pub fn members_hash(&self) -> [u8; 32] {
+        let ids: Vec<&str> = self.members.iter().map(|m| m.node_id.as_str()).collect();
+        keystore::members_hash(&ids)
     }
     
     /// Generate group key and distribute to all members
     pub async fn establish_group_key(&self) -> Result<GroupKeyContext> {
-        info!("Leader: Generating group key for {} members", self.members.len());
+        info!("Leader: Generating group key epoch {} for {} members", self.epoch, self.members.len());
+        
+        // Epoch and member list travel in the clear but are bound as AAD
+        let members_hash = self.members_hash();
+        let mut key_info = [0u8; 40];
+        key_info[..8].copy_from_slice(&self.epoch.to_be_bytes());
+        key_info[8..].copy_from_slice(&members_hash);
         
         // Generate fresh group key
         let group_key = SessionKeyMaterial::generate_random();
@@ -56,7 +86,7 @@ impl LeaderNode {
         
         // Compute key hash for verification
         let mut hasher = Sha256::new();
-        hasher.update(&group_key_bytes);
+        hasher.update(group_key_bytes.as_slice());
         let key_hash: [u8; 32] = hasher.finalize().into();
         
         info!("Leader: Group key hash: {}", hex::encode(&key_hash));
@@ -85,30 +115,40 @@ impl LeaderNode {
             let mut peer_public = vec![0u8; peer_pub_len];
             stream.read_exact(&mut peer_public).await?;
             
-            // Derive pairwise key
-            let pairwise_key = my_keypair.derive_session_key(
+            // Derive a transcript-bound pairwise key and confirm it before
+            // the group key goes out
+            let (pairwise_key, confirmation) = my_keypair.derive_with_transcript(
                 &peer_public,
-                format!("leader-{}", member.node_id).as_bytes()
+                ecdh_kex::Role::Initiator,
+                PAIRWISE_MECHANISM,
+                &self.cipher,
+                format!("leader-{}", member.node_id).as_bytes(),
             )?;
+            stream.write_all(&confirmation.finished()).await?;
+            let mut peer_finished = [0u8; ecdh_kex::KeyConfirmation::FINISHED_LEN];
+            stream.read_exact(&mut peer_finished).await?;
+            confirmation.verify_peer(&peer_finished)?;
             
             // Encrypt group key with pairwise key
-            use aes_gcm::{Aes128Gcm, aead::{Aead, KeyInit}};
-            let cipher = Aes128Gcm::new_from_slice(&pairwise_key.as_bytes()[..16])?;
-            let nonce = [0u8; 12]; // Single-use key, can use zero nonce
+            use aes_gcm::{Aes128Gcm, aead::{Aead, KeyInit, Payload}};
+            let cipher = Aes128Gcm::new_from_slice(pairwise_key.aes_key().expose_secret())?;
+            let nonce = pairwise_nonce(&pairwise_key);
             
-            let encrypted_group_key = cipher.encrypt(&nonce.into(), group_key_bytes.as_ref())
+            let payload = Payload { msg: group_key_bytes.as_slice(), aad: &key_info };
+            let encrypted_group_key = cipher.encrypt(&nonce.into(), payload)
                 .map_err(|e| anyhow::anyhow!("Encryption failed: {}", e))?;
             
-            // Send encrypted group key + hash
+            // Send encrypted group key + hash + epoch/member info
             stream.write_u32(encrypted_group_key.len() as u32).await?;
             stream.write_all(&encrypted_group_key).await?;
             stream.write_all(&key_hash).await?;
+            stream.write_all(&key_info).await?;
             
             // Wait for confirmation
             let mut confirm = [0u8; 32];
             stream.read_exact(&mut confirm).await?;
             
-            if confirm != key_hash {
+            if !ct_eq(&confirm, &key_hash) {
                 bail!("Member {} failed to confirm group key", member.node_id);
             }
             
@@ -119,6 +159,8 @@ impl LeaderNode {
             group_key,
             members: self.members.clone(),
             key_hash,
+            epoch: self.epoch,
+            members_hash,
         })
     }
 }
@@ -126,11 +168,12 @@ impl LeaderNode {
 pub struct MemberNode {
     node_id: String,
     listen_addr: String,
+    cipher: String,
 }
 
 impl MemberNode {
-    pub fn new(node_id: String, listen_addr: String) -> Self {
-        Self { node_id, listen_addr }
+    pub fn new(node_id: String, listen_addr: String, cipher: String) -> Self {
+        Self { node_id, listen_addr, cipher }
     }
     
     /// Wait for leader to distribute group key
@@ -154,11 +197,19 @@ impl MemberNode {
         stream.write_u32(my_public.len() as u32).await?;
         stream.write_all(&my_public).await?;
         
-        // Derive pairwise key
-        let pairwise_key = my_keypair.derive_session_key(
+        // Derive the pairwise key; only answer a leader that derived the
+        // same one (same mechanism, cipher and public keys)
+        let (pairwise_key, confirmation) = my_keypair.derive_with_transcript(
             &leader_public,
-            format!("leader-{}", self.node_id).as_bytes()
+            ecdh_kex::Role::Responder,
+            PAIRWISE_MECHANISM,
+            &self.cipher,
+            format!("leader-{}", self.node_id).as_bytes(),
         )?;
+        let mut leader_finished = [0u8; ecdh_kex::KeyConfirmation::FINISHED_LEN];
+        stream.read_exact(&mut leader_finished).await?;
+        confirmation.verify_peer(&leader_finished)?;
+        stream.write_all(&confirmation.finished()).await?;
         
         // Receive encrypted group key + hash
         let enc_key_len = stream.read_u32().await? as usize;
@@ -168,21 +219,26 @@ impl MemberNode {
         let mut expected_hash = [0u8; 32];
         stream.read_exact(&mut expected_hash).await?;
         
-        // Decrypt group key
-        use aes_gcm::{Aes128Gcm, aead::{Aead, KeyInit}};
-        let cipher = Aes128Gcm::new_from_slice(&pairwise_key.as_bytes()[..16])?;
-        let nonce = [0u8; 12];
+        let mut key_info = [0u8; 40];
+        stream.read_exact(&mut key_info).await?;
+        
+        // Decrypt group key (fails if epoch/member info was altered)
+        use aes_gcm::{Aes128Gcm, aead::{Aead, KeyInit, Payload}};
+        let cipher = Aes128Gcm::new_from_slice(pairwise_key.aes_key().expose_secret())?;
+        let nonce = pairwise_nonce(&pairwise_key);
         
-        let group_key_bytes = cipher.decrypt(&nonce.into(), encrypted_group_key.as_ref())
+        let payload = Payload { msg: encrypted_group_key.as_slice(), aad: &key_info };
+        let group_key_bytes = cipher.decrypt(&nonce.into(), payload)
+            .map(Zeroizing::new)
             .map_err(|e| anyhow::anyhow!("Decryption failed: {}", e))?;
         let group_key = SessionKeyMaterial::from_bytes(&group_key_bytes)?;
         
         // Verify hash
         let mut hasher = Sha256::new();
-        hasher.update(&group_key_bytes);
+        hasher.update(group_key_bytes.as_slice());
         let computed_hash: [u8; 32] = hasher.finalize().into();
         
-        if computed_hash != expected_hash {
+        if !ct_eq(&computed_hash, &expected_hash) {
             bail!("Group key hash mismatch!");
         }
         
@@ -191,31 +247,89 @@ impl MemberNode {
         // Send confirmation
         stream.write_all(&computed_hash).await?;
         
+        let epoch = u64::from_be_bytes(key_info[..8].try_into()?);
+        let mut members_hash = [0u8; 32];
+        members_hash.copy_from_slice(&key_info[8..]);
+        
         Ok(GroupKeyContext {
             group_key,
             members: vec![],
             key_hash: computed_hash,
+            epoch,
+            members_hash,
         })
     }
 }
 
-/// Save group key to file
-pub async fn save_group_key(key: &SessionKeyMaterial, path: &str) -> Result<()> {
-    let key_bytes = key.as_bytes();
-    tokio::fs::write(path, &key_bytes).await?;
-    info!("Saved group key ({} bytes) to: {}", key_bytes.len(), path);
+/// Seal the group key into an encrypted keystore (Argon2id KEK, 0600, atomic write)
+pub async fn save_group_key(ctx: &GroupKeyContext, path: &str, ttl_secs: u64) -> Result<()> {
+    let key_bytes = ctx.group_key.as_bytes();
+    let epoch = ctx.epoch;
+    let members_hash = ctx.members_hash;
+    let path = PathBuf::from(path);
+    
+    // Argon2id is deliberately slow; keep it off the async workers
+    let header = tokio::task::spawn_blocking(move || {
+        let key = SessionKeyMaterial::from_bytes(&key_bytes)?;
+        keystore::save(&path, &key, &KekSource::from_env(), epoch, ttl_secs, members_hash)
+    }).await??;
+    
+    info!("Saved group key epoch {} (expires {}) to keystore", header.epoch,
+          if header.expires_unix == 0 { "never".to_string() } else { header.expires_unix.to_string() });
     Ok(())
 }
 
-/// Load group key from file
-pub async fn load_group_key(path: &str) -> Result<SessionKeyMaterial> {
+/// Open the keystore, refusing expired, tampered or wrong-group keys
+pub async fn load_group_key(
+    path: &str,
+    expected_members_hash: Option<[u8; 32]>,
+) -> Result<(SessionKeyMaterial, KeystoreHeader)> {
     info!("Loading group key from: {}", path);
-    let key_bytes = tokio::fs::read(path).await?;
+    let path = PathBuf::from(path);
     
-    // SessionKeyMaterial is 24 bytes: 16-byte AES key + 8-byte nonce base
-    if key_bytes.len() != 24 {
-        bail!("Invalid group key file: expected 24 bytes, got {}", key_bytes.len());
+    tokio::task::spawn_blocking(move || {
+        keystore::load(&path, &KekSource::from_env(), expected_members_hash)
+    }).await?
+}
+
+/// Next epoch for a fresh group key: one past whatever is in the keystore
+pub fn next_epoch(path: &str) -> u64 {
+    match keystore::peek_header(std::path::Path::new(path)) {
+        Ok(header) => header.epoch + 1,
+        Err(_) => 1,
     }
-    
-    SessionKeyMaterial::from_bytes(&key_bytes)
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Run a leader and one member on loopback with the given ciphers
+    async fn distribute(leader_cipher: &str, member_cipher: &str) -> (Result<GroupKeyContext>, Result<GroupKeyContext>) {
+        let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let member = MemberNode::new("pi-2".to_string(), format!("127.0.0.1:{}", port), member_cipher.to_string());
+        let member = tokio::spawn(async move { member.receive_group_key().await });
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+        let members = vec![GroupMember::parse(&format!("pi-2:127.0.0.1:{}", port)).unwrap()];
+        let leader = LeaderNode::new("pi-1".to_string(), members, 1, leader_cipher.to_string());
+        let leader = leader.establish_group_key().await;
+        (leader, member.await.unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_group_key_distribution() {
+        let (leader, member) = distribute("AES-128-GCM", "AES-128-GCM").await;
+        let (leader, member) = (leader.unwrap(), member.unwrap());
+        assert_eq!(leader.group_key, member.group_key);
+        assert_eq!((leader.epoch, leader.members_hash), (member.epoch, member.members_hash));
+    }
+
+    #[tokio::test]
+    async fn test_cipher_mismatch_fails_confirmation() {
+        let (leader, member) = distribute("AES-128-GCM", "ASCON-128").await;
+        assert!(leader.is_err());
+        let err = member.unwrap_err();
+        assert!(matches!(err.downcast_ref::<crypto::HandshakeError>(), Some(crypto::HandshakeError::KeyConfirmationFailed)));
+    }
+}
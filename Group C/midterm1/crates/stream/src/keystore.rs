@@ -0,0 +1,342 @@
+// Encrypted on-disk keystore for the group key
+// crates/stream/src/keystore.rs
+//
+// Layout (all integers big-endian):
+//   [magic:4 "C4KS"][version:1][epoch:8][created_unix:8][expires_unix:8]
+//   [members_hash:32][salt:16][nonce:12][ciphertext: 24-byte key + 16-byte tag]
+//
+// The KEK is Argon2id(passphrase or machine key, salt). The machine key is only
+// a fallback: /etc/machine-id is world-readable, so without a passphrase any
+// local user can derive the KEK. Everything before the
+// ciphertext is authenticated as AAD, so editing the epoch, expiry or member
+// hash breaks the tag just like editing the key itself.
+
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::Aes256Gcm;
+use anyhow::{bail, Context, Result};
+use argon2::Argon2;
+use crypto::{ct_eq, SessionKeyMaterial};
+use rand::{rngs::OsRng, RngCore};
+use sha2::{Digest, Sha256};
+use std::io::Write;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::warn;
+use zeroize::Zeroizing;
+
+const MAGIC: &[u8; 4] = b"C4KS";
+const VERSION: u8 = 1;
+const HEADER_SIZE: usize = 4 + 1 + 8 + 8 + 8 + 32 + 16 + 12;
+const SEALED_SIZE: usize = 24 + 16;
+
+/// Environment variable holding the keystore passphrase
+pub const PASSPHRASE_ENV: &str = "STREAM_KEYSTORE_PASSPHRASE";
+
+/// Secret the key-encryption key is derived from
+pub enum KekSource {
+    Passphrase(Zeroizing<String>),
+    /// Contents of /etc/machine-id: binds the file to this Pi, but the file is
+    /// world-readable, so it keeps the key from other machines only
+    MachineKey,
+}
+
+impl KekSource {
+    /// Passphrase from `STREAM_KEYSTORE_PASSPHRASE` if set, otherwise the machine key
+    /// (with a warning)
+    pub fn from_env() -> Self {
+        match std::env::var(PASSPHRASE_ENV) {
+            Ok(p) if !p.is_empty() => KekSource::Passphrase(Zeroizing::new(p)),
+            _ => {
+                warn!(
+                    "{} is not set: the keystore is sealed under a key derived from /etc/machine-id, \
+                     which every local user can read. Set a passphrase to protect the group key.",
+                    PASSPHRASE_ENV
+                );
+                KekSource::MachineKey
+            }
+        }
+    }
+
+    fn secret(&self) -> Result<Zeroizing<Vec<u8>>> {
+        match self {
+            KekSource::Passphrase(p) => Ok(Zeroizing::new(p.as_bytes().to_vec())),
+            KekSource::MachineKey => {
+                let id = std::fs::read_to_string("/etc/machine-id")
+                    .context("No passphrase set and /etc/machine-id unreadable")?;
+                let mut secret = b"ECE4301-keystore-machine-key:".to_vec();
+                secret.extend_from_slice(id.trim().as_bytes());
+                Ok(Zeroizing::new(secret))
+            }
+        }
+    }
+
+    fn derive_kek(&self, salt: &[u8]) -> Result<Zeroizing<[u8; 32]>> {
+        let secret = self.secret()?;
+        let mut kek = Zeroizing::new([0u8; 32]);
+        Argon2::default()
+            .hash_password_into(&secret, salt, &mut kek[..])
+            .map_err(|e| anyhow::anyhow!("Argon2id KEK derivation failed: {}", e))?;
+        Ok(kek)
+    }
+}
+
+/// Plaintext metadata stored alongside the sealed key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeystoreHeader {
+    pub epoch: u64,
+    pub created_unix: u64,
+    /// 0 means the key never expires
+    pub expires_unix: u64,
+    pub members_hash: [u8; 32],
+}
+
+impl KeystoreHeader {
+    pub fn is_expired(&self, now_unix: u64) -> bool {
+        self.expires_unix != 0 && now_unix >= self.expires_unix
+    }
+}
+
+/// Order-independent hash of the group's member IDs
+pub fn members_hash<S: AsRef<str>>(node_ids: &[S]) -> [u8; 32] {
+    let mut ids: Vec<&str> = node_ids.iter().map(|s| s.as_ref()).collect();
+    ids.sort_unstable();
+
+    let mut hasher = Sha256::new();
+    hasher.update(b"ECE4301-group-members");
+    for id in ids {
+        hasher.update((id.len() as u32).to_be_bytes());
+        hasher.update(id.as_bytes());
+    }
+    hasher.finalize().into()
+}
+
+fn now_unix() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Seal `key` under the KEK and atomically write it to `path` with mode 0600
+pub fn save(
+    path: &Path,
+    key: &SessionKeyMaterial,
+    kek_source: &KekSource,
+    epoch: u64,
+    ttl_secs: u64,
+    members_hash: [u8; 32],
+) -> Result<KeystoreHeader> {
+    let created_unix = now_unix();
+    let header = KeystoreHeader {
+        epoch,
+        created_unix,
+        expires_unix: if ttl_secs == 0 { 0 } else { created_unix.saturating_add(ttl_secs) },
+        members_hash,
+    };
+
+    let mut salt = [0u8; 16];
+    let mut nonce = [0u8; 12];
+    OsRng.fill_bytes(&mut salt);
+    OsRng.fill_bytes(&mut nonce);
+
+    let mut aad = Vec::with_capacity(HEADER_SIZE);
+    aad.extend_from_slice(MAGIC);
+    aad.push(VERSION);
+    aad.extend_from_slice(&header.epoch.to_be_bytes());
+    aad.extend_from_slice(&header.created_unix.to_be_bytes());
+    aad.extend_from_slice(&header.expires_unix.to_be_bytes());
+    aad.extend_from_slice(&header.members_hash);
+    aad.extend_from_slice(&salt);
+    aad.extend_from_slice(&nonce);
+
+    let kek = kek_source.derive_kek(&salt)?;
+    let cipher = Aes256Gcm::new_from_slice(&kek[..])?;
+    let key_bytes = key.as_bytes();
+    let sealed = cipher
+        .encrypt(&nonce.into(), Payload { msg: key_bytes.as_slice(), aad: &aad })
+        .map_err(|e| anyhow::anyhow!("Keystore encryption failed: {}", e))?;
+
+    let mut contents = aad;
+    contents.extend_from_slice(&sealed);
+    write_atomic(path, &contents)?;
+
+    Ok(header)
+}
+
+/// Read the plaintext header without decrypting (e.g. to pick the next epoch)
+pub fn peek_header(path: &Path) -> Result<KeystoreHeader> {
+    let contents = std::fs::read(path)
+        .with_context(|| format!("Failed to read keystore {}", path.display()))?;
+    parse_header(&contents)
+}
+
+fn parse_header(contents: &[u8]) -> Result<KeystoreHeader> {
+    if contents.len() != HEADER_SIZE + SEALED_SIZE {
+        bail!("Invalid keystore: expected {} bytes, got {}", HEADER_SIZE + SEALED_SIZE, contents.len());
+    }
+    if &contents[0..4] != MAGIC {
+        bail!("Invalid keystore: bad magic (raw 24-byte key files are no longer accepted)");
+    }
+    if contents[4] != VERSION {
+        bail!("Unsupported keystore version {}", contents[4]);
+    }
+
+    let u64_at = |off: usize| u64::from_be_bytes(contents[off..off + 8].try_into().unwrap());
+    let mut members_hash = [0u8; 32];
+    members_hash.copy_from_slice(&contents[29..61]);
+
+    Ok(KeystoreHeader {
+        epoch: u64_at(5),
+        created_unix: u64_at(13),
+        expires_unix: u64_at(21),
+        members_hash,
+    })
+}
+
+/// Decrypt and validate a keystore.
+///
+/// Fails if the file was modified, the KEK is wrong, the key has expired, or
+/// (when `expected_members` is given) the member list does not match.
+pub fn load(
+    path: &Path,
+    kek_source: &KekSource,
+    expected_members: Option<[u8; 32]>,
+) -> Result<(SessionKeyMaterial, KeystoreHeader)> {
+    let contents = std::fs::read(path)
+        .with_context(|| format!("Failed to read keystore {}", path.display()))?;
+    let header = parse_header(&contents)?;
+
+    let aad = &contents[..HEADER_SIZE];
+    let salt = &contents[61..77];
+    let nonce: [u8; 12] = contents[77..89].try_into()?;
+    let sealed = &contents[HEADER_SIZE..];
+
+    let kek = kek_source.derive_kek(salt)?;
+    let cipher = Aes256Gcm::new_from_slice(&kek[..])?;
+    let key_bytes = cipher
+        .decrypt(&nonce.into(), Payload { msg: sealed, aad })
+        .map(Zeroizing::new)
+        .map_err(|_| anyhow::anyhow!("Keystore authentication failed: file tampered with or wrong passphrase"))?;
+
+    if header.is_expired(now_unix()) {
+        bail!("Group key epoch {} expired at unix time {}", header.epoch, header.expires_unix);
+    }
+    if let Some(expected) = expected_members {
+        if !ct_eq(&expected, &header.members_hash) {
+            bail!("Group key was issued for a different member list");
+        }
+    }
+
+    Ok((SessionKeyMaterial::from_bytes(&key_bytes)?, header))
+}
+
+/// Write to a temp file in the same directory, fsync, rename over `path`,
+/// then fsync the directory
+fn write_atomic(path: &Path, contents: &[u8]) -> Result<()> {
+    let file_name = path.file_name().context("Keystore path has no file name")?;
+    let mut tmp_name = file_name.to_os_string();
+    tmp_name.push(format!(".tmp{}", std::process::id()));
+    let tmp_path = path.with_file_name(tmp_name);
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    let result = (|| -> Result<()> {
+        let mut file = options.open(&tmp_path)
+            .with_context(|| format!("Failed to create {}", tmp_path.display()))?;
+        file.write_all(contents)?;
+        file.sync_all()?;
+        std::fs::rename(&tmp_path, path)
+            .with_context(|| format!("Failed to move keystore into {}", path.display()))?;
+        Ok(())
+    })();
+
+    if result.is_err() {
+        let _ = std::fs::remove_file(&tmp_path);
+        return result;
+    }
+
+    // Persist the rename itself, not just the file contents
+    #[cfg(unix)]
+    {
+        let dir = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        std::fs::File::open(dir)
+            .and_then(|d| d.sync_all())
+            .with_context(|| format!("Failed to sync directory {}", dir.display()))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("keystore-{}-{}.bin", name, std::process::id()))
+    }
+
+    fn kek() -> KekSource {
+        KekSource::Passphrase(Zeroizing::new("correct horse".to_string()))
+    }
+
+    #[test]
+    fn test_roundtrip_and_permissions() {
+        let path = temp_path("roundtrip");
+        let key = SessionKeyMaterial::generate_random();
+        let members = members_hash(&["pi-2", "pi-3"]);
+
+        save(&path, &key, &kek(), 3, 3600, members).unwrap();
+        let (loaded, header) = load(&path, &kek(), Some(members_hash(&["pi-3", "pi-2"]))).unwrap();
+
+        assert_eq!(loaded, key);
+        assert_eq!(header.epoch, 3);
+        assert_eq!(peek_header(&path).unwrap(), header);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        assert!(load(&path, &kek(), Some(members_hash(&["pi-2"]))).is_err());
+        let wrong = KekSource::Passphrase(Zeroizing::new("wrong".to_string()));
+        assert!(load(&path, &wrong, None).is_err());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_rejects_tampered_and_expired() {
+        let path = temp_path("tamper");
+        let key = SessionKeyMaterial::generate_random();
+        save(&path, &key, &kek(), 1, 3600, [0u8; 32]).unwrap();
+
+        // Push the expiry out by editing the plaintext header
+        let mut contents = std::fs::read(&path).unwrap();
+        contents[21..29].copy_from_slice(&u64::MAX.to_be_bytes());
+        std::fs::write(&path, &contents).unwrap();
+        assert!(load(&path, &kek(), None).is_err());
+
+        // Short TTL, then wait it out
+        save(&path, &key, &kek(), 1, 1, [0u8; 32]).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(1100));
+        let err = load(&path, &kek(), None).unwrap_err();
+        assert!(err.to_string().contains("expired"));
+
+        // A huge TTL saturates instead of wrapping into the past
+        save(&path, &key, &kek(), 1, u64::MAX, [0u8; 32]).unwrap();
+        let (_, header) = load(&path, &kek(), None).unwrap();
+        assert_eq!(header.expires_unix, u64::MAX);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -2,6 +2,7 @@ use anyhow::{Result, Context, bail};
 use clap::{Parser, ValueEnum};
 use chrono::Utc;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
 use std::time::{Instant, Duration, SystemTime};
 use tokio::net::{TcpListener, TcpStream};
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
@@ -22,8 +23,12 @@ mod metrics_lib {
 mod display;
 use display::VideoDisplay;
 
+mod config;
+mod control;
 mod group_key;
+mod keystore;
 
+use control::{Control, MemberChange};
 use crypto_lib::*;
 use metrics_lib::*;
 
@@ -34,6 +39,23 @@ enum KeyMechanism {
     Group,  // Use pre-established group key
 }
 
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum CipherKind {
+    AesGcm,
+    Ascon128,
+    Ascon128a,
+}
+
+impl From<CipherKind> for CipherSuite {
+    fn from(kind: CipherKind) -> Self {
+        match kind {
+            CipherKind::AesGcm => CipherSuite::Aes128Gcm,
+            CipherKind::Ascon128 => CipherSuite::Ascon(AsconVariant::Ascon128),
+            CipherKind::Ascon128a => CipherSuite::Ascon(AsconVariant::Ascon128a),
+        }
+    }
+}
+
 #[derive(Debug, Clone, ValueEnum)]
 enum Mode {
     Sender,
@@ -54,6 +76,10 @@ struct Args {
     #[arg(long, default_value = "ecdh")]
     mechanism: KeyMechanism,
     
+    /// Frame AEAD (must match on both ends)
+    #[arg(long, value_enum, default_value = "aes-gcm")]
+    cipher: CipherKind,
+    
     /// Host to connect to (sender) or bind to (receiver)
     #[arg(long, default_value = "0.0.0.0")]
     host: String,
@@ -98,6 +124,18 @@ struct Args {
     #[arg(long)]
     print_config: bool,
     
+    /// TOML file with named profiles; flags given on the command line override it
+    #[arg(long)]
+    config: Option<String>,
+    
+    /// Profile to use from --config (optional if the file defines only one)
+    #[arg(long)]
+    profile: Option<String>,
+    
+    /// Print the merged configuration (with the source of each value) as TOML and exit
+    #[arg(long)]
+    print_effective_config: bool,
+    
     /// Use simulated video instead of camera
     #[arg(long)]
     simulate: bool,
@@ -106,6 +144,10 @@ struct Args {
     #[arg(long)]
     display: bool,
     
+    /// Start with the statistics overlay shown on the display (toggle with `streamctl overlay`)
+    #[arg(long)]
+    overlay: bool,
+    
     /// Relay destination host (relay mode only)
     #[arg(long)]
     relay_host: Option<String>,
@@ -118,6 +160,10 @@ struct Args {
     #[arg(long, default_value = "group_key.bin")]
     group_key_file: String,
     
+    /// Group key lifetime in seconds before the keystore refuses it (0 = never expires)
+    #[arg(long, default_value = "86400")]
+    group_key_ttl: u64,
+    
     /// Multicast address for group streaming
     #[arg(long)]
     multicast_addr: Option<String>,
@@ -125,60 +171,65 @@ struct Args {
     /// Group members list (for group-leader mode): node_id:host:port,node_id:host:port
     #[arg(long)]
     members: Option<String>,
+    
+    /// Pre-generated RSA keypairs kept ready for handshakes (0 = generate inline)
+    #[arg(long, default_value = "2")]
+    rsa_pool_depth: usize,
+    
+    /// Only refill the RSA key pool once it drops to this many keys
+    #[arg(long)]
+    rsa_pool_low_water: Option<usize>,
+    
+    /// hwmon directory of a power sensor (e.g. /sys/class/hwmon/hwmon2); simulated if unset
+    #[arg(long)]
+    power_sensor: Option<String>,
+    
+    /// Unix socket for the JSON-lines control API (see streamctl)
+    #[arg(long)]
+    control_socket: Option<String>,
+    
+    /// Serve live metrics in OpenMetrics format on this address (e.g. 0.0.0.0:9464)
+    #[cfg(feature = "prometheus")]
+    #[arg(long)]
+    metrics_addr: Option<String>,
 }
 
-/// Frame header: [flags:1][timestamp_us:8][counter:4][nonce_counter:4][payload_len:4]
-const HEADER_SIZE: usize = 21;
+/// HKDF context for point-to-point handshakes (bound into the transcript)
+const HANDSHAKE_CONTEXT: &[u8] = b"ECE4301-midterm-2025";
 
-#[derive(Debug, Clone)]
-struct FrameHeader {
-    flags: u8,
-    timestamp_us: u64,
-    counter: u32,
-    nonce_counter: u32,
-    payload_len: u32,
-}
+/// Connect timeout for members added through the control API
+const MEMBER_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
 
-impl FrameHeader {
-    fn serialize(&self) -> [u8; HEADER_SIZE] {
-        let mut buf = [0u8; HEADER_SIZE];
-        buf[0] = self.flags;
-        buf[1..9].copy_from_slice(&self.timestamp_us.to_be_bytes());
-        buf[9..13].copy_from_slice(&self.counter.to_be_bytes());
-        buf[13..17].copy_from_slice(&self.nonce_counter.to_be_bytes());
-        buf[17..21].copy_from_slice(&self.payload_len.to_be_bytes());
-        buf
-    }
-    
-    fn deserialize(buf: &[u8]) -> Result<Self> {
-        if buf.len() < HEADER_SIZE {
-            bail!("Header too short");
-        }
-        
-        let flags = buf[0];
-        let timestamp_us = u64::from_be_bytes(buf[1..9].try_into()?);
-        let counter = u32::from_be_bytes(buf[9..13].try_into()?);
-        let nonce_counter = u32::from_be_bytes(buf[13..17].try_into()?);
-        let payload_len = u32::from_be_bytes(buf[17..21].try_into()?);
-        
-        Ok(Self { flags, timestamp_us, counter, nonce_counter, payload_len })
-    }
-}
+/// Highest ratchet epoch a joining group member will catch up to
+const MAX_GROUP_EPOCH: u32 = 1 << 16;
 
 struct SessionState {
-    cipher: Arc<RwLock<AesGcmCipher>>,
+    cipher: Arc<RwLock<FrameCipher>>,
+    key_material: RwLock<SessionKeyMaterial>,
+    suite: CipherSuite,
     rekey_interval: Duration,
     last_rekey: Arc<RwLock<Instant>>,
+    epoch: AtomicU32,
 }
 
 impl SessionState {
-    fn new(key_material: SessionKeyMaterial, rekey_interval: Duration) -> Self {
-        let cipher = Arc::new(RwLock::new(AesGcmCipher::new(key_material, None)));
-        Self {
+    fn new(key_material: SessionKeyMaterial, suite: CipherSuite, rekey_interval: Duration) -> Result<Self> {
+        let frame_key = SessionKeyMaterial::from_bytes(&key_material.as_bytes())?;
+        let cipher = Arc::new(RwLock::new(FrameCipher::new(suite, frame_key, None)));
+        Ok(Self {
             cipher,
+            key_material: RwLock::new(key_material),
+            suite,
             rekey_interval,
             last_rekey: Arc::new(RwLock::new(Instant::now())),
-        }
+            epoch: AtomicU32::new(0),
+        })
+    }
+    
+    /// Start at `epoch` instead of 0; `key_material` must already be that epoch's key
+    fn at_epoch(self, epoch: u32) -> Self {
+        self.epoch.store(epoch, Ordering::SeqCst);
+        self
     }
     
     async fn should_rekey(&self) -> bool {
@@ -186,11 +237,114 @@ impl SessionState {
         elapsed >= self.rekey_interval || self.cipher.read().await.should_rekey()
     }
     
-    async fn rekey(&self, new_key_material: SessionKeyMaterial) {
-        let mut cipher = self.cipher.write().await;
-        *cipher = AesGcmCipher::new(new_key_material, None);
+    /// Step to the next epoch's key. The peer does the same when it sees
+    /// `FLAG_REKEY`, so no new handshake is needed.
+    async fn ratchet(&self) -> Result<u32> {
+        let mut key_material = self.key_material.write().await;
+        let (epoch, next, cipher) = self.next_epoch(&key_material)?;
+        self.install(&mut key_material, epoch, next, cipher).await;
+        Ok(epoch)
+    }
+    
+    /// Decrypt a received frame. A `FLAG_REKEY` frame is tried under the
+    /// next epoch's key and the session only moves there once its tag
+    /// verifies, so a forged flag can't push us off the sender's epoch.
+    /// Returns the plaintext and the new epoch if this frame rekeyed.
+    async fn open_frame(&self, header: &FrameHeader, aad: &[u8], ciphertext: &[u8]) -> Result<(Vec<u8>, Option<u32>)> {
+        if header.flags & FLAG_REKEY == 0 {
+            let plaintext = self.cipher.read().await.decrypt(ciphertext, aad, header.nonce_counter)?;
+            return Ok((plaintext, None));
+        }
+        
+        let mut key_material = self.key_material.write().await;
+        let (epoch, next, cipher) = self.next_epoch(&key_material)?;
+        let plaintext = cipher.decrypt(ciphertext, aad, header.nonce_counter)?;
+        self.install(&mut key_material, epoch, next, cipher).await;
+        Ok((plaintext, Some(epoch)))
+    }
+    
+    /// Key material and cipher for the epoch after the current one
+    fn next_epoch(&self, key_material: &SessionKeyMaterial) -> Result<(u32, SessionKeyMaterial, FrameCipher)> {
+        let epoch = self.epoch() + 1;
+        let next = key_material.ratchet(epoch)?;
+        let cipher = FrameCipher::new(self.suite, SessionKeyMaterial::from_bytes(&next.as_bytes())?, None);
+        Ok((epoch, next, cipher))
+    }
+    
+    async fn install(&self, key_material: &mut SessionKeyMaterial, epoch: u32, next: SessionKeyMaterial, cipher: FrameCipher) {
+        *self.cipher.write().await = cipher;
+        *key_material = next;
+        self.epoch.store(epoch, Ordering::SeqCst);
         *self.last_rekey.write().await = Instant::now();
-        info!("Session rekeyed successfully");
+        info!("Session rekeyed to epoch {}", epoch);
+    }
+    
+    fn epoch(&self) -> u32 {
+        self.epoch.load(Ordering::SeqCst)
+    }
+}
+
+/// One outgoing encrypted stream: the receiver from --host, or a member
+/// added through the control API
+struct Peer {
+    node_id: String,
+    stream: TcpStream,
+    /// In group mode every peer holds the same session, so all frames under
+    /// the group key draw their nonces from one counter
+    session: Arc<SessionState>,
+    /// Epoch of the last frame sent to this peer
+    sent_epoch: u32,
+}
+
+impl Peer {
+    /// Encrypt and send one frame; returns the bytes written
+    async fn send_frame(
+        &mut self,
+        frame: &[u8],
+        counter: u32,
+        timestamp_us: u64,
+        force_rekey: bool,
+        collector: &MetricsCollector,
+    ) -> Result<usize> {
+        // A shared session is ratcheted by the first peer to send after a
+        // rekey is due; the others only flag the step to their receivers
+        if (force_rekey || self.session.should_rekey().await) && self.session.epoch() == self.sent_epoch {
+            warn!("Rekeying session with {}", self.node_id);
+            self.session.ratchet().await?;
+            collector.record_rekey().await;
+        }
+        let mut flags = 0;
+        let epoch = self.session.epoch();
+        if epoch != self.sent_epoch {
+            flags |= FLAG_REKEY;
+            self.sent_epoch = epoch;
+        }
+        
+        // Get the nonce counter BEFORE encrypting
+        let cipher_guard = self.session.cipher.read().await;
+        let nonce_counter = cipher_guard.get_counter();
+        
+        // Build header with the nonce counter that WILL be used
+        let header = FrameHeader {
+            flags,
+            timestamp_us,
+            counter,
+            nonce_counter,
+            payload_len: frame.len() as u32,
+        };
+        
+        // Serialize header to use as AAD
+        let aad = header.encode();
+        
+        // Now encrypt - the cipher will use nonce_counter and increment it
+        let ciphertext = cipher_guard.encrypt(frame, &aad)?;
+        drop(cipher_guard);
+        
+        // Send header + ciphertext
+        self.stream.write_all(&aad).await?;
+        self.stream.write_all(&ciphertext).await?;
+        
+        Ok(HEADER_SIZE + ciphertext.len())
     }
 }
 
@@ -264,6 +418,258 @@ fn init_gstreamer_camera(args: &Args) -> Result<(gst::Pipeline, AppSink)> {
     Ok((pipeline, appsink))
 }
 
+/// Start the OpenMetrics endpoint if `--metrics-addr` was given
+#[cfg(feature = "prometheus")]
+async fn start_metrics_endpoint(
+    args: &Args,
+    collector: &Arc<MetricsCollector>,
+    role: &str,
+) -> Result<()> {
+    let Some(addr) = args.metrics_addr.as_deref() else {
+        return Ok(());
+    };
+    
+    let labels = metrics::exporter::ExporterLabels {
+        mechanism: mechanism_label(args),
+        role: role.to_string(),
+    };
+    
+    let (bound, _) = collector.clone().serve_openmetrics(addr, labels).await?;
+    info!("Serving OpenMetrics on http://{}/metrics", bound);
+    Ok(())
+}
+
+/// Start the background RSA key pool for responders, if enabled
+fn rsa_key_pool(args: &Args) -> Option<rsa_kex::RsaKeyPool> {
+    if !matches!(args.mechanism, KeyMechanism::Rsa) || args.rsa_pool_depth == 0 {
+        return None;
+    }
+    
+    let policy = match args.rsa_pool_low_water {
+        Some(low) => rsa_kex::RefillPolicy::LowWater(low),
+        None => rsa_kex::RefillPolicy::Eager,
+    };
+    info!("Starting RSA-{} key pool (depth {}, {:?})", args.rsa_bits, args.rsa_pool_depth, policy);
+    Some(rsa_kex::RsaKeyPool::new(args.rsa_bits, args.rsa_pool_depth, policy))
+}
+
+/// Metrics label for the configured mechanism
+fn mechanism_label(args: &Args) -> String {
+    match args.mechanism {
+        KeyMechanism::Rsa => format!("RSA-{}", args.rsa_bits),
+        KeyMechanism::Ecdh => "ECDH-P256".to_string(),
+        KeyMechanism::Group => "GROUP-PSK".to_string(),
+    }
+}
+
+/// Cipher suite name bound into handshake transcripts
+fn cipher_id(args: &Args) -> String {
+    CipherSuite::from(args.cipher).to_string()
+}
+
+/// Run the configured handshake and record it with the collector.
+///
+/// A failed key confirmation is recorded as an unsuccessful handshake before
+/// the error is returned, so it shows up in the exported metrics.
+async fn establish_session(
+    args: &Args,
+    stream: &mut TcpStream,
+    is_initiator: bool,
+    key_pool: Option<&rsa_kex::RsaKeyPool>,
+    collector: &MetricsCollector,
+) -> Result<(SessionKeyMaterial, HandshakeMetrics)> {
+    let ts_start = Utc::now();
+    let result = match args.mechanism {
+        KeyMechanism::Rsa => perform_rsa_handshake(stream, is_initiator, args.rsa_bits, &cipher_id(args), key_pool, power_source(args)).await,
+        KeyMechanism::Ecdh => perform_ecdh_handshake(stream, is_initiator, &cipher_id(args), power_source(args)).await,
+        KeyMechanism::Group => perform_group_handshake(stream, &args.group_key_file, args.members.as_deref(), power_source(args)).await,
+    };
+    
+    match result {
+        Ok((key_material, metrics)) => {
+            collector.record_handshake(metrics.clone()).await;
+            Ok((key_material, metrics))
+        }
+        Err(e) => {
+            if let Some(HandshakeError::KeyConfirmationFailed) = e.downcast_ref::<HandshakeError>() {
+                error!("Handshake aborted: {}", e);
+                collector.record_handshake(HandshakeMetrics {
+                    ts_start,
+                    ts_end: Utc::now(),
+                    mechanism: mechanism_label(args),
+                    bytes_tx: 0,
+                    bytes_rx: 0,
+                    cpu_avg: 0.0,
+                    mem_mb: 0.0,
+                    energy_j: 0.0,
+                    success: false,
+                    key_source: String::new(),
+                    confirm_failures: 1,
+                }).await;
+            }
+            Err(e)
+        }
+    }
+}
+
+/// Parse a `node_id:host:port,...` member list
+fn parse_members(list: &str) -> Result<Vec<group_key::GroupMember>> {
+    list.split(',')
+        .map(|m| group_key::GroupMember::parse(m.trim()))
+        .collect()
+}
+
+/// Power source used by the handshake profiler
+fn power_source(args: &Args) -> PowerSource {
+    match &args.power_sensor {
+        Some(dir) => PowerSource::Hwmon(dir.into()),
+        None => PowerSource::default(),
+    }
+}
+
+/// Control handle for a stream loop; serves the socket if `--control-socket` was given
+fn start_control(args: &Args, role: control::Role) -> Result<Arc<Control>> {
+    let control = Arc::new(Control::new(
+        role,
+        args.node_id.clone(),
+        mechanism_label(args),
+        CipherSuite::from(args.cipher).to_string(),
+        args.video_fps.max(1) as u32,
+    ));
+    control.set_overlay_visible(args.overlay);
+    if let Some(path) = args.control_socket.as_deref() {
+        control.clone().serve(std::path::Path::new(path))?;
+    }
+    Ok(control)
+}
+
+/// Handshake with a receiver as the initiator and set up its session. In
+/// group mode a member joining a running stream gets `group`, the live group
+/// session, rather than a fresh one whose nonce counter would start over
+/// under the same key.
+async fn start_peer(
+    args: &Args,
+    node_id: String,
+    mut stream: TcpStream,
+    group: Option<Arc<SessionState>>,
+    collector: &MetricsCollector,
+) -> Result<(Peer, HandshakeMetrics)> {
+    let (key_material, handshake_metrics) = establish_session(args, &mut stream, true, None, collector).await?;
+    let session = match group {
+        Some(session) => session,
+        None => Arc::new(SessionState::new(
+            key_material,
+            args.cipher.into(),
+            Duration::from_secs(args.rekey_interval),
+        )?),
+    };
+    if let KeyMechanism::Group = args.mechanism {
+        send_group_epoch(&mut stream, session.epoch()).await?;
+    }
+    let sent_epoch = session.epoch();
+    Ok((Peer { node_id, stream, session, sent_epoch }, handshake_metrics))
+}
+
+/// Handshake with a sender as the responder and set up the receiving session
+async fn accept_session(
+    args: &Args,
+    stream: &mut TcpStream,
+    key_pool: Option<&rsa_kex::RsaKeyPool>,
+    collector: &MetricsCollector,
+) -> Result<SessionState> {
+    let (key_material, _) = establish_session(args, stream, false, key_pool, collector).await?;
+    let (key_material, epoch) = match args.mechanism {
+        KeyMechanism::Group => join_group_epoch(stream, key_material).await?,
+        _ => (key_material, 0),
+    };
+    Ok(SessionState::new(
+        key_material,
+        args.cipher.into(),
+        Duration::from_secs(args.rekey_interval),
+    )?.at_epoch(epoch))
+}
+
+/// Group mode: every member loads the same key from its keystore, so the
+/// sender says which ratchet epoch the group session has reached
+async fn send_group_epoch(stream: &mut TcpStream, epoch: u32) -> Result<()> {
+    stream.write_u32(epoch).await?;
+    Ok(())
+}
+
+/// Group mode: ratchet the keystore key forward to the sender's epoch
+async fn join_group_epoch(stream: &mut TcpStream, key_material: SessionKeyMaterial) -> Result<(SessionKeyMaterial, u32)> {
+    let epoch = stream.read_u32().await?;
+    if epoch > MAX_GROUP_EPOCH {
+        bail!("Group session epoch {} is out of range", epoch);
+    }
+    let mut key_material = key_material;
+    for step in 1..=epoch {
+        key_material = key_material.ratchet(step)?;
+    }
+    if epoch > 0 {
+        info!("Joined the group session at epoch {}", epoch);
+    }
+    Ok((key_material, epoch))
+}
+
+/// Connect to a member added at runtime and start streaming to it
+async fn add_member(
+    args: &Args,
+    member: group_key::GroupMember,
+    peers: &mut Vec<Peer>,
+    collector: &MetricsCollector,
+) -> Result<()> {
+    if peers.iter().any(|p| p.node_id == member.node_id) {
+        bail!("{} is already a member", member.node_id);
+    }
+    
+    info!("Adding member {} @ {}", member.node_id, member.address);
+    let stream = tokio::time::timeout(MEMBER_CONNECT_TIMEOUT, TcpStream::connect(&member.address))
+        .await
+        .with_context(|| format!("Timed out connecting to {}", member.address))?
+        .with_context(|| format!("Failed to connect to {}", member.address))?;
+    
+    // Group members share the running session; the others get their own
+    let group = match args.mechanism {
+        KeyMechanism::Group => peers.first().map(|p| p.session.clone()),
+        _ => None,
+    };
+    let (peer, _) = start_peer(args, member.node_id, stream, group, collector).await?;
+    peers.push(peer);
+    Ok(())
+}
+
+/// Apply add/remove-member requests queued by the control API
+async fn apply_member_changes(
+    args: &Args,
+    control: &Control,
+    peers: &mut Vec<Peer>,
+    collector: &MetricsCollector,
+) {
+    while let Some(change) = control.next_member_change() {
+        let (result, reply) = match change {
+            MemberChange::Add(member, reply) => (add_member(args, member, peers, collector).await, reply),
+            MemberChange::Remove(node_id, reply) => {
+                let before = peers.len();
+                peers.retain(|p| p.node_id != node_id);
+                let result = if peers.len() < before {
+                    info!("Removed member {}", node_id);
+                    Ok(())
+                } else {
+                    Err(anyhow::anyhow!("No member {}", node_id))
+                };
+                (result, reply)
+            }
+        };
+        let _ = reply.send(result.map_err(|e| format!("{:#}", e)));
+        control.set_peers(peer_epochs(peers));
+    }
+}
+
+fn peer_epochs(peers: &[Peer]) -> Vec<(String, u32)> {
+    peers.iter().map(|p| (p.node_id.clone(), p.session.epoch())).collect()
+}
+
 /// Calculate expected frame size for I420 format (YUV 4:2:0)
 fn i420_frame_size(width: i32, height: i32) -> usize {
     let y_size = (width * height) as usize;
@@ -275,14 +681,24 @@ async fn perform_rsa_handshake(
     stream: &mut TcpStream,
     is_initiator: bool,
     rsa_bits: usize,
+    cipher: &str,
+    key_pool: Option<&rsa_kex::RsaKeyPool>,
+    power: PowerSource,
 ) -> Result<(SessionKeyMaterial, HandshakeMetrics)> {
+    // Pool refills wait until the profile window below has closed
+    let _refill_hold = key_pool.map(|pool| pool.hold_refill());
     let start_time = Utc::now();
     let start_instant = Instant::now();
+    let profiler = HandshakeProfiler::start(power);
     
     let mut bytes_tx = 0u64;
     let mut bytes_rx = 0u64;
+    let mut key_source = "peer".to_string();
+    let mechanism = format!("RSA-{}", rsa_bits);
     
-    let key_material = if is_initiator {
+    // Each side ends up with the responder's public key, the wrapped secret
+    // and (on success) the secret itself; keys come from the transcript below
+    let (premaster, pub_key_der, wrapped) = if is_initiator {
         info!("RSA: Initiator - generating session key");
         
         // Receive responder's public key
@@ -300,12 +716,11 @@ async fn perform_rsa_handshake(
         let peer_public = rsa::RsaPublicKey::from_public_key_der(&pub_key_der)
             .context("Failed to parse RSA public key")?;
         
-        // Generate and wrap session key
-        let session_key_material = SessionKeyMaterial::generate_random();
-        let session_bytes = session_key_material.as_bytes();
+        // Generate and wrap the handshake secret
+        let premaster = Secret::<32>::random();
         
         let mut rng = rand::rngs::OsRng;
-        let wrapped = peer_public.encrypt(&mut rng, rsa::Oaep::new::<sha2::Sha256>(), &session_bytes)
+        let wrapped = peer_public.encrypt(&mut rng, rsa::Oaep::new::<sha2::Sha256>(), premaster.expose_secret())
             .context("Failed to wrap session key")?;
         
         // Send wrapped key
@@ -315,12 +730,25 @@ async fn perform_rsa_handshake(
         
         info!("RSA: Sent wrapped session key ({} bytes)", wrapped.len());
         
-        session_key_material
+        (premaster, pub_key_der, wrapped)
     } else {
-        info!("RSA: Responder - generating keypair ({} bits)", rsa_bits);
+        // Take a pre-generated keypair if the pool matches, otherwise generate
+        // now on the blocking pool so keygen doesn't stall the runtime
+        let pooled = key_pool
+            .filter(|pool| pool.bits() == rsa_bits)
+            .and_then(|pool| pool.try_take());
+        let (keypair, source) = match pooled {
+            Some(keypair) => (keypair, rsa_kex::KeySource::Pool),
+            None => {
+                let keypair = tokio::task::spawn_blocking(move || rsa_kex::RsaKeyPair::generate(rsa_bits))
+                    .await
+                    .context("RSA keygen task failed")??;
+                (keypair, rsa_kex::KeySource::Inline)
+            }
+        };
+        key_source = source.to_string();
+        info!("RSA: Responder - using {}-bit keypair ({})", rsa_bits, key_source);
         
-        // Generate RSA keypair
-        let keypair = rsa_kex::RsaKeyPair::generate(rsa_bits)?;
         let pub_key_der = keypair.public_key_der()?;
         
         // Send public key
@@ -340,24 +768,36 @@ async fn perform_rsa_handshake(
         
         info!("RSA: Received wrapped session key ({} bytes)", wrapped_len);
         
-        // Unwrap session key
+        // Unwrap the handshake secret
         let session_bytes = keypair.unwrap_session_key(&wrapped)?;
-        SessionKeyMaterial::from_bytes(&session_bytes)?
+        (Secret::<32>::from_slice(&session_bytes)?, pub_key_der, wrapped)
     };
     
+    let role = if is_initiator { handshake::Role::Initiator } else { handshake::Role::Responder };
+    let transcript = handshake::transcript_hash(&mechanism, cipher, HANDSHAKE_CONTEXT, &wrapped, &pub_key_der);
+    let (key_material, confirmation) = handshake::derive_keys(premaster.expose_secret(), role, transcript)?;
+    exchange_finished(stream, is_initiator, &confirmation).await?;
+    bytes_tx += handshake::KeyConfirmation::FINISHED_LEN as u64;
+    bytes_rx += handshake::KeyConfirmation::FINISHED_LEN as u64;
+    
+    info!("RSA: Key confirmation succeeded");
+    
     let duration = start_instant.elapsed();
     
-    let metrics = HandshakeMetrics {
+    let mut metrics = HandshakeMetrics {
         ts_start: start_time,
         ts_end: Utc::now(),
-        mechanism: format!("RSA-{}", rsa_bits),
+        mechanism,
         bytes_tx,
         bytes_rx,
         cpu_avg: 0.0,
         mem_mb: 0.0,
         energy_j: 0.0,
         success: true,
+        key_source,
+        confirm_failures: 0,
     };
+    profiler.apply(&mut metrics);
     
     info!("RSA handshake completed in {:.3}s", duration.as_secs_f64());
     
@@ -366,10 +806,13 @@ async fn perform_rsa_handshake(
 
 async fn perform_ecdh_handshake(
     stream: &mut TcpStream,
-    _is_initiator: bool,
+    is_initiator: bool,
+    cipher: &str,
+    power: PowerSource,
 ) -> Result<(SessionKeyMaterial, HandshakeMetrics)> {
     let start_time = Utc::now();
     let start_instant = Instant::now();
+    let profiler = HandshakeProfiler::start(power);
     
     let mut bytes_tx = 0u64;
     let mut bytes_rx = 0u64;
@@ -392,13 +835,19 @@ async fn perform_ecdh_handshake(
     
     info!("ECDH: Exchanged public keys ({} bytes each)", my_public.len());
     
-    // Derive shared secret
-    let context = b"ECE4301-midterm-2025";
-    let key_material = my_keypair.derive_session_key(&peer_public, context)?;
+    // Derive session keys bound to the handshake transcript
+    let role = if is_initiator { ecdh_kex::Role::Initiator } else { ecdh_kex::Role::Responder };
+    let (key_material, confirmation) = my_keypair
+        .derive_with_transcript(&peer_public, role, "ECDH-P256", cipher, HANDSHAKE_CONTEXT)?;
+    exchange_finished(stream, is_initiator, &confirmation).await?;
+    bytes_tx += ecdh_kex::KeyConfirmation::FINISHED_LEN as u64;
+    bytes_rx += ecdh_kex::KeyConfirmation::FINISHED_LEN as u64;
+    
+    info!("ECDH: Key confirmation succeeded");
     
     let duration = start_instant.elapsed();
     
-    let metrics = HandshakeMetrics {
+    let mut metrics = HandshakeMetrics {
         ts_start: start_time,
         ts_end: Utc::now(),
         mechanism: "ECDH-P256".to_string(),
@@ -408,46 +857,63 @@ async fn perform_ecdh_handshake(
         mem_mb: 0.0,
         energy_j: 0.0,
         success: true,
+        key_source: "ephemeral".to_string(),
+        confirm_failures: 0,
     };
+    profiler.apply(&mut metrics);
     
     info!("ECDH handshake completed in {:.3}s", duration.as_secs_f64());
     
     Ok((key_material, metrics))
 }
 
-async fn load_group_key(path: &str) -> Result<SessionKeyMaterial> {
-    info!("Loading group key from: {}", path);
-    let key_bytes = tokio::fs::read(path).await
-        .context("Failed to read group key file")?;
-    
-    // SessionKeyMaterial is 24 bytes: 16-byte AES key + 8-byte nonce base
-    if key_bytes.len() != 24 {
-        bail!("Invalid group key file: expected 24 bytes, got {}", key_bytes.len());
+/// Key confirmation: the initiator proves first; the responder only answers
+/// a valid finished message, so a mismatched peer just sees the connection close
+async fn exchange_finished(
+    stream: &mut TcpStream,
+    is_initiator: bool,
+    confirmation: &handshake::KeyConfirmation,
+) -> Result<()> {
+    let mut peer_finished = [0u8; handshake::KeyConfirmation::FINISHED_LEN];
+    if is_initiator {
+        stream.write_all(&confirmation.finished()).await?;
+        stream.read_exact(&mut peer_finished).await
+            .map_err(|_| HandshakeError::KeyConfirmationFailed)?;
+        confirmation.verify_peer(&peer_finished)?;
+    } else {
+        stream.read_exact(&mut peer_finished).await?;
+        confirmation.verify_peer(&peer_finished)?;
+        stream.write_all(&confirmation.finished()).await?;
     }
-    
-    SessionKeyMaterial::from_bytes(&key_bytes)
-}
-
-async fn save_group_key(key: &SessionKeyMaterial, path: &str) -> Result<()> {
-    let key_bytes = key.as_bytes();
-    tokio::fs::write(path, &key_bytes).await?;
-    info!("Saved group key to: {}", path);
     Ok(())
 }
 
 async fn perform_group_handshake(
     _stream: &mut TcpStream,
     group_key_file: &str,
+    expected_members: Option<&str>,
+    power: PowerSource,
 ) -> Result<(SessionKeyMaterial, HandshakeMetrics)> {
     let start_time = Utc::now();
     let start_instant = Instant::now();
+    let profiler = HandshakeProfiler::start(power);
+    
+    // Only check the member list when the caller knows it
+    let expected_hash = match expected_members {
+        Some(list) => Some(keystore::members_hash(&parse_members(list)?
+            .iter()
+            .map(|m| m.node_id.as_str())
+            .collect::<Vec<_>>())),
+        None => None,
+    };
     
-    // Load pre-shared group key
-    let key_material = load_group_key(group_key_file).await?;
+    // Load pre-shared group key (refuses expired or tampered keystores)
+    let (key_material, header) = group_key::load_group_key(group_key_file, expected_hash).await?;
+    info!("Group key epoch {} loaded", header.epoch);
     
     let duration = start_instant.elapsed();
     
-    let metrics = HandshakeMetrics {
+    let mut metrics = HandshakeMetrics {
         ts_start: start_time,
         ts_end: Utc::now(),
         mechanism: "GROUP-PSK".to_string(),
@@ -457,7 +923,10 @@ async fn perform_group_handshake(
         mem_mb: 0.0,
         energy_j: 0.0,
         success: true,
+        key_source: "file".to_string(),
+        confirm_failures: 0,
     };
+    profiler.apply(&mut metrics);
     
     info!("Group key loaded in {:.3}ms", duration.as_secs_f64() * 1000.0);
     
@@ -469,25 +938,23 @@ async fn run_sender(args: Args) -> Result<()> {
     
     let metrics_collector = Arc::new(MetricsCollector::new(args.node_id.clone()));
     let _metrics_task = metrics_collector.clone().start_collection();
+    #[cfg(feature = "prometheus")]
+    start_metrics_endpoint(&args, &metrics_collector, "sender").await?;
+    
+    let control = start_control(&args, control::Role::Sender)?;
     
     // Connect to receiver
     let addr = format!("{}:{}", args.host, args.port);
     info!("Connecting to {}", addr);
-    let mut stream = TcpStream::connect(&addr).await?;
+    let stream = TcpStream::connect(&addr).await?;
     
     // Perform handshake
     metrics_collector.record_power(5.0, 2.5, "handshake".to_string()).await;
     
-    let (key_material, mut handshake_metrics) = match args.mechanism {
-        KeyMechanism::Rsa => perform_rsa_handshake(&mut stream, true, args.rsa_bits).await?,
-        KeyMechanism::Ecdh => perform_ecdh_handshake(&mut stream, true).await?,
-        KeyMechanism::Group => perform_group_handshake(&mut stream, &args.group_key_file).await?,
-    };
+    let (primary, handshake_metrics) = start_peer(&args, addr.clone(), stream, None, &metrics_collector).await?;
     
-    let handshake_energy = metrics_collector.calculate_energy(Some("handshake")).await;
-    handshake_metrics.energy_j = handshake_energy;
-    
-    info!("Handshake energy: {:.3} J", handshake_energy);
+    info!("Handshake: CPU {:.1}%, peak RSS {:.1} MB, energy {:.3} J",
+          handshake_metrics.cpu_avg, handshake_metrics.mem_mb, handshake_metrics.energy_j);
     
     // Save handshake metrics
     let mech_file = match args.mechanism {
@@ -495,17 +962,15 @@ async fn run_sender(args: Args) -> Result<()> {
         KeyMechanism::Ecdh => "handshake_ecdh.csv",
         KeyMechanism::Group => "handshake_group.csv",
     };
-    MetricsCollector::write_handshake_csv(&[handshake_metrics], mech_file)?;
+    MetricsCollector::write_handshake_csv(std::slice::from_ref(&handshake_metrics), mech_file)?;
     
-    // Initialize session
-    let session = SessionState::new(
-        key_material,
-        Duration::from_secs(args.rekey_interval),
-    );
+    // Receivers streamed to; members can join and leave through the control API
+    let mut peers = vec![primary];
+    control.set_peers(peer_epochs(&peers));
     
     metrics_collector.record_power(5.0, 2.0, "steady".to_string()).await;
     
-    info!("Starting video stream");
+    info!("Starting video stream ({})", CipherSuite::from(args.cipher));
     
     // Initialize camera or simulation
     let (pipeline, appsink) = if !args.simulate {
@@ -568,44 +1033,36 @@ async fn run_sender(args: Args) -> Result<()> {
             // Simulated frame
             vec![0u8; expected_frame_size]
         };
+        control.frame(&frame_data);
         
-        // Check if we need to rekey
-        if session.should_rekey().await {
-            warn!("Rekey threshold reached, performing rekey");
-            let new_key = SessionKeyMaterial::generate_random();
-            session.rekey(new_key).await;
-        }
+        apply_member_changes(&args, &control, &mut peers, &metrics_collector).await;
+        let force_rekey = control.take_rekey_request();
         
         // Build frame header
         let timestamp_us = SystemTime::now()
             .duration_since(SystemTime::UNIX_EPOCH)?
             .as_micros() as u64;
         
-        // Get the nonce counter BEFORE encrypting
-        let cipher_guard = session.cipher.read().await;
-        let nonce_counter = cipher_guard.get_counter();
-        
-        // Build header with the nonce counter that WILL be used
-        let header = FrameHeader {
-            flags: 0,
-            timestamp_us,
-            counter: frame_count,
-            nonce_counter,
-            payload_len: frame_data.len() as u32,
-        };
-        
-        // Serialize header to use as AAD
-        let aad = header.serialize();
-        
-        // Now encrypt - the cipher will use nonce_counter and increment it
-        let ciphertext = cipher_guard.encrypt(&frame_data, &aad)?;
-        drop(cipher_guard);
-        
-        // Send header + ciphertext
-        stream.write_all(&aad).await?;
-        stream.write_all(&ciphertext).await?;
+        // Encrypt separately for every receiver; drop the ones that went away
+        let mut i = 0;
+        while i < peers.len() {
+            match peers[i].send_frame(&frame_data, frame_count, timestamp_us, force_rekey, &metrics_collector).await {
+                Ok(sent) => {
+                    total_bytes += sent as u64;
+                    i += 1;
+                },
+                Err(e) => {
+                    warn!("Dropping receiver {}: {}", peers[i].node_id, e);
+                    peers.remove(i);
+                }
+            }
+        }
+        control.set_peers(peer_epochs(&peers));
+        if peers.is_empty() {
+            info!("All receivers disconnected");
+            break;
+        }
         
-        total_bytes += (HEADER_SIZE + ciphertext.len()) as u64;
         frame_count += 1;
         
         // Update metrics every 30 frames
@@ -620,9 +1077,8 @@ async fn run_sender(args: Args) -> Result<()> {
                   frame_count, fps, goodput_mbps, dropped_frames);
         }
         
-        // Target frame rate (dynamic based on args)
-        let frame_interval_ms = 1000 / args.video_fps as u64;
-        tokio::time::sleep(Duration::from_millis(frame_interval_ms)).await;
+        // Target frame rate (from args, adjustable through the control API)
+        tokio::time::sleep(control.frame_interval(frame_data.len())).await;
         
         // Run for 60 seconds
         if stream_start.elapsed() > Duration::from_secs(60) {
@@ -656,6 +1112,13 @@ async fn run_receiver(args: Args) -> Result<()> {
     
     let metrics_collector = Arc::new(MetricsCollector::new(args.node_id.clone()));
     let _metrics_task = metrics_collector.clone().start_collection();
+    #[cfg(feature = "prometheus")]
+    start_metrics_endpoint(&args, &metrics_collector, "receiver").await?;
+    
+    let control = start_control(&args, control::Role::Receiver)?;
+    
+    // Keys generate while we wait for the sender to connect
+    let key_pool = rsa_key_pool(&args);
     
     // Listen for connections
     let addr = format!("{}:{}", args.host, args.port);
@@ -668,19 +1131,12 @@ async fn run_receiver(args: Args) -> Result<()> {
     // Perform handshake
     metrics_collector.record_power(5.0, 2.5, "handshake".to_string()).await;
     
-    let (key_material, _handshake_metrics) = match args.mechanism {
-        KeyMechanism::Rsa => perform_rsa_handshake(&mut stream, false, args.rsa_bits).await?,
-        KeyMechanism::Ecdh => perform_ecdh_handshake(&mut stream, false).await?,
-        KeyMechanism::Group => perform_group_handshake(&mut stream, &args.group_key_file).await?,
-    };
+    let session = accept_session(&args, &mut stream, key_pool.as_ref(), &metrics_collector).await?;
     
     info!("Handshake completed");
     
-    // Initialize session
-    let session = SessionState::new(
-        key_material,
-        Duration::from_secs(args.rekey_interval),
-    );
+    let peer_id = peer_addr.to_string();
+    control.set_peers(vec![(peer_id.clone(), session.epoch())]);
     
     metrics_collector.record_power(5.0, 2.0, "steady".to_string()).await;
     
@@ -703,9 +1159,21 @@ async fn run_receiver(args: Args) -> Result<()> {
         None
     };
     
+    // Statistics overlay, shown or hidden through the control API
+    let overlay_task = display.as_ref().map(|d| {
+        let control = control.clone();
+        d.overlay().spawn_updates(
+            metrics_collector.clone(),
+            mechanism_label(&args),
+            CipherSuite::from(args.cipher).to_string(),
+            move || control.overlay_visible(),
+        )
+    });
+    
     let stream_start = Instant::now();
     let mut frame_count = 0u32;
     let mut tag_failures = 0u32;
+    let mut total_bytes = 0u64;
     
     loop {
         // Read frame header
@@ -718,15 +1186,21 @@ async fn run_receiver(args: Args) -> Result<()> {
             }
         }
         
-        let header = FrameHeader::deserialize(&header_buf)?;
+        let header = FrameHeader::decode(&header_buf)?;
         
         // Read ciphertext
         let mut ciphertext = vec![0u8; header.payload_len as usize + 16]; // +16 for GCM tag
         stream.read_exact(&mut ciphertext).await?;
         
-        // Decrypt and verify using the nonce_counter from header
-        match session.cipher.read().await.decrypt(&ciphertext, &header_buf, header.nonce_counter) {
-            Ok(plaintext) => {
+        // Decrypt and verify using the nonce_counter from header; a
+        // FLAG_REKEY frame moves us to the sender's next epoch if it verifies
+        match session.open_frame(&header, &header_buf, &ciphertext).await {
+            Ok((plaintext, rekeyed)) => {
+                if let Some(epoch) = rekeyed {
+                    metrics_collector.record_rekey().await;
+                    control.set_peers(vec![(peer_id.clone(), epoch)]);
+                }
+                
                 // Calculate latency
                 let now_us = SystemTime::now()
                     .duration_since(SystemTime::UNIX_EPOCH)?
@@ -734,6 +1208,8 @@ async fn run_receiver(args: Args) -> Result<()> {
                 let latency_ms = (now_us.saturating_sub(header.timestamp_us)) as f32 / 1000.0;
                 
                 frame_count += 1;
+                total_bytes += (HEADER_SIZE + ciphertext.len()) as u64;
+                control.frame(&plaintext);
                 
                 // Display frame if enabled
                 if let Some(ref display) = display {
@@ -745,8 +1221,9 @@ async fn run_receiver(args: Args) -> Result<()> {
                 if frame_count % 30 == 0 {
                     let elapsed = stream_start.elapsed().as_secs_f64();
                     let fps = frame_count as f32 / elapsed as f32;
+                    let goodput_mbps = (total_bytes as f64 * 8.0 / elapsed) / 1_000_000.0;
                     
-                    metrics_collector.update_stream_stats(fps, 0.0, latency_ms).await;
+                    metrics_collector.update_stream_stats(fps, goodput_mbps as f32, latency_ms).await;
                     
                     info!("Received {} frames, {:.2} fps, latency {:.2}ms (tag failures: {})", 
                           frame_count, fps, latency_ms, tag_failures);
@@ -765,6 +1242,9 @@ async fn run_receiver(args: Args) -> Result<()> {
     }
     
     // Stop display
+    if let Some(task) = overlay_task {
+        task.abort();
+    }
     if let Some(display) = display {
         display.stop()?;
     }
@@ -781,11 +1261,17 @@ async fn run_receiver(args: Args) -> Result<()> {
 async fn run_relay(args: Args) -> Result<()> {
     info!("Starting relay mode");
     
-    let relay_host = args.relay_host.context("--relay-host required for relay mode")?;
+    let relay_host = args.relay_host.clone().context("--relay-host required for relay mode")?;
     let relay_port = args.relay_port.context("--relay-port required for relay mode")?;
     
     let metrics_collector = Arc::new(MetricsCollector::new(args.node_id.clone()));
     let _metrics_task = metrics_collector.clone().start_collection();
+    #[cfg(feature = "prometheus")]
+    start_metrics_endpoint(&args, &metrics_collector, "relay").await?;
+    
+    let control = start_control(&args, control::Role::Relay)?;
+    
+    let key_pool = rsa_key_pool(&args);
     
     // Listen for incoming connection (from sender)
     let listen_addr = format!("{}:{}", args.host, args.port);
@@ -804,35 +1290,35 @@ async fn run_relay(args: Args) -> Result<()> {
     // Perform handshake with sender (as receiver)
     metrics_collector.record_power(5.0, 2.5, "handshake_in".to_string()).await;
     
-    let (key_material_in, _) = match args.mechanism {
-        KeyMechanism::Rsa => perform_rsa_handshake(&mut incoming_stream, false, args.rsa_bits).await?,
-        KeyMechanism::Ecdh => perform_ecdh_handshake(&mut incoming_stream, false).await?,
-        KeyMechanism::Group => perform_group_handshake(&mut incoming_stream, &args.group_key_file).await?,
-    };
+    let session_in = accept_session(&args, &mut incoming_stream, key_pool.as_ref(), &metrics_collector).await?;
     
     info!("Incoming handshake completed");
     
     // Perform handshake with receiver (as sender)
     metrics_collector.record_power(5.0, 2.5, "handshake_out".to_string()).await;
     
-    let (key_material_out, _) = match args.mechanism {
-        KeyMechanism::Rsa => perform_rsa_handshake(&mut outgoing_stream, true, args.rsa_bits).await?,
-        KeyMechanism::Ecdh => perform_ecdh_handshake(&mut outgoing_stream, true).await?,
-        KeyMechanism::Group => perform_group_handshake(&mut outgoing_stream, &args.group_key_file).await?,
-    };
+    let (key_material_out, _) = establish_session(&args, &mut outgoing_stream, true, None, &metrics_collector).await?;
+    if let KeyMechanism::Group = args.mechanism {
+        send_group_epoch(&mut outgoing_stream, 0).await?;
+    }
     
     info!("Outgoing handshake completed");
     
-    // Initialize sessions
-    let session_in = SessionState::new(
-        key_material_in,
-        Duration::from_secs(args.rekey_interval),
-    );
-    
     let session_out = SessionState::new(
         key_material_out,
+        args.cipher.into(),
         Duration::from_secs(args.rekey_interval),
-    );
+    )?;
+    
+    // Each hop has its own epochs: the upstream sender's and ours
+    let sender_id = sender_addr.to_string();
+    let publish_epochs = |session_in: &SessionState, session_out: &SessionState| {
+        control.set_peers(vec![
+            (sender_id.clone(), session_in.epoch()),
+            (relay_addr.clone(), session_out.epoch()),
+        ]);
+    };
+    publish_epochs(&session_in, &session_out);
     
     metrics_collector.record_power(5.0, 2.0, "steady".to_string()).await;
     
@@ -853,16 +1339,31 @@ async fn run_relay(args: Args) -> Result<()> {
             }
         }
         
-        let header = FrameHeader::deserialize(&header_buf)?;
+        let header = FrameHeader::decode(&header_buf)?;
         
         // Read ciphertext from sender
         let mut ciphertext_in = vec![0u8; header.payload_len as usize + 16];
         incoming_stream.read_exact(&mut ciphertext_in).await?;
         
-        // Decrypt from sender
-        match session_in.cipher.read().await.decrypt(&ciphertext_in, &header_buf, header.nonce_counter) {
-            Ok(plaintext) => {
+        // Decrypt from sender; an upstream epoch change only takes effect
+        // once verified, and our outgoing hop rekeys on its own schedule
+        match session_in.open_frame(&header, &header_buf, &ciphertext_in).await {
+            Ok((plaintext, rekeyed)) => {
+                if rekeyed.is_some() {
+                    metrics_collector.record_rekey().await;
+                    publish_epochs(&session_in, &session_out);
+                }
                 frame_count += 1;
+                control.frame(&plaintext);
+                
+                let mut flags_out = header.flags & !FLAG_REKEY;
+                if control.take_rekey_request() || session_out.should_rekey().await {
+                    warn!("Rekeying session with {}", relay_addr);
+                    session_out.ratchet().await?;
+                    metrics_collector.record_rekey().await;
+                    publish_epochs(&session_in, &session_out);
+                    flags_out |= FLAG_REKEY;
+                }
                 
                 // Get the nonce counter for outgoing encryption
                 let cipher_guard = session_out.cipher.read().await;
@@ -870,14 +1371,14 @@ async fn run_relay(args: Args) -> Result<()> {
                 
                 // Build new header with updated nonce counter
                 let new_header = FrameHeader {
-                    flags: header.flags,
+                    flags: flags_out,
                     timestamp_us: header.timestamp_us,
                     counter: header.counter,
                     nonce_counter: nonce_counter_out,
                     payload_len: plaintext.len() as u32,
                 };
                 
-                let aad_out = new_header.serialize();
+                let aad_out = new_header.encode();
                 
                 // Re-encrypt for receiver
                 let ciphertext_out = cipher_guard.encrypt(&plaintext, &aad_out)?;
@@ -920,21 +1421,18 @@ async fn run_relay(args: Args) -> Result<()> {
 async fn run_group_leader(args: Args) -> Result<()> {
     info!("Starting group leader mode");
     
-    let members_str = args.members.context("--members required for group-leader mode")?;
+    let members_str = args.members.as_deref().context("--members required for group-leader mode")?;
     
     // Parse members list
-    let members: Result<Vec<group_key::GroupMember>> = members_str
-        .split(',')
-        .map(|m| group_key::GroupMember::parse(m.trim()))
-        .collect();
-    let members = members?;
+    let members = parse_members(members_str)?;
     
     info!("Group leader will distribute keys to {} members:", members.len());
     for member in &members {
         info!("  - {} @ {}", member.node_id, member.address);
     }
     
-    let leader = group_key::LeaderNode::new(args.node_id.clone(), members);
+    let epoch = group_key::next_epoch(&args.group_key_file);
+    let leader = group_key::LeaderNode::new(args.node_id.clone(), members, epoch, cipher_id(&args));
     
     info!("Establishing group key...");
     let group_ctx = leader.establish_group_key().await?;
@@ -942,8 +1440,8 @@ async fn run_group_leader(args: Args) -> Result<()> {
     info!("Group key established successfully!");
     info!("Key hash: {:02x?}", &group_ctx.key_hash[..8]);
     
-    // Save group key to file
-    save_group_key(&group_ctx.group_key, &args.group_key_file).await?;
+    // Save group key to the encrypted keystore
+    group_key::save_group_key(&group_ctx, &args.group_key_file, args.group_key_ttl).await?;
     
     info!("Group key saved to: {}", args.group_key_file);
     info!("All members should now have the same group key.");
@@ -955,7 +1453,7 @@ async fn run_group_member(args: Args) -> Result<()> {
     info!("Starting group member mode");
     
     let listen_addr = format!("{}:{}", args.host, args.port);
-    let member = group_key::MemberNode::new(args.node_id.clone(), listen_addr);
+    let member = group_key::MemberNode::new(args.node_id.clone(), listen_addr, cipher_id(&args));
     
     info!("Waiting for leader to distribute group key...");
     let group_ctx = member.receive_group_key().await?;
@@ -963,8 +1461,8 @@ async fn run_group_member(args: Args) -> Result<()> {
     info!("Group key received successfully!");
     info!("Key hash: {:02x?}", &group_ctx.key_hash[..8]);
     
-    // Save group key to file
-    save_group_key(&group_ctx.group_key, &args.group_key_file).await?;
+    // Save group key to the encrypted keystore
+    group_key::save_group_key(&group_ctx, &args.group_key_file, args.group_key_ttl).await?;
     
     info!("Group key saved to: {}", args.group_key_file);
     info!("Ready to use group key for encrypted streaming.");
@@ -977,13 +1475,19 @@ async fn main() -> Result<()> {
     // Initialize logging
     tracing_subscriber::fmt::init();
     
-    let args = Args::parse();
+    let parsed = config::parse::<Args>(std::env::args_os().collect())?;
+    if parsed.args.print_effective_config {
+        print!("{}", parsed.effective_toml());
+        return Ok(());
+    }
+    let args = parsed.args;
     
     // Print configuration if requested
     if args.print_config {
         println!("=== Configuration ===");
         println!("Mode: {:?}", args.mode);
         println!("Mechanism: {:?}", args.mechanism);
+        println!("Cipher: {}", CipherSuite::from(args.cipher));
         println!("Host: {}", args.host);
         println!("Port: {}", args.port);
         println!("Node ID: {}", args.node_id);
@@ -1010,4 +1514,105 @@ async fn main() -> Result<()> {
         Mode::GroupLeader => run_group_leader(args).await,
         Mode::GroupMember => run_group_member(args).await,
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Header and ciphertext for one frame from `session`
+    async fn seal(session: &SessionState, flags: u8, frame: &[u8]) -> (FrameHeader, Vec<u8>) {
+        let cipher = session.cipher.read().await;
+        let header = FrameHeader {
+            flags,
+            timestamp_us: 0,
+            counter: 0,
+            nonce_counter: cipher.get_counter(),
+            payload_len: frame.len() as u32,
+        };
+        let ciphertext = cipher.encrypt(frame, &header.encode()).unwrap();
+        (header, ciphertext)
+    }
+
+    #[tokio::test]
+    async fn test_forged_rekey_flag_keeps_epoch() {
+        let key = SessionKeyMaterial::generate_random();
+        let sender = SessionState::new(SessionKeyMaterial::from_bytes(&key.as_bytes()).unwrap(), CipherSuite::Aes128Gcm, Duration::from_secs(600)).unwrap();
+        let receiver = SessionState::new(key, CipherSuite::Aes128Gcm, Duration::from_secs(600)).unwrap();
+
+        // An on-path attacker sets FLAG_REKEY on a valid epoch-0 frame
+        let (mut header, ciphertext) = seal(&sender, 0, b"frame 1").await;
+        header.flags |= FLAG_REKEY;
+        assert!(receiver.open_frame(&header, &header.encode(), &ciphertext).await.is_err());
+        assert_eq!(receiver.epoch(), 0);
+
+        // ...so the next genuine frame still decrypts under epoch 0
+        let (header, ciphertext) = seal(&sender, 0, b"frame 2").await;
+        let (plaintext, rekeyed) = receiver.open_frame(&header, &header.encode(), &ciphertext).await.unwrap();
+        assert_eq!((plaintext.as_slice(), rekeyed), (&b"frame 2"[..], None));
+
+        // A real rekey frame moves the receiver along with the sender
+        sender.ratchet().await.unwrap();
+        let (header, ciphertext) = seal(&sender, FLAG_REKEY, b"frame 3").await;
+        let (plaintext, rekeyed) = receiver.open_frame(&header, &header.encode(), &ciphertext).await.unwrap();
+        assert_eq!((plaintext.as_slice(), rekeyed), (&b"frame 3"[..], Some(1)));
+        assert_eq!(receiver.epoch(), sender.epoch());
+    }
+
+    #[tokio::test]
+    async fn test_member_added_mid_stream_never_reuses_a_nonce() {
+        let path = std::env::temp_dir().join(format!("group-key-midstream-{}.bin", std::process::id()));
+        std::env::set_var(keystore::PASSPHRASE_ENV, "midstream test passphrase");
+        keystore::save(&path, &SessionKeyMaterial::generate_random(), &keystore::KekSource::from_env(), 1, 0, [0; 32]).unwrap();
+        let args = Args::try_parse_from([
+            "stream", "--mode", "sender", "--mechanism", "group", "--group-key-file", path.to_str().unwrap(),
+        ]).unwrap();
+        let collector = MetricsCollector::new("test".into());
+        let (args, collector) = (&args, &collector);
+        
+        // A member: decrypt everything it is sent and note (epoch, nonce) per frame
+        let member = move |listener: TcpListener| async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let session = accept_session(args, &mut stream, None, collector).await.unwrap();
+            let mut seen = Vec::new();
+            let mut header_buf = [0u8; HEADER_SIZE];
+            while stream.read_exact(&mut header_buf).await.is_ok() {
+                let header = FrameHeader::decode(&header_buf).unwrap();
+                let mut ciphertext = vec![0u8; header.payload_len as usize + 16];
+                stream.read_exact(&mut ciphertext).await.unwrap();
+                session.open_frame(&header, &header_buf, &ciphertext).await.unwrap();
+                seen.push((session.epoch(), header.nonce_counter));
+            }
+            seen
+        };
+        let first = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let second = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let second_addr = second.local_addr().unwrap();
+        let first_addr = first.local_addr().unwrap();
+        
+        let sender = async move {
+            let stream = TcpStream::connect(first_addr).await.unwrap();
+            let (primary, _) = start_peer(args, "pi-2".into(), stream, None, collector).await.unwrap();
+            let mut peers = vec![primary];
+            for i in 0..6u32 {
+                // The second member joins after a rekey, and another follows
+                if i == 3 {
+                    let joining = group_key::GroupMember::parse(&format!("pi-3:{}", second_addr)).unwrap();
+                    add_member(args, joining, &mut peers, collector).await.unwrap();
+                }
+                for peer in peers.iter_mut() {
+                    peer.send_frame(&[i as u8; 64], i, 0, i == 2 || i == 4, collector).await.unwrap();
+                }
+            }
+        };
+        let (first_seen, second_seen, ()) = tokio::join!(member(first), member(second), sender);
+        std::fs::remove_file(&path).unwrap();
+        
+        assert_eq!(first_seen.len(), 6);
+        assert_eq!(second_seen.iter().map(|&(epoch, _)| epoch).collect::<Vec<_>>(), [1, 2, 2]);
+        let mut all: Vec<_> = first_seen.iter().chain(&second_seen).collect();
+        all.sort();
+        all.dedup();
+        assert_eq!(all.len(), first_seen.len() + second_seen.len(), "(epoch, nonce) reused: {:?} / {:?}", first_seen, second_seen);
+    }
 }
\ No newline at end of file
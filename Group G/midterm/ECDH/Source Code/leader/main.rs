@@ -1,10 +1,8 @@
 use anyhow::{anyhow, Result};
-use aes_gcm::{Aes128Gcm, aead::{Aead, KeyInit, Payload}, Nonce};
+use aes_gcm::{Aes128Gcm, aead::{Aead, Payload}, Nonce};
 use clap::Parser;
-use hkdf::Hkdf;
 use p256::{ecdh::EphemeralSecret, EncodedPoint, PublicKey};
 use rand::{rngs::OsRng, RngCore};
-use sha2::Sha256;
 use std::{time::{Instant, Duration, SystemTime}, sync::{Arc, Mutex}, thread};
 use std::arch;
 use zeroize::Zeroize;
@@ -22,7 +20,10 @@ use std::sync::atomic::{AtomicU32, Ordering};
 use sysinfo::{System, SystemExt, CpuExt}; 
 
 mod metrics;
+#[path = "../../../session/src/lib.rs"]
+mod session;
 use metrics::{MetricsLogger, log_arm_crypto_support};
+use session::{derive_session_keys, DirKeys, Role, SessionKeys, TAG_LEN};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about = "Leader sender: ECDH + AES-GCM + MJPEG video stream (with rekey & metrics)")]
@@ -49,7 +50,6 @@ struct Args {
     queue: usize,
 }
 
-const CONTEXT: &[u8] = b"ECE4301-midterm-2025";
 const REKEY_FRAMES: u64 = 1 << 20;
 const REKEY_INTERVAL: Duration = Duration::from_secs(10 * 60);
 
@@ -132,23 +132,24 @@ async fn main() -> Result<()> {
     let shared = my_secret.diffie_hellman(&peer_pub);
     let mut shared_ikm = shared.raw_secret_bytes().to_vec();
 
-    // derive key + nonce & send salt envelope
-    let (cipher, nonce_base, salt) = derive_key_from_ikm_and_salt(&shared_ikm)?;
+    // derive per-direction keys + nonces & send salt envelope
+    let (keys, salt) = derive_key_from_ikm_and_salt(&shared_ikm)?;
+    shared_ikm.zeroize();
     let salt_env_len = (4 + 1 + 4 + salt.len()) as u64;
     bytes_exchanged += salt_env_len;
     bytes_tx_handshake += salt_env_len;
 
     let ks = Arc::new(Mutex::new(KeyState {
-        cipher,
-        nonce_base,
+        cipher: keys.send.cipher()?,
+        nonce_base: keys.send.nonce_base,
         frames_sent: 0,
         started_at: Instant::now(),
         counter: AtomicU32::new(0),
     }));
 
     send_salt_envelope(&mut sock, &salt).await?;
-    // wait for ACK
-    wait_for_ack_and_validate(&mut sock, &ks).await?;
+    // wait for ACK (member->leader direction)
+    wait_for_ack_and_validate(&mut sock, &keys.recv).await?;
     // ACK read is inside wait_for_ack_and_validate — that function may account for bytes_rx_handshake if updated there.
 
     let hs_end = SystemTime::now();
@@ -340,18 +341,11 @@ async fn read_envelope_payload(sock: &mut tokio::net::TcpStream) -> Result<Vec<u
     Ok(env)
 }
 
-fn derive_key_from_ikm_and_salt(shared_ikm: &[u8]) -> Result<(Aes128Gcm, [u8;8], [u8;16])> {
+fn derive_key_from_ikm_and_salt(shared_ikm: &[u8]) -> Result<(SessionKeys, [u8;16])> {
     let mut salt = [0u8; 16];
     OsRng.fill_bytes(&mut salt);
-    let hk = Hkdf::<Sha256>::new(Some(&salt), shared_ikm);
-    let mut okm = [0u8; 16 + 8];
-    hk.expand(CONTEXT, &mut okm).map_err(|_| anyhow!("HKDF expand failed"))?;
-    let aes_key = &okm[..16];
-    let mut nonce_base = [0u8;8];
-    nonce_base.copy_from_slice(&okm[16..24]);
-    let cipher = Aes128Gcm::new_from_slice(aes_key)?;
-    okm.zeroize();
-    Ok((cipher, nonce_base, salt))
+    let keys = derive_session_keys(shared_ikm, &salt, Role::Leader)?;
+    Ok((keys, salt))
 }
 
 async fn send_salt_envelope(sock: &mut tokio::net::TcpStream, salt: &[u8;16]) -> Result<()> {
@@ -365,29 +359,30 @@ async fn send_salt_envelope(sock: &mut tokio::net::TcpStream, salt: &[u8;16]) ->
     Ok(())
 }
 
-async fn wait_for_ack_and_validate(sock: &mut tokio::net::TcpStream, ks: &Arc<Mutex<KeyState>>) -> Result<()> {
+async fn wait_for_ack_and_validate(sock: &mut tokio::net::TcpStream, recv: &DirKeys) -> Result<()> {
     let mut len_buf = [0u8; 4];
     sock.read_exact(&mut len_buf).await?;
     let ack_msg_len = u32::from_be_bytes(len_buf) as usize;
     let mut ack_msg = vec![0u8; ack_msg_len];
     sock.read_exact(&mut ack_msg).await?;
+    if ack_msg.len() < 1 + 4 {
+        return Err(anyhow!("malformed ACK payload"));
+    }
     if ack_msg[0] != F_ACK {
         return Err(anyhow!("expected ACK flag from receiver"));
     }
-    if ack_msg.len() < 1 + 4 {
+    let ack_cipher_len = u32::from_be_bytes(ack_msg[1..5].try_into().unwrap()) as usize;
+    if ack_cipher_len < TAG_LEN || ack_msg.len() < 5 + ack_cipher_len {
         return Err(anyhow!("malformed ACK payload"));
     }
-    let ack_cipher_len = u32::from_be_bytes(ack_msg[1..5].try_into().unwrap()) as usize;
     let ack_cipher = &ack_msg[5..5 + ack_cipher_len];
 
-    let guard = ks.lock().unwrap();
-    let mut ack_nonce12 = [0u8; 12];
-    ack_nonce12[..8].copy_from_slice(&guard.nonce_base);
-    ack_nonce12[8..12].copy_from_slice(&0u32.to_be_bytes());
+    // ACK is the first message on the member->leader key, so counter 0 is safe here
+    let ack_nonce12 = recv.nonce(0);
     let ack_nonce = Nonce::from_slice(&ack_nonce12);
     let mut ack_aad = [0u8; 12];
     ack_aad[8..12].copy_from_slice(&(ack_cipher_len as u32).to_be_bytes());
-    let ack_plain = guard.cipher.decrypt(ack_nonce, Payload { msg: ack_cipher, aad: &ack_aad })
+    let ack_plain = recv.cipher()?.decrypt(ack_nonce, Payload { msg: ack_cipher, aad: &ack_aad })
         .map_err(|e| anyhow!("ack decrypt failed: {:?}", e))?;
     println!("Received decrypted ACK: {:?}", String::from_utf8_lossy(&ack_plain));
     Ok(())
@@ -406,49 +401,20 @@ async fn perform_rekey_exchange(sock: &mut tokio::net::TcpStream, ks: &Arc<Mutex
 
     let shared = my_secret.diffie_hellman(&peer_pub);
     let mut new_shared_vec = shared.raw_secret_bytes().to_vec();
-    let mut salt = [0u8; 16];
-    OsRng.fill_bytes(&mut salt);
-    let hk = Hkdf::<Sha256>::new(Some(&salt), &new_shared_vec);
-    let mut okm = [0u8; 16 + 8];
-    hk.expand(CONTEXT, &mut okm).map_err(|_| anyhow!("HKDF expand failed"))?;
-    let new_aes_key = &okm[..16];
-    let mut new_nonce_base = [0u8;8];
-    new_nonce_base.copy_from_slice(&okm[16..24]);
-    let new_cipher = Aes128Gcm::new_from_slice(new_aes_key)?;
+    let (new_keys, mut salt) = derive_key_from_ikm_and_salt(&new_shared_vec)?;
     new_shared_vec.zeroize();
-    okm.zeroize();
 
     send_salt_envelope(sock, &salt).await?;
 
-    // wait for ACK encrypted with new key
-    let mut len_buf = [0u8; 4];
-    sock.read_exact(&mut len_buf).await?;
-    let ack_msg_len = u32::from_be_bytes(len_buf) as usize;
-    let mut ack_msg = vec![0u8; ack_msg_len];
-    sock.read_exact(&mut ack_msg).await?;
-    if ack_msg[0] != F_ACK { return Err(anyhow!("expected ACK")); }
-    if ack_msg.len() < 1 + 4 {
-        return Err(anyhow!("malformed ACK payload"));
-    }
-    let ack_cipher_len = u32::from_be_bytes(ack_msg[1..5].try_into().unwrap()) as usize;
-    let ack_cipher = &ack_msg[5..5 + ack_cipher_len];
-
-    let mut ack_nonce12 = [0u8; 12];
-    ack_nonce12[..8].copy_from_slice(&new_nonce_base);
-    ack_nonce12[8..12].copy_from_slice(&0u32.to_be_bytes());
-    let ack_nonce = Nonce::from_slice(&ack_nonce12);
-    let mut ack_aad = [0u8; 12];
-    ack_aad[8..12].copy_from_slice(&(ack_cipher_len as u32).to_be_bytes());
-
-    let ack_plain = new_cipher.decrypt(ack_nonce, Payload { msg: ack_cipher, aad: &ack_aad })
-        .map_err(|e| anyhow!("rekey ACK decrypt failed: {:?}", e))?;
-    println!("Rekey ACK decrypted: {:?}", String::from_utf8_lossy(&ack_plain));
+    // wait for ACK encrypted with the new member->leader key
+    wait_for_ack_and_validate(sock, &new_keys.recv).await?;
+    println!("Rekey ACK validated");
 
     // swap in new key
     {
         let mut g = ks.lock().unwrap();
-        g.cipher = new_cipher;
-        g.nonce_base = new_nonce_base;
+        g.cipher = new_keys.send.cipher()?;
+        g.nonce_base = new_keys.send.nonce_base;
         g.counter.store(0, Ordering::SeqCst);
         g.frames_sent = 0;
         g.started_at = Instant::now();
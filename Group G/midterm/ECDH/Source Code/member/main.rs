@@ -1,10 +1,8 @@
 use anyhow::{anyhow, Result};
-use aes_gcm::{Aes128Gcm, aead::{Aead, KeyInit, Payload}, Nonce};
+use aes_gcm::{aead::{Aead, Payload}, Nonce};
 use clap::Parser;
-use hkdf::Hkdf;
 use p256::{ecdh::EphemeralSecret, EncodedPoint, PublicKey};
 use rand::rngs::OsRng;
-use sha2::Sha256;
 use std::time::{Instant, Duration, SystemTime};
 use zeroize::Zeroize;
 use tokio::{
@@ -18,7 +16,10 @@ use gst_app::AppSrc;
 use sysinfo::{System, SystemExt, CpuExt};
 
 mod metrics;
+#[path = "../../../session/src/lib.rs"]
+mod session;
 use metrics::{MetricsLogger, log_arm_crypto_support};
+use session::{derive_session_keys, Role, TAG_LEN};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about = "Member receiver: AES-GCM MJPEG live video player (with metrics)")]
@@ -33,8 +34,6 @@ struct Args {
     metrics_dir: String,
 }
 
-const CONTEXT: &[u8] = b"ECE4301-midterm-2025";
-
 const F_ECDH_PUB: u8 = 0x20;
 const F_NONCE_BASE: u8 = 0x21;
 const F_ACK: u8 = 0x11;
@@ -104,16 +103,12 @@ async fn main() -> Result<()> {
     let salt_len = u32::from_be_bytes(salt_env[1..5].try_into().unwrap()) as usize;
     let salt = &salt_env[5..5 + salt_len];
 
-    // derive key/nonce and send ACK
-    let hk = Hkdf::<Sha256>::new(Some(salt), &ikm);
-    let mut okm = [0u8; 16 + 8];
-    hk.expand(CONTEXT, &mut okm).map_err(|_| anyhow!("HKDF expand failed"))?;
-    let aes_key = okm[..16].to_vec();
-    let mut nonce_base = [0u8; 8];
-    nonce_base.copy_from_slice(&okm[16..24]);
+    // derive per-direction keys/nonces and send ACK
+    let keys = derive_session_keys(&ikm, salt, Role::Member)?;
     ikm.zeroize();
-    okm.zeroize();
-    let cipher = Aes128Gcm::new_from_slice(&aes_key)?;
+    let ack_cipher_key = keys.send.cipher()?;
+    let cipher = keys.recv.cipher()?;
+    let nonce_base = keys.recv.nonce_base;
 
     // build and send ACK (encrypted)
     let mut ack_plain = Vec::new();
@@ -121,24 +116,16 @@ async fn main() -> Result<()> {
     ack_plain.extend_from_slice(&ts_millis.to_be_bytes());
     ack_plain.extend_from_slice(b"OK");
 
-    let mut ack_nonce12 = [0u8; 12];
-    ack_nonce12[..8].copy_from_slice(&nonce_base);
-    ack_nonce12[8..12].copy_from_slice(&0u32.to_be_bytes());
+    // first message on the member->leader key; leader frames use their own key from ctr 0
+    let ack_nonce12 = keys.send.nonce(0);
     let ack_nonce = Nonce::from_slice(&ack_nonce12);
 
     // Build aad for ACK (match leader expectation: last 4 bytes = ack_cipher_len as u32 BE)
     let mut ack_aad = [0u8; 12];
 
-    let ack_cipher = {
-
-        let first = cipher.encrypt(ack_nonce, Payload { msg: &ack_plain, aad: &[] })
-            .map_err(|e| anyhow!("ack encrypt failed: {:?}", e))?;
-        let len = first.len();
-        ack_aad[8..12].copy_from_slice(&(len as u32).to_be_bytes());
-        // now encrypt with the intended AAD
-        cipher.encrypt(ack_nonce, Payload { msg: &ack_plain, aad: &ack_aad })
-            .map_err(|e| anyhow!("ack encrypt failed: {:?}", e))?
-    };
+    ack_aad[8..12].copy_from_slice(&((ack_plain.len() + TAG_LEN) as u32).to_be_bytes());
+    let ack_cipher = ack_cipher_key.encrypt(ack_nonce, Payload { msg: &ack_plain, aad: &ack_aad })
+        .map_err(|e| anyhow!("ack encrypt failed: {:?}", e))?;
 
     let mut ack_env = Vec::with_capacity(4 + 1 + 4 + ack_cipher.len());
     let payload_len = 1 + 4 + ack_cipher.len();
@@ -1,5 +1,5 @@
 use anyhow::{anyhow, Result};
-use aes_gcm::{Aes128Gcm, aead::{Aead, KeyInit, Payload}, Nonce};
+use aes_gcm::{Aes128Gcm, aead::{Aead, Payload}, Nonce};
 use clap::Parser;
 use rand::{rngs::OsRng, RngCore};
 use rsa::{pkcs8::DecodePublicKey, RsaPublicKey, PublicKey, Oaep};
@@ -16,7 +16,10 @@ use sysinfo::{System, SystemExt, CpuExt};
 use std::sync::atomic::{AtomicU32, Ordering};
 
 mod metrics;
+#[path = "../../../session/src/lib.rs"]
+mod session;
 use metrics::{MetricsLogger, log_arm_crypto_support};
+use session::{derive_session_keys, DirKeys, Role, TAG_LEN};
 
 /// Leader (sender) implementing RSA-OAEP (SHA-256) key-transport handshake.
 /// After the handshake the leader uses AES-128-GCM for frame encryption.
@@ -46,7 +49,7 @@ struct Args {
 }
 
 const F_RSA_PUB: u8 = 0x30; // member -> leader: member public key envelope
-const F_WRAP_KEY: u8 = 0x31; // leader -> member: wrapped AES key envelope
+const F_WRAP_KEY: u8 = 0x31; // leader -> member: wrapped session secret envelope
 const F_ACK: u8 = 0x11;
 const F_FRAME: u8 = 0x01;
 
@@ -105,18 +108,15 @@ async fn main() -> Result<()> {
     let member_pub = RsaPublicKey::from_public_key_der(member_pub_der)
         .map_err(|e| anyhow!("invalid member RSA public key: {:?}", e))?;
 
-    // 2) Leader generates fresh AES key + nonce_base + salt
-    let mut aes_key = [0u8; 16];
-    OsRng.fill_bytes(&mut aes_key);
-    let mut nonce_base = [0u8; 8];
-    OsRng.fill_bytes(&mut nonce_base);
+    // 2) Leader generates fresh session secret + salt
+    let mut secret = [0u8; 32];
+    OsRng.fill_bytes(&mut secret);
     let mut salt = [0u8; 16];
     OsRng.fill_bytes(&mut salt);
 
-    // Build plaintext blob: AES key || nonce_base || salt (salt optional, kept for parity with member)
-    let mut wrapped_plain = Vec::with_capacity(16 + 8 + 16);
-    wrapped_plain.extend_from_slice(&aes_key);
-    wrapped_plain.extend_from_slice(&nonce_base);
+    // Build plaintext blob: secret || salt; both sides expand it into per-direction keys
+    let mut wrapped_plain = Vec::with_capacity(32 + 16);
+    wrapped_plain.extend_from_slice(&secret);
     wrapped_plain.extend_from_slice(&salt);
 
     // 3) Wrap with RSA-OAEP(SHA-256)
@@ -138,16 +138,18 @@ async fn main() -> Result<()> {
     bytes_exchanged += (4 + payload_len) as u64;
     bytes_tx_handshake += (4 + payload_len) as u64;
 
-    // instantiate AES-GCM cipher from aes_key for ACK decryption and subsequent streaming
-    let cipher = Aes128Gcm::new_from_slice(&aes_key)?;
+    // derive leader->member (frames) and member->leader (ACK) keys
+    let keys = derive_session_keys(&secret, &salt, Role::Leader)?;
+    secret.fill(0);
+    wrapped_plain.fill(0);
     let ks = Arc::new(Mutex::new(KeyState {
-        cipher,
-        nonce_base,
+        cipher: keys.send.cipher()?,
+        nonce_base: keys.send.nonce_base,
         counter: AtomicU32::new(0),
     }));
 
-    // 4) Wait for ACK encrypted with the transported AES key (counter = 0)
-    wait_for_ack_and_validate_with_cipher(&mut sock, &ks).await?;
+    // 4) Wait for ACK encrypted with the member->leader key (counter = 0)
+    wait_for_ack_and_validate_with_cipher(&mut sock, &keys.recv).await?;
 
     let hs_end = SystemTime::now();
     let hs_duration = hs_start_instant.elapsed();
@@ -244,26 +246,24 @@ async fn read_envelope_payload(sock: &mut tokio::net::TcpStream) -> Result<Vec<u
     Ok(env)
 }
 
-/// Wait for ACK envelope and decrypt it using ks.cipher with counter=0
-async fn wait_for_ack_and_validate_with_cipher(sock: &mut tokio::net::TcpStream, ks: &Arc<Mutex<KeyState>>) -> Result<()> {
+/// Wait for ACK envelope and decrypt it using the member->leader key with counter=0
+async fn wait_for_ack_and_validate_with_cipher(sock: &mut tokio::net::TcpStream, recv: &DirKeys) -> Result<()> {
     let mut len_buf = [0u8; 4];
     sock.read_exact(&mut len_buf).await?;
     let ack_msg_len = u32::from_be_bytes(len_buf) as usize;
     let mut ack_msg = vec![0u8; ack_msg_len];
     sock.read_exact(&mut ack_msg).await?;
-    if ack_msg[0] != F_ACK { return Err(anyhow!("expected ACK")); }
     if ack_msg.len() < 1 + 4 { return Err(anyhow!("malformed ACK payload")); }
+    if ack_msg[0] != F_ACK { return Err(anyhow!("expected ACK")); }
     let ack_cipher_len = u32::from_be_bytes(ack_msg[1..5].try_into().unwrap()) as usize;
+    if ack_cipher_len < TAG_LEN || ack_msg.len() < 5 + ack_cipher_len { return Err(anyhow!("malformed ACK payload")); }
     let ack_cipher = &ack_msg[5..5 + ack_cipher_len];
 
-    let guard = ks.lock().unwrap();
-    let mut ack_nonce12 = [0u8; 12];
-    ack_nonce12[..8].copy_from_slice(&guard.nonce_base);
-    ack_nonce12[8..12].copy_from_slice(&0u32.to_be_bytes());
+    let ack_nonce12 = recv.nonce(0);
     let ack_nonce = Nonce::from_slice(&ack_nonce12);
     let mut ack_aad = [0u8; 12];
     ack_aad[8..12].copy_from_slice(&(ack_cipher_len as u32).to_be_bytes());
-    let ack_plain = guard.cipher.decrypt(ack_nonce, Payload { msg: ack_cipher, aad: &ack_aad })
+    let ack_plain = recv.cipher()?.decrypt(ack_nonce, Payload { msg: ack_cipher, aad: &ack_aad })
         .map_err(|e| anyhow!("ack decrypt failed: {:?}", e))?;
     println!("Received decrypted ACK: {:?}", String::from_utf8_lossy(&ack_plain));
     Ok(())
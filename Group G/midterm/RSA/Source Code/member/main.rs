@@ -1,5 +1,5 @@
 use anyhow::{anyhow, Result};
-use aes_gcm::{Aes128Gcm, aead::{Aead, KeyInit, Payload}, Nonce};
+use aes_gcm::{aead::{Aead, Payload}, Nonce};
 use clap::Parser;
 use rand::rngs::OsRng;
 use rsa::{pkcs8::EncodePublicKey, RsaPrivateKey, Oaep};
@@ -18,7 +18,10 @@ use gstreamer_app as gst_app;
 use gst::prelude::*;
 
 mod metrics;
+#[path = "../../../session/src/lib.rs"]
+mod session;
 use metrics::{MetricsLogger, log_arm_crypto_support};
+use session::{derive_session_keys, Role, TAG_LEN};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about = "Member receiver (verbose) with display")]
@@ -121,32 +124,26 @@ async fn main() -> Result<()> {
     let wrapped = &wrap_env[1..];
 
     // Unwrap with RSA-OAEP-SHA256 using Oaep helper
-    let decrypted = private_key.decrypt(Oaep::new::<Sha256>(), wrapped)
+    let mut decrypted = private_key.decrypt(Oaep::new::<Sha256>(), wrapped)
         .map_err(|e| anyhow!("rsa decrypt failed: {:?}", e))?;
-    if decrypted.len() < 16 + 8 {
+    if decrypted.len() < 32 + 16 {
         return Err(anyhow!("wrapped blob too small"));
     }
-    let aes_key = &decrypted[..16];
-    let mut nonce_base = [0u8; 8];
-    nonce_base.copy_from_slice(&decrypted[16..24]);
+    // secret (32) || salt (16) -> per-direction keys
+    let keys = derive_session_keys(&decrypted[..32], &decrypted[32..48], Role::Member)?;
+    decrypted.fill(0);
+    let cipher = keys.send.cipher()?;
 
-    // AES-GCM cipher
-    let cipher = Aes128Gcm::new_from_slice(aes_key)?;
-
-    // Send ACK encrypted as before
+    // Send ACK on the member->leader key (counter 0 is not shared with leader frames)
     let mut ack_plain = Vec::new();
     let ts_millis = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as u64;
     ack_plain.extend_from_slice(&ts_millis.to_be_bytes());
     ack_plain.extend_from_slice(b"OK");
-    let mut ack_nonce12 = [0u8; 12];
-    ack_nonce12[..8].copy_from_slice(&nonce_base);
-    ack_nonce12[8..12].copy_from_slice(&0u32.to_be_bytes());
+    let ack_nonce12 = keys.send.nonce(0);
     let ack_nonce = Nonce::from_slice(&ack_nonce12);
-    let tmp = cipher.encrypt(ack_nonce, Payload { msg: &ack_plain, aad: &[] })
-        .map_err(|e| anyhow!("ack encrypt tmp failed: {:?}", e))?;
-    let ack_len = tmp.len();
+    // AAD carries the ciphertext length, known up front: plaintext + GCM tag
     let mut ack_aad = [0u8; 12];
-    ack_aad[8..12].copy_from_slice(&(ack_len as u32).to_be_bytes());
+    ack_aad[8..12].copy_from_slice(&((ack_plain.len() + TAG_LEN) as u32).to_be_bytes());
     let ack_cipher = cipher.encrypt(ack_nonce, Payload { msg: &ack_plain, aad: &ack_aad })
         .map_err(|e| anyhow!("ack encrypt failed: {:?}", e))?;
     let mut ack_env = Vec::with_capacity(4 + 1 + 4 + ack_cipher.len());
@@ -160,8 +157,8 @@ async fn main() -> Result<()> {
     bytes_tx_handshake += (4 + payload_len) as u64;
 
     // Receiver state
-    let ks_cipher = cipher;
-    let ks_nonce_base = nonce_base;
+    let ks_cipher = keys.recv.cipher()?;
+    let ks_nonce_base = keys.recv.nonce_base;
     let ks_counter = Arc::new(AtomicU32::new(0));
 
     let hs_end_time = SystemTime::now();
@@ -0,0 +1,129 @@
+//! Per-direction session keys derived from the handshake secret.
+//! Leader and member each send under their own AES key and nonce base, so the
+//! member's ACK (counter 0) can never reuse the nonce of the leader's first frame.
+
+use anyhow::{anyhow, Result};
+use aes_gcm::{Aes128Gcm, KeyInit};
+use hkdf::Hkdf;
+use sha2::Sha256;
+
+const CONTEXT: &[u8] = b"ECE4301-midterm-2025";
+
+/// AES-GCM tag appended to every ciphertext; ciphertext length = plaintext + TAG_LEN.
+pub const TAG_LEN: usize = 16;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Role {
+    Leader,
+    Member,
+}
+
+impl Role {
+    fn label(self) -> &'static [u8] {
+        match self {
+            Role::Leader => b"leader",
+            Role::Member => b"member",
+        }
+    }
+
+    fn peer(self) -> Role {
+        match self {
+            Role::Leader => Role::Member,
+            Role::Member => Role::Leader,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct DirKeys {
+    pub key: [u8; 16],
+    pub nonce_base: [u8; 8],
+}
+
+impl DirKeys {
+    pub fn cipher(&self) -> Result<Aes128Gcm> {
+        Ok(Aes128Gcm::new_from_slice(&self.key)?)
+    }
+
+    /// 96-bit GCM nonce: nonce_base (8) || counter (4, BE)
+    pub fn nonce(&self, ctr: u32) -> [u8; 12] {
+        let mut nonce12 = [0u8; 12];
+        nonce12[..8].copy_from_slice(&self.nonce_base);
+        nonce12[8..12].copy_from_slice(&ctr.to_be_bytes());
+        nonce12
+    }
+}
+
+/// `send` is what this role encrypts with, `recv` what it expects from the peer.
+pub struct SessionKeys {
+    pub send: DirKeys,
+    pub recv: DirKeys,
+}
+
+fn expand_dir(hk: &Hkdf<Sha256>, from: Role, to: Role) -> Result<DirKeys> {
+    // info = CONTEXT || "|" || from || "->" || to
+    let info = [CONTEXT, b"|", from.label(), b"->", to.label()].concat();
+    let mut okm = [0u8; 16 + 8];
+    hk.expand(&info, &mut okm).map_err(|_| anyhow!("HKDF expand failed"))?;
+    let mut key = [0u8; 16];
+    key.copy_from_slice(&okm[..16]);
+    let mut nonce_base = [0u8; 8];
+    nonce_base.copy_from_slice(&okm[16..24]);
+    okm.fill(0);
+    Ok(DirKeys { key, nonce_base })
+}
+
+/// Derive both directions from the handshake secret (ECDH shared secret, or the
+/// RSA-transported secret) and the leader's salt.
+pub fn derive_session_keys(ikm: &[u8], salt: &[u8], me: Role) -> Result<SessionKeys> {
+    let hk = Hkdf::<Sha256>::new(Some(salt), ikm);
+    Ok(SessionKeys {
+        send: expand_dir(&hk, me, me.peer())?,
+        recv: expand_dir(&hk, me.peer(), me)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aes_gcm::aead::{Aead, Payload};
+    use aes_gcm::Nonce;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_roles_agree_on_directions() {
+        let leader = derive_session_keys(&[7u8; 32], &[1u8; 16], Role::Leader).unwrap();
+        let member = derive_session_keys(&[7u8; 32], &[1u8; 16], Role::Member).unwrap();
+        assert_eq!(leader.send.key, member.recv.key);
+        assert_eq!(leader.send.nonce_base, member.recv.nonce_base);
+        assert_eq!(leader.recv.key, member.send.key);
+        assert_eq!(leader.recv.nonce_base, member.send.nonce_base);
+        assert_ne!(leader.send.key, leader.recv.key);
+    }
+
+    #[test]
+    fn test_no_key_nonce_reuse_across_directions() {
+        let leader = derive_session_keys(&[9u8; 32], &[2u8; 16], Role::Leader).unwrap();
+        let member = derive_session_keys(&[9u8; 32], &[2u8; 16], Role::Member).unwrap();
+
+        // Member ACK uses counter 0 on its own direction; leader frames count from 0 on theirs
+        let mut used = HashSet::new();
+        assert!(used.insert((member.send.key, member.send.nonce(0))));
+        for ctr in 0..10_000u32 {
+            assert!(used.insert((leader.send.key, leader.send.nonce(ctr))), "reused (key, nonce) at ctr {}", ctr);
+        }
+
+        // The ACK only opens with the leader's receive key
+        let ack = member.send.cipher().unwrap()
+            .encrypt(Nonce::from_slice(&member.send.nonce(0)), Payload { msg: b"OK", aad: &[] })
+            .unwrap();
+        let opened = leader.recv.cipher().unwrap()
+            .decrypt(Nonce::from_slice(&leader.recv.nonce(0)), Payload { msg: &ack, aad: &[] })
+            .unwrap();
+        assert_eq!(opened, b"OK");
+        assert_eq!(ack.len(), b"OK".len() + TAG_LEN);
+        assert!(leader.send.cipher().unwrap()
+            .decrypt(Nonce::from_slice(&leader.send.nonce(0)), Payload { msg: &ack, aad: &[] })
+            .is_err());
+    }
+}
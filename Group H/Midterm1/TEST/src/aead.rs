@@ -21,4 +21,10 @@ impl NonceCtr {
     pub fn new(base:[u8;8]) -> Self { Self{ base, ctr:0 } }
     pub fn next(&mut self) -> [u8;12] { let mut n=[0u8;12]; n[..8].copy_from_slice(&self.base); n[8..].copy_from_slice(&self.ctr.to_be_bytes()); self.ctr=self.ctr.wrapping_add(1); n }
     pub fn reset(&mut self, base:[u8;8]) { self.base = base; self.ctr = 0; }
+    // Frame nonce taken from the frame's own seq, so a dropped frame does not desync the rest.
+    // seq 0 is the Confirm; None once seq no longer fits the 32-bit counter (rekey instead).
+    pub fn at(&self, seq:u64) -> Option<[u8;12]> {
+        let ctr = u32::try_from(seq).ok()?;
+        let mut n=[0u8;12]; n[..8].copy_from_slice(&self.base); n[8..].copy_from_slice(&ctr.to_be_bytes()); Some(n)
+    }
 }
\ No newline at end of file
@@ -1,3 +1,7 @@
+pub mod negotiate;
+pub mod ticket;
+pub mod wire;
+
 pub mod keying {
     use hkdf::Hkdf;
     use p256::ecdh::EphemeralSecret;
@@ -66,6 +70,14 @@ pub mod aead {
             self.ctr = self.ctr.wrapping_add(1);
             n
         }
+        // Frame nonce taken from the frame's own seq (see src/aead.rs)
+        pub fn at(&self, seq: u64) -> Option<[u8;12]> {
+            let ctr = u32::try_from(seq).ok()?;
+            let mut n=[0u8;12];
+            n[..8].copy_from_slice(&self.base);
+            n[8..].copy_from_slice(&ctr.to_be_bytes());
+            Some(n)
+        }
     }
 
     pub struct AeadCtx(Aes128Gcm);
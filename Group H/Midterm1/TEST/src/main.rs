@@ -2,14 +2,18 @@ mod video;
 mod aead;
 mod session;
 mod keying;
+mod negotiate;
+mod ticket;
+mod wire;
 
 use aead::{AeadCtx, NonceCtr};
 use serde::{Deserialize, Serialize};
 use std::env;
 use std::io;
-use std::time::{SystemTime, UNIX_EPOCH};
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use std::path::Path;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 use tokio::net::{TcpListener, TcpStream};
+use wire::ProtocolError;
 use sha2::{Digest, Sha256}; // keep this import near other top-level uses
 
 fn fp16(b: &[u8]) -> String {
@@ -51,22 +55,55 @@ enum Msg {
     // Common
     Confirm { ct: Vec<u8> },                     // seq=0
     Frame { seq: u64, ts_ns: u64, ct: Vec<u8> }, // seq>=1
+
+    // Resumption (PSK ticket + fresh ECDH)
+    Resume { ticket: Vec<u8>, pubkey: Vec<u8>, salt: [u8; 32] }, // sender -> receiver
+    ResumeAck { pubkey: Vec<u8>, salt: [u8; 32] },               // receiver -> sender
+    ResumeReject { reason: String },                             // sender falls back to full handshake
+    NewTicket { ticket: Vec<u8>, lifetime_s: u64 },              // receiver -> sender, after Confirm
 }
 
-async fn write_msg(stream: &mut TcpStream, msg: &Msg) -> io::Result<()> {
-    let buf = bincode::serialize(msg).unwrap();
-    let len = (buf.len() as u32).to_be_bytes();
-    stream.write_all(&len).await?;
-    stream.write_all(&buf).await
+impl Msg {
+    fn name(&self) -> &'static str {
+        match self {
+            Msg::Hello { .. } => "Hello",
+            Msg::HelloAck { .. } => "HelloAck",
+            Msg::RsaHelloReq => "RsaHelloReq",
+            Msg::RsaPub { .. } => "RsaPub",
+            Msg::RsaWrapped { .. } => "RsaWrapped",
+            Msg::RekeyHello { .. } => "RekeyHello",
+            Msg::RekeyAck { .. } => "RekeyAck",
+            Msg::RekeyConfirm { .. } => "RekeyConfirm",
+            Msg::Confirm { .. } => "Confirm",
+            Msg::Frame { .. } => "Frame",
+            Msg::Resume { .. } => "Resume",
+            Msg::ResumeAck { .. } => "ResumeAck",
+            Msg::ResumeReject { .. } => "ResumeReject",
+            Msg::NewTicket { .. } => "NewTicket",
+        }
+    }
+}
+
+// Every read/write is bounded by wire::MAX_MSG_LEN; handshake reads/writes also by wire::IO_TIMEOUT
+async fn write_frame<T: Serialize>(stream: &mut TcpStream, v: &T) -> Result<(), ProtocolError> {
+    wire::timed("sending", wire::IO_TIMEOUT, wire::write_frame(stream, v)).await
+}
+async fn read_frame(stream: &mut TcpStream) -> Result<Vec<u8>, ProtocolError> {
+    wire::timed("waiting for peer", wire::IO_TIMEOUT, wire::read_frame(stream)).await
+}
+async fn write_msg(stream: &mut TcpStream, msg: &Msg) -> Result<(), ProtocolError> {
+    write_frame(stream, msg).await
 }
-async fn read_msg(stream: &mut TcpStream) -> io::Result<Msg> {
-    let mut lenb = [0u8; 4];
-    stream.read_exact(&mut lenb).await?;
-    let len = u32::from_be_bytes(lenb) as usize;
-    let mut buf = vec![0u8; len];
-    stream.read_exact(&mut buf).await?;
-    Ok(bincode::deserialize::<Msg>(&buf).unwrap())
+async fn read_msg(stream: &mut TcpStream) -> Result<Msg, ProtocolError> {
+    wire::decode(&read_frame(stream).await?)
 }
+// Steady-state read: a sender whose camera stalls is idle, not gone, so no timeout here
+async fn next_msg(stream: &mut TcpStream) -> Result<Msg, ProtocolError> {
+    wire::decode(&wire::read_frame(stream).await?)
+}
+
+// A rejected ticket must be followed by a full handshake, not another ticket
+const MAX_RESUME_ATTEMPTS: u32 = 1;
 
 fn arg_val<'a>(args: &'a [String], key: &str) -> Option<&'a str> {
     args.iter()
@@ -76,21 +113,24 @@ fn arg_val<'a>(args: &'a [String], key: &str) -> Option<&'a str> {
 }
 
 #[tokio::main]
-async fn main() -> io::Result<()> {
+async fn main() -> Result<(), ProtocolError> {
     log_arm_ce();
 
     // CLI: --mode sender|receiver --host <ip> --port <p> [--mech ecdh|rsa]
+    //      [--ticket <file>] (sender)  [--ticket-lifetime <secs>] (receiver)
     let args: Vec<String> = env::args().collect();
     let mode = arg_val(&args, "--mode").unwrap_or("receiver");
     let host = arg_val(&args, "--host").unwrap_or("127.0.0.1");
     let port: u16 = arg_val(&args, "--port").and_then(|s| s.parse().ok()).unwrap_or(5000);
     let mech = arg_val(&args, "--mech").unwrap_or("ecdh");
+    let ticket_file = arg_val(&args, "--ticket").map(Path::new);
+    let ticket_lifetime: u64 = arg_val(&args, "--ticket-lifetime").and_then(|s| s.parse().ok()).unwrap_or(3600);
 
     match mode {
-        "receiver" => receiver(port).await,
-        "sender" => sender(host, port, mech).await,
+        "receiver" => receiver(port, ticket_lifetime).await,
+        "sender" => sender(host, port, mech, ticket_file).await,
         _ => {
-            eprintln!("Usage: --mode sender|receiver [--host IP] [--port P] [--mech ecdh|rsa]");
+            eprintln!("Usage: --mode sender|receiver [--host IP] [--port P] [--mech ecdh|rsa] [--ticket FILE] [--ticket-lifetime SECS]");
             Ok(())
         }
     }
@@ -101,31 +141,74 @@ struct CryptoDirs {
     n_tx: NonceCtr,
     enc_rx: AeadCtx,
     n_rx: NonceCtr,
+    resumption: [u8; 32], // PSK for the next session's ticket
 }
 
-async fn sender(host: &str, port: u16, mech: &str) -> io::Result<()> {
+async fn sender(host: &str, port: u16, mech: &str, ticket_file: Option<&Path>) -> Result<(), ProtocolError> {
     let addr = format!("{host}:{port}");
     eprintln!("SENDER: connecting to {addr}");
     let mut s = TcpStream::connect(&addr).await?;
-
-    // Handshake ? CryptoDirs
-    let mut crypto = match mech {
-        "rsa" => rsa_handshake_sender(&mut s).await?,
-        _ => ecdh_handshake_sender(&mut s).await?,
+    let t0 = Instant::now();
+
+    // Version + mechanism negotiation (before any Msg)
+    let hello = negotiate::ClientHello::new(mech);
+    write_frame(&mut s, &hello).await?;
+    let server_hello = negotiate::ServerHello::decode(&read_frame(&mut s).await?)?;
+    let (version, mech) = server_hello.chosen(&hello)?;
+    let transcript = negotiate::transcript(&hello, &server_hello);
+    eprintln!("SENDER: protocol v{version}, mech {mech}");
+
+    // Resume with a saved ticket if we have one, else full handshake ? CryptoDirs
+    let saved = ticket_file.and_then(|p| ticket::ClientTicket::take(p, ticket::now_s()));
+    let resumed = match saved {
+        Some(saved) => resume_handshake_sender(&mut s, saved).await?,
+        None => None,
+    };
+    let how = if resumed.is_some() { "resumed" } else { mech.as_str() };
+    let mut crypto = match (resumed, mech.as_str()) {
+        (Some(crypto), _) => crypto,
+        (None, "rsa") => rsa_handshake_sender(&mut s).await?,
+        (None, _) => ecdh_handshake_sender(&mut s).await?,
     };
 
-    // Confirm (seq=0)
-    let ct0 = crypto.enc_tx.seal(crypto.n_tx.next(), 0, b"confirm");
+    // Confirm (seq=0) carries the transcript, so a tampered hello fails here
+    let ct0 = crypto.enc_tx.seal(crypto.n_tx.next(), 0, &negotiate::confirm_payload(&transcript));
     write_msg(&mut s, &Msg::Confirm { ct: ct0 }).await?;
-    eprintln!("SENDER: handshake complete ({mech})");
+
+    // Receiver's Confirm (seq=0 on its direction) must echo the same transcript
+    match read_msg(&mut s).await? {
+        Msg::Confirm { ct } => {
+            let pt = crypto.enc_rx.open(crypto.n_rx.next(), 0, &ct).map_err(|_| auth_failed("bad confirm from receiver"))?;
+            negotiate::check_confirm(&pt, &transcript)?;
+        }
+        other => return Err(unexpected("waiting for Confirm", &other)),
+    }
+
+    // Receiver answers a good Confirm with a ticket for the next connection
+    match read_msg(&mut s).await? {
+        Msg::NewTicket { ticket, lifetime_s } => {
+            if let Some(path) = ticket_file {
+                let saved = ticket::ClientTicket { ticket, psk: crypto.resumption, expires_at: ticket::now_s().saturating_add(lifetime_s.min(ticket::MAX_LIFETIME_S)) };
+                if let Err(e) = saved.save(path) {
+                    eprintln!("SENDER: could not save ticket to {}: {e}", path.display());
+                }
+            }
+        }
+        other => return Err(unexpected("waiting for NewTicket", &other)),
+    }
+    eprintln!("SENDER: handshake complete ({how}) in {:.1} ms", t0.elapsed().as_secs_f64() * 1e3);
 
     // Camera ? H.264 AUs ? Encrypt ? Send
-    let rx_aus = video::start_sender_pipeline("/dev/video0", 640, 480, 15).expect("gstreamer sender");
+    let rx_aus = video::start_sender_pipeline("/dev/video0", 640, 480, 15).map_err(to_io)?;
 
     for (seq, au) in (1u64..).zip(rx_aus.iter()) {
         eprintln!("SENDER: AU {} bytes", au.len());
         let ts_ns = now_ns();
-        let ct = crypto.enc_tx.seal(crypto.n_tx.next(), seq, &au);
+        let Some(nonce) = crypto.n_tx.at(seq) else {
+            eprintln!("SENDER: nonce space exhausted at seq={seq}, reconnect to rekey");
+            break;
+        };
+        let ct = crypto.enc_tx.seal(nonce, seq, &au);
         if let Err(e) = write_msg(&mut s, &Msg::Frame { seq, ts_ns, ct }).await {
             eprintln!("SENDER: write failed at seq={seq}: {e}");
             break;
@@ -134,69 +217,120 @@ async fn sender(host: &str, port: u16, mech: &str) -> io::Result<()> {
     Ok(())
 }
 
-async fn receiver(port: u16) -> io::Result<()> {
+async fn receiver(port: u16, ticket_lifetime: u64) -> Result<(), ProtocolError> {
     let addr = format!("0.0.0.0:{port}");
     let listener = TcpListener::bind(&addr).await?;
     eprintln!("RECEIVER: listening on {addr}");
-    let (mut s, peer) = listener.accept().await?;
-    eprintln!("RECEIVER: connection from {peer}");
 
-    // Peek first message to determine mechanism
-    let first = read_msg(&mut s).await?;
+    // Tickets stay valid across reconnects for as long as this process runs
+    let mut tickets = ticket::TicketIssuer::new(ticket_lifetime);
+    let video = video::ReceiverVideo::new().map_err(to_io)?;
 
-    // Perform handshake ? CryptoDirs
-    let mut crypto = match first {
-        Msg::Hello { .. } => ecdh_handshake_receiver(&mut s, first).await?,
-        Msg::RsaHelloReq => rsa_handshake_receiver(&mut s).await?,
-        other => return Err(io_err(format!("unexpected first msg: {:?}", other))),
-    };
+    loop {
+        let (s, peer) = listener.accept().await?;
+        eprintln!("RECEIVER: connection from {peer}");
+        if let Err(e) = serve_sender(s, &mut tickets, &video).await {
+            eprintln!("RECEIVER: connection from {peer} failed: {e}");
+        }
+    }
+}
 
-    // Expect Confirm (seq=0) using RX direction
-    match read_msg(&mut s).await? {
-        Msg::Confirm { ct } => {
-            match crypto.enc_rx.open(crypto.n_rx.next(), 0, &ct) {
-                Ok(pt) if pt.as_slice() == b"confirm" => {}
-                _ => {
-                    eprintln!("bad confirm");
-                    return Ok(())
+async fn serve_sender(
+    mut s: TcpStream,
+    tickets: &mut ticket::TicketIssuer,
+    video: &video::ReceiverVideo,
+) -> Result<(), ProtocolError> {
+    let t0 = Instant::now();
+
+    // Version + mechanism negotiation; incompatible peers get a Reject with the reason
+    let buf = read_frame(&mut s).await?;
+    let (hello, server_hello) = match negotiate::ClientHello::decode(&buf).and_then(|h| Ok((h.accept()?, h))) {
+        Ok((accept, hello)) => (hello, accept),
+        Err(e) => {
+            write_frame(&mut s, &negotiate::ServerHello::reject(&e)).await?;
+            return Err(e.into());
+        }
+    };
+    write_frame(&mut s, &server_hello).await?;
+    let (version, mech) = server_hello.chosen(&hello)?;
+    let transcript = negotiate::transcript(&hello, &server_hello);
+    eprintln!("RECEIVER: protocol v{version}, mech {mech}");
+
+    // Peek first message; only the negotiated mechanism (or a ticket) is accepted
+    let mut first = read_msg(&mut s).await?;
+
+    // Perform handshake ? CryptoDirs (a rejected ticket is followed by a full handshake)
+    let mut resumes = 0;
+    let mut crypto = loop {
+        match first {
+            Msg::Hello { .. } if mech == "ecdh" => break ecdh_handshake_receiver(&mut s, first).await?,
+            Msg::RsaHelloReq if mech == "rsa" => break rsa_handshake_receiver(&mut s).await?,
+            Msg::Resume { .. } if resumes < MAX_RESUME_ATTEMPTS => {
+                resumes += 1;
+                match resume_handshake_receiver(&mut s, first, tickets).await? {
+                    Some(crypto) => break crypto,
+                    None => first = read_msg(&mut s).await?,
                 }
             }
+            other => return Err(unexpected("waiting for handshake", &other)),
         }
-        other => {
-            eprintln!("expected Confirm, got {:?}", other);
-            return Ok(())
+    };
+
+    // Expect Confirm (seq=0) using RX direction, echoing the negotiation transcript
+    match read_msg(&mut s).await? {
+        Msg::Confirm { ct } => {
+            let pt = crypto.enc_rx.open(crypto.n_rx.next(), 0, &ct).map_err(|_| auth_failed("bad confirm"))?;
+            negotiate::check_confirm(&pt, &transcript)?;
         }
+        other => return Err(unexpected("waiting for Confirm", &other)),
     }
-    eprintln!("RECEIVER: handshake complete");
-
-    let video = video::ReceiverVideo::new().expect("gstreamer receiver");
+    let ct0 = crypto.enc_tx.seal(crypto.n_tx.next(), 0, &negotiate::confirm_payload(&transcript));
+    write_msg(&mut s, &Msg::Confirm { ct: ct0 }).await?;
+    let ticket = tickets.issue(ticket::now_s(), &crypto.resumption);
+    write_msg(&mut s, &Msg::NewTicket { ticket, lifetime_s: tickets.lifetime_s() }).await?;
+    eprintln!("RECEIVER: handshake complete in {:.1} ms", t0.elapsed().as_secs_f64() * 1e3);
 
-    // Receive frames, decrypt with RX direction
+    // Receive frames, decrypt with RX direction; the nonce comes from seq, which must keep increasing
+    let mut last_seq = 0;
     loop {
-        match read_msg(&mut s).await {
+        match next_msg(&mut s).await {
             Ok(Msg::Frame { seq, ts_ns, ct }) => {
-                match crypto.enc_rx.open(crypto.n_rx.next(), seq, &ct) {
+                if seq <= last_seq {
+                    eprintln!("RECEIVER: stale seq={seq} (last {last_seq}) - dropped");
+                    continue;
+                }
+                let Some(nonce) = crypto.n_rx.at(seq) else {
+                    eprintln!("RECEIVER: seq={seq} out of nonce range - dropped");
+                    continue;
+                };
+                match crypto.enc_rx.open(nonce, seq, &ct) {
                     Ok(au) => {
+                        last_seq = seq;
                         video.push_au(&au);
                         let latency_ms = (now_ns().saturating_sub(ts_ns)) as f64 / 1e6;
                         eprintln!("frame seq={seq} len={} ~{latency_ms:.2} ms", au.len());
                     }
                     Err(_) => {
-                        eprintln!("GCM tag failure at seq={seq} ? dropped");
+                        eprintln!("GCM tag failure at seq={seq} - dropped");
                         continue;
                     }
                 }
             }
 	    Ok(m @ Msg::RekeyHello { .. }) => {
 	        crypto = ecdh_rekey_receiver(&mut s, m).await?;
+	        last_seq = 0;
 	        eprintln!("RECEIVER: rekeyed");
 	        continue;
 	    }
-            Ok(other) => eprintln!("unexpected {:?}", other),
-            Err(e) => {
-                eprintln!("recv done: {e}");
+            Ok(other) => eprintln!("{}", unexpected("streaming", &other)),
+            // Framing is still intact: drop this message and keep reading
+            Err(e) if e.is_recoverable() => eprintln!("RECEIVER: {e} - dropped"),
+            Err(ProtocolError::Closed) => {
+                eprintln!("recv done: sender closed");
                 break;
             }
+            // Oversize / truncated / timeout / I/O: stream is lost, sender has to reconnect
+            Err(e) => return Err(e),
         }
     }
     Ok(())
@@ -204,7 +338,7 @@ async fn receiver(port: u16) -> io::Result<()> {
 
 // ---------------- Handshakes (return CryptoDirs) ----------------
 
-async fn ecdh_handshake_sender(s: &mut TcpStream) -> io::Result<CryptoDirs> {
+async fn ecdh_handshake_sender(s: &mut TcpStream) -> Result<CryptoDirs, ProtocolError> {
     use p256::PublicKey;
     use crate::{aead, keying, session};
 
@@ -222,7 +356,7 @@ async fn ecdh_handshake_sender(s: &mut TcpStream) -> io::Result<CryptoDirs> {
     // Peer offer
     let (peer_pub, peer_salt) = match read_msg(s).await? {
         Msg::HelloAck { pubkey, salt } => (pubkey, salt),
-        other => return Err(io_err(format!("unexpected {:?}", other))),
+        other => return Err(unexpected("waiting for HelloAck", &other)),
     };
 
     // Combine salts and compute ECDH shared
@@ -230,7 +364,7 @@ async fn ecdh_handshake_sender(s: &mut TcpStream) -> io::Result<CryptoDirs> {
     for i in 0..32 {
         salt[i] = offer_s.salt[i] ^ peer_salt[i];
     }
-    let peer_pk = PublicKey::from_sec1_bytes(&peer_pub).map_err(to_io)?;
+    let peer_pk = PublicKey::from_sec1_bytes(&peer_pub).map_err(malformed)?;
     let shared = secret_s.diffie_hellman(&peer_pk);
 
     // Derive per-direction keys
@@ -255,19 +389,20 @@ async fn ecdh_handshake_sender(s: &mut TcpStream) -> io::Result<CryptoDirs> {
         n_tx: aead::NonceCtr::new(sess.tx.nonce_base),
         enc_rx: aead::AeadCtx::new(sess.rx.enc_key),
         n_rx: aead::NonceCtr::new(sess.rx.nonce_base),
+        resumption: ticket::resumption_secret(shared.raw_secret_bytes(), &salt),
     })
 }
 
 async fn ecdh_handshake_receiver(
     s: &mut TcpStream,
     first: Msg,
-) -> io::Result<CryptoDirs> {
+) -> Result<CryptoDirs, ProtocolError> {
     use p256::PublicKey;
     use crate::{aead, keying, session};
 
     let (peer_pub, peer_salt) = match first {
         Msg::Hello { pubkey, salt } => (pubkey, salt),
-        other => return Err(io_err(format!("expected Hello, got {:?}", other))),
+        other => return Err(unexpected("waiting for Hello", &other)),
     };
 
     let (offer_r, secret_r) = keying::start_offer();
@@ -284,7 +419,7 @@ async fn ecdh_handshake_receiver(
     for i in 0..32 {
         salt[i] = offer_r.salt[i] ^ peer_salt[i];
     }
-    let peer_pk = PublicKey::from_sec1_bytes(&peer_pub).map_err(to_io)?;
+    let peer_pk = PublicKey::from_sec1_bytes(&peer_pub).map_err(malformed)?;
     let shared = secret_r.diffie_hellman(&peer_pk);
 
     let sess = session::derive_bidirectional(
@@ -308,10 +443,11 @@ async fn ecdh_handshake_receiver(
         n_tx: aead::NonceCtr::new(sess.tx.nonce_base),
         enc_rx: aead::AeadCtx::new(sess.rx.enc_key),
         n_rx: aead::NonceCtr::new(sess.rx.nonce_base),
+        resumption: ticket::resumption_secret(shared.raw_secret_bytes(), &salt),
     })
 }
 
-async fn rsa_handshake_sender(s: &mut TcpStream) -> io::Result<CryptoDirs> {
+async fn rsa_handshake_sender(s: &mut TcpStream) -> Result<CryptoDirs, ProtocolError> {
     use rsa::{Oaep, RsaPublicKey};
     use rsa::pkcs1::DecodeRsaPublicKey;
     use rsa::rand_core::{OsRng, RngCore};
@@ -322,9 +458,9 @@ async fn rsa_handshake_sender(s: &mut TcpStream) -> io::Result<CryptoDirs> {
     write_msg(s, &Msg::RsaHelloReq).await?;
     let pk_der = match read_msg(s).await? {
         Msg::RsaPub { pk_der } => pk_der,
-        other => return Err(io_err(format!("expected RsaPub, got {:?}", other))),
+        other => return Err(unexpected("waiting for RsaPub", &other)),
     };
-    let peer_pk = RsaPublicKey::from_pkcs1_der(&pk_der).map_err(to_io)?;
+    let peer_pk = RsaPublicKey::from_pkcs1_der(&pk_der).map_err(malformed)?;
 
     // Fresh salt + prekey (prekey is the HKDF input "secret")
     let mut rng = OsRng;
@@ -348,10 +484,11 @@ async fn rsa_handshake_sender(s: &mut TcpStream) -> io::Result<CryptoDirs> {
         n_tx: aead::NonceCtr::new(sess.tx.nonce_base),
         enc_rx: aead::AeadCtx::new(sess.rx.enc_key),
         n_rx: aead::NonceCtr::new(sess.rx.nonce_base),
+        resumption: ticket::resumption_secret(&prekey, &salt),
     })
 }
 
-async fn rsa_handshake_receiver(s: &mut TcpStream) -> io::Result<CryptoDirs> {
+async fn rsa_handshake_receiver(s: &mut TcpStream) -> Result<CryptoDirs, ProtocolError> {
     use rsa::{Oaep, RsaPrivateKey, RsaPublicKey};
     use rsa::pkcs1::EncodeRsaPublicKey;
     use rsa::rand_core::OsRng;
@@ -368,10 +505,10 @@ async fn rsa_handshake_receiver(s: &mut TcpStream) -> io::Result<CryptoDirs> {
     // Receive wrapped (salt||prekey)
     let ct = match read_msg(s).await? {
         Msg::RsaWrapped { ct } => ct,
-        other => return Err(io_err(format!("expected RsaWrapped, got {:?}", other))),
+        other => return Err(unexpected("waiting for RsaWrapped", &other)),
     };
-    let pt = sk.decrypt(Oaep::new::<Sha256>(), &ct).map_err(to_io)?;
-    if pt.len() != 64 { return Err(io_err("bad RSA payload length".into())); }
+    let pt = sk.decrypt(Oaep::new::<Sha256>(), &ct).map_err(|_| auth_failed("RSA-OAEP unwrap"))?;
+    if pt.len() != 64 { return Err(malformed("bad RSA payload length")); }
     let mut salt = [0u8; 32];
     salt.copy_from_slice(&pt[..32]);
     let mut prekey = [0u8; 32];
@@ -383,43 +520,146 @@ async fn rsa_handshake_receiver(s: &mut TcpStream) -> io::Result<CryptoDirs> {
         n_tx: aead::NonceCtr::new(sess.tx.nonce_base),
         enc_rx: aead::AeadCtx::new(sess.rx.enc_key),
         n_rx: aead::NonceCtr::new(sess.rx.nonce_base),
+        resumption: ticket::resumption_secret(&prekey, &salt),
     })
 }
 
+// ------- Resumption: ticket PSK + fresh ECDH (skips RSA keygen / full exchange) ---------
+
+/// Ok(None) means the receiver rejected the ticket; the caller runs a full handshake
+/// on the same connection.
+async fn resume_handshake_sender(
+    s: &mut TcpStream,
+    saved: ticket::ClientTicket,
+) -> Result<Option<CryptoDirs>, ProtocolError> {
+    use p256::PublicKey;
+    use crate::{aead, keying, session};
+
+    let (offer_s, secret_s) = keying::start_offer();
+    write_msg(
+        s,
+        &Msg::Resume {
+            ticket: saved.ticket,
+            pubkey: offer_s.pubkey_sec1.clone(),
+            salt: offer_s.salt,
+        },
+    )
+    .await?;
+
+    let (peer_pub, peer_salt) = match read_msg(s).await? {
+        Msg::ResumeAck { pubkey, salt } => (pubkey, salt),
+        Msg::ResumeReject { reason } => {
+            eprintln!("SENDER: ticket rejected ({reason}), falling back to full handshake");
+            return Ok(None);
+        }
+        other => return Err(unexpected("waiting for ResumeAck", &other)),
+    };
+
+    let mut salt = [0u8; 32];
+    for i in 0..32 {
+        salt[i] = offer_s.salt[i] ^ peer_salt[i];
+    }
+    let peer_pk = PublicKey::from_sec1_bytes(&peer_pub).map_err(malformed)?;
+    let shared = secret_s.diffie_hellman(&peer_pk);
+    let ikm = ticket::resume_ikm(&saved.psk, shared.raw_secret_bytes());
+
+    let sess = session::derive_bidirectional(&ikm, &salt, b"SENDER", b"RECEIVER");
+    Ok(Some(CryptoDirs {
+        enc_tx: aead::AeadCtx::new(sess.tx.enc_key),
+        n_tx: aead::NonceCtr::new(sess.tx.nonce_base),
+        enc_rx: aead::AeadCtx::new(sess.rx.enc_key),
+        n_rx: aead::NonceCtr::new(sess.rx.nonce_base),
+        resumption: ticket::resumption_secret(&ikm, &salt),
+    }))
+}
+
+/// Ok(None) means the ticket was rejected (bad, expired or replayed) and the sender
+/// was told so; its next message starts a full handshake.
+async fn resume_handshake_receiver(
+    s: &mut TcpStream,
+    first: Msg,
+    tickets: &mut ticket::TicketIssuer,
+) -> Result<Option<CryptoDirs>, ProtocolError> {
+    use p256::PublicKey;
+    use crate::{aead, keying, session};
+
+    let (tkt, peer_pub, peer_salt) = match first {
+        Msg::Resume { ticket, pubkey, salt } => (ticket, pubkey, salt),
+        other => return Err(unexpected("waiting for Resume", &other)),
+    };
+    let psk = match tickets.redeem(ticket::now_s(), &tkt) {
+        Ok(psk) => psk,
+        Err(e) => {
+            eprintln!("RECEIVER: rejecting ticket: {e}");
+            write_msg(s, &Msg::ResumeReject { reason: e.to_string() }).await?;
+            return Ok(None);
+        }
+    };
+
+    let (offer_r, secret_r) = keying::start_offer();
+    write_msg(
+        s,
+        &Msg::ResumeAck {
+            pubkey: offer_r.pubkey_sec1.clone(),
+            salt: offer_r.salt,
+        },
+    )
+    .await?;
+
+    let mut salt = [0u8; 32];
+    for i in 0..32 {
+        salt[i] = offer_r.salt[i] ^ peer_salt[i];
+    }
+    let peer_pk = PublicKey::from_sec1_bytes(&peer_pub).map_err(malformed)?;
+    let shared = secret_r.diffie_hellman(&peer_pk);
+    let ikm = ticket::resume_ikm(&psk, shared.raw_secret_bytes());
+
+    let sess = session::derive_bidirectional(&ikm, &salt, b"RECEIVER", b"SENDER");
+    Ok(Some(CryptoDirs {
+        enc_tx: aead::AeadCtx::new(sess.tx.enc_key),
+        n_tx: aead::NonceCtr::new(sess.tx.nonce_base),
+        enc_rx: aead::AeadCtx::new(sess.rx.enc_key),
+        n_rx: aead::NonceCtr::new(sess.rx.nonce_base),
+        resumption: ticket::resumption_secret(&ikm, &salt),
+    }))
+}
+
 // ------- Optional: mid-stream ECDH rekey helpers ---------
-async fn ecdh_rekey_sender(s: &mut TcpStream) -> io::Result<CryptoDirs> {
+async fn ecdh_rekey_sender(s: &mut TcpStream) -> Result<CryptoDirs, ProtocolError> {
     use p256::PublicKey; use crate::{aead, keying, session};
     let (offer, secret) = keying::start_offer();
     write_msg(s, &Msg::RekeyHello{ pubkey: offer.pubkey_sec1.clone(), salt: offer.salt }).await?;
-    let (peer_pub, peer_salt) = match read_msg(s).await? { Msg::RekeyAck{pubkey, salt} => (pubkey, salt), other => return Err(io_err(format!("unexpected {other:?}"))) };
+    let (peer_pub, peer_salt) = match read_msg(s).await? { Msg::RekeyAck{pubkey, salt} => (pubkey, salt), other => return Err(unexpected("waiting for RekeyAck", &other)) };
     let mut salt=[0u8;32]; for i in 0..32 { salt[i]=offer.salt[i]^peer_salt[i]; }
-    let peer_pk = PublicKey::from_sec1_bytes(&peer_pub).map_err(to_io)?;
+    let peer_pk = PublicKey::from_sec1_bytes(&peer_pub).map_err(malformed)?;
     let shared = secret.diffie_hellman(&peer_pk);
     let sess = session::derive_bidirectional(shared.raw_secret_bytes(), &salt, b"SENDER", b"RECEIVER");
-    let mut crypto = CryptoDirs{ enc_tx:aead::AeadCtx::new(sess.tx.enc_key), n_tx:aead::NonceCtr::new(sess.tx.nonce_base), enc_rx:aead::AeadCtx::new(sess.rx.enc_key), n_rx:aead::NonceCtr::new(sess.rx.nonce_base) };
+    let mut crypto = CryptoDirs{ enc_tx:aead::AeadCtx::new(sess.tx.enc_key), n_tx:aead::NonceCtr::new(sess.tx.nonce_base), enc_rx:aead::AeadCtx::new(sess.rx.enc_key), n_rx:aead::NonceCtr::new(sess.rx.nonce_base), resumption: ticket::resumption_secret(shared.raw_secret_bytes(), &salt) };
     let ct = crypto.enc_tx.seal(crypto.n_tx.next(), 0, b"rekey-ok");
     write_msg(s, &Msg::RekeyConfirm{ ct }).await?;
     Ok(crypto)
 }
 
-async fn ecdh_rekey_receiver(s: &mut TcpStream, first: Msg) -> io::Result<CryptoDirs> {
+async fn ecdh_rekey_receiver(s: &mut TcpStream, first: Msg) -> Result<CryptoDirs, ProtocolError> {
     use p256::PublicKey; use crate::{aead, keying, session};
-    let (peer_pub, peer_salt) = match first { Msg::RekeyHello{pubkey, salt} => (pubkey, salt), other => return Err(io_err(format!("unexpected {other:?}"))) };
+    let (peer_pub, peer_salt) = match first { Msg::RekeyHello{pubkey, salt} => (pubkey, salt), other => return Err(unexpected("waiting for RekeyHello", &other)) };
     let (offer, secret) = keying::start_offer();
     write_msg(s, &Msg::RekeyAck{ pubkey: offer.pubkey_sec1.clone(), salt: offer.salt }).await?;
     let mut salt=[0u8;32]; for i in 0..32 { salt[i]=offer.salt[i]^peer_salt[i]; }
-    let peer_pk = PublicKey::from_sec1_bytes(&peer_pub).map_err(to_io)?;
+    let peer_pk = PublicKey::from_sec1_bytes(&peer_pub).map_err(malformed)?;
     let shared = secret.diffie_hellman(&peer_pk);
     let sess = session::derive_bidirectional(shared.raw_secret_bytes(), &salt, b"RECEIVER", b"SENDER");
-    let mut crypto = CryptoDirs{ enc_tx:aead::AeadCtx::new(sess.tx.enc_key), n_tx:aead::NonceCtr::new(sess.tx.nonce_base), enc_rx:aead::AeadCtx::new(sess.rx.enc_key), n_rx:aead::NonceCtr::new(sess.rx.nonce_base) };
+    let mut crypto = CryptoDirs{ enc_tx:aead::AeadCtx::new(sess.tx.enc_key), n_tx:aead::NonceCtr::new(sess.tx.nonce_base), enc_rx:aead::AeadCtx::new(sess.rx.enc_key), n_rx:aead::NonceCtr::new(sess.rx.nonce_base), resumption: ticket::resumption_secret(shared.raw_secret_bytes(), &salt) };
     match read_msg(s).await? { Msg::RekeyConfirm{ ct } => {
-        match crypto.enc_rx.open(crypto.n_rx.next(), 0, &ct) { Ok(pt) if pt.as_slice()==b"rekey-ok" => Ok(crypto), _ => Err(io_err("bad rekey confirm".into())) }
-    }, other => Err(io_err(format!("expected RekeyConfirm, got {other:?}"))) }
+        match crypto.enc_rx.open(crypto.n_rx.next(), 0, &ct) { Ok(pt) if pt.as_slice()==b"rekey-ok" => Ok(crypto), _ => Err(auth_failed("bad rekey confirm")) }
+    }, other => Err(unexpected("waiting for RekeyConfirm", &other)) }
 }
 
 // ---------------- helpers ----------------
 fn now_ns() -> u64 {
     SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos() as u64
 }
-fn to_io<E: std::fmt::Display>(e: E) -> io::Error { io::Error::new(io::ErrorKind::Other, e.to_string()) }
-fn io_err(msg: String) -> io::Error { io::Error::new(io::ErrorKind::Other, msg) }
\ No newline at end of file
+fn to_io<E: std::fmt::Display>(e: E) -> ProtocolError { ProtocolError::Io(io::Error::other(e.to_string())) }
+fn malformed<E: std::fmt::Display>(e: E) -> ProtocolError { ProtocolError::Malformed(e.to_string()) }
+fn auth_failed(what: &str) -> ProtocolError { ProtocolError::AuthFailed(what.to_string()) }
+fn unexpected(state: &'static str, got: &Msg) -> ProtocolError { ProtocolError::Unexpected { state, got: got.name() } }
\ No newline at end of file
@@ -0,0 +1,117 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt;
+
+// Every connection opens with ClientHello -> ServerHello before any `Msg`. These two
+// structs are the only wire layout peers of different versions must agree on, so an
+// incompatible peer is turned away with a reason instead of a decode failure later.
+pub const MAGIC: [u8; 4] = *b"RSS\0";
+pub const VERSION: u16 = 2; // 1 = the original unversioned protocol
+pub const MIN_VERSION: u16 = 2;
+/// Key-exchange mechanisms this build speaks, strongest first.
+pub const MECHS: &[&str] = &["ecdh", "rsa"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NegotiateError {
+    Malformed,
+    BadMagic,
+    UnsupportedVersion(u16),
+    NoCommonMech(Vec<String>),
+    Rejected(String),
+    Downgrade,
+}
+
+impl fmt::Display for NegotiateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NegotiateError::Malformed => write!(f, "malformed hello"),
+            NegotiateError::BadMagic => write!(f, "not an rpi-secure-stream peer"),
+            NegotiateError::UnsupportedVersion(v) => {
+                write!(f, "unsupported protocol version {v} (supported {MIN_VERSION}..={VERSION})")
+            }
+            NegotiateError::NoCommonMech(offered) => write!(f, "no common mechanism (offered {offered:?}, supported {MECHS:?})"),
+            NegotiateError::Rejected(reason) => write!(f, "peer rejected hello: {reason}"),
+            NegotiateError::Downgrade => write!(f, "handshake transcript mismatch (possible downgrade)"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ClientHello {
+    pub magic: [u8; 4],
+    pub version: u16,
+    pub mechs: Vec<String>, // preference order; unknown names are ignored
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum ServerHello {
+    Accept { version: u16, mech: String },
+    Reject { reason: String, min_version: u16, max_version: u16 },
+}
+
+impl ClientHello {
+    /// Offer every supported mechanism, `preferred` first.
+    pub fn new(preferred: &str) -> Self {
+        let mut mechs = vec![preferred.to_string()];
+        mechs.extend(MECHS.iter().filter(|m| **m != preferred).map(|m| m.to_string()));
+        Self { magic: MAGIC, version: VERSION, mechs }
+    }
+
+    pub fn decode(buf: &[u8]) -> Result<Self, NegotiateError> {
+        let hello: Self = bincode::deserialize(buf).map_err(|_| NegotiateError::Malformed)?;
+        if hello.magic != MAGIC { return Err(NegotiateError::BadMagic); }
+        Ok(hello)
+    }
+
+    /// Receiver side: pick the version and the sender's most preferred mechanism we support.
+    pub fn accept(&self) -> Result<ServerHello, NegotiateError> {
+        if self.version < MIN_VERSION { return Err(NegotiateError::UnsupportedVersion(self.version)); }
+        let mech = self.mechs.iter()
+            .find(|m| MECHS.contains(&m.as_str()))
+            .ok_or_else(|| NegotiateError::NoCommonMech(self.mechs.clone()))?;
+        Ok(ServerHello::Accept { version: self.version.min(VERSION), mech: mech.clone() })
+    }
+}
+
+impl ServerHello {
+    pub fn reject(err: &NegotiateError) -> Self {
+        ServerHello::Reject { reason: err.to_string(), min_version: MIN_VERSION, max_version: VERSION }
+    }
+
+    pub fn decode(buf: &[u8]) -> Result<Self, NegotiateError> {
+        bincode::deserialize(buf).map_err(|_| NegotiateError::Malformed)
+    }
+
+    /// Sender side: the (version, mech) the receiver chose, which must be something we offered.
+    pub fn chosen(&self, hello: &ClientHello) -> Result<(u16, String), NegotiateError> {
+        match self {
+            ServerHello::Reject { reason, .. } => Err(NegotiateError::Rejected(reason.clone())),
+            ServerHello::Accept { version, .. } if !(MIN_VERSION..=hello.version).contains(version) => {
+                Err(NegotiateError::UnsupportedVersion(*version))
+            }
+            ServerHello::Accept { mech, .. } if !hello.mechs.contains(mech) => {
+                Err(NegotiateError::NoCommonMech(hello.mechs.clone()))
+            }
+            ServerHello::Accept { version, mech } => Ok((*version, mech.clone())),
+        }
+    }
+}
+
+/// Hash of both hellos as each side saw them. An on-path edit of either one (e.g.
+/// stripping "ecdh" to force RSA) leaves the two sides with different transcripts.
+pub fn transcript(client: &ClientHello, server: &ServerHello) -> [u8; 32] {
+    let mut h = Sha256::new();
+    h.update(b"ECE4301-midterm-2025|transcript");
+    h.update(bincode::serialize(client).unwrap());
+    h.update(bincode::serialize(server).unwrap());
+    h.finalize().into()
+}
+
+/// Plaintext of the seq=0 Confirm each side seals under its new TX key.
+pub fn confirm_payload(transcript: &[u8; 32]) -> Vec<u8> {
+    [b"confirm".as_slice(), transcript].concat()
+}
+
+pub fn check_confirm(pt: &[u8], transcript: &[u8; 32]) -> Result<(), NegotiateError> {
+    if pt == confirm_payload(transcript).as_slice() { Ok(()) } else { Err(NegotiateError::Downgrade) }
+}
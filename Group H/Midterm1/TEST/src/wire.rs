@@ -0,0 +1,123 @@
+use crate::negotiate::NegotiateError;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fmt;
+use std::future::Future;
+use std::io;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+// Frame = u32 BE length || bincode payload
+/// Largest frame we accept or send. A 640x480 H.264 keyframe is well under 1 MB;
+/// anything bigger is a corrupt length prefix, refused before allocating.
+pub const MAX_MSG_LEN: usize = 4 << 20;
+/// How long a single handshake frame read/write may take before the peer counts as gone.
+/// Streaming reads are not bounded: an idle sender is not a dead one.
+pub const IO_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug)]
+pub enum ProtocolError {
+    /// Peer closed the connection cleanly between frames.
+    Closed,
+    /// Length prefix above MAX_MSG_LEN; the stream cannot be resynced.
+    Oversize { len: usize, max: usize },
+    /// Connection ended in the middle of a frame.
+    Truncated,
+    /// Complete frame whose payload does not decode; the next frame is still readable.
+    Malformed(String),
+    /// Well-formed message that is not valid in the current protocol state.
+    Unexpected { state: &'static str, got: &'static str },
+    /// AEAD tag / confirm / transcript check failed.
+    AuthFailed(String),
+    Timeout(&'static str),
+    Negotiate(NegotiateError),
+    Io(io::Error),
+}
+
+impl fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProtocolError::Closed => write!(f, "connection closed"),
+            ProtocolError::Oversize { len, max } => write!(f, "frame of {len} bytes exceeds limit of {max}"),
+            ProtocolError::Truncated => write!(f, "connection closed mid-frame"),
+            ProtocolError::Malformed(e) => write!(f, "malformed message: {e}"),
+            ProtocolError::Unexpected { state, got } => write!(f, "unexpected {got} while {state}"),
+            ProtocolError::AuthFailed(what) => write!(f, "authentication failed: {what}"),
+            ProtocolError::Timeout(what) => write!(f, "timed out {what}"),
+            ProtocolError::Negotiate(e) => write!(f, "{e}"),
+            ProtocolError::Io(e) => write!(f, "I/O error: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for ProtocolError {}
+
+impl From<io::Error> for ProtocolError {
+    fn from(e: io::Error) -> Self {
+        match e.kind() {
+            io::ErrorKind::UnexpectedEof => ProtocolError::Truncated,
+            _ => ProtocolError::Io(e),
+        }
+    }
+}
+
+impl From<NegotiateError> for ProtocolError {
+    fn from(e: NegotiateError) -> Self {
+        match e {
+            NegotiateError::Downgrade => ProtocolError::AuthFailed(e.to_string()),
+            e => ProtocolError::Negotiate(e),
+        }
+    }
+}
+
+impl ProtocolError {
+    /// True if the connection is still framed correctly and the caller may keep reading.
+    pub fn is_recoverable(&self) -> bool {
+        matches!(self, ProtocolError::Malformed(_) | ProtocolError::Unexpected { .. } | ProtocolError::AuthFailed(_))
+    }
+}
+
+pub async fn timed<T>(
+    what: &'static str,
+    limit: Duration,
+    fut: impl Future<Output = Result<T, ProtocolError>>,
+) -> Result<T, ProtocolError> {
+    tokio::time::timeout(limit, fut).await.map_err(|_| ProtocolError::Timeout(what))?
+}
+
+pub async fn write_frame<W, T>(w: &mut W, v: &T) -> Result<(), ProtocolError>
+where
+    W: AsyncWrite + Unpin,
+    T: Serialize,
+{
+    let buf = bincode::serialize(v).map_err(|e| ProtocolError::Malformed(e.to_string()))?;
+    if buf.len() > MAX_MSG_LEN {
+        return Err(ProtocolError::Oversize { len: buf.len(), max: MAX_MSG_LEN });
+    }
+    w.write_all(&(buf.len() as u32).to_be_bytes()).await?;
+    w.write_all(&buf).await?;
+    Ok(())
+}
+
+pub async fn read_frame<R: AsyncRead + Unpin>(r: &mut R) -> Result<Vec<u8>, ProtocolError> {
+    let mut lenb = [0u8; 4];
+    let mut got = 0;
+    while got < lenb.len() {
+        match r.read(&mut lenb[got..]).await? {
+            0 if got == 0 => return Err(ProtocolError::Closed),
+            0 => return Err(ProtocolError::Truncated),
+            n => got += n,
+        }
+    }
+    let len = u32::from_be_bytes(lenb) as usize;
+    if len > MAX_MSG_LEN {
+        return Err(ProtocolError::Oversize { len, max: MAX_MSG_LEN });
+    }
+    let mut buf = vec![0u8; len];
+    r.read_exact(&mut buf).await?;
+    Ok(buf)
+}
+
+pub fn decode<T: DeserializeOwned>(buf: &[u8]) -> Result<T, ProtocolError> {
+    bincode::deserialize(buf).map_err(|e| ProtocolError::Malformed(e.to_string()))
+}
@@ -0,0 +1,91 @@
+use rpi_secure_stream::negotiate::{self, ClientHello, NegotiateError, ServerHello};
+
+#[test]
+fn receiver_picks_senders_preferred_mech() {
+    let hello = ClientHello::new("ecdh");
+    assert_eq!(hello.mechs, vec!["ecdh", "rsa"]);
+    let accept = hello.accept().unwrap();
+    assert_eq!(accept.chosen(&hello), Ok((negotiate::VERSION, "ecdh".to_string())));
+
+    let hello = ClientHello::new("rsa");
+    assert_eq!(hello.accept().unwrap().chosen(&hello), Ok((negotiate::VERSION, "rsa".to_string())));
+}
+
+#[test]
+fn incompatible_versions_are_rejected_cleanly() {
+    let mut hello = ClientHello::new("ecdh");
+    hello.version = 1;
+    let err = hello.accept().unwrap_err();
+    assert_eq!(err, NegotiateError::UnsupportedVersion(1));
+
+    // The sender gets the reason back instead of a decode failure
+    let reject = ServerHello::reject(&err);
+    let wire = bincode::serialize(&reject).unwrap();
+    let got = ServerHello::decode(&wire).unwrap();
+    assert!(matches!(got.chosen(&hello), Err(NegotiateError::Rejected(r)) if r.contains("version 1")));
+
+    // A newer sender is answered with our version
+    let mut hello = ClientHello::new("ecdh");
+    hello.version = negotiate::VERSION + 1;
+    assert_eq!(hello.accept().unwrap().chosen(&hello).unwrap().0, negotiate::VERSION);
+}
+
+#[test]
+fn garbage_and_legacy_first_frames_do_not_decode() {
+    // Pre-negotiation peers open with a bare Msg (variant index first)
+    assert_eq!(ClientHello::decode(&[2, 0, 0, 0]), Err(NegotiateError::Malformed));
+    assert_eq!(ClientHello::decode(&[]), Err(NegotiateError::Malformed));
+
+    let mut hello = ClientHello::new("ecdh");
+    hello.magic = *b"HTTP";
+    let wire = bincode::serialize(&hello).unwrap();
+    assert_eq!(ClientHello::decode(&wire), Err(NegotiateError::BadMagic));
+}
+
+#[test]
+fn no_common_mech_is_rejected() {
+    let mut hello = ClientHello::new("ecdh");
+    hello.mechs = vec!["x25519".into(), "dh1024".into()];
+    assert!(matches!(hello.accept(), Err(NegotiateError::NoCommonMech(_))));
+}
+
+#[test]
+fn receiver_cannot_pick_unoffered_mech() {
+    let mut hello = ClientHello::new("ecdh");
+    hello.mechs.retain(|m| m == "ecdh");
+    let forged = ServerHello::Accept { version: negotiate::VERSION, mech: "rsa".into() };
+    assert!(forged.chosen(&hello).is_err());
+}
+
+#[test]
+fn tampered_hello_is_caught_by_confirm() {
+    // Sender offers ECDH first; an on-path attacker strips it to force RSA
+    let sent = ClientHello::new("ecdh");
+    let mut seen = sent.clone();
+    seen.mechs.retain(|m| m != "ecdh");
+    let accept = seen.accept().unwrap();
+    assert_eq!(accept.chosen(&seen).unwrap().1, "rsa");
+
+    // Both sides still agree on keys, but their transcripts differ
+    let sender_t = negotiate::transcript(&sent, &accept);
+    let receiver_t = negotiate::transcript(&seen, &accept);
+    assert_ne!(sender_t, receiver_t);
+
+    let key = [0x11u8; 16];
+    let mut n_tx = rpi_secure_stream::aead::NonceCtr::new([0x22; 8]);
+    let mut n_rx = rpi_secure_stream::aead::NonceCtr::new([0x22; 8]);
+    let ctx = rpi_secure_stream::aead::AeadCtx::new(key);
+    let ct = ctx.seal(n_tx.next(), 0, &negotiate::confirm_payload(&sender_t));
+    let pt = ctx.open(n_rx.next(), 0, &ct);
+    assert_eq!(negotiate::check_confirm(&pt, &receiver_t), Err(NegotiateError::Downgrade));
+    assert_eq!(negotiate::check_confirm(&pt, &sender_t), Ok(()));
+}
+
+#[test]
+fn tampered_server_hello_is_caught_by_confirm() {
+    let hello = ClientHello::new("ecdh");
+    let sent = hello.accept().unwrap();
+    let seen = ServerHello::Accept { version: negotiate::VERSION, mech: "rsa".into() };
+    assert!(seen.chosen(&hello).is_ok(), "rsa was offered, so the sender alone cannot tell");
+    assert_ne!(negotiate::transcript(&hello, &sent), negotiate::transcript(&hello, &seen));
+}
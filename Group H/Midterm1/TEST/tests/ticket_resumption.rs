@@ -0,0 +1,97 @@
+use rpi_secure_stream::{keying, session, ticket};
+use rpi_secure_stream::ticket::{ClientTicket, TicketError, TicketIssuer};
+
+const T0: u64 = 1_700_000_000;
+
+#[test]
+fn ticket_roundtrip_returns_psk() {
+    let mut issuer = TicketIssuer::new(60);
+    let psk = [0x42u8; 32];
+    let tkt = issuer.issue(T0, &psk);
+    assert_eq!(issuer.redeem(T0 + 59, &tkt), Ok(psk));
+}
+
+#[test]
+fn expired_ticket_is_rejected() {
+    let mut issuer = TicketIssuer::new(60);
+    let tkt = issuer.issue(T0, &[1u8; 32]);
+    assert_eq!(issuer.redeem(T0 + 60, &tkt), Err(TicketError::Expired));
+    assert_eq!(issuer.redeem(T0 + 3600, &tkt), Err(TicketError::Expired));
+}
+
+#[test]
+fn future_ticket_is_rejected() {
+    let mut issuer = TicketIssuer::new(60);
+    let tkt = issuer.issue(T0 + 10, &[4u8; 32]);
+    assert_eq!(issuer.redeem(T0, &tkt), Err(TicketError::NotYetValid));
+}
+
+#[test]
+fn lifetime_is_capped() {
+    let mut issuer = TicketIssuer::new(u64::MAX);
+    assert_eq!(issuer.lifetime_s(), ticket::MAX_LIFETIME_S);
+    let tkt = issuer.issue(T0, &[6u8; 32]);
+    assert_eq!(issuer.redeem(T0 + ticket::MAX_LIFETIME_S, &tkt), Err(TicketError::Expired));
+}
+
+#[test]
+fn replayed_ticket_is_rejected() {
+    let mut issuer = TicketIssuer::new(60);
+    let tkt = issuer.issue(T0, &[2u8; 32]);
+    assert!(issuer.redeem(T0 + 1, &tkt).is_ok());
+    assert_eq!(issuer.redeem(T0 + 2, &tkt), Err(TicketError::Replayed));
+
+    // A second ticket for the same PSK is still good once
+    let tkt2 = issuer.issue(T0 + 2, &[2u8; 32]);
+    assert!(issuer.redeem(T0 + 3, &tkt2).is_ok());
+}
+
+#[test]
+fn forged_or_foreign_ticket_is_rejected() {
+    let mut issuer = TicketIssuer::new(60);
+    let mut other = TicketIssuer::new(60);
+    let mut tkt = issuer.issue(T0, &[3u8; 32]);
+
+    assert_eq!(other.redeem(T0, &tkt), Err(TicketError::BadTag), "ticket key is per receiver");
+    assert_eq!(issuer.redeem(T0, &tkt[..20]), Err(TicketError::Malformed));
+    tkt[30] ^= 0x01;
+    assert_eq!(issuer.redeem(T0, &tkt), Err(TicketError::BadTag));
+}
+
+#[test]
+fn resumed_keys_match_and_depend_on_psk() {
+    let psk = ticket::resumption_secret(&[7u8; 32], &[9u8; 32]);
+    let (offer_s, secret_s) = keying::start_offer();
+    let (offer_r, secret_r) = keying::start_offer();
+    let pk_s = p256::PublicKey::from_sec1_bytes(&offer_s.pubkey_sec1).unwrap();
+    let pk_r = p256::PublicKey::from_sec1_bytes(&offer_r.pubkey_sec1).unwrap();
+    let shared_s = secret_s.diffie_hellman(&pk_r);
+    let shared_r = secret_r.diffie_hellman(&pk_s);
+
+    let salt: [u8; 32] = std::array::from_fn(|i| offer_s.salt[i] ^ offer_r.salt[i]);
+    let ikm_s = ticket::resume_ikm(&psk, shared_s.raw_secret_bytes());
+    let ikm_r = ticket::resume_ikm(&psk, shared_r.raw_secret_bytes());
+    let sender = session::derive_bidirectional(&ikm_s, &salt, b"SENDER", b"RECEIVER");
+    let receiver = session::derive_bidirectional(&ikm_r, &salt, b"RECEIVER", b"SENDER");
+    assert_eq!(sender.tx.enc_key, receiver.rx.enc_key);
+    assert_eq!(sender.rx.nonce_base, receiver.tx.nonce_base);
+
+    let wrong = ticket::resume_ikm(&[0u8; 32], shared_r.raw_secret_bytes());
+    let wrong = session::derive_bidirectional(&wrong, &salt, b"RECEIVER", b"SENDER");
+    assert_ne!(sender.tx.enc_key, wrong.rx.enc_key, "keys must depend on the PSK");
+}
+
+#[test]
+fn client_ticket_file_is_single_use_and_expires() {
+    let path = std::env::temp_dir().join(format!("ticket-test-{}", std::process::id()));
+    let saved = ClientTicket { ticket: vec![1, 2, 3], psk: [5u8; 32], expires_at: T0 + 60 };
+
+    saved.save(&path).unwrap();
+    let loaded = ClientTicket::take(&path, T0).expect("saved ticket");
+    assert_eq!(loaded.ticket, vec![1, 2, 3]);
+    assert_eq!(loaded.psk, [5u8; 32]);
+    assert!(ClientTicket::take(&path, T0).is_none(), "ticket file is consumed");
+
+    saved.save(&path).unwrap();
+    assert!(ClientTicket::take(&path, T0 + 60).is_none(), "expired ticket is not offered");
+}
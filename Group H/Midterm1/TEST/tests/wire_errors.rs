@@ -0,0 +1,98 @@
+use rpi_secure_stream::aead::{AeadCtx, NonceCtr};
+use rpi_secure_stream::negotiate::ClientHello;
+use rpi_secure_stream::wire::{self, ProtocolError, MAX_MSG_LEN};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+fn frame(payload: &[u8]) -> Vec<u8> {
+    [&(payload.len() as u32).to_be_bytes()[..], payload].concat()
+}
+
+#[tokio::test]
+async fn frame_roundtrip() {
+    let hello = ClientHello::new("ecdh");
+    let mut buf = Vec::new();
+    wire::write_frame(&mut buf, &hello).await.unwrap();
+
+    let mut r = buf.as_slice();
+    let got: ClientHello = wire::decode(&wire::read_frame(&mut r).await.unwrap()).unwrap();
+    assert_eq!(got, hello);
+    assert!(matches!(wire::read_frame(&mut r).await, Err(ProtocolError::Closed)));
+}
+
+#[tokio::test]
+async fn oversize_length_is_refused_before_allocating() {
+    let mut r: &[u8] = &[0xFF, 0xFF, 0xFF, 0xFF];
+    match wire::read_frame(&mut r).await {
+        Err(ProtocolError::Oversize { len, max }) => {
+            assert_eq!(len, u32::MAX as usize);
+            assert_eq!(max, MAX_MSG_LEN);
+        }
+        other => panic!("expected Oversize, got {other:?}"),
+    }
+
+    let mut sink = Vec::new();
+    let too_big = vec![0u8; MAX_MSG_LEN + 1];
+    assert!(matches!(wire::write_frame(&mut sink, &too_big).await, Err(ProtocolError::Oversize { .. })));
+    assert!(sink.is_empty(), "nothing is sent for an oversize message");
+}
+
+#[tokio::test]
+async fn truncated_frames() {
+    // Half a length prefix
+    let mut r: &[u8] = &[0, 0];
+    assert!(matches!(wire::read_frame(&mut r).await, Err(ProtocolError::Truncated)));
+
+    // Length says 10, body has 3
+    let mut bytes = frame(&[0u8; 10]);
+    bytes.truncate(4 + 3);
+    let mut r = bytes.as_slice();
+    assert!(matches!(wire::read_frame(&mut r).await, Err(ProtocolError::Truncated)));
+}
+
+#[derive(Serialize, Deserialize)]
+struct Frame { seq: u64, ct: Vec<u8> }
+
+#[tokio::test]
+async fn malformed_payload_keeps_stream_in_sync() {
+    let ctx = AeadCtx::new([0x11; 16]);
+    let n = NonceCtr::new([0x22; 8]);
+    let seal = |seq: u64, pt: &[u8]| Frame { seq, ct: ctx.seal(n.at(seq).unwrap(), seq, pt) };
+
+    // seq 1 is fine, then a garbage frame, then seq 2 lost in it and seq 3 arrives intact
+    let bytes = [
+        frame(&bincode::serialize(&seal(1, b"au-1")).unwrap()),
+        frame(&[0xde, 0xad]),
+        frame(&bincode::serialize(&seal(3, b"au-3")).unwrap()),
+    ].concat();
+    let mut r = bytes.as_slice();
+
+    let mut opened = Vec::new();
+    loop {
+        match wire::read_frame(&mut r).await.and_then(|b| wire::decode::<Frame>(&b)) {
+            Ok(f) => opened.push(ctx.open(n.at(f.seq).unwrap(), f.seq, &f.ct)),
+            Err(e @ ProtocolError::Malformed(_)) => assert!(e.is_recoverable()),
+            Err(ProtocolError::Closed) => break,
+            Err(e) => panic!("unexpected {e:?}"),
+        }
+    }
+    assert_eq!(opened, [b"au-1".to_vec(), b"au-3".to_vec()]);
+}
+
+#[tokio::test]
+async fn silent_peer_times_out() {
+    let (mut near, _far) = tokio::io::duplex(64);
+    let res = wire::timed("waiting for peer", Duration::from_millis(50), wire::read_frame(&mut near)).await;
+    assert!(matches!(res, Err(ProtocolError::Timeout("waiting for peer"))));
+}
+
+#[test]
+fn fatal_errors_are_not_recoverable() {
+    assert!(!ProtocolError::Truncated.is_recoverable());
+    assert!(!ProtocolError::Oversize { len: 1, max: 0 }.is_recoverable());
+    assert!(!ProtocolError::Timeout("x").is_recoverable());
+    assert!(ProtocolError::AuthFailed("frame".into()).is_recoverable());
+
+    let eof = std::io::Error::from(std::io::ErrorKind::UnexpectedEof);
+    assert!(matches!(ProtocolError::from(eof), ProtocolError::Truncated));
+}
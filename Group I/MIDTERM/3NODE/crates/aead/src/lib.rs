@@ -5,26 +5,28 @@ use aes_gcm::{
 use anyhow::{anyhow, Result};
 
 pub struct AesGcmCtx {
-    cipher: Aes128Gcm,
-    nonce_base: [u8; 8], // 64-bit random per direction
+    cipher: Aes128Gcm, // key schedule is zeroized on drop (aes-gcm "zeroize")
+    iv: [u8; 12],      // per-epoch, per-direction IV from the key schedule
 }
 
 impl AesGcmCtx {
-    pub fn new(key: [u8; 16], nonce_base: [u8; 8]) -> Self {
+    pub fn new(key: [u8; 16], iv: [u8; 12]) -> Self {
         let cipher = Aes128Gcm::new_from_slice(&key).expect("Aes128Gcm key");
-        Self { cipher, nonce_base }
+        Self { cipher, iv }
     }
 
+    /// TLS 1.3-style nonce: iv XOR (0^4 || seq_be(8)), so the full 64-bit sequence is used
     #[inline]
-    fn make_nonce_bytes(&self, seq: u32) -> [u8; 12] {
-        let mut n = [0u8; 12];
-        n[..8].copy_from_slice(&self.nonce_base);
-        n[8..].copy_from_slice(&seq.to_be_bytes());
+    fn make_nonce_bytes(&self, seq: u64) -> [u8; 12] {
+        let mut n = self.iv;
+        for (b, s) in n[4..].iter_mut().zip(seq.to_be_bytes()) {
+            *b ^= s;
+        }
         n
     }
 
-    /// Encrypt with AES-GCM; AAD typically = seq (big-endian)
-    pub fn encrypt(&self, seq: u32, plaintext: &[u8], aad: &[u8]) -> Result<Vec<u8>> {
+    /// Encrypt with AES-GCM; AAD typically = frame header
+    pub fn encrypt(&self, seq: u64, plaintext: &[u8], aad: &[u8]) -> Result<Vec<u8>> {
         let n = self.make_nonce_bytes(seq);
         let nonce = GenericArray::from_slice(&n); // &GenericArray<u8, U12>
         self.cipher
@@ -33,7 +35,7 @@ impl AesGcmCtx {
     }
 
     /// Decrypt with AES-GCM; AAD must match sender’s
-    pub fn decrypt(&self, seq: u32, ciphertext: &[u8], aad: &[u8]) -> Result<Vec<u8>> {
+    pub fn decrypt(&self, seq: u64, ciphertext: &[u8], aad: &[u8]) -> Result<Vec<u8>> {
         let n = self.make_nonce_bytes(seq);
         let nonce = GenericArray::from_slice(&n);
         self.cipher
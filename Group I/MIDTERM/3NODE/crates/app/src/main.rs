@@ -1,5 +1,9 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
 use std::time::Duration;
 use keying::{log_arm_crypto_support, demo_ecdh, demo_rsa};
+use tokio::sync::{mpsc, Mutex};
 // use transport::{run_receiver, run_sender};
 
 fn arg_val(args: &[String], key: &str) -> Option<String> {
@@ -52,6 +56,129 @@ fn parse_rekey(s: &str) -> Option<Duration> {
     Some(Duration::from_secs(secs))
 }
 
+fn parse_kex(args: &[String]) -> transport::KeyExchange {
+    // --kex ecdh (default) | hybrid (ECDH + ML-KEM-768)
+    match arg_val(args, "--kex") {
+        Some(s) => transport::KeyExchange::parse(&s).unwrap_or_else(|| {
+            eprintln!("unknown --kex {s} (ecdh|hybrid)");
+            std::process::exit(2);
+        }),
+        None => transport::KeyExchange::default(),
+    }
+}
+
+fn parse_protect(args: &[String]) -> transport::NalProtection {
+    // --protect full (default) | idr | slices | payload
+    match arg_val(args, "--protect") {
+        Some(s) => transport::NalProtection::parse(&s).unwrap_or_else(|| {
+            eprintln!("unknown --protect {s} (full|idr|slices|payload)");
+            std::process::exit(2);
+        }),
+        None => transport::NalProtection::default(),
+    }
+}
+
+// Receiver loss reports force an IDR in the capture pipeline (rate-limited in video).
+fn keyframe_hook(req: anyhow::Result<video::KeyframeRequester>) -> Option<transport::KeyframeHook> {
+    match req {
+        Ok(kf) => Some(Arc::new(move || { kf.request(); })),
+        Err(e) => { eprintln!("warning: keyframe requests disabled: {e}"); None }
+    }
+}
+
+// Per-peer frame channel; None while the sender task is (re)connecting.
+type SenderSlot = Arc<Mutex<Option<mpsc::Sender<Vec<u8>>>>>;
+// Mesh peers keyed by node ID (discovered) or host:port (--peer).
+type PeerSenders = Arc<Mutex<HashMap<String, (SenderSlot, tokio::task::JoinHandle<()>)>>>;
+// Node IDs of discovered peers by beacon source IP, for naming incoming streams.
+type PeerNames = Arc<Mutex<HashMap<IpAddr, String>>>;
+
+/// Node ID of the peer connecting from `peer_addr`, once its beacon has been
+/// heard (it may connect a beacon interval before we hear it); else the address.
+async fn peer_name(names: &PeerNames, peer_addr: &str) -> String {
+    let Ok(addr) = peer_addr.parse::<SocketAddr>() else { return peer_addr.to_string() };
+    let deadline = tokio::time::Instant::now() + 2 * discovery::BEACON_INTERVAL;
+    loop {
+        if let Some(id) = names.lock().await.get(&addr.ip()) {
+            return id.clone();
+        }
+        if names.lock().await.is_empty() || tokio::time::Instant::now() >= deadline {
+            return peer_addr.to_string();
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+}
+
+/// Spawn a reconnecting sender task towards `peer` and return its frame slot.
+fn spawn_peer_sender(peer: String, opts: transport::SenderOptions, metrics: Option<metrics::Metrics>) -> (SenderSlot, tokio::task::JoinHandle<()>) {
+    let sender: SenderSlot = Arc::new(Mutex::new(None));
+    let sender_clone = sender.clone();
+    let handle = tokio::spawn(async move {
+        loop {
+            let (tx, rx) = mpsc::channel::<Vec<u8>>(32);
+            // Update the sender for broadcaster
+            {
+                let mut s = sender_clone.lock().await;
+                *s = Some(tx);
+            }
+            match transport::run_sender_from_channel(&peer, opts.clone(), rx, metrics.clone()).await {
+                Ok(_) => break, // exit loop if connection and run succeed
+                Err(e) => {
+                    eprintln!("mesh sender to {peer} failed: {e}, retrying in 2s");
+                    tokio::time::sleep(Duration::from_secs(2)).await;
+                }
+            }
+        }
+    });
+    (sender, handle)
+}
+
+/// Advertise this node on the LAN and keep `senders` in step with the peers heard.
+/// Each sender only accepts a receiver that holds the key first advertised for its node ID.
+async fn run_mesh_discovery(args: &[String], bind: &str, identity: &discovery::Identity, senders: PeerSenders, names: PeerNames, opts: transport::SenderOptions, metrics: Option<metrics::Metrics>) -> anyhow::Result<()> {
+    let listen_port = bind.parse::<SocketAddr>()?.port();
+    let node_id = match arg_val(args, "--node-id") {
+        Some(id) => id,
+        None => hostname::get()?.to_string_lossy().into_owned(),
+    };
+    let disc_port = arg_val(args, "--discovery-port").and_then(|s| s.parse::<u16>().ok()).unwrap_or(discovery::DISCOVERY_PORT);
+
+    let beacon = discovery::Beacon { node_id: node_id.clone(), port: listen_port, fingerprint: identity.fingerprint() };
+    eprintln!("[disc] node={node_id} port={listen_port} key={}", discovery::fingerprint_hex(&beacon.fingerprint));
+
+    let sock = discovery::Discovery::bind(SocketAddr::from(([0, 0, 0, 0], disc_port))).await?;
+    let announce_to = vec![SocketAddr::from(([255, 255, 255, 255], disc_port))];
+    let (mut events, _task) = sock.spawn(beacon, announce_to, discovery::BEACON_INTERVAL, discovery::PEER_EXPIRY);
+
+    tokio::spawn(async move {
+        while let Some(ev) = events.recv().await {
+            match ev {
+                discovery::PeerEvent::Up(p) => {
+                    eprintln!("[disc] peer up: {} at {} key={}", p.node_id, p.addr, discovery::fingerprint_hex(&p.fingerprint));
+                    names.lock().await.insert(p.addr.ip(), p.node_id.clone());
+                    let opts = transport::SenderOptions { peer_fingerprint: Some(p.fingerprint), ..opts.clone() };
+                    let entry = spawn_peer_sender(p.addr.to_string(), opts, metrics.clone());
+                    if let Some((_, old)) = senders.lock().await.insert(p.node_id, entry) {
+                        old.abort();
+                    }
+                }
+                discovery::PeerEvent::Down(p) => {
+                    eprintln!("[disc] peer down: {} at {}", p.node_id, p.addr);
+                    let mut names = names.lock().await;
+                    if names.get(&p.addr.ip()) == Some(&p.node_id) {
+                        names.remove(&p.addr.ip());
+                    }
+                    drop(names);
+                    if let Some((_, task)) = senders.lock().await.remove(&p.node_id) {
+                        task.abort();
+                    }
+                }
+            }
+        }
+    });
+    Ok(())
+}
+
 #[tokio::main]
 async fn main() {
     let args: Vec<String> = std::env::args().collect();
@@ -84,17 +211,14 @@ async fn main() {
             }
         });
 
-        // peers: multiple --peer entries
+        // peers: multiple --peer entries; with none given (or --discover) peers are found on the LAN
         let peers = arg_vals(&args, "--peer");
-        if peers.is_empty() {
-            eprintln!("mesh mode requires at least one --peer=host:port");
-            std::process::exit(1);
-        }
+        let discover = peers.is_empty() || has_flag(&args, "--discover");
 
         let bind = arg_val(&args, "--bind").unwrap_or_else(|| "0.0.0.0:5000".to_string());
         let payload = arg_val(&args, "--payload").unwrap_or_else(|| "video".to_string());
 
-        eprintln!("[app] mode=mesh payload={payload} peers={:?}", peers);
+        eprintln!("[app] mode=mesh payload={payload} peers={:?} discover={discover}", peers);
 
         if payload == "video" {
             let fps = arg_val(&args, "--fps").and_then(|s| s.parse::<i32>().ok()).unwrap_or(30);
@@ -106,7 +230,7 @@ async fn main() {
 
             // start capture source
             let dev = arg_val(&args, "--device").unwrap_or_else(|| "libcamera".to_string());
-            let (mut src_rx, _cap_pipe) = if dev.eq_ignore_ascii_case("libcamera") {
+            let (mut src_rx, cap_pipe) = if dev.eq_ignore_ascii_case("libcamera") {
                 eprintln!("[app] using libcamerasrc (Pi CSI camera)");
                 match video::start_h264_capture_libcamera(1280, 720, fps) {
                     Ok(v) => v,
@@ -121,39 +245,42 @@ async fn main() {
             };
 
             // For each peer create a dedicated sender channel and task
-            use tokio::sync::Mutex;
-            use std::sync::Arc;
-            let mut sender_txs = Vec::new();
+            let senders: PeerSenders = Arc::new(Mutex::new(HashMap::new()));
             // Force rekey every 5 seconds
             let rekey = Some(Duration::from_secs(5));
+            // Every Nth rekey mixes in a fresh ECDH (post-compromise recovery)
+            let dh_every = arg_val(&args, "--rekey-dh").and_then(|s| s.parse::<u32>().ok());
+            let kex = parse_kex(&args);
+            // One encoder feeds every peer, so any of them may ask for a keyframe
+            let keyframe = keyframe_hook(video::KeyframeRequester::new(&cap_pipe, video::KEYFRAME_MIN_INTERVAL));
+            let protect = parse_protect(&args);
+            let opts = transport::SenderOptions { rekey_every: rekey, dh_every, kex, protect, keyframe, ..Default::default() };
             for peer in &peers {
-                let sender = Arc::new(Mutex::new(None));
-                sender_txs.push(sender.clone());
-                let peer_clone = peer.clone();
-                let metrics_clone = metrics.clone();
-                let sender_clone = sender.clone();
-                tokio::spawn(async move {
-                    loop {
-                        let (tx, rx) = tokio::sync::mpsc::channel::<Vec<u8>>(32);
-                        // Update the sender for broadcaster
-                        {
-                            let mut s = sender_clone.lock().await;
-                            *s = Some(tx);
-                        }
-                        match transport::run_sender_from_channel(&peer_clone, rekey, rx, metrics_clone.clone()).await {
-                            Ok(_) => break, // exit loop if connection and run succeed
-                            Err(e) => {
-                                eprintln!("mesh sender to {peer_clone} failed: {e}, retrying in 2s");
-                                tokio::time::sleep(Duration::from_secs(2)).await;
-                            }
-                        }
-                    }
-                });
+                let entry = spawn_peer_sender(peer.clone(), opts.clone(), metrics.clone());
+                senders.lock().await.insert(peer.clone(), entry);
             }
+            let names: PeerNames = Arc::new(Mutex::new(HashMap::new()));
+            // Static node key: advertised in beacons and proven in every handshake we answer
+            let identity = if discover {
+                let identity_path = arg_val(&args, "--identity").unwrap_or_else(|| "node.key".to_string());
+                let identity = match discovery::Identity::load_or_generate(std::path::Path::new(&identity_path)) {
+                    Ok(id) => Arc::new(id),
+                    Err(e) => { eprintln!("mesh error: identity: {e}"); std::process::exit(1); }
+                };
+                if let Err(e) = run_mesh_discovery(&args, &bind, &identity, senders.clone(), names.clone(), opts, metrics.clone()).await {
+                    eprintln!("mesh error: discovery failed: {e}");
+                    std::process::exit(1);
+                }
+                Some(identity)
+            } else {
+                None
+            };
 
-            // Broadcast captured frames to all sender channels
+            // Broadcast captured frames to all sender channels (current peer set per frame)
+            let senders_bcast = senders.clone();
             tokio::spawn(async move {
                 while let Some(frame) = src_rx.recv().await {
+                    let sender_txs: Vec<SenderSlot> = senders_bcast.lock().await.values().map(|(s, _)| s.clone()).collect();
                     for sender in &sender_txs {
                         let tx_opt = sender.lock().await;
                         if let Some(tx) = tx_opt.as_ref() {
@@ -164,28 +291,54 @@ async fn main() {
                 eprintln!("mesh capture ended");
             });
 
-            // Start receiver to accept many incoming connections and spawn a playback pipeline for each
-            use tokio::sync::mpsc;
+            // Optional single tiled output instead of one window per peer
+            let mosaic = if has_flag(&args, "--mosaic") {
+                let sink = match arg_val(&args, "--mosaic-sink").as_deref() {
+                    None | Some("display") => video::mosaic::MosaicSink::Display,
+                    Some("fake") => video::mosaic::MosaicSink::Fake,
+                    Some(other) => match other.strip_prefix("file:") {
+                        Some(path) => video::mosaic::MosaicSink::File(path.to_string()),
+                        None => { eprintln!("mesh error: --mosaic-sink must be display, fake or file:PATH"); std::process::exit(1); }
+                    },
+                };
+                let opts = video::mosaic::MosaicOptions { fps, sink, ..Default::default() };
+                match video::mosaic::Mosaic::new(opts) {
+                    Ok(m) => Some(m),
+                    Err(e) => { eprintln!("mesh error: mosaic init failed: {e}"); std::process::exit(1); }
+                }
+            } else {
+                None
+            };
+
+            // Start receiver to accept many incoming connections and spawn a playback pipeline (or mosaic tile) for each
             let handler = move |rx: mpsc::Receiver<Vec<u8>>, peer_addr: String| {
                 let fps = arg_val(&args, "--fps").and_then(|s| s.parse::<i32>().ok()).unwrap_or(30);
+                let mosaic = mosaic.clone();
+                let names = names.clone();
                 tokio::spawn(async move {
-                    match video::start_h264_playback(fps) {
+                    // tiles are labelled with the sender's node ID when discovery knows it
+                    let label = peer_name(&names, &peer_addr).await;
+                    let playback = match &mosaic {
+                        Some(m) => m.add_source(&label).map(|tx| (tx, None)),
+                        None => video::start_h264_playback(fps).map(|(tx, pipe)| (tx, Some(pipe))),
+                    };
+                    match playback {
                         Ok((tx, _pipe)) => {
                             let mut rx = rx;
                             // Forward frames from rx to playback tx
                             while let Some(frame) = rx.recv().await {
                                 let _ = tx.send(frame).await;
                             }
-                            eprintln!("[app] playback for {peer_addr} ended");
+                            eprintln!("[app] playback for {label} ended");
                         }
                         Err(e) => {
-                            eprintln!("mesh error: playback init failed for {peer_addr}: {e}");
+                            eprintln!("mesh error: playback init failed for {label}: {e}");
                         }
                     }
                 })
             };
 
-            if let Err(e) = transport::run_multi_receiver_to_channel(&bind, metrics.clone(), handler).await {
+            if let Err(e) = transport::run_multi_receiver_to_channel(&bind, identity, metrics.clone(), handler).await {
                 eprintln!("mesh multi-receiver error: {e}");
                 std::process::exit(1);
             }
@@ -212,12 +365,19 @@ async fn main() {
         });
     let bind    = arg_val(&args, "--bind").unwrap_or_else(|| "127.0.0.1:5000".to_string());
     let payload = arg_val(&args, "--payload").unwrap_or_else(|| "bytes".to_string());
-    eprintln!("[app] mode=receiver payload={payload}");
+    // --rtp: SRTP over UDP on the same port instead of framed TCP (video only)
+    let rtp = has_flag(&args, "--rtp");
+    eprintln!("[app] mode=receiver payload={payload} rtp={rtp}");
 
         if payload == "video" {
         let fps = arg_val(&args, "--fps").and_then(|s| s.parse::<i32>().ok()).unwrap_or(30);
         let (tx, _pipe) = video::start_h264_playback(fps).expect("gst playback");
-            if let Err(e) = transport::run_receiver_to_channel(&bind, tx, metrics.clone()).await {
+            let res = if rtp {
+                transport::run_srtp_receiver_to_channel(&bind, tx, metrics.clone()).await
+            } else {
+                transport::run_receiver_to_channel(&bind, tx, metrics.clone()).await
+            };
+            if let Err(e) = res {
             eprintln!("receiver error: {e}");
             std::process::exit(1);
         }
@@ -246,9 +406,15 @@ async fn main() {
     let host   = arg_val(&args, "--host").unwrap_or_else(|| "127.0.0.1:5000".to_string());
     let _n     = arg_val(&args, "--frames").and_then(|s| s.parse::<u32>().ok()).unwrap_or(300);
     let rekey  = arg_val(&args, "--rekey").and_then(|s| parse_rekey(&s));
+    let dh_every = arg_val(&args, "--rekey-dh").and_then(|s| s.parse::<u32>().ok());
+    let kex    = parse_kex(&args);
+    let protect = parse_protect(&args);
     let payload= arg_val(&args, "--payload").unwrap_or_else(|| "bytes".to_string());
+    let rtp    = has_flag(&args, "--rtp");
+    let srtp_key_log = arg_val(&args, "--srtp-key-log").map(std::path::PathBuf::from);
+    let rtp_dest = arg_val(&args, "--rtp-dest");
 
-    eprintln!("[app] mode=sender payload={payload} rekey={rekey:?}");
+    eprintln!("[app] mode=sender payload={payload} rekey={rekey:?} rekey_dh={dh_every:?} kex={} protect={} rtp={rtp}", kex.label(), protect.label());
 
         if payload == "video" {
         let dev = arg_val(&args, "--device").unwrap_or_else(|| "/dev/video0".to_string());
@@ -256,7 +422,7 @@ async fn main() {
         let h   = arg_val(&args, "--height").and_then(|s| s.parse::<i32>().ok()).unwrap_or(720);
         let fps = arg_val(&args, "--fps").and_then(|s| s.parse::<i32>().ok()).unwrap_or(30);
 
-        let (rx, _pipe) = if dev.eq_ignore_ascii_case("libcamera") {
+        let (rx, pipe) = if dev.eq_ignore_ascii_case("libcamera") {
             eprintln!("[app] using libcamerasrc (Pi CSI camera)");
             match video::start_h264_capture_libcamera(w, h, fps) {
                 Ok(v) => v,
@@ -271,7 +437,14 @@ async fn main() {
             }
         };
 
-        if let Err(e) = transport::run_sender_from_channel(&host, rekey, rx, metrics.clone()).await {
+        let keyframe = keyframe_hook(video::KeyframeRequester::new(&pipe, video::KEYFRAME_MIN_INTERVAL));
+        let opts = transport::SenderOptions { rekey_every: rekey, dh_every, kex, protect, keyframe, srtp_key_log, rtp_dest, ..Default::default() };
+        let res = if rtp {
+            transport::run_srtp_sender(&host, opts, rx, metrics.clone()).await
+        } else {
+            transport::run_sender_from_channel(&host, opts, rx, metrics.clone()).await
+        };
+        if let Err(e) = res {
             eprintln!("sender error: {e}");
             std::process::exit(1);
         }
@@ -286,6 +459,8 @@ async fn main() {
     eprintln!("Usage:");
     eprintln!("  rpi-secure-stream --print-config");
     eprintln!("  rpi-secure-stream --demo-ecdh | --demo-rsa");
-    eprintln!("  rpi-secure-stream --mode=receiver --bind 127.0.0.1:5000");
-    eprintln!("  rpi-secure-stream --mode=sender  --host 127.0.0.1:5000 [--frames 300] [--rekey 10s|5m|1h]");
+    eprintln!("  rpi-secure-stream --mode=receiver --bind 127.0.0.1:5000 [--payload video [--rtp]]");
+    eprintln!("  rpi-secure-stream --mode=sender  --host 127.0.0.1:5000 [--frames 300] [--rekey 10s|5m|1h] [--rekey-dh N] [--kex ecdh|hybrid] [--protect full|idr|slices|payload]");
+    eprintln!("  rpi-secure-stream --mode=sender  --host 127.0.0.1:5000 --payload video --rtp [--kex ecdh|hybrid] [--srtp-key-log keys.txt] [--rtp-dest host:port]");
+    eprintln!("  rpi-secure-stream --mode=mesh --bind 0.0.0.0:5000 [--peer host:port ...] [--discover] [--node-id ID] [--identity node.key] [--discovery-port 5454] [--rekey-dh N] [--kex ecdh|hybrid] [--protect full|idr|slices|payload]");
 }
\ No newline at end of file
@@ -0,0 +1,161 @@
+//! Crypto primitives under hardware counters (bench --counters).
+//!
+//! The operations of Group I's SHA256_RUST and AES256GCM_RUST programs, plus
+//! AES-128-GCM as the session cipher uses it, over a range of buffer sizes.
+//! Each sample is a batch of calls inside one counter read, minus the cost of
+//! an empty start/stop. Cycles per byte tell whether the crypto instructions
+//! (ARMv8 AES/PMULL/SHA2, x86 AES-NI/PCLMULQDQ/SHA) are in use: hardware
+//! AES-GCM and SHA-256 take a few cycles per byte, the portable code tens.
+
+use crate::perf::{Counters, Sample};
+use aes_gcm::aead::AeadInPlace;
+use aes_gcm::{Aes128Gcm, Aes256Gcm, KeyInit};
+use anyhow::Result;
+use sha2::{Digest, Sha256};
+use std::hint::black_box;
+use std::time::Instant;
+
+/// Bytes per sample; small buffers are batched up to this
+const BATCH_BYTES: usize = 64 * 1024;
+
+pub const OPS: [&str; 3] = ["sha256", "aes256gcm", "aes128gcm"];
+
+pub struct Row {
+    pub op: &'static str,
+    pub size: usize,
+    pub calls: u64,
+    pub ns_per_op: f64,
+    pub mb_per_s: f64,
+    pub cycles_per_byte: Option<f64>,
+    pub ipc: Option<f64>,
+    pub cache_misses_per_op: Option<f64>,
+    pub branch_misses_per_op: Option<f64>,
+}
+
+/// Crypto features the CPU reports, e.g. "aes=yes pmull=yes sha2=yes".
+pub fn cpu_features() -> String {
+    #[cfg(target_arch = "aarch64")]
+    let f = [
+        ("aes", std::arch::is_aarch64_feature_detected!("aes")),
+        ("pmull", std::arch::is_aarch64_feature_detected!("pmull")),
+        ("sha2", std::arch::is_aarch64_feature_detected!("sha2")),
+    ];
+    #[cfg(target_arch = "x86_64")]
+    let f = [
+        ("aes", std::arch::is_x86_feature_detected!("aes")),
+        ("pclmulqdq", std::arch::is_x86_feature_detected!("pclmulqdq")),
+        ("sha", std::arch::is_x86_feature_detected!("sha")),
+    ];
+    #[cfg(not(any(target_arch = "aarch64", target_arch = "x86_64")))]
+    let f: [(&str, bool); 0] = [];
+    f.iter().map(|(n, on)| format!("{n}={}", if *on { "yes" } else { "no" })).collect::<Vec<_>>().join(" ")
+}
+
+/// One call on a buffer; state (cipher, nonce) is kept across calls.
+type Op = Box<dyn FnMut(&mut [u8])>;
+
+fn operation(op: &str) -> Op {
+    match op {
+        "sha256" => Box::new(|buf: &mut [u8]| {
+            black_box(Sha256::digest(&*buf));
+        }),
+        "aes256gcm" => {
+            let cipher = Aes256Gcm::new(&[0x11; 32].into());
+            let mut nonce = 0u128;
+            Box::new(move |buf: &mut [u8]| {
+                nonce += 1;
+                let n = nonce.to_be_bytes();
+                black_box(cipher.encrypt_in_place_detached(n[4..].into(), b"", buf).expect("encrypt"));
+            })
+        }
+        _ => {
+            let cipher = Aes128Gcm::new(&[0x22; 16].into());
+            let mut nonce = 0u128;
+            Box::new(move |buf: &mut [u8]| {
+                nonce += 1;
+                let n = nonce.to_be_bytes();
+                black_box(cipher.encrypt_in_place_detached(n[4..].into(), b"", buf).expect("encrypt"));
+            })
+        }
+    }
+}
+
+/// Run `op` on `size`-byte buffers for `reps` samples, under `counters` if open.
+pub fn measure(op: &'static str, size: usize, reps: usize, counters: Option<&Counters>) -> Result<Row> {
+    let mut f = operation(op);
+    let mut buf = vec![0x5a; size];
+    let batch = (BATCH_BYTES / size.max(1)).max(1);
+    for _ in 0..batch {
+        f(&mut buf);
+    }
+
+    // cost of start/stop alone, taken as the smallest of a few empty reads
+    let overhead = match counters {
+        Some(c) => {
+            let mut best: Option<Sample> = None;
+            for _ in 0..32 {
+                let (_, s) = c.measure(|| ())?;
+                if best.is_none_or(|b| s.cycles < b.cycles) {
+                    best = Some(s);
+                }
+            }
+            best.unwrap_or_default()
+        }
+        None => Sample::default(),
+    };
+
+    let mut total = Sample::default();
+    let start = Instant::now();
+    for _ in 0..reps {
+        let mut body = || {
+            for _ in 0..batch {
+                f(&mut buf);
+            }
+        };
+        match counters {
+            Some(c) => {
+                let (_, s) = c.measure(body)?;
+                let s = s.minus(&overhead);
+                let add = |t: &mut Option<u64>, v: Option<u64>| *t = v.map(|v| t.unwrap_or(0) + v);
+                add(&mut total.cycles, s.cycles);
+                add(&mut total.instructions, s.instructions);
+                add(&mut total.cache_misses, s.cache_misses);
+                add(&mut total.branch_misses, s.branch_misses);
+            }
+            None => body(),
+        }
+    }
+    let secs = start.elapsed().as_secs_f64();
+    black_box(&buf);
+
+    let calls = (reps * batch) as u64;
+    let bytes = calls as f64 * size as f64;
+    let per_op = |v: Option<u64>| v.map(|v| v as f64 / calls as f64);
+    Ok(Row {
+        op,
+        size,
+        calls,
+        ns_per_op: secs * 1e9 / calls as f64,
+        mb_per_s: bytes / secs / 1e6,
+        cycles_per_byte: total.cycles.map(|c| c as f64 / bytes),
+        ipc: total.instructions.zip(total.cycles).filter(|&(_, c)| c > 0).map(|(i, c)| i as f64 / c as f64),
+        cache_misses_per_op: per_op(total.cache_misses),
+        branch_misses_per_op: per_op(total.branch_misses),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn measure_without_counters_reports_wall_clock_only() {
+        for op in OPS {
+            let row = measure(op, 64, 2, None).unwrap();
+            assert_eq!((row.op, row.size, row.calls), (op, 64, 2 * (BATCH_BYTES / 64) as u64));
+            assert!(row.ns_per_op > 0.0 && row.mb_per_s > 0.0);
+            assert!(row.cycles_per_byte.is_none() && row.ipc.is_none());
+            assert!(row.cache_misses_per_op.is_none() && row.branch_misses_per_op.is_none());
+        }
+    }
+}
@@ -0,0 +1,239 @@
+//! Hardware counters through perf_event_open(2): this thread, user space only.
+//!
+//! Cycles (group leader), instructions, cache misses and branch misses are read
+//! as one group, so all four cover exactly the same interval. Containers, CI
+//! and most VMs refuse perf_event_open (seccomp, perf_event_paranoid > 2, no
+//! virtual PMU); `Counters::open` then fails with the reason and callers keep
+//! to wall-clock numbers. A counter the PMU lacks is left out on its own.
+
+use anyhow::{bail, Result};
+
+const PERF_TYPE_HARDWARE: u32 = 0;
+const PERF_COUNT_HW_CPU_CYCLES: u64 = 0;
+const PERF_COUNT_HW_INSTRUCTIONS: u64 = 1;
+const PERF_COUNT_HW_CACHE_MISSES: u64 = 3;
+const PERF_COUNT_HW_BRANCH_MISSES: u64 = 5;
+
+const PERF_FORMAT_TOTAL_TIME_ENABLED: u64 = 1 << 0;
+const PERF_FORMAT_TOTAL_TIME_RUNNING: u64 = 1 << 1;
+const PERF_FORMAT_GROUP: u64 = 1 << 3;
+
+/// attr.flags bits
+const ATTR_DISABLED: u64 = 1 << 0;
+const ATTR_EXCLUDE_KERNEL: u64 = 1 << 5;
+const ATTR_EXCLUDE_HV: u64 = 1 << 6;
+
+const PERF_FLAG_FD_CLOEXEC: libc::c_ulong = 1 << 3;
+const PERF_EVENT_IOC_ENABLE: libc::c_ulong = 0x2400;
+const PERF_EVENT_IOC_DISABLE: libc::c_ulong = 0x2401;
+const PERF_EVENT_IOC_RESET: libc::c_ulong = 0x2403;
+const PERF_IOC_FLAG_GROUP: libc::c_ulong = 1;
+
+/// `struct perf_event_attr` up to config1 (PERF_ATTR_SIZE_VER0); the kernel
+/// zero-extends older layouts.
+#[repr(C)]
+#[derive(Default)]
+struct PerfEventAttr {
+    type_: u32,
+    size: u32,
+    config: u64,
+    sample_period: u64,
+    sample_type: u64,
+    read_format: u64,
+    flags: u64,
+    wakeup_events: u32,
+    bp_type: u32,
+    config1: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Event {
+    Cycles,
+    Instructions,
+    CacheMisses,
+    BranchMisses,
+}
+
+impl Event {
+    const ALL: [Event; 4] = [Event::Cycles, Event::Instructions, Event::CacheMisses, Event::BranchMisses];
+
+    fn config(self) -> u64 {
+        match self {
+            Event::Cycles => PERF_COUNT_HW_CPU_CYCLES,
+            Event::Instructions => PERF_COUNT_HW_INSTRUCTIONS,
+            Event::CacheMisses => PERF_COUNT_HW_CACHE_MISSES,
+            Event::BranchMisses => PERF_COUNT_HW_BRANCH_MISSES,
+        }
+    }
+}
+
+/// Counts over one interval; None for a counter this machine does not have.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Sample {
+    pub cycles: Option<u64>,
+    pub instructions: Option<u64>,
+    pub cache_misses: Option<u64>,
+    pub branch_misses: Option<u64>,
+}
+
+impl Sample {
+    fn set(&mut self, ev: Event, v: u64) {
+        let slot = match ev {
+            Event::Cycles => &mut self.cycles,
+            Event::Instructions => &mut self.instructions,
+            Event::CacheMisses => &mut self.cache_misses,
+            Event::BranchMisses => &mut self.branch_misses,
+        };
+        *slot = Some(v);
+    }
+
+    /// `self - base`, saturating, e.g. to remove the start/stop overhead.
+    pub fn minus(&self, base: &Sample) -> Sample {
+        let sub = |a: Option<u64>, b: Option<u64>| a.map(|a| a.saturating_sub(b.unwrap_or(0)));
+        Sample {
+            cycles: sub(self.cycles, base.cycles),
+            instructions: sub(self.instructions, base.instructions),
+            cache_misses: sub(self.cache_misses, base.cache_misses),
+            branch_misses: sub(self.branch_misses, base.branch_misses),
+        }
+    }
+}
+
+/// An open counter group for the calling thread.
+pub struct Counters {
+    /// Group members in read order; the first is the leader
+    fds: Vec<(Event, libc::c_int)>,
+}
+
+impl Counters {
+    pub fn open() -> Result<Self> {
+        let mut fds: Vec<(Event, libc::c_int)> = Vec::new();
+        for ev in Event::ALL {
+            let leader = fds.first().map_or(-1, |&(_, fd)| fd);
+            match open_event(ev, leader) {
+                Ok(fd) => fds.push((ev, fd)),
+                Err(e) if fds.is_empty() => {
+                    let paranoid = std::fs::read_to_string("/proc/sys/kernel/perf_event_paranoid").unwrap_or_default();
+                    bail!("perf_event_open: {e} (perf_event_paranoid={})", paranoid.trim());
+                }
+                Err(_) => {}
+            }
+        }
+        Ok(Self { fds })
+    }
+
+    /// Which counters are being read.
+    pub fn events(&self) -> Vec<Event> {
+        self.fds.iter().map(|&(ev, _)| ev).collect()
+    }
+
+    /// Reset and start the whole group.
+    pub fn start(&self) {
+        let leader = self.fds[0].1;
+        // SAFETY: leader is an open perf event fd owned by self
+        unsafe {
+            libc::ioctl(leader, PERF_EVENT_IOC_RESET as _, PERF_IOC_FLAG_GROUP);
+            libc::ioctl(leader, PERF_EVENT_IOC_ENABLE as _, PERF_IOC_FLAG_GROUP);
+        }
+    }
+
+    /// Stop the group and read it. Counts are scaled up if the kernel had to
+    /// multiplex the PMU; an interval it never scheduled reads as empty.
+    pub fn stop(&self) -> Result<Sample> {
+        let leader = self.fds[0].1;
+        // SAFETY: as in start
+        unsafe { libc::ioctl(leader, PERF_EVENT_IOC_DISABLE as _, PERF_IOC_FLAG_GROUP) };
+
+        // nr, time_enabled, time_running, values[nr]
+        let mut buf = [0u64; 3 + Event::ALL.len()];
+        // SAFETY: buf is large enough for the group read format of up to four events
+        let n = unsafe { libc::read(leader, buf.as_mut_ptr().cast(), std::mem::size_of_val(&buf)) };
+        if n < 0 {
+            bail!("read perf counters: {}", std::io::Error::last_os_error());
+        }
+        let (nr, enabled, running) = (buf[0] as usize, buf[1], buf[2]);
+        let mut s = Sample::default();
+        for (i, &(ev, _)) in self.fds.iter().enumerate().take(nr) {
+            if let Some(v) = scale(buf[3 + i], enabled, running) {
+                s.set(ev, v);
+            }
+        }
+        Ok(s)
+    }
+
+    /// Counters around `f`.
+    pub fn measure<T>(&self, f: impl FnOnce() -> T) -> Result<(T, Sample)> {
+        self.start();
+        let out = f();
+        let s = self.stop()?;
+        Ok((out, s))
+    }
+}
+
+impl Drop for Counters {
+    fn drop(&mut self) {
+        for &(_, fd) in &self.fds {
+            // SAFETY: fd was returned by perf_event_open and is closed only here
+            unsafe { libc::close(fd) };
+        }
+    }
+}
+
+/// A count extrapolated to the whole enabled time when the group was only on
+/// the PMU for `running` of it; None if it never ran.
+fn scale(count: u64, enabled: u64, running: u64) -> Option<u64> {
+    match running {
+        0 => None,
+        r if r < enabled => Some((count as u128 * enabled as u128 / r as u128).min(u64::MAX as u128) as u64),
+        _ => Some(count),
+    }
+}
+
+fn open_event(ev: Event, group_fd: libc::c_int) -> std::io::Result<libc::c_int> {
+    let mut attr = PerfEventAttr {
+        type_: PERF_TYPE_HARDWARE,
+        size: std::mem::size_of::<PerfEventAttr>() as u32,
+        config: ev.config(),
+        read_format: PERF_FORMAT_GROUP | PERF_FORMAT_TOTAL_TIME_ENABLED | PERF_FORMAT_TOTAL_TIME_RUNNING,
+        flags: ATTR_EXCLUDE_KERNEL | ATTR_EXCLUDE_HV,
+        ..Default::default()
+    };
+    if group_fd == -1 {
+        // members follow the leader's enable state
+        attr.flags |= ATTR_DISABLED;
+    }
+    // SAFETY: attr is a valid perf_event_attr of the size it declares; pid 0 /
+    // cpu -1 = this thread on any CPU
+    let fd = unsafe {
+        libc::syscall(libc::SYS_perf_event_open, &attr as *const PerfEventAttr, 0, -1, group_fd, PERF_FLAG_FD_CLOEXEC)
+    };
+    if fd < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(fd as libc::c_int)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn minus_saturates_and_keeps_missing_counters_missing() {
+        let a = Sample { cycles: Some(100), instructions: Some(5), cache_misses: None, branch_misses: Some(7) };
+        let base = Sample { cycles: Some(30), instructions: Some(9), cache_misses: Some(4), branch_misses: None };
+        let want = Sample { cycles: Some(70), instructions: Some(0), cache_misses: None, branch_misses: Some(7) };
+        assert_eq!(a.minus(&base), want);
+        assert_eq!(Sample::default().minus(&a), Sample::default());
+    }
+
+    #[test]
+    fn multiplexed_counts_are_scaled_to_the_enabled_time() {
+        assert_eq!(scale(1000, 10, 10), Some(1000));
+        assert_eq!(scale(1000, 10, 5), Some(2000));
+        assert_eq!(scale(3, 10, 4), Some(7));
+        // enabled and running from one read can disagree by a tick
+        assert_eq!(scale(1000, 9, 10), Some(1000));
+        assert_eq!(scale(1000, 10, 0), None);
+        assert_eq!(scale(u64::MAX, 3, 1), Some(u64::MAX));
+    }
+}
@@ -0,0 +1,152 @@
+//! Synthetic H.264 stream for runs without a recorded clip: baseline SPS/PPS,
+//! one IDR per GOP and P frames in between, with valid slice headers and
+//! random (start-code free) slice data sized to the requested bitrate.
+
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    bits: u32,
+}
+
+impl BitWriter {
+    fn u(&mut self, n: u32, v: u32) -> &mut Self {
+        for i in (0..n).rev() {
+            if self.bits.is_multiple_of(8) {
+                self.bytes.push(0);
+            }
+            *self.bytes.last_mut().unwrap() |= (((v >> i) & 1) as u8) << (7 - self.bits % 8);
+            self.bits += 1;
+        }
+        self
+    }
+
+    fn ue(&mut self, v: u32) -> &mut Self {
+        let n = 32 - (v + 1).leading_zeros();
+        self.u(n - 1, 0).u(n, v + 1)
+    }
+
+    /// Pad the header to a byte boundary with one bits, then append raw slice data.
+    fn data(&mut self, filler: &mut Filler, n: usize) -> &mut Self {
+        while !self.bits.is_multiple_of(8) {
+            self.u(1, 1);
+        }
+        filler.fill(&mut self.bytes, n);
+        self.bits += 8 * n as u32;
+        self
+    }
+
+    /// Byte-align with rbsp_trailing_bits and append as a NAL unit to `au`.
+    fn finish(&mut self, header: u8, au: &mut Vec<u8>) {
+        self.u(1, 1);
+        au.extend_from_slice(&[0, 0, 0, 1, header]);
+        au.extend_from_slice(&self.bytes);
+    }
+}
+
+/// xorshift64*: reproducible filler without pulling in rand
+struct Filler(u64);
+
+impl Filler {
+    fn fill(&mut self, out: &mut Vec<u8>, n: usize) {
+        for _ in 0..n {
+            self.0 ^= self.0 >> 12;
+            self.0 ^= self.0 << 25;
+            self.0 ^= self.0 >> 27;
+            let b = (self.0.wrapping_mul(0x2545_f491_4f6c_dd1d) >> 56) as u8;
+            out.push(b.max(1)); // no zero bytes, so no emulated start codes
+        }
+    }
+}
+
+pub struct Params {
+    pub width: u32,
+    pub height: u32,
+    pub fps: u32,
+    pub kbps: u32,
+    pub gop: u32,
+}
+
+/// `frames` access units; IDRs are ~6x the size of a P frame.
+pub fn stream(p: &Params, frames: usize) -> Vec<Vec<u8>> {
+    let avg = (p.kbps as usize * 1000 / 8) / p.fps.max(1) as usize;
+    let gop = p.gop.max(1) as usize;
+    let p_bytes = avg * gop / (gop + 5);
+    let idr_bytes = p_bytes * 6;
+    let mut filler = Filler(0x9e37_79b9_7f4a_7c15);
+    let mut out = Vec::with_capacity(frames);
+    let mut idr_id = 0;
+    for i in 0..frames {
+        let pos = (i % gop) as u32;
+        let mut au = Vec::with_capacity(idr_bytes + 64);
+        let mut w = BitWriter::default();
+        if pos == 0 {
+            // SPS: baseline, log2_max_frame_num 16, POC type 2, frame MBs only
+            BitWriter::default()
+                .u(8, 66).u(16, 31).ue(0).ue(12).ue(2).ue(1).u(1, 0)
+                .ue(p.width.div_ceil(16) - 1).ue(p.height.div_ceil(16) - 1)
+                .u(4, 0b1100)
+                .finish(0x67, &mut au);
+            // PPS: CAVLC, no weighting, deblocking control present
+            BitWriter::default()
+                .ue(0).ue(0).u(2, 0).ue(0).ue(0).ue(0).u(3, 0).ue(0).ue(0).ue(0).u(3, 0b100)
+                .finish(0x68, &mut au);
+            // I slice: first_mb, type 7, pps, frame_num, idr_pic_id, marking, qp, deblock
+            w.ue(0).ue(7).ue(0).u(16, 0).ue(idr_id % 65536).u(2, 0).ue(0).ue(0).ue(0).ue(0);
+            idr_id += 1;
+            w.data(&mut filler, idr_bytes).finish(0x65, &mut au);
+        } else {
+            // P slice: no override / reordering / MMCO, deblocking off
+            w.ue(0).ue(5).ue(0).u(16, pos).u(3, 0).ue(0).ue(1).data(&mut filler, p_bytes).finish(0x41, &mut au);
+        }
+        out.push(au);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use transport::nal::nal_units;
+
+    /// first_mb_in_slice and slice_type, the two ue(v) fields that open a slice header
+    fn slice_start(nal: &[u8]) -> (u32, u32) {
+        let bit = |i: usize| ((nal[i / 8] >> (7 - i % 8)) & 1) as u32;
+        let mut pos = 8; // past the NAL header byte
+        let mut ue = || {
+            let zeros = (pos..).take_while(|&i| bit(i) == 0).count();
+            pos += zeros + 1;
+            let v = (0..zeros).fold(1, |v, k| v << 1 | bit(pos + k));
+            pos += zeros;
+            v - 1
+        };
+        (ue(), ue())
+    }
+
+    #[test]
+    fn stream_parses_into_idr_and_p_access_units_of_the_planned_size() {
+        let p = Params { width: 1280, height: 720, fps: 30, kbps: 2000, gop: 10 };
+        let aus = stream(&p, 25);
+        assert_eq!(aus.len(), 25);
+
+        let avg = 2000 * 1000 / 8 / 30;
+        let p_bytes = avg * 10 / 15;
+        for (i, au) in aus.iter().enumerate() {
+            let nals: Vec<&[u8]> = nal_units(au).into_iter().map(|r| &au[r]).collect();
+            let types: Vec<u8> = nals.iter().map(|n| n[0] & 0x1f).collect();
+            let slice = nals.last().unwrap();
+            if i % 10 == 0 {
+                assert_eq!(types, [7, 8, 5], "AU {i}: SPS, PPS, IDR slice");
+                assert_eq!(slice_start(slice), (0, 7));
+                assert!((6 * p_bytes..6 * p_bytes + 16).contains(&slice.len()), "IDR slice {} bytes", slice.len());
+            } else {
+                assert_eq!(types, [1], "AU {i}: one non-IDR slice");
+                assert_eq!(slice_start(slice), (0, 5));
+                assert!((p_bytes..p_bytes + 16).contains(&slice.len()), "P slice {} bytes", slice.len());
+            }
+        }
+
+        // a whole GOP comes to the requested bitrate
+        let gop_bytes: usize = aus[..10].iter().map(Vec::len).sum();
+        assert!(gop_bytes.abs_diff(10 * avg) < 10 * 64, "{gop_bytes} bytes per GOP");
+    }
+}
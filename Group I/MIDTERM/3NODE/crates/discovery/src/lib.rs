@@ -0,0 +1,424 @@
+//! Zero-configuration LAN discovery for mesh mode.
+//!
+//! Every node periodically sends a small UDP beacon (node ID, TCP listen port
+//! and the SHA-256 fingerprint of its static P-256 public key) to the LAN
+//! broadcast address. Incoming beacons are tracked in a `PeerTable`; peers are
+//! reported `Up` on their first beacon and `Down` once their beacons stop for
+//! longer than the expiry window.
+//!
+//! Beacons are not authenticated. The first fingerprint heard for a node ID is
+//! pinned for the life of the process (trust on first use), and later beacons
+//! for that ID with another fingerprint are ignored. A connecting node has the
+//! transport handshake check the peer's static key against the pinned
+//! fingerprint, so once a node has been seen a spoofed beacon cannot redirect
+//! the stream to another key. A spoofer heard before the real node wins the
+//! pin, so this does not protect the very first contact.
+
+use anyhow::{anyhow, Context, Result};
+use p256::ecdh::SharedSecret;
+use p256::elliptic_curve::sec1::ToEncodedPoint;
+use p256::{PublicKey, SecretKey};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::io::Write;
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+use std::path::Path;
+use tokio::net::UdpSocket;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio::time::{Duration, Instant};
+
+/// UDP port beacons are sent to and received on.
+pub const DISCOVERY_PORT: u16 = 5454;
+pub const BEACON_INTERVAL: Duration = Duration::from_secs(1);
+/// A peer is dropped after this long without a beacon (three missed beacons).
+pub const PEER_EXPIRY: Duration = Duration::from_secs(3);
+
+const MAGIC: &[u8; 4] = b"RSSD";
+const VERSION: u8 = 1;
+const MAX_BEACON: usize = 4 + 1 + 1 + 255 + 2 + 32;
+
+/// Default beacon destination: limited broadcast on DISCOVERY_PORT.
+pub fn broadcast_addr() -> SocketAddr {
+    SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::BROADCAST, DISCOVERY_PORT))
+}
+
+// ---------- static node identity
+
+/// Long-lived P-256 key pair identifying a node across restarts.
+pub struct Identity {
+    secret: SecretKey,
+}
+
+impl Identity {
+    pub fn generate() -> Self {
+        Self { secret: SecretKey::random(&mut rand::rngs::OsRng) }
+    }
+
+    /// Load the raw 32-byte secret scalar from `path`, or create it (mode 0600) if missing.
+    pub fn load_or_generate(path: &Path) -> Result<Self> {
+        if path.exists() {
+            let bytes = std::fs::read(path).with_context(|| format!("read identity {}", path.display()))?;
+            let secret = SecretKey::from_slice(&bytes).map_err(|_| anyhow!("bad identity key in {}", path.display()))?;
+            return Ok(Self { secret });
+        }
+        let id = Self::generate();
+        // Created 0600, so the key is never readable by others, not even briefly
+        let mut opts = std::fs::OpenOptions::new();
+        opts.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut opts, 0o600);
+        let mut file = opts.open(path).with_context(|| format!("create identity {}", path.display()))?;
+        file.write_all(&id.secret.to_bytes()).with_context(|| format!("write identity {}", path.display()))?;
+        Ok(id)
+    }
+
+    pub fn public_key(&self) -> PublicKey {
+        self.secret.public_key()
+    }
+
+    /// SHA-256 over the compressed SEC1 public key.
+    pub fn fingerprint(&self) -> [u8; 32] {
+        fingerprint(&self.public_key())
+    }
+
+    /// Static-ephemeral ECDH with a peer's handshake key; proves this node holds
+    /// the key its beacon advertises.
+    pub fn diffie_hellman(&self, peer: &PublicKey) -> SharedSecret {
+        p256::ecdh::diffie_hellman(self.secret.to_nonzero_scalar(), peer.as_affine())
+    }
+}
+
+/// Fingerprint of any node key, as carried in beacons.
+pub fn fingerprint(key: &PublicKey) -> [u8; 32] {
+    Sha256::digest(key.to_encoded_point(true).as_bytes()).into()
+}
+
+pub fn fingerprint_hex(fp: &[u8; 32]) -> String {
+    fp.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+// ---------- beacon wire format
+// MAGIC(4) || VERSION(1) || id_len(1) || node_id || port(u16 BE) || fingerprint(32)
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Beacon {
+    pub node_id: String,
+    pub port: u16,
+    pub fingerprint: [u8; 32],
+}
+
+impl Beacon {
+    pub fn encode(&self) -> Vec<u8> {
+        let id = self.node_id.as_bytes();
+        let id = &id[..id.len().min(255)];
+        let mut out = Vec::with_capacity(4 + 1 + 1 + id.len() + 2 + 32);
+        out.extend_from_slice(MAGIC);
+        out.push(VERSION);
+        out.push(id.len() as u8);
+        out.extend_from_slice(id);
+        out.extend_from_slice(&self.port.to_be_bytes());
+        out.extend_from_slice(&self.fingerprint);
+        out
+    }
+
+    pub fn decode(buf: &[u8]) -> Result<Self> {
+        if buf.len() < 6 || &buf[..4] != MAGIC {
+            return Err(anyhow!("not a beacon"));
+        }
+        if buf[4] != VERSION {
+            return Err(anyhow!("unsupported beacon version {}", buf[4]));
+        }
+        let id_len = buf[5] as usize;
+        if buf.len() != 6 + id_len + 2 + 32 {
+            return Err(anyhow!("bad beacon length {}", buf.len()));
+        }
+        let node_id = std::str::from_utf8(&buf[6..6 + id_len])
+            .map_err(|_| anyhow!("beacon node id is not UTF-8"))?
+            .to_string();
+        let rest = &buf[6 + id_len..];
+        let port = u16::from_be_bytes([rest[0], rest[1]]);
+        let mut fingerprint = [0u8; 32];
+        fingerprint.copy_from_slice(&rest[2..34]);
+        Ok(Self { node_id, port, fingerprint })
+    }
+}
+
+// ---------- peer tracking
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Peer {
+    pub node_id: String,
+    /// Beacon source IP + advertised listen port.
+    pub addr: SocketAddr,
+    pub fingerprint: [u8; 32],
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PeerEvent {
+    Up(Peer),
+    Down(Peer),
+}
+
+/// Live peers keyed by node ID. Pure bookkeeping; the caller supplies the clock.
+pub struct PeerTable {
+    self_id: String,
+    expiry: Duration,
+    peers: HashMap<String, (Peer, Instant)>,
+    /// Fingerprint first heard for each node ID; kept after the peer expires.
+    pinned: HashMap<String, [u8; 32]>,
+}
+
+impl PeerTable {
+    pub fn new(self_id: &str, expiry: Duration) -> Self {
+        Self { self_id: self_id.to_string(), expiry, peers: HashMap::new(), pinned: HashMap::new() }
+    }
+
+    /// Record a beacon received from `from`. Beacons whose fingerprint differs
+    /// from the one pinned for their node ID are ignored, and so are beacons
+    /// from a new address while the peer is still live; a node that really
+    /// moved is reported Up at its new address once the old entry expires.
+    pub fn observe(&mut self, beacon: &Beacon, from: SocketAddr, now: Instant) -> Vec<PeerEvent> {
+        if beacon.node_id == self.self_id {
+            return Vec::new();
+        }
+        let pinned = self.pinned.entry(beacon.node_id.clone()).or_insert(beacon.fingerprint);
+        if *pinned != beacon.fingerprint {
+            return Vec::new();
+        }
+        let peer = Peer {
+            node_id: beacon.node_id.clone(),
+            addr: SocketAddr::new(from.ip(), beacon.port),
+            fingerprint: beacon.fingerprint,
+        };
+        match self.peers.get_mut(&peer.node_id) {
+            Some((known, seen)) => {
+                if *known == peer {
+                    *seen = now;
+                }
+                Vec::new()
+            }
+            None => {
+                self.peers.insert(peer.node_id.clone(), (peer.clone(), now));
+                vec![PeerEvent::Up(peer)]
+            }
+        }
+    }
+
+    /// Drop peers whose last beacon is older than the expiry window.
+    pub fn expire(&mut self, now: Instant) -> Vec<PeerEvent> {
+        let stale: Vec<String> = self
+            .peers
+            .iter()
+            .filter(|(_, (_, seen))| now.duration_since(*seen) > self.expiry)
+            .map(|(id, _)| id.clone())
+            .collect();
+        stale
+            .into_iter()
+            .filter_map(|id| self.peers.remove(&id))
+            .map(|(peer, _)| PeerEvent::Down(peer))
+            .collect()
+    }
+
+    pub fn peers(&self) -> impl Iterator<Item = &Peer> {
+        self.peers.values().map(|(p, _)| p)
+    }
+}
+
+// ---------- UDP beacon loop
+
+pub struct Discovery {
+    sock: UdpSocket,
+}
+
+impl Discovery {
+    /// Bind the beacon socket (normally `0.0.0.0:DISCOVERY_PORT`).
+    pub async fn bind(addr: SocketAddr) -> Result<Self> {
+        let sock = UdpSocket::bind(addr).await.with_context(|| format!("bind discovery {addr}"))?;
+        sock.set_broadcast(true).context("set_broadcast")?;
+        Ok(Self { sock })
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.sock.local_addr()?)
+    }
+
+    /// Announce `beacon` to every address in `announce_to` each `interval`, and
+    /// report peer changes on the returned channel. The task stops when the
+    /// receiver is dropped or the handle is aborted.
+    pub fn spawn(
+        self,
+        beacon: Beacon,
+        announce_to: Vec<SocketAddr>,
+        interval: Duration,
+        expiry: Duration,
+    ) -> (mpsc::Receiver<PeerEvent>, JoinHandle<()>) {
+        let (ev_tx, ev_rx) = mpsc::channel(32);
+        let handle = tokio::spawn(async move {
+            let mut table = PeerTable::new(&beacon.node_id, expiry);
+            let wire = beacon.encode();
+            let mut tick = tokio::time::interval(interval);
+            let mut buf = [0u8; MAX_BEACON];
+            loop {
+                let events = tokio::select! {
+                    _ = tick.tick() => {
+                        for dst in &announce_to {
+                            if let Err(e) = self.sock.send_to(&wire, dst).await {
+                                eprintln!("[disc] beacon to {dst} failed: {e}");
+                            }
+                        }
+                        table.expire(Instant::now())
+                    }
+                    r = self.sock.recv_from(&mut buf) => match r {
+                        Ok((n, from)) => match Beacon::decode(&buf[..n]) {
+                            Ok(b) => table.observe(&b, from, Instant::now()),
+                            Err(_) => Vec::new(),
+                        },
+                        Err(e) => { eprintln!("[disc] recv error: {e}"); Vec::new() }
+                    },
+                };
+                for ev in events {
+                    if ev_tx.send(ev).await.is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+        (ev_rx, handle)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn beacon(id: &str, port: u16, fp: u8) -> Beacon {
+        Beacon { node_id: id.to_string(), port, fingerprint: [fp; 32] }
+    }
+
+    #[test]
+    fn beacon_roundtrip_and_rejects_garbage() {
+        let b = beacon("pi2", 5000, 7);
+        assert_eq!(Beacon::decode(&b.encode()).unwrap(), b);
+        assert!(Beacon::decode(b"hello").is_err());
+        let mut wire = b.encode();
+        wire.pop();
+        assert!(Beacon::decode(&wire).is_err());
+    }
+
+    #[test]
+    fn table_tracks_up_refresh_change_and_expiry() {
+        let t0 = Instant::now();
+        let from: SocketAddr = "10.42.0.2:5454".parse().unwrap();
+        let mut t = PeerTable::new("pi1", Duration::from_secs(3));
+
+        assert!(t.observe(&beacon("pi1", 5000, 1), from, t0).is_empty(), "own beacon ignored");
+        let up = t.observe(&beacon("pi2", 5000, 2), from, t0);
+        assert!(matches!(&up[..], [PeerEvent::Up(p)] if p.addr == "10.42.0.2:5000".parse().unwrap()));
+        assert!(t.observe(&beacon("pi2", 5000, 2), from, t0 + Duration::from_secs(2)).is_empty());
+
+        // Moved while still live: the old address keeps the slot until it expires
+        let moved: SocketAddr = "10.42.0.9:5454".parse().unwrap();
+        assert!(t.observe(&beacon("pi2", 5000, 2), moved, t0 + Duration::from_secs(2)).is_empty());
+        assert_eq!(t.peers().count(), 1);
+
+        assert!(t.expire(t0 + Duration::from_secs(4)).is_empty());
+        assert!(matches!(&t.expire(t0 + Duration::from_secs(6))[..], [PeerEvent::Down(p)] if p.node_id == "pi2"));
+        assert_eq!(t.peers().count(), 0);
+
+        let up = t.observe(&beacon("pi2", 5000, 2), moved, t0 + Duration::from_secs(7));
+        assert!(matches!(&up[..], [PeerEvent::Up(p)] if p.addr == "10.42.0.9:5000".parse().unwrap()));
+    }
+
+    #[test]
+    fn spoofed_beacon_for_known_node_is_rejected() {
+        let t0 = Instant::now();
+        let real: SocketAddr = "10.42.0.2:5454".parse().unwrap();
+        let spoof: SocketAddr = "10.42.0.66:5454".parse().unwrap();
+        let mut t = PeerTable::new("pi1", Duration::from_secs(3));
+        assert_eq!(t.observe(&beacon("pi2", 5000, 2), real, t0).len(), 1);
+
+        // Same node ID, attacker's key: no Down/Up flapping while both beacon
+        for s in 1..=3 {
+            let now = t0 + Duration::from_secs(s);
+            assert!(t.observe(&beacon("pi2", 6000, 66), spoof, now).is_empty());
+            assert!(t.observe(&beacon("pi2", 5000, 2), real, now).is_empty());
+        }
+        let p = t.peers().next().unwrap();
+        assert_eq!((p.addr, p.fingerprint), ("10.42.0.2:5000".parse().unwrap(), [2; 32]));
+
+        // The pin outlives the peer: after the real node goes silent the spoofer still cannot take its ID
+        assert_eq!(t.expire(t0 + Duration::from_secs(10)).len(), 1);
+        assert!(t.observe(&beacon("pi2", 6000, 66), spoof, t0 + Duration::from_secs(11)).is_empty());
+        assert_eq!(t.peers().count(), 0);
+    }
+
+    #[test]
+    fn identity_persists_and_fingerprint_is_stable() {
+        let dir = std::env::temp_dir().join(format!("disc-id-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("node.key");
+        let a = Identity::load_or_generate(&path).unwrap();
+        let b = Identity::load_or_generate(&path).unwrap();
+        assert_eq!(a.fingerprint(), b.fingerprint());
+        assert_ne!(a.fingerprint(), Identity::generate().fingerprint());
+        assert_eq!(fingerprint_hex(&a.fingerprint()).len(), 64);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    async fn next_event(rx: &mut mpsc::Receiver<PeerEvent>) -> PeerEvent {
+        tokio::time::timeout(Duration::from_secs(5), rx.recv()).await.expect("no discovery event").unwrap()
+    }
+
+    #[tokio::test]
+    async fn loopback_nodes_find_each_other_and_drop_silent_peer() {
+        let interval = Duration::from_millis(50);
+        let expiry = Duration::from_millis(400);
+        let ids: Vec<_> = (0..3).map(|_| Identity::generate()).collect();
+
+        let mut socks = Vec::new();
+        for _ in 0..3 {
+            socks.push(Discovery::bind("127.0.0.1:0".parse().unwrap()).await.unwrap());
+        }
+        let addrs: Vec<SocketAddr> = socks.iter().map(|s| s.local_addr().unwrap()).collect();
+
+        let mut nodes = Vec::new();
+        for (i, sock) in socks.into_iter().enumerate() {
+            let b = Beacon { node_id: format!("node{i}"), port: 6000 + i as u16, fingerprint: ids[i].fingerprint() };
+            nodes.push(sock.spawn(b, addrs.clone(), interval, expiry));
+        }
+
+        // Every node sees the other two with their advertised port and key
+        for (i, (rx, _)) in nodes.iter_mut().enumerate() {
+            let mut seen = Vec::new();
+            while seen.len() < 2 {
+                match next_event(rx).await {
+                    PeerEvent::Up(p) => seen.push(p),
+                    other => panic!("node{i}: unexpected {other:?}"),
+                }
+            }
+            seen.sort_by(|a, b| a.node_id.cmp(&b.node_id));
+            let others: Vec<usize> = (0..3).filter(|&j| j != i).collect();
+            for (p, j) in seen.iter().zip(others) {
+                assert_eq!(p.node_id, format!("node{j}"));
+                assert_eq!(p.addr, SocketAddr::new([127, 0, 0, 1].into(), 6000 + j as u16));
+                assert_eq!(p.fingerprint, ids[j].fingerprint());
+            }
+        }
+
+        // node2 goes silent; the others drop it
+        let (_, h2) = nodes.pop().unwrap();
+        h2.abort();
+        for (rx, _) in nodes.iter_mut() {
+            match next_event(rx).await {
+                PeerEvent::Down(p) => assert_eq!(p.node_id, "node2"),
+                other => panic!("expected node2 Down, got {other:?}"),
+            }
+        }
+    }
+}
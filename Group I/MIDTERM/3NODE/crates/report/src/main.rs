@@ -0,0 +1,92 @@
+//! Offline experiment report from metrics CSV directories.
+//!
+//! report [--out report.html|report.md] [--format html|md] [--title T] RUN_DIR...
+//!
+//! A RUN_DIR is a directory of CSVs (Group I `--metrics-dir`, Group G `CSV/<role>`,
+//! Group A `data/`), or a parent whose immediate subdirectories are such runs.
+
+mod render;
+mod run;
+mod svg;
+mod table;
+
+use render::Format;
+use std::path::{Path, PathBuf};
+
+fn arg_val(args: &[String], key: &str) -> Option<String> {
+    // accepts: --key value  OR  --key=value
+    for i in 0..args.len() {
+        if args[i] == key {
+            if i + 1 < args.len() { return Some(args[i + 1].clone()); }
+        } else if let Some(rest) = args[i].strip_prefix(&(key.to_string() + "=")) {
+            return Some(rest.to_string());
+        }
+    }
+    None
+}
+
+// Everything that is neither a --flag nor the value of one
+fn positional(args: &[String], valued: &[&str]) -> Vec<String> {
+    let mut out = Vec::new();
+    let mut skip = false;
+    for a in args {
+        if skip {
+            skip = false;
+        } else if valued.contains(&a.as_str()) {
+            skip = true;
+        } else if !a.starts_with("--") {
+            out.push(a.clone());
+        }
+    }
+    out
+}
+
+fn usage() {
+    eprintln!("Usage:");
+    eprintln!("  report [--out report.html|report.md] [--format html|md] [--title TITLE] RUN_DIR [RUN_DIR ...]");
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.is_empty() || args.iter().any(|a| a == "--help" || a == "-h") {
+        usage();
+        std::process::exit(if args.is_empty() { 2 } else { 0 });
+    }
+    if let Err(e) = real_main(&args) {
+        eprintln!("report error: {e:#}");
+        std::process::exit(1);
+    }
+}
+
+fn real_main(args: &[String]) -> anyhow::Result<()> {
+    let out = PathBuf::from(arg_val(args, "--out").unwrap_or_else(|| "report.html".to_string()));
+    let fmt = match arg_val(args, "--format").as_deref() {
+        Some("md") | Some("markdown") => Format::Markdown,
+        Some("html") => Format::Html,
+        Some(other) => anyhow::bail!("unknown --format {other} (html|md)"),
+        None if out.extension().is_some_and(|x| x == "md") => Format::Markdown,
+        None => Format::Html,
+    };
+    let title = arg_val(args, "--title").unwrap_or_else(|| "Secure streaming experiment report".to_string());
+
+    let inputs: Vec<PathBuf> = positional(args, &["--out", "--format", "--title"]).into_iter().map(PathBuf::from).collect();
+    let dirs = run::discover(&inputs)?;
+    if dirs.is_empty() {
+        anyhow::bail!("no CSV files found under {inputs:?}");
+    }
+    let mut runs = Vec::new();
+    for (d, name) in dirs.iter().zip(run::run_names(&dirs)) {
+        let r = run::Run::load(d, name)?;
+        eprintln!("[report] {}: {}", r.name, if r.sources.is_empty() { "no usable CSVs".to_string() } else { r.sources.join(", ") });
+        runs.push(r);
+    }
+
+    // Markdown charts go to "<stem>_files/" beside the report
+    let stem = out.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+    let asset_rel = format!("{stem}_files");
+    let asset_dir = out.parent().unwrap_or(Path::new("")).join(&asset_rel);
+    let doc = render::render(&runs, &title, fmt, Some((&asset_dir, &asset_rel)))?;
+    std::fs::write(&out, doc)?;
+    eprintln!("[report] wrote {} ({} runs)", out.display(), runs.len());
+    Ok(())
+}
@@ -0,0 +1,218 @@
+//! HTML / Markdown report around the SVG charts.
+//!
+//! HTML inlines every chart so the report is a single file. Markdown links the
+//! charts as separate .svg files written next to it.
+
+use crate::run::{mean, percentile, Run};
+use crate::svg::{self, esc, Series};
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Html,
+    Markdown,
+}
+
+struct Doc {
+    fmt: Format,
+    out: String,
+    /// Markdown only: directory for chart files and its path relative to the report.
+    assets: Option<(PathBuf, String)>,
+}
+
+impl Doc {
+    fn heading(&mut self, level: usize, text: &str) {
+        match self.fmt {
+            Format::Html => self.out.push_str(&format!("<h{level}>{}</h{level}>\n", esc(text))),
+            Format::Markdown => self.out.push_str(&format!("{} {text}\n\n", "#".repeat(level))),
+        }
+    }
+
+    fn para(&mut self, text: &str) {
+        match self.fmt {
+            Format::Html => self.out.push_str(&format!("<p>{}</p>\n", esc(text))),
+            Format::Markdown => self.out.push_str(&format!("{text}\n\n")),
+        }
+    }
+
+    fn table(&mut self, headers: &[&str], rows: &[Vec<String>]) {
+        match self.fmt {
+            Format::Html => {
+                self.out.push_str("<table>\n<tr>");
+                for h in headers {
+                    self.out.push_str(&format!("<th>{}</th>", esc(h)));
+                }
+                self.out.push_str("</tr>\n");
+                for r in rows {
+                    self.out.push_str("<tr>");
+                    for c in r {
+                        self.out.push_str(&format!("<td>{}</td>", esc(c)));
+                    }
+                    self.out.push_str("</tr>\n");
+                }
+                self.out.push_str("</table>\n");
+            }
+            Format::Markdown => {
+                self.out.push_str(&format!("| {} |\n", headers.join(" | ")));
+                self.out.push_str(&format!("|{}\n", "---|".repeat(headers.len())));
+                for r in rows {
+                    let cells: Vec<String> = r.iter().map(|c| c.replace('|', "\\|")).collect();
+                    self.out.push_str(&format!("| {} |\n", cells.join(" | ")));
+                }
+                self.out.push('\n');
+            }
+        }
+    }
+
+    fn chart(&mut self, name: &str, title: &str, svg: String) -> Result<()> {
+        match (self.fmt, &self.assets) {
+            (Format::Markdown, Some((dir, rel))) => {
+                std::fs::create_dir_all(dir)?;
+                let file = format!("{name}.svg");
+                std::fs::write(dir.join(&file), svg)?;
+                self.out.push_str(&format!("![{title}]({rel}/{file})\n\n"));
+            }
+            _ => self.out.push_str(&format!("<div class=\"chart\">{svg}</div>\n")),
+        }
+        Ok(())
+    }
+}
+
+/// Render the report. For Markdown, `assets` is where chart files go and how
+/// the report refers to that directory.
+pub fn render(runs: &[Run], title: &str, fmt: Format, assets: Option<(&Path, &str)>) -> Result<String> {
+    let mut doc = Doc { fmt, out: String::new(), assets: assets.map(|(d, r)| (d.to_path_buf(), r.to_string())) };
+    if fmt == Format::Html {
+        doc.out.push_str(&format!(
+            "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>{}</title><style>\
+             body{{font-family:sans-serif;max-width:1000px;margin:2em auto}}\
+             table{{border-collapse:collapse;font-size:13px}}td,th{{border:1px solid #ccc;padding:3px 8px;text-align:right}}\
+             td:first-child,th:first-child{{text-align:left}}.chart{{margin:1em 0}}</style></head><body>\n",
+            esc(title)
+        ));
+    }
+    doc.heading(1, title);
+    doc.para(&format!("{} run(s). Times are seconds from the first sample of each run; dashed lines mark rekeys.", runs.len()));
+
+    doc.heading(2, "Comparison");
+    let rows: Vec<Vec<String>> = runs.iter().map(summary_row).collect();
+    doc.table(
+        &["run", "span s", "goodput Mbps", "fps", "latency p50 ms", "latency p95 ms", "CPU %", "max temp °C", "rekeys", "errors", "handshake median ms"],
+        &rows,
+    );
+    if runs.len() > 1 {
+        let tp: Vec<Series> = runs.iter().map(|r| Series { label: r.name.clone(), points: &r.throughput_mbps }).collect();
+        if tp.iter().any(|s| !s.points.is_empty()) {
+            doc.chart("compare_throughput", "Goodput by run", svg::line_chart("Goodput by run", "Mbps", &tp, &[]))?;
+        }
+        let lat: Vec<Series> = runs.iter().map(|r| Series { label: r.name.clone(), points: &r.latency_ms }).collect();
+        if lat.iter().any(|s| !s.points.is_empty()) {
+            doc.chart("compare_latency", "Latency by run", svg::line_chart("Latency (p50) by run", "ms", &lat, &[]))?;
+        }
+        let hs: Vec<(String, Vec<f64>)> = runs
+            .iter()
+            .flat_map(|r| r.handshakes.iter().map(move |(m, v)| (format!("{} {m}", r.name), v.clone())))
+            .collect();
+        if !hs.is_empty() {
+            doc.chart("compare_handshake", "Handshake time by run", svg::box_chart("Handshake time by run and mechanism", "ms", &hs))?;
+        }
+    }
+
+    for (i, run) in runs.iter().enumerate() {
+        doc.heading(2, &format!("Run: {}", run.name));
+        doc.para(&format!("Sources: {}", if run.sources.is_empty() { "none".to_string() } else { run.sources.join(", ") }));
+        let charts: [(&str, &str, Vec<Series>); 4] = [
+            ("throughput", "Mbps", vec![Series { label: "goodput".into(), points: &run.throughput_mbps }]),
+            (
+                "latency",
+                "ms",
+                vec![
+                    Series { label: "p50".into(), points: &run.latency_ms },
+                    Series { label: "p95".into(), points: &run.latency_p95_ms },
+                ],
+            ),
+            ("cpu", "%", vec![Series { label: "CPU".into(), points: &run.cpu_pct }]),
+            ("temperature", "°C", vec![Series { label: "SoC temp".into(), points: &run.temp_c }]),
+        ];
+        for (name, unit, series) in charts {
+            if series.iter().all(|s| s.points.is_empty()) {
+                continue;
+            }
+            let title = format!("{} – {name}", run.name);
+            doc.chart(&format!("run{i}_{name}"), &title, svg::line_chart(&title, unit, &series, &run.rekeys))?;
+        }
+        if !run.handshakes.is_empty() {
+            let groups: Vec<(String, Vec<f64>)> = run.handshakes.iter().map(|(m, v)| (m.clone(), v.clone())).collect();
+            let title = format!("{} – handshake time", run.name);
+            doc.chart(&format!("run{i}_handshake"), &title, svg::box_chart(&title, "ms", &groups))?;
+        }
+    }
+    if fmt == Format::Html {
+        doc.out.push_str("</body></html>\n");
+    }
+    Ok(doc.out)
+}
+
+fn summary_row(r: &Run) -> Vec<String> {
+    let vals = |p: &[(f64, f64)]| p.iter().map(|x| x.1).collect::<Vec<f64>>();
+    let f = |v: Option<f64>, prec: usize| v.map_or("–".to_string(), |v| format!("{v:.prec$}"));
+    let lat = vals(&r.latency_ms);
+    let p95 = if r.latency_p95_ms.is_empty() {
+        percentile(&lat, 95.0)
+    } else {
+        percentile(&vals(&r.latency_p95_ms), 50.0)
+    };
+    let span = [&r.throughput_mbps, &r.latency_ms, &r.cpu_pct, &r.temp_c]
+        .iter()
+        .flat_map(|s| s.iter().map(|p| p.0))
+        .fold(None, |m: Option<f64>, t| Some(m.map_or(t, |m| m.max(t))));
+    let hs: Vec<String> = r
+        .handshakes
+        .iter()
+        .map(|(m, v)| format!("{m} {} (n={})", f(percentile(v, 50.0), 3), v.len()))
+        .collect();
+    vec![
+        r.name.clone(),
+        f(span, 0),
+        f(mean(vals(&r.throughput_mbps)), 2),
+        f(mean(vals(&r.fps)), 1),
+        f(percentile(&lat, 50.0), 2),
+        f(p95, 2),
+        f(mean(vals(&r.cpu_pct)), 1),
+        f(r.temp_c.iter().map(|p| p.1).reduce(f64::max), 1),
+        r.rekeys.len().to_string(),
+        r.errors.to_string(),
+        if hs.is_empty() { "–".to_string() } else { hs.join("; ") },
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(name: &str) -> Run {
+        let mut r = Run { name: name.into(), ..Default::default() };
+        r.throughput_mbps = vec![(0.0, 2.0), (5.0, 4.0)];
+        r.handshakes.insert("ECDH".into(), vec![1.0, 3.0]);
+        r.rekeys = vec![2.0];
+        r
+    }
+
+    #[test]
+    fn html_is_single_file_with_comparison() {
+        let html = render(&[run("a"), run("b")], "T", Format::Html, None).unwrap();
+        assert!(html.contains("Goodput by run"));
+        assert_eq!(html.matches("<svg").count(), 2 + 2 * 2, "2 comparison + 2 per run");
+        assert!(html.contains("ECDH 2.000 (n=2)"));
+    }
+
+    #[test]
+    fn markdown_writes_chart_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let md = render(&[run("a")], "T", Format::Markdown, Some((&dir.path().join("r_files"), "r_files"))).unwrap();
+        assert!(md.contains("![a – throughput](r_files/run0_throughput.svg)"));
+        assert!(dir.path().join("r_files/run0_handshake.svg").exists());
+        assert!(md.contains("| a | 5 | 3.00 |"));
+    }
+}
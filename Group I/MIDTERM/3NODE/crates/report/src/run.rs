@@ -0,0 +1,478 @@
+//! One run directory normalised into time series.
+//!
+//! Three layouts are understood, all detected by file and column names:
+//! - Group I `metrics` crate: handshake / throughput / latency / system / errors.csv
+//! - Group G: handshake(_ecdh) / throughput / latency / steady_stream / sys / loss.csv
+//! - Group A: steady_stream.csv, handshake_<peer>.csv, rekey_{tx,rx}.csv (ns clock)
+//!
+//! All times are seconds since the first sample of the run.
+
+use crate::table::{is_fine_ts, Table};
+use anyhow::Result;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+pub type Points = Vec<(f64, f64)>;
+
+#[derive(Default)]
+pub struct Run {
+    pub name: String,
+    /// CSV files that contributed data.
+    pub sources: Vec<String>,
+    pub throughput_mbps: Points,
+    pub fps: Points,
+    pub latency_ms: Points,
+    pub latency_p95_ms: Points,
+    pub cpu_pct: Points,
+    pub temp_c: Points,
+    /// Mechanism -> handshake durations in ms.
+    pub handshakes: BTreeMap<String, Vec<f64>>,
+    pub rekeys: Vec<f64>,
+    /// Drops + tag failures + send failures, as logged.
+    pub errors: u64,
+}
+
+/// Expand the given paths into run directories: a directory with CSVs is a run,
+/// otherwise each immediate subdirectory with CSVs is (e.g. `CSV/leader`, `CSV/member`).
+pub fn discover(paths: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    let mut out = Vec::new();
+    for p in paths {
+        if has_csv(p)? {
+            out.push(p.clone());
+            continue;
+        }
+        let mut subs: Vec<PathBuf> = std::fs::read_dir(p)?
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|s| s.is_dir())
+            .collect();
+        subs.sort();
+        for s in subs {
+            if has_csv(&s)? {
+                out.push(s);
+            }
+        }
+    }
+    Ok(out)
+}
+
+fn has_csv(dir: &Path) -> Result<bool> {
+    if !dir.is_dir() {
+        return Ok(false);
+    }
+    Ok(std::fs::read_dir(dir)?
+        .filter_map(|e| e.ok())
+        .any(|e| e.path().extension().is_some_and(|x| x == "csv")))
+}
+
+/// Short display names: the shortest path suffix (two components or more) that
+/// tells the runs apart, e.g. `ECDH/CSV/leader` vs `RSA/CSV/leader`.
+pub fn run_names(dirs: &[PathBuf]) -> Vec<String> {
+    let comps: Vec<Vec<String>> = dirs
+        .iter()
+        .map(|d| d.components().map(|c| c.as_os_str().to_string_lossy().into_owned()).collect())
+        .collect();
+    let longest = comps.iter().map(|c| c.len()).max().unwrap_or(0);
+    let suffixes = |k: usize| -> Vec<String> { comps.iter().map(|c| c[c.len().saturating_sub(k)..].join("/")).collect() };
+    for k in 2..longest {
+        let names = suffixes(k);
+        let unique: std::collections::BTreeSet<&String> = names.iter().collect();
+        if unique.len() == names.len() {
+            return names;
+        }
+    }
+    suffixes(longest)
+}
+
+// Values only used when the primary file for that metric is missing or empty
+#[derive(Default)]
+struct Fallback {
+    throughput_mbps: Points,
+    fps: Points,
+    latency_ms: Points,
+    cpu_pct: Points,
+    temp_c: Points,
+    /// (mechanism, duration in ms when the stamps are fine enough to time it)
+    span_handshakes: Vec<(String, Option<f64>)>,
+}
+
+impl Run {
+    pub fn load(dir: &Path, name: String) -> Result<Run> {
+        let mut run = Run { name, ..Default::default() };
+        let mut fb = Fallback::default();
+
+        let mut files: Vec<PathBuf> = std::fs::read_dir(dir)?
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| p.extension().is_some_and(|x| x == "csv"))
+            .collect();
+        files.sort();
+
+        for path in &files {
+            let stem = path.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+            let Some(t) = Table::read(path)? else { continue };
+            let used = match stem.as_str() {
+                "throughput" => run.load_throughput(&t),
+                "latency" => run.load_latency(&t),
+                "handshake" => run.load_handshake(&t),
+                "system" | "sys" => run.load_system(&t),
+                "errors" | "loss" => run.load_errors(&t),
+                "steady_stream" => run.load_steady(&t, &mut fb),
+                s if s.starts_with("handshake_") => load_span_handshakes(&t, &mut fb),
+                s if s.starts_with("rekey_") => run.load_rekeys(&t),
+                _ => false,
+            };
+            if used {
+                run.sources.push(path.file_name().unwrap_or_default().to_string_lossy().into_owned());
+            }
+        }
+        run.apply_fallback(fb);
+        run.rebase();
+        Ok(run)
+    }
+
+    fn load_throughput(&mut self, t: &Table) -> bool {
+        let Some(ts) = t.col(&["timestamp", "ts"]) else { return false };
+        if let Some(v) = t.col(&["encrypted_goodput_mbps", "mbps"]) {
+            self.throughput_mbps = t.points(ts, v, 1.0);
+        }
+        if let Some(v) = t.col(&["fps"]) {
+            // Group G leaves fps at 0 here and logs it in steady_stream.csv
+            self.fps = t.points(ts, v, 1.0).into_iter().filter(|p| p.1 > 0.0).collect();
+        }
+        !self.throughput_mbps.is_empty()
+    }
+
+    fn load_latency(&mut self, t: &Table) -> bool {
+        if let (Some(ts), Some(p50)) = (t.col(&["timestamp"]), t.col(&["p50_ms"])) {
+            // Interval summaries; empty intervals are logged with count 0
+            let count = t.col(&["count"]);
+            let rows = t.rows.iter().filter(|r| count.and_then(|c| Table::f64_at(r, c)) != Some(0.0));
+            for r in rows {
+                let Some(at) = Table::ts_at(r, ts) else { continue };
+                if let Some(v) = Table::f64_at(r, p50) {
+                    self.latency_ms.push((at, v));
+                }
+                if let Some(v) = t.col(&["p95_ms"]).and_then(|c| Table::f64_at(r, c)) {
+                    self.latency_p95_ms.push((at, v));
+                }
+            }
+        } else if let (Some(ts), Some(v)) = (t.col(&["recv_ts_iso", "ts"]), t.col(&["latency_s"])) {
+            // Per-frame latency is only meaningful if the stamps resolve below a second
+            if t.rows.first().and_then(|r| r.get(ts)).is_some_and(|s| is_fine_ts(s)) {
+                self.latency_ms = t.points(ts, v, 1000.0);
+            }
+        }
+        !self.latency_ms.is_empty()
+    }
+
+    fn load_handshake(&mut self, t: &Table) -> bool {
+        let (Some(ts), Some(d)) = (t.col(&["timestamp", "ts"]), t.col(&["duration_s"])) else { return false };
+        let mech = t.col(&["method", "mech"]);
+        let role = t.col(&["role"]);
+        let success = t.col(&["success"]);
+        for r in &t.rows {
+            if success.and_then(|c| r.get(c)).is_some_and(|s| s == "false") {
+                continue;
+            }
+            let Some(ms) = Table::f64_at(r, d).map(|s| s * 1000.0) else { continue };
+            let label = match (mech.and_then(|c| r.get(c)), role.and_then(|c| r.get(c))) {
+                (Some(m), _) => m.clone(),
+                // Group G logs the role here; the mechanism comes from handshake_ecdh.csv
+                (None, Some(role)) => format!("handshake ({role})"),
+                (None, None) => "handshake".to_string(),
+            };
+            if label.starts_with("REKEY") {
+                if let Some(at) = Table::ts_at(r, ts) {
+                    self.rekeys.push(at);
+                }
+            }
+            self.handshakes.entry(label).or_default().push(ms);
+        }
+        !self.handshakes.is_empty()
+    }
+
+    fn load_system(&mut self, t: &Table) -> bool {
+        let Some(ts) = t.col(&["timestamp", "ts"]) else { return false };
+        if let Some(c) = t.col(&["cpu_usage_percent", "cpu_percent"]) {
+            self.cpu_pct = t.points(ts, c, 1.0);
+        }
+        if let Some(c) = t.col(&["temp_c"]) {
+            self.temp_c = t.points(ts, c, 1.0);
+        }
+        !self.cpu_pct.is_empty() || !self.temp_c.is_empty()
+    }
+
+    fn load_errors(&mut self, t: &Table) -> bool {
+        let cols: Vec<usize> = ["drops", "gcm_tag_failures", "count"].iter().filter_map(|n| t.col(&[n])).collect();
+        let before = self.errors;
+        for r in &t.rows {
+            for &c in &cols {
+                self.errors += Table::f64_at(r, c).unwrap_or(0.0) as u64;
+            }
+        }
+        self.errors > before
+    }
+
+    fn load_steady(&mut self, t: &Table, fb: &mut Fallback) -> bool {
+        let Some(ts) = t.col(&["ts"]) else { return false };
+        if let Some(c) = t.col(&["goodput_mbps"]) {
+            fb.throughput_mbps = t.points(ts, c, 1.0);
+        }
+        if let Some(c) = t.col(&["fps"]) {
+            fb.fps = t.points(ts, c, 1.0);
+        }
+        if let Some(c) = t.col(&["latency_ms", "latency_ms_p50"]) {
+            // 0.0 means "not measured" in these logs
+            fb.latency_ms = t.points(ts, c, 1.0).into_iter().filter(|p| p.1 > 0.0).collect();
+        }
+        if let Some(c) = t.col(&["cpu_pct"]) {
+            fb.cpu_pct = t.points(ts, c, 1.0);
+        }
+        if let Some(c) = t.col(&["temp_c"]) {
+            fb.temp_c = t.points(ts, c, 1.0);
+        }
+        // drops / tag_fail are running totals
+        for name in ["drops", "tag_fail"] {
+            if let Some(c) = t.col(&[name]) {
+                let max = t.rows.iter().filter_map(|r| Table::f64_at(r, c)).fold(0.0, f64::max);
+                self.errors += max as u64;
+            }
+        }
+        true
+    }
+
+    fn load_rekeys(&mut self, t: &Table) -> bool {
+        let idx = t.col(&["ts"]).unwrap_or(0);
+        let before = self.rekeys.len();
+        self.rekeys.extend(t.rows.iter().filter_map(|r| Table::ts_at(r, idx)));
+        self.rekeys.len() > before
+    }
+
+    fn apply_fallback(&mut self, fb: Fallback) {
+        fn fill(dst: &mut Points, src: Points) {
+            if dst.is_empty() {
+                *dst = src;
+            }
+        }
+        fill(&mut self.throughput_mbps, fb.throughput_mbps);
+        fill(&mut self.fps, fb.fps);
+        fill(&mut self.latency_ms, fb.latency_ms);
+        fill(&mut self.cpu_pct, fb.cpu_pct);
+        fill(&mut self.temp_c, fb.temp_c);
+
+        // ts_start/ts_end spans give real durations only with a fine clock (Group A);
+        // otherwise they just name the mechanism for Group G's role-labelled rows.
+        let mech = fb.span_handshakes.first().map(|(m, _)| m.clone());
+        if self.handshakes.is_empty() {
+            for (m, ms) in fb.span_handshakes {
+                if let Some(ms) = ms {
+                    self.handshakes.entry(m).or_default().push(ms);
+                }
+            }
+        } else if let Some(mech) = mech {
+            let relabelled: BTreeMap<String, Vec<f64>> = std::mem::take(&mut self.handshakes)
+                .into_iter()
+                .map(|(k, v)| match k.strip_prefix("handshake") {
+                    Some(role) => (format!("{mech}{role}"), v),
+                    None => (k, v),
+                })
+                .collect();
+            self.handshakes = relabelled;
+        }
+    }
+
+    /// Shift every series so the earliest sample is t = 0.
+    fn rebase(&mut self) {
+        let series = [
+            &self.throughput_mbps,
+            &self.fps,
+            &self.latency_ms,
+            &self.latency_p95_ms,
+            &self.cpu_pct,
+            &self.temp_c,
+        ];
+        let t0 = series
+            .iter()
+            .flat_map(|s| s.iter().map(|p| p.0))
+            .chain(self.rekeys.iter().copied())
+            .fold(f64::INFINITY, f64::min);
+        if !t0.is_finite() {
+            return;
+        }
+        for s in [
+            &mut self.throughput_mbps,
+            &mut self.fps,
+            &mut self.latency_ms,
+            &mut self.latency_p95_ms,
+            &mut self.cpu_pct,
+            &mut self.temp_c,
+        ] {
+            s.sort_by(|a, b| a.0.total_cmp(&b.0));
+            for p in s.iter_mut() {
+                p.0 -= t0;
+            }
+        }
+        for r in self.rekeys.iter_mut() {
+            *r -= t0;
+        }
+        self.rekeys.sort_by(f64::total_cmp);
+    }
+}
+
+fn load_span_handshakes(t: &Table, fb: &mut Fallback) -> bool {
+    let (Some(s), Some(e), Some(m)) = (t.col(&["ts_start"]), t.col(&["ts_end"]), t.col(&["mech"])) else {
+        return false;
+    };
+    for r in &t.rows {
+        if let (Some(start), Some(end), Some(mech)) = (Table::ts_at(r, s), Table::ts_at(r, e), r.get(m)) {
+            let fine = r.get(s).is_some_and(|v| is_fine_ts(v));
+            fb.span_handshakes.push((mech.clone(), fine.then(|| (end - start).max(0.0) * 1000.0)));
+        }
+    }
+    !fb.span_handshakes.is_empty()
+}
+
+/// Linear-interpolated percentile (p in 0..=100) of an unsorted slice.
+pub fn percentile(values: &[f64], p: f64) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    let mut v = values.to_vec();
+    v.sort_by(f64::total_cmp);
+    let rank = (p / 100.0) * (v.len() - 1) as f64;
+    let lo = rank.floor() as usize;
+    let hi = rank.ceil() as usize;
+    Some(v[lo] + (v[hi] - v[lo]) * (rank - lo as f64))
+}
+
+pub fn mean(values: impl IntoIterator<Item = f64>) -> Option<f64> {
+    let (sum, n) = values.into_iter().fold((0.0, 0usize), |(s, n), v| (s + v, n + 1));
+    (n > 0).then(|| sum / n as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn group_i_metrics_layout() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("handshake.csv"),
+            "timestamp,method,duration_s,bytes_sent,bytes_received,success,failure_reason,peer\n\
+             2025-11-02T02:47:39.2Z,ECDH,0.0019,75,75,true,,10.42.0.1:1\n\
+             2025-11-02T02:47:45.0Z,REKEY,0.0001,0,30,true,,10.42.0.1:1\n\
+             2025-11-02T02:47:46.0Z,ECDH,0.5,0,0,false,boom,10.42.0.3:1\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("throughput.csv"),
+            "timestamp,duration_s,encrypted_goodput_mbps,frames,fps\n\
+             2025-11-02T02:47:44.2Z,5.0,1.6,159,31.7\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("latency.csv"),
+            "timestamp,count,mean_ms,p50_ms,p95_ms\n\
+             2025-11-02T02:47:44.8Z,305,3.6,3.4,4.9\n\
+             2025-11-02T02:47:49.8Z,0,0,0,0\n",
+        )
+        .unwrap();
+        fs::write(dir.path().join("errors.csv"), "").unwrap();
+
+        let run = Run::load(dir.path(), "t".into()).unwrap();
+        assert_eq!(run.handshakes["ECDH"], vec![1.9], "failed handshakes are skipped");
+        assert_eq!(run.handshakes["REKEY"].len(), 1);
+        assert_eq!(run.rekeys.len(), 1);
+        assert_eq!(run.throughput_mbps, vec![(0.0, 1.6)]);
+        assert_eq!(run.latency_ms.len(), 1, "empty intervals are skipped");
+        assert!((run.latency_p95_ms[0].0 - 0.6).abs() < 1e-6);
+        assert!((run.rekeys[0] - 0.8).abs() < 1e-6);
+    }
+
+    #[test]
+    fn group_g_layout_with_repeated_headers() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("handshake.csv"),
+            "ts,role,duration_s,bytes_exchanged,success,note\n\
+             ts,role,duration_s,bytes_exchanged,success,note\n\
+             2025-11-01T01:49:52Z,leader,2.23,816,true,\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("handshake_ecdh.csv"),
+            "ts_start,ts_end,mech,bytes_tx,bytes_rx,cpu_avg,mem_mb,energy_j\n\
+             2025-11-01T01:49:49Z,2025-11-01T01:49:52Z,RSA-OAEP-256+AES-GCM,389,427,4.6,0,0.0\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("steady_stream.csv"),
+            "ts,fps,goodput_mbps,latency_ms_p50,cpu_pct,mem_mb,temp_c,drops,tag_fail\n\
+             ts,fps,goodput_mbps,latency_ms_p50,cpu_pct,mem_mb,temp_c,drops,tag_fail\n\
+             2025-10-31T23:34:18Z,13.9,10.1,0.0,4.3,2402608,50.15,0,0\n\
+             2025-10-31T23:34:23Z,14.0,10.3,0.0,4.1,2402608,50.65,2,1\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("sys.csv"),
+            "ts,cpu_percent,total_mem_kb,used_mem_kb,temp_c\n\
+             2025-10-31T23:34:19Z,4.2,8454815744,2425864192,54.55\n",
+        )
+        .unwrap();
+
+        let run = Run::load(dir.path(), "t".into()).unwrap();
+        assert_eq!(run.handshakes.keys().collect::<Vec<_>>(), vec!["RSA-OAEP-256+AES-GCM (leader)"]);
+        assert_eq!(run.throughput_mbps.len(), 2, "steady_stream fills in for throughput.csv");
+        assert!(run.latency_ms.is_empty(), "unmeasured latency is not plotted");
+        assert_eq!(run.cpu_pct, vec![(1.0, 4.2)], "sys.csv wins over steady_stream");
+        assert_eq!(run.errors, 3);
+    }
+
+    #[test]
+    fn group_a_ns_clock_and_headerless_rekey_log() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("handshake_10.0.0.2_5000.csv"),
+            "ts_start,ts_end,mech,bytes_tx,bytes_rx,cpu_avg,mem_mb,energy_j\n\
+             5000000000,5012500000,RSA-OAEP-256,300,40,1.0,12.0,0.010\n",
+        )
+        .unwrap();
+        fs::write(dir.path().join("rekey_tx.csv"), "8000000000,1000,10.0.0.2:5000\n").unwrap();
+        fs::write(
+            dir.path().join("steady_stream.csv"),
+            "ts,fps,goodput_mbps,latency_ms,cpu_pct,mem_mb,temp_c,drops,tag_fail\n\
+             6000000000,30.00,4.000,12.50,20.0,100.0,48.0,0,0\n",
+        )
+        .unwrap();
+
+        let run = Run::load(dir.path(), "t".into()).unwrap();
+        assert!((run.handshakes["RSA-OAEP-256"][0] - 12.5).abs() < 1e-6);
+        assert_eq!(run.rekeys, vec![2.0]);
+        assert_eq!(run.latency_ms, vec![(0.0, 12.5)]);
+    }
+
+    #[test]
+    fn discover_descends_one_level() {
+        let dir = tempfile::tempdir().unwrap();
+        for sub in ["leader", "member", "empty"] {
+            fs::create_dir(dir.path().join(sub)).unwrap();
+        }
+        fs::write(dir.path().join("leader/sys.csv"), "").unwrap();
+        fs::write(dir.path().join("member/sys.csv"), "").unwrap();
+        let runs = discover(&[dir.path().to_path_buf()]).unwrap();
+        assert_eq!(runs.len(), 2);
+        assert!(run_names(&runs)[0].ends_with("/leader"));
+
+        let names = run_names(&["x/ECDH/CSV/leader".into(), "x/RSA/CSV/leader".into(), "y/metrics/node2".into()]);
+        assert_eq!(names, vec!["ECDH/CSV/leader", "RSA/CSV/leader", "y/metrics/node2"]);
+        assert_eq!(run_names(&["/a/b/c".into()]), vec!["b/c"]);
+    }
+
+    #[test]
+    fn percentile_interpolates() {
+        assert_eq!(percentile(&[4.0, 1.0, 3.0, 2.0], 50.0), Some(2.5));
+        assert_eq!(percentile(&[], 50.0), None);
+        assert_eq!(mean([1.0, 2.0, 6.0]), Some(3.0));
+    }
+}
@@ -0,0 +1,252 @@
+//! Minimal self-contained SVG charts (no scripts, no external fonts or CSS).
+
+use crate::run::percentile;
+use std::fmt::Write;
+
+const W: f64 = 720.0;
+const H: f64 = 300.0;
+const LEFT: f64 = 60.0;
+const RIGHT: f64 = 20.0;
+const TOP: f64 = 30.0;
+const BOTTOM: f64 = 45.0;
+const PALETTE: [&str; 8] = ["#1f77b4", "#ff7f0e", "#2ca02c", "#d62728", "#9467bd", "#8c564b", "#e377c2", "#17becf"];
+
+pub fn color(i: usize) -> &'static str {
+    PALETTE[i % PALETTE.len()]
+}
+
+pub struct Series<'a> {
+    pub label: String,
+    pub points: &'a [(f64, f64)],
+}
+
+/// Line chart of one or more series over time, with dashed vertical markers
+/// (rekeys) at the given x positions.
+pub fn line_chart(title: &str, y_label: &str, series: &[Series], markers: &[f64]) -> String {
+    let xs = series.iter().flat_map(|s| s.points.iter().map(|p| p.0)).chain(markers.iter().copied());
+    let (x0, x1) = bounds(xs, false);
+    let (y0, y1) = bounds(series.iter().flat_map(|s| s.points.iter().map(|p| p.1)), true);
+    let sx = |x: f64| LEFT + (x - x0) / (x1 - x0) * (W - LEFT - RIGHT);
+    let sy = |y: f64| H - BOTTOM - (y - y0) / (y1 - y0) * (H - TOP - BOTTOM);
+
+    let mut svg = open(title);
+    axes(&mut svg, x0, x1, y0, y1, "time (s)", y_label);
+    for &m in markers {
+        let x = sx(m);
+        let _ = write!(
+            svg,
+            r##"<line x1="{x:.1}" y1="{TOP}" x2="{x:.1}" y2="{:.1}" stroke="#999" stroke-dasharray="4 3"><title>rekey @ {m:.1}s</title></line>"##,
+            H - BOTTOM
+        );
+    }
+    for (i, s) in series.iter().enumerate() {
+        if s.points.is_empty() {
+            continue;
+        }
+        let path: Vec<String> = s.points.iter().map(|&(x, y)| format!("{:.1},{:.1}", sx(x), sy(y))).collect();
+        let _ = write!(
+            svg,
+            r#"<polyline fill="none" stroke="{}" stroke-width="1.5" points="{}"/>"#,
+            color(i),
+            path.join(" ")
+        );
+        if s.points.len() == 1 {
+            let (x, y) = s.points[0];
+            let _ = write!(svg, r#"<circle cx="{:.1}" cy="{:.1}" r="3" fill="{}"/>"#, sx(x), sy(y), color(i));
+        }
+    }
+    let mut labels: Vec<(String, &str)> = series.iter().enumerate().map(|(i, s)| (s.label.clone(), color(i))).collect();
+    if !markers.is_empty() {
+        labels.push(("rekey".to_string(), "#999"));
+    }
+    legend(&mut svg, &labels);
+    svg.push_str("</svg>");
+    svg
+}
+
+/// Box plot per group (whiskers at min/max, box at p25–p75, line at median),
+/// with the individual samples drawn over it.
+pub fn box_chart(title: &str, y_label: &str, groups: &[(String, Vec<f64>)]) -> String {
+    let (y0, y1) = bounds(groups.iter().flat_map(|g| g.1.iter().copied()), true);
+    let sy = |y: f64| H - BOTTOM - (y - y0) / (y1 - y0) * (H - TOP - BOTTOM);
+    let slot = (W - LEFT - RIGHT) / groups.len().max(1) as f64;
+
+    let mut svg = open(title);
+    axes(&mut svg, 0.0, 0.0, y0, y1, "", y_label);
+    for (i, (label, v)) in groups.iter().enumerate() {
+        let cx = LEFT + slot * (i as f64 + 0.5);
+        let bw = (slot * 0.5).min(60.0);
+        let c = color(i);
+        let _ = write!(
+            svg,
+            r#"<text x="{cx:.1}" y="{:.1}" font-size="11" text-anchor="middle">{}</text>"#,
+            H - BOTTOM + 16.0,
+            esc(label)
+        );
+        let (Some(lo), Some(q1), Some(med), Some(q3), Some(hi)) = (
+            percentile(v, 0.0),
+            percentile(v, 25.0),
+            percentile(v, 50.0),
+            percentile(v, 75.0),
+            percentile(v, 100.0),
+        ) else {
+            continue;
+        };
+        let _ = write!(
+            svg,
+            r#"<line x1="{cx:.1}" y1="{:.1}" x2="{cx:.1}" y2="{:.1}" stroke="{c}"/>"#,
+            sy(lo),
+            sy(hi)
+        );
+        let _ = write!(
+            svg,
+            r#"<rect x="{:.1}" y="{:.1}" width="{bw:.1}" height="{:.1}" fill="{c}" fill-opacity="0.25" stroke="{c}"/>"#,
+            cx - bw / 2.0,
+            sy(q3),
+            (sy(q1) - sy(q3)).max(1.0)
+        );
+        let _ = write!(
+            svg,
+            r#"<line x1="{:.1}" y1="{:.1}" x2="{:.1}" y2="{:.1}" stroke="{c}" stroke-width="2"><title>median {med:.3}</title></line>"#,
+            cx - bw / 2.0,
+            sy(med),
+            cx + bw / 2.0,
+            sy(med)
+        );
+        for (j, &y) in v.iter().enumerate() {
+            // deterministic jitter so overlapping samples stay visible
+            let dx = ((j * 7919) % 21) as f64 / 20.0 - 0.5;
+            let _ = write!(
+                svg,
+                r#"<circle cx="{:.1}" cy="{:.1}" r="2" fill="{c}" fill-opacity="0.6"/>"#,
+                cx + dx * bw * 0.8,
+                sy(y)
+            );
+        }
+    }
+    svg.push_str("</svg>");
+    svg
+}
+
+fn open(title: &str) -> String {
+    format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{W}" height="{H}" viewBox="0 0 {W} {H}" font-family="sans-serif"><rect width="{W}" height="{H}" fill="white"/><text x="{LEFT}" y="18" font-size="13" font-weight="bold">{}</text>"#,
+        esc(title)
+    )
+}
+
+/// Frame, y ticks and, when x1 > x0, x ticks.
+fn axes(svg: &mut String, x0: f64, x1: f64, y0: f64, y1: f64, x_label: &str, y_label: &str) {
+    let (l, r, t, b) = (LEFT, W - RIGHT, TOP, H - BOTTOM);
+    let _ = write!(svg, r##"<rect x="{l}" y="{t}" width="{}" height="{}" fill="none" stroke="#444"/>"##, r - l, b - t);
+    for y in ticks(y0, y1) {
+        let py = b - (y - y0) / (y1 - y0) * (b - t);
+        let _ = write!(
+            svg,
+            r##"<line x1="{l}" y1="{py:.1}" x2="{r}" y2="{py:.1}" stroke="#eee"/><text x="{:.1}" y="{:.1}" font-size="10" text-anchor="end">{}</text>"##,
+            l - 4.0,
+            py + 3.0,
+            fmt_tick(y)
+        );
+    }
+    if x1 > x0 {
+        for x in ticks(x0, x1) {
+            let px = l + (x - x0) / (x1 - x0) * (r - l);
+            let _ = write!(
+                svg,
+                r#"<text x="{px:.1}" y="{:.1}" font-size="10" text-anchor="middle">{}</text>"#,
+                b + 14.0,
+                fmt_tick(x)
+            );
+        }
+    }
+    let _ = write!(svg, r#"<text x="{:.1}" y="{:.1}" font-size="11" text-anchor="middle">{}</text>"#, (l + r) / 2.0, H - 8.0, esc(x_label));
+    let _ = write!(
+        svg,
+        r#"<text x="14" y="{:.1}" font-size="11" text-anchor="middle" transform="rotate(-90 14 {:.1})">{}</text>"#,
+        (t + b) / 2.0,
+        (t + b) / 2.0,
+        esc(y_label)
+    );
+}
+
+fn legend(svg: &mut String, labels: &[(String, &str)]) {
+    let mut x = W - RIGHT;
+    for (label, c) in labels.iter().rev() {
+        let w = 18.0 + 6.5 * label.chars().count() as f64;
+        x -= w;
+        let _ = write!(
+            svg,
+            r#"<rect x="{x:.1}" y="9" width="10" height="10" fill="{c}"/><text x="{:.1}" y="18" font-size="11">{}</text>"#,
+            x + 13.0,
+            esc(label)
+        );
+        x -= 8.0;
+    }
+}
+
+/// Padded [min, max] range; `from_zero` anchors non-negative data at 0.
+fn bounds(values: impl Iterator<Item = f64>, from_zero: bool) -> (f64, f64) {
+    let (mut lo, mut hi) = values.fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), v| (lo.min(v), hi.max(v)));
+    if !lo.is_finite() {
+        return (0.0, 1.0);
+    }
+    if from_zero && lo >= 0.0 {
+        lo = 0.0;
+    }
+    if hi - lo < 1e-9 {
+        hi = lo + 1.0;
+    } else if from_zero {
+        hi += (hi - lo) * 0.05;
+    }
+    (lo, hi)
+}
+
+/// About five round-numbered ticks within [lo, hi].
+fn ticks(lo: f64, hi: f64) -> Vec<f64> {
+    let raw = (hi - lo) / 5.0;
+    let mag = 10f64.powf(raw.log10().floor());
+    let step = [1.0, 2.0, 5.0, 10.0].iter().map(|m| m * mag).find(|s| *s >= raw).unwrap_or(10.0 * mag);
+    let mut t = (lo / step).ceil() * step;
+    let mut out = Vec::new();
+    while t <= hi + step * 1e-6 {
+        out.push(t);
+        t += step;
+    }
+    out
+}
+
+fn fmt_tick(v: f64) -> String {
+    let s = format!("{v:.3}");
+    let s = s.trim_end_matches('0').trim_end_matches('.');
+    if s == "-0" { "0".to_string() } else { s.to_string() }
+}
+
+pub fn esc(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ticks_are_round_and_in_range() {
+        assert_eq!(ticks(0.0, 10.0), vec![0.0, 2.0, 4.0, 6.0, 8.0, 10.0]);
+        let t = ticks(0.013, 0.087);
+        assert!(t.iter().all(|v| (0.013..=0.087).contains(v)) && t.len() >= 3);
+        assert_eq!([0.0, 0.5, 1.0, 1500.0].map(fmt_tick), ["0", "0.5", "1", "1500"]);
+    }
+
+    #[test]
+    fn charts_are_well_formed_and_escape_labels() {
+        let pts = [(0.0, 1.0), (5.0, 2.0)];
+        let svg = line_chart("A & B", "Mbps", &[Series { label: "<run>".into(), points: &pts }], &[2.5]);
+        assert!(svg.starts_with("<svg") && svg.ends_with("</svg>"));
+        assert!(svg.contains("A &amp; B") && svg.contains("&lt;run&gt;"));
+        assert!(svg.contains("stroke-dasharray"), "rekey marker drawn");
+
+        let svg = box_chart("hs", "ms", &[("ECDH".into(), vec![1.0, 2.0, 3.0]), ("empty".into(), vec![])]);
+        assert_eq!(svg.matches("<rect x=").count(), 2, "frame + one box");
+    }
+}
@@ -0,0 +1,81 @@
+//! Tolerant CSV loading for the different per-run logs.
+//!
+//! The loggers in this repo append to existing files, so the header line can
+//! appear more than once, and some logs (rekey_*.csv, stream_*.csv) have no
+//! header at all. Rows are kept as strings and parsed on access.
+
+use anyhow::{Context, Result};
+use chrono::DateTime;
+use std::path::Path;
+
+pub struct Table {
+    /// Empty for headerless files; columns are then addressed by index.
+    pub headers: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    /// `None` when the file has no rows.
+    pub fn read(path: &Path) -> Result<Option<Table>> {
+        let mut rdr = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .from_path(path)
+            .with_context(|| format!("open {}", path.display()))?;
+        let mut records = Vec::new();
+        for rec in rdr.records() {
+            let rec = rec.with_context(|| format!("parse {}", path.display()))?;
+            let row: Vec<String> = rec.iter().map(|c| c.trim().to_string()).collect();
+            if row.iter().all(|c| c.is_empty()) {
+                continue;
+            }
+            records.push(row);
+        }
+        let Some(first) = records.first() else { return Ok(None) };
+
+        // A header row starts with a non-numeric, non-timestamp cell
+        let headerless = parse_ts(&first[0]).is_some();
+        let headers = if headerless { Vec::new() } else { records.remove(0) };
+        let rows: Vec<Vec<String>> = records.into_iter().filter(|r| *r != headers).collect();
+        if rows.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(Table { headers, rows }))
+    }
+
+    /// Index of the first column whose header matches one of `names`.
+    pub fn col(&self, names: &[&str]) -> Option<usize> {
+        names.iter().find_map(|n| self.headers.iter().position(|h| h == n))
+    }
+
+    pub fn f64_at(row: &[String], idx: usize) -> Option<f64> {
+        row.get(idx)?.parse::<f64>().ok().filter(|v| v.is_finite())
+    }
+
+    pub fn ts_at(row: &[String], idx: usize) -> Option<f64> {
+        parse_ts(row.get(idx)?)
+    }
+
+    /// (t, value) pairs for two columns, skipping rows where either fails to parse.
+    pub fn points(&self, t_idx: usize, v_idx: usize, scale: f64) -> Vec<(f64, f64)> {
+        self.rows
+            .iter()
+            .filter_map(|r| Some((Self::ts_at(r, t_idx)?, Self::f64_at(r, v_idx)? * scale)))
+            .collect()
+    }
+}
+
+/// Seconds from an RFC 3339 timestamp or a bare integer in nanoseconds
+/// (Group A logs the GStreamer system clock).
+pub fn parse_ts(s: &str) -> Option<f64> {
+    if let Ok(dt) = DateTime::parse_from_rfc3339(s) {
+        return Some(dt.timestamp() as f64 + dt.timestamp_subsec_nanos() as f64 / 1e9);
+    }
+    s.parse::<u64>().ok().map(|ns| ns as f64 / 1e9)
+}
+
+/// Whether a timestamp carries sub-second precision. Group G stamps whole
+/// seconds, which is too coarse to time a handshake or a frame.
+pub fn is_fine_ts(s: &str) -> bool {
+    s.parse::<u64>().is_ok() || (parse_ts(s).is_some() && s.contains('.'))
+}
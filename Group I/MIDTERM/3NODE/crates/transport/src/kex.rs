@@ -0,0 +1,125 @@
+//! Key-exchange mechanisms offered by the handshake.
+//!
+//! `Ecdh` is the original P-256 exchange. `HybridMlKem768` runs the same ECDH and
+//! additionally an ML-KEM-768 encapsulation to the client's ephemeral key; both
+//! shared secrets go into the HKDF, so the session keys stay secret as long as
+//! either P-256 or ML-KEM holds.
+
+use anyhow::{anyhow, Result};
+use ml_kem::kem::{Decapsulate, Encapsulate};
+use ml_kem::{Ciphertext, Encoded, EncodedSizeUser, KemCore, MlKem768};
+use rand::rngs::OsRng;
+use zeroize::Zeroizing;
+
+pub type KemDecapsulationKey = <MlKem768 as KemCore>::DecapsulationKey;
+type KemEncapsulationKey = <MlKem768 as KemCore>::EncapsulationKey;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum KeyExchange {
+    #[default]
+    Ecdh,
+    HybridMlKem768,
+}
+
+impl KeyExchange {
+    /// `method` column in metrics/handshake.csv
+    pub fn label(self) -> &'static str {
+        match self {
+            KeyExchange::Ecdh => "ECDH",
+            KeyExchange::HybridMlKem768 => "ECDH+ML-KEM-768",
+        }
+    }
+
+    /// Accepts the CLI spellings: ecdh | hybrid | ecdh+mlkem768
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "ecdh" | "p256" => Some(KeyExchange::Ecdh),
+            "hybrid" | "mlkem" | "ecdh+mlkem768" | "ecdh+ml-kem-768" => Some(KeyExchange::HybridMlKem768),
+            _ => None,
+        }
+    }
+
+    /// First byte of the client hello.
+    pub(crate) fn id(self) -> u8 {
+        match self {
+            KeyExchange::Ecdh => 1,
+            KeyExchange::HybridMlKem768 => 2,
+        }
+    }
+
+    pub(crate) fn from_id(id: u8) -> Option<Self> {
+        match id {
+            1 => Some(KeyExchange::Ecdh),
+            2 => Some(KeyExchange::HybridMlKem768),
+            _ => None,
+        }
+    }
+
+    /// HKDF info labels for the c2s / s2c chain keys. The hybrid uses its own so
+    /// a downgraded peer can never land on the same keys.
+    pub(crate) fn kdf_info(self) -> (&'static [u8], &'static [u8]) {
+        match self {
+            KeyExchange::Ecdh => (b"ctx:ck_c2s", b"ctx:ck_s2c"),
+            KeyExchange::HybridMlKem768 => (b"ctx:ck_c2s|ecdh+mlkem768", b"ctx:ck_s2c|ecdh+mlkem768"),
+        }
+    }
+}
+
+/// Client: fresh ML-KEM-768 key pair; the encapsulation key (1184 B) goes on the wire.
+pub(crate) fn kem_keypair() -> (KemDecapsulationKey, Vec<u8>) {
+    let (dk, ek) = MlKem768::generate(&mut OsRng);
+    (dk, ek.as_bytes().to_vec())
+}
+
+/// Server: encapsulate to the client's key. Returns (ciphertext (1088 B), shared secret).
+pub(crate) fn kem_encapsulate(ek_bytes: &[u8]) -> Result<(Vec<u8>, Zeroizing<[u8; 32]>)> {
+    let ek = Encoded::<KemEncapsulationKey>::try_from(ek_bytes).map_err(|_| anyhow!("bad ML-KEM key length"))?;
+    let (ct, ss) = KemEncapsulationKey::from_bytes(&ek)
+        .encapsulate(&mut OsRng)
+        .map_err(|_| anyhow!("ML-KEM encapsulate failed"))?;
+    Ok((ct.to_vec(), Zeroizing::new(ss.into())))
+}
+
+/// Client: recover the shared secret from the server's ciphertext.
+pub(crate) fn kem_decapsulate(dk: &KemDecapsulationKey, ct_bytes: &[u8]) -> Result<Zeroizing<[u8; 32]>> {
+    let ct = Ciphertext::<MlKem768>::try_from(ct_bytes).map_err(|_| anyhow!("bad ML-KEM ciphertext length"))?;
+    let ss = dk.decapsulate(&ct).map_err(|_| anyhow!("ML-KEM decapsulate failed"))?;
+    Ok(Zeroizing::new(ss.into()))
+}
+
+/// HKDF input keying material: ECDH secret, followed by the ML-KEM secret for the hybrid.
+pub(crate) fn combine(ecdh: &[u8; 32], kem: Option<&[u8; 32]>) -> Zeroizing<Vec<u8>> {
+    let mut ikm = Zeroizing::new(Vec::with_capacity(64));
+    ikm.extend_from_slice(ecdh);
+    if let Some(kem) = kem {
+        ikm.extend_from_slice(kem);
+    }
+    ikm
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kem_roundtrip_and_sizes() {
+        let (dk, ek) = kem_keypair();
+        assert_eq!(ek.len(), 1184);
+        let (ct, ss_server) = kem_encapsulate(&ek).unwrap();
+        assert_eq!(ct.len(), 1088);
+        assert_eq!(*kem_decapsulate(&dk, &ct).unwrap(), *ss_server);
+        assert!(kem_encapsulate(&ek[..100]).is_err());
+        assert!(kem_decapsulate(&dk, &ct[..100]).is_err());
+    }
+
+    #[test]
+    fn mechanism_ids_and_names_roundtrip() {
+        for k in [KeyExchange::Ecdh, KeyExchange::HybridMlKem768] {
+            assert_eq!(KeyExchange::from_id(k.id()), Some(k));
+            assert_eq!(KeyExchange::parse(k.label()), Some(k));
+        }
+        assert_eq!(KeyExchange::parse("hybrid"), Some(KeyExchange::HybridMlKem768));
+        assert_eq!(KeyExchange::from_id(0), None);
+        assert_ne!(KeyExchange::Ecdh.kdf_info(), KeyExchange::HybridMlKem768.kdf_info());
+    }
+}
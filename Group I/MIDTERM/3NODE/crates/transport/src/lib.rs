@@ -1,6 +1,8 @@
 /// Multi-connection receiver: for each incoming connection, spawn a handler with a new channel.
+/// With `identity`, every handshake proves this node holds the key it advertises.
 pub async fn run_multi_receiver_to_channel<Handler>(
     bind: &str,
+    identity: Option<Arc<discovery::Identity>>,
     metrics_opt: Option<metrics::Metrics>,
     handler: Handler,
 ) -> anyhow::Result<()>
@@ -10,278 +12,313 @@ where
     use anyhow::Context;
     let listener = tokio::net::TcpListener::bind(bind).await.context("bind")?;
     eprintln!("[recv] listening on {bind} (multi)");
+    serve_multi_receiver(listener, identity, metrics_opt, handler).await
+}
 
-    let handler = std::sync::Arc::new(handler);
-
+/// Accept loop of `run_multi_receiver_to_channel`.
+async fn serve_multi_receiver<L, Handler>(
+    listener: L,
+    identity: Option<Arc<discovery::Identity>>,
+    metrics_opt: Option<metrics::Metrics>,
+    handler: Handler,
+) -> anyhow::Result<()>
+where
+    L: Accept,
+    Handler: Fn(tokio::sync::mpsc::Receiver<Vec<u8>>, String) -> tokio::task::JoinHandle<()> + Send + Sync + 'static,
+{
+    use anyhow::Context;
     loop {
-        let (mut sock, peer) = listener.accept().await.context("accept")?;
+        let (sock, peer) = listener.accept().await.context("accept")?;
         let peer_str = peer.to_string();
         eprintln!("[recv] connection from {peer_str}");
 
-        let metrics_clone = metrics_opt.clone();
-        let handler = handler.clone();
-
         // Create a new channel for this connection
         let (frame_tx, frame_rx) = tokio::sync::mpsc::channel::<Vec<u8>>(32);
         // Spawn the playback handler for this connection
         let _playback_handle = (handler)(frame_rx, peer_str.clone());
 
-        tokio::spawn(async move {
-            let hs_start = std::time::Instant::now();
-            let (mut _tx_unused, mut rx, shared32, client_pub_len, server_pub_len) = match handshake_server(&mut sock).await.context("handshake_server") {
-                Ok(v) => v,
-                Err(e) => {
-                    if let Some(m) = &metrics_clone {
-                        let _ = m.record_handshake("ECDH", hs_start.elapsed(), 0, 0, false, Some(format!("{e}")), Some(peer_str.clone()));
-                    }
-                    eprintln!("[recv:{peer_str}] handshake failed: {e}");
-                    return;
-                }
-            };
-            let hs_d = hs_start.elapsed();
-            eprintln!("[recv:{peer_str}] handshake OK");
-            if let Some(m) = &metrics_clone {
-                let bytes_sent = (2 + server_pub_len + 8) as u64;
-                let bytes_received = (2 + client_pub_len + 8) as u64;
-                let _ = m.record_handshake("ECDH", hs_d, bytes_sent, bytes_received, true, None, Some(peer_str.clone()));
-            }
-
-            // spawn periodic system snapshot and latency summary if requested
-            if let Some(m) = metrics_clone.clone() {
-                tokio::spawn(async move {
-                    loop {
-                        tokio::time::sleep(std::time::Duration::from_secs(5)).await;
-                        let _ = m.record_system_snapshot();
-                        let _ = m.write_latency_summary();
-                    }
-                });
-            }
-
-            // throughput counters
-            let mut throughput_bytes: u64 = 0;
-            let mut throughput_frames: u64 = 0;
-            let mut throughput_last = std::time::Instant::now();
-
-            loop {
-                let mut hdr=[0u8;12];
-                if let Err(e)=sock.read_exact(&mut hdr).await { eprintln!("[recv:{peer_str}] closed: {e}"); break; }
-                let seq = u64::from_be_bytes(hdr[..8].try_into().unwrap()) as u32;
-                let ct_len = u32::from_be_bytes(hdr[8..12].try_into().unwrap()) as usize;
-                let mut ct = vec![0u8; ct_len];
-                if let Err(e) = sock.read_exact(&mut ct).await { eprintln!("[recv:{peer_str}] read error: {e}"); break; }
-                // measure rekey handling time for rekey control frames (decrypt + hkdf + rotate)
-                let handling_start = std::time::Instant::now();
-                let pt = match rx.decrypt(seq, &ct, &aad(seq)) {
-                    Ok(p) => { p }
-                    Err(e) => {
-                        eprintln!("[recv:{peer_str}] decrypt error: {e}");
-                        if let Some(m) = &metrics_clone {
-                            let _ = m.record_errors(0, 1, 0);
-                        }
-                        continue;
-                    }
-                };
-
-                if seq == REKEY_SEQ {
-                    if pt.len()!=REKEY_PT_LEN { eprintln!("[recv:{peer_str}] bad rekey payload length"); continue; }
-                    // extract fields
-                    let mut salt=[0u8;16]; salt.copy_from_slice(&pt[0..16]);
-                    let mut c2s=[0u8;8];   c2s.copy_from_slice(&pt[16..24]);
-                    let mut s2c=[0u8;8];   s2c.copy_from_slice(&pt[24..32]);
-
-                    // Server role: new RX is k_c2s with c2s_base
-                    let (k_c2s, _k_s2c) = hkdf_pair_from_shared(&shared32, &salt);
-                    rx = AesGcmCtx::new(k_c2s, c2s);
-
-                    // record total handling duration for REKEY
-                    if let Some(m) = &metrics_clone {
-                        let bytes_received = (12 + ct.len()) as u64; // header + ciphertext
-                        let handling_d = handling_start.elapsed();
-                        let _ = m.record_handshake("REKEY", handling_d, 0, bytes_received, true, None, Some(peer_str.clone()));
-                    }
-                    eprintln!("[recv:{peer_str}] rekey applied");
-                    // fallthrough to continue processing normal frames if any
-                }
-
-                // normal data frame: compute latency from 8B timestamp, strip it and forward H.264 AU
-                if pt.len() >= 8 {
-                    let mut tsb = [0u8;8]; tsb.copy_from_slice(&pt[0..8]);
-                    let sender_ns = u64::from_be_bytes(tsb);
-                    let now_ns = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_nanos() as u64;
-                    if now_ns >= sender_ns {
-                        let latency_ms = (now_ns - sender_ns) as f64 / 1e6;
-                        if let Some(m) = &metrics_clone {
-                            let _ = m.record_frame_latency_ms(latency_ms);
-                        }
-                    }
-                    // throughput accounting
-                    throughput_frames += 1;
-                    throughput_bytes += (ct.len() + 12) as u64;
-                    if let Some(m) = &metrics_clone {
-                        let now = std::time::Instant::now();
-                        let dur = now.duration_since(throughput_last).as_secs_f64();
-                        if dur >= 5.0 {
-                            let goodput_mbps = (throughput_bytes as f64 * 8.0) / (dur * 1e6);
-                            let _ = m.record_throughput(dur, goodput_mbps, throughput_frames);
-                            throughput_last = now;
-                            throughput_bytes = 0;
-                            throughput_frames = 0;
-                        }
-                    }
-                }
-                let h264 = if pt.len()>=8 { pt[8..].to_vec() } else { Vec::new() };
-                if frame_tx.send(h264).await.is_err() { break; }
-            }
-            eprintln!("[recv:{peer_str}] connection handler exiting");
-        });
+        tokio::spawn(receive_session(sock, peer_str, identity.clone(), frame_tx, metrics_opt.clone()));
     }
 }
+pub mod kex;
+pub mod nal;
+pub mod ratchet;
+pub mod rtp;
+pub mod srtp;
+#[cfg(test)]
+mod netsim;
+
 use anyhow::{anyhow, Context, Result};
-use hkdf::Hkdf;
 use p256::ecdh::EphemeralSecret;
+use p256::elliptic_curve::sec1::ToEncodedPoint;
 use p256::{EncodedPoint, PublicKey};
+use sha2::{Digest, Sha256};
+use rand::rngs::OsRng;
 use rand::RngCore;
-use sha2::Sha256;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::net::{TcpListener, TcpStream};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream, UdpSocket};
 use std::convert::TryFrom;
 use tokio::time::{Duration, Instant};
-use aead::AesGcmCtx;
 use tokio::sync::mpsc;
+use std::sync::{Arc, OnceLock};
+use zeroize::Zeroize;
+pub use kex::KeyExchange;
+pub use nal::NalProtection;
+use ratchet::{Chain, FrameHeader, HDR_LEN, MAX_CT_LEN, KIND_DATA, KIND_DATA_SELECTIVE, KIND_DH_OFFER, KIND_DH_REPLY, KIND_KEYFRAME_REQ, KIND_REKEY, KIND_RTP_STREAM};
+use ratchet::{KEYFRAME_AUTH_FAIL, KEYFRAME_GAP};
+
+/// Called on the sender when the receiver asks for a keyframe (typically forces
+/// an IDR in the capture pipeline; rate limiting is up to the callee).
+pub type KeyframeHook = std::sync::Arc<dyn Fn() + Send + Sync>;
+
+/// Sender-side session settings.
+#[derive(Clone, Default)]
+pub struct SenderOptions {
+    /// Timed rekey interval (None = never)
+    pub rekey_every: Option<Duration>,
+    /// Every Nth rekey mixes in a fresh ECDH
+    pub dh_every: Option<u32>,
+    pub kex: KeyExchange,
+    /// Which parts of each H.264 access unit are encrypted
+    pub protect: NalProtection,
+    pub keyframe: Option<KeyframeHook>,
+    /// RTP mode: append `SSRC key||salt` (hex) per stream so external tools
+    /// such as srtpdec can decrypt it
+    pub srtp_key_log: Option<std::path::PathBuf>,
+    /// RTP mode: UDP destination of the SRTP stream (default: the handshake peer)
+    pub rtp_dest: Option<String>,
+    /// Fingerprint the receiver's static node key must match (a discovered
+    /// peer's beacon); None accepts any receiver
+    pub peer_fingerprint: Option<[u8; 32]>,
+}
 
 const SALT: &[u8] = b"salt:ECE4301-midterm-2025";
-const KDF_INFO_C2S: &[u8] = b"ctx:k_c2s";
-const KDF_INFO_S2C: &[u8] = b"ctx:k_s2c";
-const REKEY_SEQ: u32 = u32::MAX;               // reserved sequence for control frame
-const REKEY_PT_LEN: usize = 16 + 8 + 8;
 
-#[inline] fn aad(seq: u32) -> [u8; 4] { seq.to_be_bytes() }
+/// A peer that has not finished the key exchange by then is dropped, so a
+/// stalled connection cannot hold a sender (or a receiver task) forever.
+pub const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+async fn with_handshake_timeout<T>(handshake: impl std::future::Future<Output = Result<T>>) -> Result<T> {
+    tokio::time::timeout(HANDSHAKE_TIMEOUT, handshake)
+        .await
+        .map_err(|_| anyhow!("handshake timed out after {HANDSHAKE_TIMEOUT:?}"))?
+}
+
+/// What metrics/handshake.csv records about a completed handshake.
+trait HandshakeStats {
+    fn kex(&self) -> KeyExchange;
+    /// (bytes sent, bytes received)
+    fn hs_bytes(&self) -> (usize, usize);
+}
+
+/// `with_handshake_timeout`, recorded in handshake.csv when metrics are on. A
+/// failure is filed under `mechanism` once it is known: the sender sets it up
+/// front, the receiver when the client hello names it; before that, "unknown".
+async fn recorded_handshake<T: HandshakeStats>(
+    handshake: impl std::future::Future<Output = Result<T>>,
+    mechanism: &OnceLock<KeyExchange>,
+    peer: &str,
+    metrics_opt: Option<&metrics::Metrics>,
+) -> Result<(T, Duration)> {
+    let start = Instant::now();
+    let res = with_handshake_timeout(handshake).await;
+    let elapsed = start.elapsed();
+    if let Some(m) = metrics_opt {
+        let _ = match &res {
+            Ok(sess) => {
+                let (sent, received) = sess.hs_bytes();
+                m.record_handshake(sess.kex().label(), elapsed, sent as u64, received as u64, true, None, Some(peer.to_string()))
+            }
+            Err(e) => {
+                let method = mechanism.get().map_or("unknown", |k| k.label());
+                m.record_handshake(method, elapsed, 0, 0, false, Some(format!("{e:#}")), Some(peer.to_string()))
+            }
+        };
+    }
+    res.map(|sess| (sess, elapsed))
+}
+
+/// System snapshot and latency summary every 5 s while metrics are on; the
+/// task stops when this is dropped.
+struct MetricsTicker(Option<tokio::task::JoinHandle<()>>);
+
+impl MetricsTicker {
+    fn start(metrics_opt: Option<&metrics::Metrics>) -> Self {
+        MetricsTicker(metrics_opt.cloned().map(|m| {
+            tokio::spawn(async move {
+                loop {
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                    let _ = m.record_system_snapshot();
+                    let _ = m.write_latency_summary();
+                }
+            })
+        }))
+    }
+}
+
+impl Drop for MetricsTicker {
+    fn drop(&mut self) {
+        if let Some(task) = &self.0 {
+            task.abort();
+        }
+    }
+}
+
+/// Goodput counters, written to throughput.csv about every 5 s.
+struct Throughput {
+    bytes: u64,
+    frames: u64,
+    since: Instant,
+}
+
+impl Throughput {
+    fn new() -> Self {
+        Throughput { bytes: 0, frames: 0, since: Instant::now() }
+    }
+
+    fn add(&mut self, bytes: u64, frames: u64, metrics_opt: Option<&metrics::Metrics>) {
+        self.bytes += bytes;
+        self.frames += frames;
+        if let Some(m) = metrics_opt {
+            let now = Instant::now();
+            let dur = now.duration_since(self.since).as_secs_f64();
+            if dur >= 5.0 {
+                let goodput_mbps = (self.bytes as f64 * 8.0) / (dur * 1e6);
+                let _ = m.record_throughput(dur, goodput_mbps, self.frames);
+                *self = Throughput::new();
+            }
+        }
+    }
+}
+
+/// Read the control frames coming back on a session (DH replies, keyframe
+/// requests) on a side task, so they can be selected on next to the media.
+fn spawn_control_reader<R>(mut rd: R) -> (tokio::task::JoinHandle<()>, mpsc::Receiver<(FrameHeader, Vec<u8>)>)
+where
+    R: AsyncRead + Unpin + Send + 'static,
+{
+    let (ctl_tx, ctl_rx) = mpsc::channel(4);
+    let reader = tokio::spawn(async move {
+        while let Ok(frame) = read_frame(&mut rd).await {
+            if ctl_tx.send(frame).await.is_err() { break; }
+        }
+    });
+    (reader, ctl_rx)
+}
+
+/// Pass a receiver's keyframe request on to the encoder, if it can take one.
+fn keyframe_requested(reason: u8, keyframe: Option<&KeyframeHook>) {
+    let why = if reason == KEYFRAME_AUTH_FAIL { "auth failure" } else { "loss" };
+    eprintln!("[send] receiver requested a keyframe ({why})");
+    if let Some(hook) = keyframe {
+        hook();
+    }
+}
+
+/// Where the receivers take connections from: a TCP listener, or the
+/// simulated network in `netsim`.
+trait Accept {
+    type Stream: AsyncRead + AsyncWrite + Unpin + Send + 'static;
+    async fn accept(&self) -> std::io::Result<(Self::Stream, std::net::SocketAddr)>;
+}
+
+impl Accept for TcpListener {
+    type Stream = TcpStream;
+    async fn accept(&self) -> std::io::Result<(TcpStream, std::net::SocketAddr)> {
+        TcpListener::accept(self).await
+    }
+}
 //cutoffstart
 pub async fn run_sender_from_channel(
     host: &str,
-    rekey_every: Option<Duration>,
-    mut frame_rx: mpsc::Receiver<Vec<u8>>,
+    opts: SenderOptions,
+    frame_rx: mpsc::Receiver<Vec<u8>>,
     metrics_opt: Option<metrics::Metrics>,
 ) -> anyhow::Result<()> {
     use anyhow::Context;
-    use rand::RngCore;
-    use std::time::{SystemTime, UNIX_EPOCH};
-    let mut sock = tokio::net::TcpStream::connect(host).await.context("connect")?;
+    let sock = tokio::net::TcpStream::connect(host).await.context("connect")?;
     eprintln!("[send] connected to {host}");
+    run_sender_on(sock, host, opts, frame_rx, metrics_opt).await
+}
+
+/// `run_sender_from_channel` over an established connection.
+async fn run_sender_on<S>(
+    mut sock: S,
+    host: &str,
+    opts: SenderOptions,
+    mut frame_rx: mpsc::Receiver<Vec<u8>>,
+    metrics_opt: Option<metrics::Metrics>,
+) -> anyhow::Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let SenderOptions { rekey_every, dh_every, kex, protect, keyframe, peer_fingerprint, .. } = opts;
+    let handshake = handshake_client(&mut sock, kex, peer_fingerprint.as_ref());
+    let (mut sess, hs_d) = recorded_handshake(handshake, &OnceLock::from(kex), host, metrics_opt.as_ref())
+        .await
+        .context("handshake_client")?;
+    eprintln!("[send] handshake OK ({}, {hs_d:?}, protect={})", kex.label(), protect.label());
+    sess.set_protection(protect);
+
+    // The receiver only talks back to answer DH offers and to ask for keyframes;
+    // read those on a side task.
+    let (rd, mut wr) = tokio::io::split(sock);
+    let (reader, mut ctl_rx) = spawn_control_reader(rd);
 
-    let hs_start = Instant::now();
-    let (mut tx, _rx_unused, shared32, client_pub_len, server_pub_len) = match handshake_client(&mut sock).await.context("handshake_client") {
-        Ok(v) => v,
-        Err(e) => {
-            // no metrics instance available, just return error
-            return Err(e);
-        }
-    };
-    let hs_d = hs_start.elapsed();
-    eprintln!("[send] handshake OK");
-    // record handshake if metrics provided via global app wiring
-    if let Some(m) = &metrics_opt {
-        let bytes_sent = (2 + client_pub_len + 8) as u64;
-        let bytes_received = (2 + server_pub_len + 8) as u64;
-        let _ = m.record_handshake("ECDH", hs_d, bytes_sent, bytes_received, true, None, Some(host.to_string()));
-    }
     let mut next_rekey_at = rekey_every.map(|d| Instant::now() + d);
-    let mut seq: u32 = 0;
-    // throughput counters
-    let mut throughput_bytes: u64 = 0;
-    let mut throughput_frames: u64 = 0;
-    let mut throughput_last = Instant::now();
-
-    // spawn periodic system snapshot and latency summary if metrics requested
-    if let Some(m) = metrics_opt.clone() {
-        tokio::spawn(async move {
-            loop {
-                tokio::time::sleep(Duration::from_secs(5)).await;
-                let _ = m.record_system_snapshot();
-                let _ = m.write_latency_summary();
-            }
-        });
-    }
+    // in-flight DH rekey: (offer time, bytes sent so far)
+    let mut dh_pending: Option<(Instant, usize)> = None;
+    let mut throughput = Throughput::new();
+    let _ticker = MetricsTicker::start(metrics_opt.as_ref());
 
     loop {
-        // handle timer first (if set), else wait for a frame
-        if let Some(when) = next_rekey_at {
-            tokio::select! {
-                _ = tokio::time::sleep_until(when) => {
-                    // send rekey control frame (salt + bases)
-                    let mut salt = [0u8;16]; let mut c2s=[0u8;8]; let mut s2c=[0u8;8];
-                    rand::rngs::OsRng.fill_bytes(&mut salt);
-                    rand::rngs::OsRng.fill_bytes(&mut c2s);
-                    rand::rngs::OsRng.fill_bytes(&mut s2c);
-                    let mut pt=[0u8;REKEY_PT_LEN]; pt[0..16].copy_from_slice(&salt); pt[16..24].copy_from_slice(&c2s); pt[24..32].copy_from_slice(&s2c);
-                    // measure full rekey send time (rng, encrypt, write, rotate)
-                    let rekey_start = Instant::now();
-                    let ct = tx.encrypt(REKEY_SEQ, &pt, &aad(REKEY_SEQ))?;
-                    let mut hdr=[0u8;12]; hdr[..8].copy_from_slice(&(REKEY_SEQ as u64).to_be_bytes()); hdr[8..12].copy_from_slice(&(u32::try_from(ct.len()).unwrap()).to_be_bytes());
-                    sock.write_all(&hdr).await?; sock.write_all(&ct).await?;
-                    // rotate local sender key/base
-                    let (k_c2s,_)=hkdf_pair_from_shared(&shared32,&salt); tx = aead::AesGcmCtx::new(k_c2s,c2s);
-                    // record rekey event in metrics (if present) with measured total rekey duration
-                    if let Some(m) = &metrics_opt {
-                        let bytes_sent = (12 + ct.len()) as u64; // hdr + ct
-                        let rekey_d = rekey_start.elapsed();
-                        let _ = m.record_handshake("REKEY", rekey_d, bytes_sent, 0, true, None, Some(host.to_string()));
+        tokio::select! {
+            _ = tokio::time::sleep_until(next_rekey_at.unwrap_or_else(Instant::now)), if next_rekey_at.is_some() => {
+                // measure full rekey send time (seal, write, ratchet)
+                let rekey_start = Instant::now();
+                match sess.start_rekey(&mut wr, dh_every).await? {
+                    RekeyStart::Offered(n) => {
+                        dh_pending = Some((rekey_start, n));
+                        eprintln!("[send] DH offer sent (epoch {})", sess.tx.epoch());
                     }
-                    seq=0; eprintln!("[send] rekey sent+applied");
-                    next_rekey_at = rekey_every.map(|d| Instant::now() + d);
-                }
-                maybe = frame_rx.recv() => {
-                    let frame = match maybe { Some(f) => f, None => break };
-                    let now_ns = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos() as u64;
-                    let mut pt = Vec::with_capacity(8 + frame.len());
-                    pt.extend_from_slice(&now_ns.to_be_bytes()); // 8B ts
-                    pt.extend_from_slice(&frame);
-                    let ct = tx.encrypt(seq, &pt, &aad(seq))?;
-                    let mut hdr=[0u8;12]; hdr[..8].copy_from_slice(&(seq as u64).to_be_bytes()); hdr[8..12].copy_from_slice(&(u32::try_from(ct.len()).unwrap()).to_be_bytes());
-                    sock.write_all(&hdr).await?; sock.write_all(&ct).await?;
-                    // throughput accounting
-                    throughput_frames += 1;
-                    throughput_bytes += (ct.len() + 12) as u64;
-                    if let Some(m) = &metrics_opt {
-                        let now = Instant::now();
-                        let dur = now.duration_since(throughput_last).as_secs_f64();
-                        if dur >= 5.0 {
-                            let goodput_mbps = (throughput_bytes as f64 * 8.0) / (dur * 1e6);
-                            let _ = m.record_throughput(dur, goodput_mbps, throughput_frames);
-                            throughput_last = now;
-                            throughput_bytes = 0;
-                            throughput_frames = 0;
+                    RekeyStart::Advanced(n) => {
+                        // record rekey event in metrics (if present) with measured total rekey duration
+                        if let Some(m) = &metrics_opt {
+                            let rekey_d = rekey_start.elapsed();
+                            let _ = m.record_handshake("REKEY", rekey_d, n as u64, 0, true, None, Some(host.to_string()));
                         }
+                        eprintln!("[send] rekey sent+applied (epoch {})", sess.tx.epoch());
                     }
-                    seq = seq.wrapping_add(1);
                 }
+                next_rekey_at = rekey_every.map(|d| Instant::now() + d);
             }
-        } else {
-            // no rekey timer — just send frames
-            let Some(frame) = frame_rx.recv().await else { break };
-            let now_ns = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos() as u64;
-            let mut pt = Vec::with_capacity(8 + frame.len());
-            pt.extend_from_slice(&now_ns.to_be_bytes());
-            pt.extend_from_slice(&frame);
-            let ct = tx.encrypt(seq, &pt, &aad(seq))?;
-            let mut hdr=[0u8;12]; hdr[..8].copy_from_slice(&(seq as u64).to_be_bytes()); hdr[8..12].copy_from_slice(&(u32::try_from(ct.len()).unwrap()).to_be_bytes());
-            sock.write_all(&hdr).await?; sock.write_all(&ct).await?;
-            throughput_frames += 1;
-            throughput_bytes += (ct.len() + 12) as u64;
-            if let Some(m) = &metrics_opt {
-                let now = Instant::now();
-                let dur = now.duration_since(throughput_last).as_secs_f64();
-                if dur >= 5.0 {
-                    let goodput_mbps = (throughput_bytes as f64 * 8.0) / (dur * 1e6);
-                    let _ = m.record_throughput(dur, goodput_mbps, throughput_frames);
-                    throughput_last = now;
-                    throughput_bytes = 0;
-                    throughput_frames = 0;
+            Some((hdr, ct)) = ctl_rx.recv() => {
+                match sess.on_control(&mut wr, &hdr, &ct).await? {
+                    Control::Rekeyed(n) => {
+                        if let Some((started, offered)) = dh_pending.take() {
+                            if let Some(m) = &metrics_opt {
+                                let bytes_sent = (offered + n) as u64;
+                                let bytes_received = (HDR_LEN + ct.len()) as u64;
+                                let _ = m.record_handshake("REKEY_DH", started.elapsed(), bytes_sent, bytes_received, true, None, Some(host.to_string()));
+                            }
+                        }
+                        eprintln!("[send] DH rekey sent+applied (epoch {})", sess.tx.epoch());
+                    }
+                    Control::KeyframeRequested(reason) => keyframe_requested(reason, keyframe.as_ref()),
                 }
             }
-            seq = seq.wrapping_add(1);
+            maybe = frame_rx.recv() => {
+                let frame = match maybe { Some(f) => f, None => break };
+                let n = sess.send_data(&mut wr, &frame).await?;
+                throughput.add(n as u64, 1, metrics_opt.as_ref());
+            }
         }
     }
+    // Half-close and wait for the receiver to hang up: dropping the socket with a
+    // reply still unread resets the connection, which can discard our last frames.
+    let _ = wr.shutdown().await;
+    let _ = tokio::time::timeout(Duration::from_secs(1), async { while ctl_rx.recv().await.is_some() {} }).await;
+    reader.abort();
     eprintln!("[send] video channel closed; done");
     Ok(())
 }
@@ -296,254 +333,799 @@ pub async fn run_receiver_to_channel(
     let listener = tokio::net::TcpListener::bind(bind).await.context("bind")?;
     eprintln!("[recv] listening on {bind}");
 
+    serve_receiver(listener, frame_tx, metrics_opt).await
+}
+
+/// Accept loop of `run_receiver_to_channel`: every connection feeds `frame_tx`.
+async fn serve_receiver<L: Accept>(
+    listener: L,
+    frame_tx: mpsc::Sender<Vec<u8>>,
+    metrics_opt: Option<metrics::Metrics>,
+) -> anyhow::Result<()> {
+    loop {
+        let (sock, peer) = listener.accept().await.context("accept")?;
+        eprintln!("[recv] connection from {peer}");
+        // Spawn a task to handle this connection independently so we can accept more.
+        tokio::spawn(receive_session(sock, peer.to_string(), None, frame_tx.clone(), metrics_opt.clone()));
+    }
+}
+
+/// One receiving connection: handshake, then decrypt frames into `frame_tx`
+/// until the peer goes away or the channel closes.
+async fn receive_session<S>(
+    mut sock: S,
+    peer: String,
+    identity: Option<Arc<discovery::Identity>>,
+    frame_tx: mpsc::Sender<Vec<u8>>,
+    metrics_opt: Option<metrics::Metrics>,
+) where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let mechanism = OnceLock::new();
+    let handshake = handshake_server(&mut sock, identity.as_deref(), &mechanism);
+    let mut sess = match recorded_handshake(handshake, &mechanism, &peer, metrics_opt.as_ref()).await {
+        Ok((sess, _)) => sess,
+        Err(e) => {
+            eprintln!("[recv:{peer}] handshake failed: {e:#}");
+            return;
+        }
+    };
+    eprintln!("[recv:{peer}] handshake OK ({})", sess.kex.label());
+
+    let _ticker = MetricsTicker::start(metrics_opt.as_ref());
+    let mut throughput = Throughput::new();
+
+    loop {
+        let (hdr, ct) = match read_frame(&mut sock).await {
+            Ok(v) => v,
+            Err(e) => { eprintln!("[recv:{peer}] closed: {e}"); break; }
+        };
+        // measure rekey handling time for rekey control frames (decrypt + hkdf + rotate)
+        let handling_start = Instant::now();
+        let pt = match sess.handle(&mut sock, &hdr, &ct).await {
+            Ok(Received::Data(p)) => p,
+            Ok(Received::Rekeyed { epoch, dh }) => {
+                // record total handling duration for REKEY
+                if let Some(m) = &metrics_opt {
+                    let bytes_received = (HDR_LEN + ct.len()) as u64; // header + ciphertext
+                    let handling_d = handling_start.elapsed();
+                    let method = if dh { "REKEY_DH" } else { "REKEY" };
+                    let _ = m.record_handshake(method, handling_d, 0, bytes_received, true, None, Some(peer.clone()));
+                }
+                eprintln!("[recv:{peer}] rekey applied (epoch {epoch}, dh={dh})");
+                continue;
+            }
+            Ok(Received::DhOffered) => { eprintln!("[recv:{peer}] answered DH offer"); continue; }
+            Err(e) => {
+                eprintln!("[recv:{peer}] decrypt error: {e}");
+                if let Some(m) = &metrics_opt {
+                    let _ = m.record_errors(0, 1, 0);
+                }
+                continue;
+            }
+        };
+        let lost = sess.take_lost();
+        if lost > 0 {
+            eprintln!("[recv:{peer}] {lost} frame(s) lost; keyframe requested");
+            if let Some(m) = &metrics_opt {
+                let _ = m.record_errors(lost, 0, 0);
+            }
+        }
+
+        // normal data frame: compute latency from 8B timestamp, strip it and forward H.264 AU
+        if pt.len() >= 8 {
+            let mut tsb = [0u8;8]; tsb.copy_from_slice(&pt[0..8]);
+            let sender_ns = u64::from_be_bytes(tsb);
+            let now_ns = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_nanos() as u64;
+            if now_ns >= sender_ns {
+                let latency_ms = (now_ns - sender_ns) as f64 / 1e6;
+                if let Some(m) = &metrics_opt {
+                    let _ = m.record_frame_latency_ms(latency_ms);
+                }
+            }
+            throughput.add((ct.len() + HDR_LEN) as u64, 1, metrics_opt.as_ref());
+        }
+        let h264 = if pt.len()>=8 { pt[8..].to_vec() } else { Vec::new() };
+        if frame_tx.send(h264).await.is_err() { break; }
+    }
+    eprintln!("[recv:{peer}] connection handler exiting");
+}
+
+/// SEND (RTP): handshake on TCP as usual, then H.264 as RTP (RFC 6184) under
+/// SRTP AES-128-GCM (RFC 7714) over UDP to the same host:port (or `rtp_dest`).
+/// The TCP connection stays open for keyframe requests; closing it ends the stream.
+/// SRTP runs under one master key for the whole stream, so rekeying and
+/// selective protection are refused rather than quietly dropped.
+pub async fn run_srtp_sender(
+    host: &str,
+    opts: SenderOptions,
+    mut frame_rx: mpsc::Receiver<Vec<u8>>,
+    metrics_opt: Option<metrics::Metrics>,
+) -> anyhow::Result<()> {
+    use anyhow::Context;
+    let SenderOptions { rekey_every, dh_every, kex, protect, keyframe, srtp_key_log, rtp_dest, peer_fingerprint } = opts;
+    if rekey_every.is_some() || dh_every.is_some() {
+        return Err(anyhow!("rekeying is not supported in RTP mode: SRTP keeps one master key per stream"));
+    }
+    if protect != NalProtection::Full {
+        return Err(anyhow!("--protect {} is not supported in RTP mode: SRTP encrypts every payload", protect.label()));
+    }
+
+    let mut sock = tokio::net::TcpStream::connect(host).await.context("connect")?;
+    let peer = sock.peer_addr().context("peer address")?;
+    eprintln!("[send] connected to {host}");
+
+    let handshake = handshake_client(&mut sock, kex, peer_fingerprint.as_ref());
+    let (mut sess, hs_d) = recorded_handshake(handshake, &OnceLock::from(kex), host, metrics_opt.as_ref())
+        .await
+        .context("handshake_client")?;
+
+    // SRTP master key from the c2s chain; the receiver derives the same one
+    let ssrc = OsRng.next_u32();
+    let master = srtp::MasterKey::from_chain(&sess.tx);
+    let mut srtp_ctx = srtp::Context::new(&master);
+    if let Some(path) = &srtp_key_log {
+        srtp::append_key_log(path, ssrc, &master).with_context(|| format!("write {}", path.display()))?;
+    }
+    drop(master);
+    sess.announce_rtp(&mut sock, ssrc).await?;
+
+    let dest = match &rtp_dest {
+        Some(d) => tokio::net::lookup_host(d).await?.next().ok_or_else(|| anyhow!("no address for {d}"))?,
+        None => peer,
+    };
+    let udp = UdpSocket::bind(if dest.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" }).await.context("bind udp")?;
+    udp.connect(dest).await.context("connect udp")?;
+    eprintln!("[send] handshake OK ({}, {hs_d:?}); SRTP AES-128-GCM to udp://{dest} ssrc={ssrc:08x}", kex.label());
+
+    let (rd, mut wr) = sock.into_split();
+    let (reader, mut ctl_rx) = spawn_control_reader(rd);
+
+    let mut pay = rtp::H264Packetizer::new(ssrc, rtp::DEFAULT_PAYLOAD_TYPE, rtp::DEFAULT_MAX_PAYLOAD);
+    let clock = Instant::now();
+    let mut throughput = Throughput::new();
+    let _ticker = MetricsTicker::start(metrics_opt.as_ref());
+
+    loop {
+        tokio::select! {
+            ctl = ctl_rx.recv() => {
+                let Some((hdr, ct)) = ctl else {
+                    eprintln!("[send] receiver closed the session");
+                    break;
+                };
+                if let Control::KeyframeRequested(reason) = sess.on_control(&mut wr, &hdr, &ct).await? {
+                    keyframe_requested(reason, keyframe.as_ref());
+                }
+            }
+            maybe = frame_rx.recv() => {
+                let frame = match maybe { Some(f) => f, None => break };
+                let ts = (clock.elapsed().as_nanos() * rtp::H264_CLOCK_RATE as u128 / 1_000_000_000) as u32;
+                let mut sent = 0;
+                for pkt in pay.packetize(&frame, ts) {
+                    let pkt = srtp_ctx.protect(&pkt)?;
+                    sent += udp.send(&pkt).await.context("udp send")? as u64;
+                }
+                throughput.add(sent, 1, metrics_opt.as_ref());
+            }
+        }
+    }
+    reader.abort();
+    eprintln!("[send] video channel closed; done");
+    Ok(())
+}
+
+/// Receive state of one announced SRTP stream.
+struct RtpStream {
+    srtp: srtp::Context,
+    depay: rtp::H264Depacketizer,
+    /// Packets that failed to open since the last authentic one
+    failed_since_ok: u64,
+    /// Keyframe request reasons, sent on by the stream's TCP session
+    keyframe_tx: mpsc::Sender<u8>,
+    peer: String,
+    /// Address of the session that announced the stream; packets from
+    /// anywhere else are dropped before they reach the SRTP context
+    peer_ip: std::net::IpAddr,
+}
+
+type RtpStreams = std::sync::Arc<std::sync::Mutex<std::collections::HashMap<u32, RtpStream>>>;
+
+/// RECV (RTP): handshakes on TCP `bind`, SRTP on UDP at the same address. Each
+/// sender announces its SSRC over its session; an SSRC that is already in use
+/// is refused, and packets are only taken from the announcing host. Access
+/// units from all streams go to `frame_tx`. There is no send timestamp in RTP,
+/// so no latency metrics.
+pub async fn run_srtp_receiver_to_channel(
+    bind: &str,
+    frame_tx: mpsc::Sender<Vec<u8>>,
+    metrics_opt: Option<metrics::Metrics>,
+) -> anyhow::Result<()> {
+    use anyhow::Context;
+    let listener = TcpListener::bind(bind).await.context("bind")?;
+    let udp = UdpSocket::bind(listener.local_addr()?).await.context("bind udp")?;
+    eprintln!("[recv] listening on {bind} (SRTP over udp)");
+    let _ticker = MetricsTicker::start(metrics_opt.as_ref());
+
+    let streams = RtpStreams::default();
+    tokio::select! {
+        r = accept_rtp_sessions(listener, streams.clone(), metrics_opt.clone()) => r,
+        r = receive_srtp(udp, streams, frame_tx, metrics_opt) => r,
+    }
+}
+
+async fn accept_rtp_sessions(listener: TcpListener, streams: RtpStreams, metrics_opt: Option<metrics::Metrics>) -> Result<()> {
     loop {
         let (mut sock, peer) = listener.accept().await.context("accept")?;
         let peer_str = peer.to_string();
         eprintln!("[recv] connection from {peer_str}");
-
-        let tx_clone = frame_tx.clone();
+        let streams = streams.clone();
         let metrics_clone = metrics_opt.clone();
 
-        // Spawn a task to handle this connection independently so we can accept more.
         tokio::spawn(async move {
-            let hs_start = Instant::now();
-            let (mut _tx_unused, mut rx, shared32, client_pub_len, server_pub_len) = match handshake_server(&mut sock).await.context("handshake_server") {
-                Ok(v) => v,
+            let mechanism = OnceLock::new();
+            let handshake = handshake_server(&mut sock, None, &mechanism);
+            let mut sess = match recorded_handshake(handshake, &mechanism, &peer_str, metrics_clone.as_ref()).await {
+                Ok((sess, _)) => sess,
                 Err(e) => {
-                    if let Some(m) = &metrics_clone {
-                        let _ = m.record_handshake("ECDH", hs_start.elapsed(), 0, 0, false, Some(format!("{e}")), Some(peer_str.clone()));
-                    }
-                    eprintln!("[recv:{peer}] handshake failed: {e}");
+                    eprintln!("[recv:{peer}] handshake failed: {e:#}");
                     return;
                 }
             };
-            let hs_d = hs_start.elapsed();
-            eprintln!("[recv:{peer}] handshake OK");
-            if let Some(m) = &metrics_clone {
-                let bytes_sent = (2 + server_pub_len + 8) as u64;
-                let bytes_received = (2 + client_pub_len + 8) as u64;
-                let _ = m.record_handshake("ECDH", hs_d, bytes_sent, bytes_received, true, None, Some(peer_str.clone()));
-            }
+            let ssrc = match sess.read_rtp_announce(&mut sock).await {
+                Ok(s) => s,
+                Err(e) => { eprintln!("[recv:{peer}] no RTP stream: {e}"); return; }
+            };
 
-            // spawn periodic system snapshot and latency summary if requested
-            if let Some(m) = metrics_clone.clone() {
-                tokio::spawn(async move {
-                    loop {
-                        tokio::time::sleep(Duration::from_secs(5)).await;
-                        let _ = m.record_system_snapshot();
-                        let _ = m.write_latency_summary();
-                    }
-                });
+            let (keyframe_tx, mut keyframe_rx) = mpsc::channel::<u8>(4);
+            let master = srtp::MasterKey::from_chain(&sess.rx);
+            let stream = RtpStream {
+                srtp: srtp::Context::new(&master),
+                depay: rtp::H264Depacketizer::new(),
+                failed_since_ok: 0,
+                keyframe_tx,
+                peer: peer_str.clone(),
+                peer_ip: peer.ip(),
+            };
+            drop(master);
+            // Never hand a live SSRC to another session: it would take over the
+            // stream, and the first session's cleanup would then remove it
+            match streams.lock().unwrap().entry(ssrc) {
+                std::collections::hash_map::Entry::Occupied(_) => {
+                    eprintln!("[recv:{peer}] ssrc {ssrc:08x} is already in use; refused");
+                    return;
+                }
+                std::collections::hash_map::Entry::Vacant(slot) => {
+                    slot.insert(stream);
+                }
             }
+            if let Err(e) = sess.confirm_rtp(&mut sock, ssrc).await {
+                eprintln!("[recv:{peer}] could not confirm the stream: {e}");
+                streams.lock().unwrap().remove(&ssrc);
+                return;
+            }
+            eprintln!("[recv:{peer}] handshake OK ({}); SRTP stream ssrc={ssrc:08x}", sess.kex.label());
 
-            // throughput counters
-            let mut throughput_bytes: u64 = 0;
-            let mut throughput_frames: u64 = 0;
-            let mut throughput_last = Instant::now();
-
+            // The session only carries keyframe requests from here on; the
+            // sender closing it ends the stream.
+            let (rd, mut wr) = sock.into_split();
+            let (reader, mut ctl_rx) = spawn_control_reader(rd);
             loop {
-                let mut hdr=[0u8;12];
-                if let Err(e)=sock.read_exact(&mut hdr).await { eprintln!("[recv:{peer}] closed: {e}"); break; }
-                let seq = u64::from_be_bytes(hdr[..8].try_into().unwrap()) as u32;
-                let ct_len = u32::from_be_bytes(hdr[8..12].try_into().unwrap()) as usize;
-                let mut ct = vec![0u8; ct_len];
-                if let Err(e) = sock.read_exact(&mut ct).await { eprintln!("[recv:{peer}] read error: {e}"); break; }
-                // measure rekey handling time for rekey control frames (decrypt + hkdf + rotate)
-                let handling_start = Instant::now();
-                let pt = match rx.decrypt(seq, &ct, &aad(seq)) {
-                    Ok(p) => { p }
-                    Err(e) => {
-                        eprintln!("[recv:{peer}] decrypt error: {e}");
-                        if let Some(m) = &metrics_clone {
-                            let _ = m.record_errors(0, 1, 0);
+                tokio::select! {
+                    ctl = ctl_rx.recv() => {
+                        let Some((hdr, ct)) = ctl else { break };
+                        if let Err(e) = sess.handle(&mut wr, &hdr, &ct).await {
+                            eprintln!("[recv:{peer}] control frame refused: {e}");
                         }
-                        continue;
                     }
-                };
-
-                if seq == REKEY_SEQ {
-                    if pt.len()!=REKEY_PT_LEN { eprintln!("[recv:{peer}] bad rekey payload length"); continue; }
-                    // extract fields
-                    let mut salt=[0u8;16]; salt.copy_from_slice(&pt[0..16]);
-                    let mut c2s=[0u8;8];   c2s.copy_from_slice(&pt[16..24]);
-                    let mut s2c=[0u8;8];   s2c.copy_from_slice(&pt[24..32]);
-
-                    // Server role: new RX is k_c2s with c2s_base
-                    let (k_c2s, _k_s2c) = hkdf_pair_from_shared(&shared32, &salt);
-                    rx = AesGcmCtx::new(k_c2s, c2s);
-
-                    // record total handling duration for REKEY
-                    if let Some(m) = &metrics_clone {
-                        let bytes_received = (12 + ct.len()) as u64; // header + ciphertext
-                        let handling_d = handling_start.elapsed();
-                        let _ = m.record_handshake("REKEY", handling_d, 0, bytes_received, true, None, Some(peer_str.clone()));
-                    }
-                    eprintln!("[recv:{peer}] rekey applied");
-                    // fallthrough to continue processing normal frames if any
-                }
-
-                // normal data frame: compute latency from 8B timestamp, strip it and forward H.264 AU
-                if pt.len() >= 8 {
-                    let mut tsb = [0u8;8]; tsb.copy_from_slice(&pt[0..8]);
-                    let sender_ns = u64::from_be_bytes(tsb);
-                    let now_ns = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_nanos() as u64;
-                    if now_ns >= sender_ns {
-                        let latency_ms = (now_ns - sender_ns) as f64 / 1e6;
-                        if let Some(m) = &metrics_clone {
-                            let _ = m.record_frame_latency_ms(latency_ms);
-                        }
-                    }
-                    // throughput accounting
-                    throughput_frames += 1;
-                    throughput_bytes += (ct.len() + 12) as u64;
-                    if let Some(m) = &metrics_clone {
-                        let now = Instant::now();
-                        let dur = now.duration_since(throughput_last).as_secs_f64();
-                        if dur >= 5.0 {
-                            let goodput_mbps = (throughput_bytes as f64 * 8.0) / (dur * 1e6);
-                            let _ = m.record_throughput(dur, goodput_mbps, throughput_frames);
-                            throughput_last = now;
-                            throughput_bytes = 0;
-                            throughput_frames = 0;
+                    Some(reason) = keyframe_rx.recv() => {
+                        if let Err(e) = sess.request_keyframe(&mut wr, reason).await {
+                            eprintln!("[recv:{peer}] keyframe request failed: {e}");
+                            break;
                         }
                     }
                 }
-                let h264 = if pt.len()>=8 { pt[8..].to_vec() } else { Vec::new() };
-                if tx_clone.send(h264).await.is_err() { break; }
             }
-            eprintln!("[recv:{peer}] connection handler exiting");
+            reader.abort();
+            streams.lock().unwrap().remove(&ssrc);
+            eprintln!("[recv:{peer}] session closed; ssrc {ssrc:08x} dropped");
         });
     }
 }
-//cutoffend
 
-fn hkdf_pair_from_shared(shared32: &[u8; 32], salt16: &[u8; 16]) -> ([u8; 16], [u8; 16]) {
-   let hk = Hkdf::<Sha256>::new(Some(salt16), shared32);
-   let mut k_c2s = [0u8; 16];
-   let mut k_s2c = [0u8; 16];
-   hk.expand(b"ctx:k_c2s", &mut k_c2s).expect("HKDF expand c2s (rekey)");
-   hk.expand(b"ctx:k_s2c", &mut k_s2c).expect("HKDF expand s2c (rekey)");
-   (k_c2s, k_s2c)
-}
+async fn receive_srtp(udp: UdpSocket, streams: RtpStreams, frame_tx: mpsc::Sender<Vec<u8>>, metrics_opt: Option<metrics::Metrics>) -> Result<()> {
+    let mut buf = vec![0u8; 65536];
+    let mut throughput = Throughput::new();
 
+    loop {
+        let (n, from) = udp.recv_from(&mut buf).await.context("udp recv")?;
+        let pkt = &buf[..n];
+        let mut auth_failed = false;
+        let mut lost = 0;
+        let aus = {
+            let mut map = streams.lock().unwrap();
+            // packets for SSRCs nobody announced, or sent from another host, are dropped unread
+            let Some(s) = rtp::RtpHeader::parse(pkt).ok().and_then(|(h, _)| map.get_mut(&h.ssrc)) else { continue };
+            if from.ip() != s.peer_ip {
+                continue;
+            }
+            match s.srtp.unprotect(pkt) {
+                Err(e) => {
+                    eprintln!("[recv:{}] {e}", s.peer);
+                    s.failed_since_ok += 1;
+                    auth_failed = true;
+                    Vec::new()
+                }
+                Ok(opened) => {
+                    let aus = match rtp::RtpHeader::split(&opened) {
+                        Ok((hdr, payload)) => s.depay.push(&hdr, payload),
+                        Err(_) => Vec::new(),
+                    };
+                    // as on TCP: act on loss once an authentic packet shows the sequence moved on
+                    let gap = s.depay.take_lost();
+                    if gap > 0 {
+                        let reason = if s.failed_since_ok > 0 { KEYFRAME_AUTH_FAIL } else { KEYFRAME_GAP };
+                        let _ = s.keyframe_tx.try_send(reason);
+                        lost = gap.saturating_sub(s.failed_since_ok);
+                        eprintln!("[recv:{}] {gap} packet(s) missing; keyframe requested", s.peer);
+                    }
+                    s.failed_since_ok = 0;
+                    aus
+                }
+            }
+        };
+        if let Some(m) = &metrics_opt {
+            if auth_failed {
+                let _ = m.record_errors(0, 1, 0);
+            }
+            if lost > 0 {
+                let _ = m.record_errors(lost, 0, 0);
+            }
+        }
 
-fn hkdf_keys(shared: &[u8]) -> ([u8; 16], [u8; 16]) {
-   let hk = Hkdf::<Sha256>::new(Some(SALT), shared);
-   let mut k_c2s = [0u8; 16];
-   let mut k_s2c = [0u8; 16];
-   hk.expand(KDF_INFO_C2S, &mut k_c2s).expect("HKDF expand c2s");
-   hk.expand(KDF_INFO_S2C, &mut k_s2c).expect("HKDF expand s2c");
-   (k_c2s, k_s2c)
+        throughput.add(n as u64, aus.len() as u64, metrics_opt.as_ref());
+        for au in aus {
+            if frame_tx.send(au).await.is_err() {
+                return Ok(());
+            }
+        }
+    }
 }
+//cutoffend
 
-
-async fn read_exact(stream: &mut TcpStream, buf: &mut [u8]) -> Result<()> {
+async fn read_exact<S: AsyncRead + Unpin>(stream: &mut S, buf: &mut [u8]) -> Result<()> {
    stream.read_exact(buf).await.context("read_exact")?;
    Ok(())
 }
-async fn write_all(stream: &mut TcpStream, buf: &[u8]) -> Result<()> {
+async fn write_all<S: AsyncWrite + Unpin>(stream: &mut S, buf: &[u8]) -> Result<()> {
    stream.write_all(buf).await.context("write_all")?;
    Ok(())
 }
 
 
-async fn handshake_client(stream: &mut TcpStream) -> Result<(AesGcmCtx, AesGcmCtx, [u8; 32], usize, usize)> {
-   // Generate client ECDH
-   let client_secret = EphemeralSecret::random(&mut rand::rngs::OsRng);
-   let client_pub = PublicKey::from(&client_secret);
-   let client_pub_point = EncodedPoint::from(client_pub);
-   let client_pub_bytes = client_pub_point.as_bytes();
+async fn read_frame<R: AsyncRead + Unpin>(r: &mut R) -> Result<(FrameHeader, Vec<u8>)> {
+   let mut hdr = [0u8; HDR_LEN];
+   r.read_exact(&mut hdr).await.context("read header")?;
+   let hdr = FrameHeader::decode(&hdr);
+   if hdr.ct_len > MAX_CT_LEN {
+       return Err(anyhow!("frame length {} exceeds {MAX_CT_LEN}", hdr.ct_len));
+   }
+   let mut ct = vec![0u8; hdr.ct_len as usize];
+   r.read_exact(&mut ct).await.context("read ciphertext")?;
+   Ok((hdr, ct))
+}
 
+async fn write_frame<W: AsyncWrite + Unpin>(w: &mut W, hdr: &FrameHeader, ct: &[u8]) -> Result<usize> {
+   w.write_all(&hdr.encode()).await.context("write header")?;
+   w.write_all(ct).await.context("write ciphertext")?;
+   Ok(HDR_LEN + ct.len())
+}
 
-   // 1) Send client pub (u16 len + bytes)
-   let len_u16 = u16::try_from(client_pub_bytes.len()).expect("pubkey len fits u16");
-   write_all(stream, &len_u16.to_be_bytes()).await?;
-   write_all(stream, client_pub_bytes).await?;
+fn ecdh_bytes(secret: &EphemeralSecret, peer_pub: &[u8]) -> Result<[u8; 32]> {
+   let peer = PublicKey::from_sec1_bytes(peer_pub).map_err(|_| anyhow!("bad DH public key"))?;
+   let mut out = [0u8; 32];
+   out.copy_from_slice(secret.diffie_hellman(&peer).raw_secret_bytes().as_ref());
+   Ok(out)
+}
 
 
-   // 2) Read server pub
-   let mut lbuf = [0u8; 2];
-   read_exact(stream, &mut lbuf).await?;
-   let servidor_len = u16::from_be_bytes(lbuf) as usize;
-   let mut server_pub_bytes = vec![0u8; servidor_len];
-   read_exact(stream, &mut server_pub_bytes).await?;
-   let server_pub = PublicKey::from_sec1_bytes(&server_pub_bytes).map_err(|_| anyhow!("bad server pub"))?;
+impl HandshakeStats for ClientSession {
+   fn kex(&self) -> KeyExchange {
+       self.kex
+   }
+   fn hs_bytes(&self) -> (usize, usize) {
+       (self.hs_bytes_sent, self.hs_bytes_received)
+   }
+}
 
+impl HandshakeStats for ServerSession {
+   fn kex(&self) -> KeyExchange {
+       self.kex
+   }
+   fn hs_bytes(&self) -> (usize, usize) {
+       (self.hs_bytes_sent, self.hs_bytes_received)
+   }
+}
 
-   // ECDH -> shared
-   let shared = client_secret.diffie_hellman(&server_pub);
-   let shared_bytes = shared.raw_secret_bytes();
-   let mut shared32 = [0u8; 32];
-   shared32.copy_from_slice(shared_bytes.as_slice());
+/// Sending side of a session: `tx` carries c2s frames, `rx` the receiver's DH replies.
+struct ClientSession {
+   tx: Chain,
+   rx: Chain,
+   dh_secret: Option<EphemeralSecret>,
+   rekeys: u32,
+   kex: KeyExchange,
+   /// Selective NAL encryption; None seals whole access units
+   nal: Option<nal::Selector>,
+   /// Handshake bytes on the wire, for metrics
+   hs_bytes_sent: usize,
+   hs_bytes_received: usize,
+}
 
+enum RekeyStart {
+   /// Plain ratchet step sent and applied (bytes on the wire).
+   Advanced(usize),
+   /// DH offer sent; the step follows once the reply arrives.
+   Offered(usize),
+}
 
-   // Initial keys via your existing hkdf_keys (uses static SALT)
-   let (k_c2s, k_s2c) = hkdf_keys(shared_bytes.as_slice());
+enum Control {
+   /// DH rekey finished (bytes of the REKEY frame).
+   Rekeyed(usize),
+   /// Receiver lost a frame (reason byte: KEYFRAME_GAP / KEYFRAME_AUTH_FAIL).
+   KeyframeRequested(u8),
+}
 
+impl ClientSession {
+   fn set_protection(&mut self, protect: NalProtection) {
+       self.nal = (protect != NalProtection::Full).then(|| nal::Selector::new(protect));
+   }
+
+   /// DATA frame: 8-byte send timestamp (ns, BE) || payload. With selective
+   /// protection, a DATA_SELECTIVE frame carrying the same timestamp and AU.
+   async fn send_data<W: AsyncWrite + Unpin>(&mut self, w: &mut W, frame: &[u8]) -> Result<usize> {
+       use std::time::{SystemTime, UNIX_EPOCH};
+       let now_ns = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos() as u64;
+       let (hdr, ct) = match self.nal.as_mut() {
+           Some(sel) => {
+               let ranges = sel.ranges(frame);
+               nal::seal_selective(&mut self.tx, now_ns, frame, &ranges)?
+           }
+           None => {
+               let mut pt = Vec::with_capacity(8 + frame.len());
+               pt.extend_from_slice(&now_ns.to_be_bytes());
+               pt.extend_from_slice(frame);
+               self.tx.seal(KIND_DATA, &pt)?
+           }
+       };
+       write_frame(w, &hdr, &ct).await
+   }
 
-   // 3) Exchange nonce bases (client sends first)
-   let mut client_base = [0u8; 8];
-   rand::rngs::OsRng.fill_bytes(&mut client_base);
-   write_all(stream, &client_base).await?;
+   /// Timed rekey. Every `dh_every`-th one starts a fresh ECDH instead of a plain
+   /// ratchet step; while an offer is outstanding, plain steps are used.
+   async fn start_rekey<W: AsyncWrite + Unpin>(&mut self, w: &mut W, dh_every: Option<u32>) -> Result<RekeyStart> {
+       self.rekeys = self.rekeys.wrapping_add(1);
+       let want_dh = dh_every.is_some_and(|n| n > 0 && self.rekeys.is_multiple_of(n));
+       if want_dh && self.dh_secret.is_none() {
+           let secret = EphemeralSecret::random(&mut OsRng);
+           let offer = EncodedPoint::from(PublicKey::from(&secret));
+           let (hdr, ct) = self.tx.seal(KIND_DH_OFFER, offer.as_bytes())?;
+           let n = write_frame(w, &hdr, &ct).await?;
+           self.dh_secret = Some(secret);
+           return Ok(RekeyStart::Offered(n));
+       }
+       Ok(RekeyStart::Advanced(self.step(w, None).await?))
+   }
+
+   /// Frame from the receiver: a DH reply finishes the pending rekey, a
+   /// keyframe request is handed back to the caller.
+   async fn on_control<W: AsyncWrite + Unpin>(&mut self, w: &mut W, hdr: &FrameHeader, ct: &[u8]) -> Result<Control> {
+       let pt = self.rx.open(hdr, ct)?;
+       match hdr.kind {
+           KIND_DH_REPLY => {
+               let secret = self.dh_secret.take().ok_or_else(|| anyhow!("DH reply without an offer"))?;
+               let dh = ecdh_bytes(&secret, &pt)?;
+               Ok(Control::Rekeyed(self.step(w, Some(dh)).await?))
+           }
+           KIND_KEYFRAME_REQ => Ok(Control::KeyframeRequested(pt.first().copied().unwrap_or(KEYFRAME_GAP))),
+           k => Err(anyhow!("unexpected frame kind {k} from receiver")),
+       }
+   }
+
+   /// Tell the receiver which SSRC carries this session's SRTP stream and wait
+   /// until it is registered, so the first packets are not dropped.
+   async fn announce_rtp<S: AsyncRead + AsyncWrite + Unpin>(&mut self, s: &mut S, ssrc: u32) -> Result<()> {
+       let (hdr, ct) = self.tx.seal(KIND_RTP_STREAM, &ssrc.to_be_bytes())?;
+       write_frame(s, &hdr, &ct).await?;
+       let (hdr, ct) = read_frame(s).await.with_context(|| format!("receiver refused ssrc {ssrc:08x}"))?;
+       let pt = self.rx.open(&hdr, &ct)?;
+       if hdr.kind != KIND_RTP_STREAM || pt != ssrc.to_be_bytes() {
+           return Err(anyhow!("receiver did not confirm ssrc {ssrc:08x}"));
+       }
+       Ok(())
+   }
+
+   /// Announce the step under the current epoch, then ratchet locally.
+   async fn step<W: AsyncWrite + Unpin>(&mut self, w: &mut W, mut dh: Option<[u8; 32]>) -> Result<usize> {
+       let (hdr, ct) = self.tx.seal(KIND_REKEY, &[dh.is_some() as u8])?;
+       let n = write_frame(w, &hdr, &ct).await?;
+       self.tx.advance(dh.as_ref().map(|d| &d[..]));
+       if let Some(d) = dh.as_mut() { d.zeroize(); }
+       Ok(n)
+   }
+}
+
+
+/// Receiving side of a session: `rx` opens c2s frames, `tx` seals DH replies
+/// and keyframe requests.
+struct ServerSession {
+   rx: Chain,
+   tx: Chain,
+   pending_dh: Option<[u8; 32]>,
+   /// Frames that failed to open since the last authentic one
+   failed_since_ok: u64,
+   /// Frames missing from the sequence (never arrived), not yet reported
+   lost: u64,
+   keyframe_requests: u64,
+   kex: KeyExchange,
+   /// Handshake bytes on the wire, for metrics
+   hs_bytes_sent: usize,
+   hs_bytes_received: usize,
+}
+
+enum Received {
+   Data(Vec<u8>),
+   Rekeyed { epoch: u32, dh: bool },
+   DhOffered,
+}
+
+impl ServerSession {
+   async fn handle<W: AsyncWrite + Unpin>(&mut self, w: &mut W, hdr: &FrameHeader, ct: &[u8]) -> Result<Received> {
+       // Loss is only acted on once an authentic frame proves the sequence moved
+       // past it, so injected garbage alone cannot make us spam keyframe requests.
+       let gap = if hdr.epoch == self.rx.epoch() { hdr.seq.saturating_sub(self.rx.seq()) } else { 0 };
+       let opened = if hdr.kind == KIND_DATA_SELECTIVE {
+           nal::open_selective(&mut self.rx, hdr, ct)
+       } else {
+           self.rx.open(hdr, ct)
+       };
+       let pt = match opened {
+           Ok(pt) => pt,
+           Err(e) => {
+               self.failed_since_ok += 1;
+               return Err(e);
+           }
+       };
+       if gap > 0 {
+           let reason = if self.failed_since_ok > 0 { KEYFRAME_AUTH_FAIL } else { KEYFRAME_GAP };
+           self.lost += gap.saturating_sub(self.failed_since_ok);
+           self.request_keyframe(w, reason).await?;
+       }
+       self.failed_since_ok = 0;
+       match hdr.kind {
+           KIND_DATA | KIND_DATA_SELECTIVE => Ok(Received::Data(pt)),
+           KIND_REKEY => {
+               let dh = pt.first().copied() == Some(1);
+               let mut secret = if dh {
+                   Some(self.pending_dh.take().ok_or_else(|| anyhow!("DH rekey without a pending offer"))?)
+               } else {
+                   None
+               };
+               self.rx.advance(secret.as_ref().map(|d| &d[..]));
+               if let Some(d) = secret.as_mut() { d.zeroize(); }
+               Ok(Received::Rekeyed { epoch: self.rx.epoch(), dh })
+           }
+           KIND_DH_OFFER => {
+               let secret = EphemeralSecret::random(&mut OsRng);
+               let mut shared = ecdh_bytes(&secret, &pt)?;
+               if let Some(mut old) = self.pending_dh.replace(shared) { old.zeroize(); }
+               shared.zeroize();
+               let reply = EncodedPoint::from(PublicKey::from(&secret));
+               let (h, c) = self.tx.seal(KIND_DH_REPLY, reply.as_bytes())?;
+               write_frame(w, &h, &c).await?;
+               Ok(Received::DhOffered)
+           }
+           k => Err(anyhow!("unexpected frame kind {k}")),
+       }
+   }
 
+   async fn request_keyframe<W: AsyncWrite + Unpin>(&mut self, w: &mut W, reason: u8) -> Result<()> {
+       let (h, c) = self.tx.seal(KIND_KEYFRAME_REQ, &[reason])?;
+       write_frame(w, &h, &c).await?;
+       self.keyframe_requests += 1;
+       Ok(())
+   }
 
-   let mut server_base = [0u8; 8];
-   read_exact(stream, &mut server_base).await?;
+   /// First frame of an RTP-mode session: the SSRC of the sender's stream.
+   async fn read_rtp_announce<R: AsyncRead + Unpin>(&mut self, r: &mut R) -> Result<u32> {
+       let (hdr, ct) = read_frame(r).await?;
+       let pt = self.rx.open(&hdr, &ct)?;
+       match (hdr.kind, <[u8; 4]>::try_from(&pt[..])) {
+           (KIND_RTP_STREAM, Ok(ssrc)) => Ok(u32::from_be_bytes(ssrc)),
+           (k, _) => Err(anyhow!("expected an RTP stream announcement, got frame kind {k}")),
+       }
+   }
 
+   /// Echo the SSRC once its SRTP context is in place.
+   async fn confirm_rtp<W: AsyncWrite + Unpin>(&mut self, w: &mut W, ssrc: u32) -> Result<()> {
+       let (h, c) = self.tx.seal(KIND_RTP_STREAM, &ssrc.to_be_bytes())?;
+       write_frame(w, &h, &c).await?;
+       Ok(())
+   }
 
-    let tx = AesGcmCtx::new(k_c2s, client_base); // client -> server
-    let rx = AesGcmCtx::new(k_s2c, server_base); // server -> client
-    Ok((tx, rx, shared32, client_pub_bytes.len(), server_pub_bytes.len()))
+   /// Frames lost in transit since the last call (excludes authentication failures).
+   fn take_lost(&mut self) -> u64 {
+       std::mem::take(&mut self.lost)
+   }
 }
 
 
-async fn handshake_server(stream: &mut TcpStream) -> Result<(AesGcmCtx, AesGcmCtx, [u8; 32], usize, usize)> {
-   // Read client pub
+async fn write_blob<S: AsyncWrite + Unpin>(stream: &mut S, bytes: &[u8]) -> Result<usize> {
+   let len_u16 = u16::try_from(bytes.len()).map_err(|_| anyhow!("handshake field too large"))?;
+   write_all(stream, &len_u16.to_be_bytes()).await?;
+   write_all(stream, bytes).await?;
+   Ok(2 + bytes.len())
+}
+
+async fn read_blob<S: AsyncRead + Unpin>(stream: &mut S) -> Result<Vec<u8>> {
    let mut lbuf = [0u8; 2];
    read_exact(stream, &mut lbuf).await?;
-   let client_len = u16::from_be_bytes(lbuf) as usize;
-   let mut client_pub_bytes = vec![0u8; client_len];
-   read_exact(stream, &mut client_pub_bytes).await?;
-   let client_pub = PublicKey::from_sec1_bytes(&client_pub_bytes).map_err(|_| anyhow!("bad client pub"))?;
+   let mut buf = vec![0u8; u16::from_be_bytes(lbuf) as usize];
+   read_exact(stream, &mut buf).await?;
+   Ok(buf)
+}
 
 
-   // Generate server ECDH and send pub
-   let server_secret = EphemeralSecret::random(&mut rand::rngs::OsRng);
-   let server_pub = PublicKey::from(&server_secret);
-   let server_pub_point = EncodedPoint::from(server_pub);
-   let server_pub_bytes = server_pub_point.as_bytes();
-   let len_u16 = u16::try_from(server_pub_bytes.len()).expect("pubkey len fits u16");
-   write_all(stream, &len_u16.to_be_bytes()).await?;
-   write_all(stream, server_pub_bytes).await?;
+/// HKDF salt over everything both sides put on the wire, so the chain keys
+/// are bound to the mechanism, both ephemeral keys and the receiver's node key.
+fn transcript_salt(kex: KeyExchange, client_pub: &[u8], server_pub: &[u8], node_pub: &[u8]) -> [u8; 32] {
+   let mut h = Sha256::new();
+   h.update(SALT);
+   h.update([kex.id()]);
+   for field in [client_pub, server_pub, node_pub] {
+       h.update((field.len() as u16).to_be_bytes());
+       h.update(field);
+   }
+   h.finalize().into()
+}
 
+/// Client hello: mech(u8) || [u16 len][P-256 pub] || hybrid: [u16 len][ML-KEM-768 ek]
+/// Server reply:             [u16 len][P-256 pub] || [u16 len][node key, empty without one]
+///                           || hybrid: [u16 len][ML-KEM-768 ct]
+/// With a node key, ECDH(client ephemeral, node key) goes into the HKDF as well,
+/// so only the holder of that key ends up with the session keys.
+async fn handshake_client<S: AsyncRead + AsyncWrite + Unpin>(stream: &mut S, kex: KeyExchange, expect_node: Option<&[u8; 32]>) -> Result<ClientSession> {
+   // Generate client ECDH (+ ML-KEM key pair for the hybrid)
+   let client_secret = EphemeralSecret::random(&mut OsRng);
+   let client_pub = PublicKey::from(&client_secret);
+   let client_pub_point = EncodedPoint::from(client_pub);
+   let client_pub_bytes = client_pub_point.as_bytes();
+   let kem = (kex == KeyExchange::HybridMlKem768).then(kex::kem_keypair);
 
-   // ECDH -> shared
-   let shared = server_secret.diffie_hellman(&client_pub);
-   let shared_bytes = shared.raw_secret_bytes();
-   let mut shared32 = [0u8; 32];
-   shared32.copy_from_slice(shared_bytes.as_slice());
 
+   // 1) Send mechanism, client pub (u16 len + bytes), ML-KEM encapsulation key
+   write_all(stream, &[kex.id()]).await?;
+   let mut sent = 1 + write_blob(stream, client_pub_bytes).await?;
+   if let Some((_, ek)) = &kem {
+       sent += write_blob(stream, ek).await?;
+   }
 
-   let (k_c2s, k_s2c) = hkdf_keys(shared_bytes.as_slice());
 
+   // 2) Read server pub, its node key (+ ML-KEM ciphertext)
+   let server_pub_bytes = read_blob(stream).await?;
+   let node_pub_bytes = read_blob(stream).await?;
+   let mut received = 2 + server_pub_bytes.len() + 2 + node_pub_bytes.len();
+   let node_pub = match (node_pub_bytes.is_empty(), expect_node) {
+       (true, None) => None,
+       (true, Some(_)) => return Err(anyhow!("receiver did not present a node key")),
+       (false, _) => {
+           let key = PublicKey::from_sec1_bytes(&node_pub_bytes).map_err(|_| anyhow!("bad node key"))?;
+           if expect_node.is_some_and(|fp| *fp != discovery::fingerprint(&key)) {
+               return Err(anyhow!("receiver node key {} does not match its beacon", discovery::fingerprint_hex(&discovery::fingerprint(&key))));
+           }
+           Some(key)
+       }
+   };
+   let kem_ss = match &kem {
+       Some((dk, _)) => {
+           let ct = read_blob(stream).await?;
+           received += 2 + ct.len();
+           Some(kex::kem_decapsulate(dk, &ct)?)
+       }
+       None => None,
+   };
 
-   // Receive client's base; send server base
-   let mut client_base = [0u8; 8];
-   read_exact(stream, &mut client_base).await?;
-   let mut server_base = [0u8; 8];
-   rand::rngs::OsRng.fill_bytes(&mut server_base);
-   write_all(stream, &server_base).await?;
+
+   // ECDH (|| ML-KEM) -> shared -> per-direction chain keys (epoch 0)
+   let mut shared32 = ecdh_bytes(&client_secret, &server_pub_bytes)?;
+   let mut ikm = kex::combine(&shared32, kem_ss.as_deref());
+   if let Some(node) = &node_pub {
+       ikm.extend_from_slice(client_secret.diffie_hellman(node).raw_secret_bytes());
+   }
+   let salt = transcript_salt(kex, client_pub_bytes, &server_pub_bytes, &node_pub_bytes);
+   let (info_c2s, info_s2c) = kex.kdf_info();
+   let (ck_c2s, ck_s2c) = ratchet::initial_chain_keys(&ikm, &salt, info_c2s, info_s2c);
+   shared32.zeroize();
+
+
+    Ok(ClientSession {
+        tx: Chain::new(ck_c2s), // client -> server
+        rx: Chain::new(ck_s2c), // server -> client
+        dh_secret: None,
+        rekeys: 0,
+        kex,
+        nal: None,
+        hs_bytes_sent: sent,
+        hs_bytes_received: received,
+    })
+}
 
 
-    let tx = AesGcmCtx::new(k_s2c, server_base); // server -> client
-    let rx = AesGcmCtx::new(k_c2s, client_base); // client -> server
-    Ok((tx, rx, shared32, client_pub_bytes.len(), server_pub_bytes.len()))
+/// Answers whichever mechanism the client asked for, presenting `identity` if
+/// given. The mechanism goes into `negotiated` as soon as the hello names it.
+async fn handshake_server<S: AsyncRead + AsyncWrite + Unpin>(
+   stream: &mut S,
+   identity: Option<&discovery::Identity>,
+   negotiated: &OnceLock<KeyExchange>,
+) -> Result<ServerSession> {
+   // Read mechanism, client pub (+ ML-KEM encapsulation key)
+   let mut mech = [0u8; 1];
+   read_exact(stream, &mut mech).await?;
+   let kex = KeyExchange::from_id(mech[0]).ok_or_else(|| anyhow!("unknown key exchange {}", mech[0]))?;
+   let _ = negotiated.set(kex);
+   let client_pub_bytes = read_blob(stream).await?;
+   let mut received = 1 + 2 + client_pub_bytes.len();
+   let client_ek = match kex {
+       KeyExchange::HybridMlKem768 => {
+           let ek = read_blob(stream).await?;
+           received += 2 + ek.len();
+           Some(ek)
+       }
+       KeyExchange::Ecdh => None,
+   };
+
+
+   // Generate server ECDH and send pub (+ ML-KEM ciphertext)
+   let server_secret = EphemeralSecret::random(&mut OsRng);
+   let server_pub = PublicKey::from(&server_secret);
+   let server_pub_point = EncodedPoint::from(server_pub);
+   let server_pub_bytes = server_pub_point.as_bytes();
+   let mut shared32 = ecdh_bytes(&server_secret, &client_pub_bytes).context("bad client pub")?;
+   let kem = client_ek.as_deref().map(kex::kem_encapsulate).transpose()?;
+   let node_pub_bytes = identity.map(|id| id.public_key().to_encoded_point(true).as_bytes().to_vec()).unwrap_or_default();
+   let mut sent = write_blob(stream, server_pub_bytes).await?;
+   sent += write_blob(stream, &node_pub_bytes).await?;
+   if let Some((ct, _)) = &kem {
+       sent += write_blob(stream, ct).await?;
+   }
+
+
+   let mut ikm = kex::combine(&shared32, kem.as_ref().map(|(_, ss)| &**ss));
+   if let Some(id) = identity {
+       let client_pub = PublicKey::from_sec1_bytes(&client_pub_bytes).map_err(|_| anyhow!("bad client pub"))?;
+       ikm.extend_from_slice(id.diffie_hellman(&client_pub).raw_secret_bytes());
+   }
+   let salt = transcript_salt(kex, &client_pub_bytes, server_pub_bytes, &node_pub_bytes);
+   let (info_c2s, info_s2c) = kex.kdf_info();
+   let (ck_c2s, ck_s2c) = ratchet::initial_chain_keys(&ikm, &salt, info_c2s, info_s2c);
+   shared32.zeroize();
+
+
+    Ok(ServerSession {
+        rx: Chain::new(ck_c2s), // client -> server
+        tx: Chain::new(ck_s2c), // server -> client
+        pending_dh: None,
+        failed_since_ok: 0,
+        lost: 0,
+        keyframe_requests: 0,
+        kex,
+        hs_bytes_sent: sent,
+        hs_bytes_received: received,
+    })
 }
 
 
 
 
-/// Receiver: bind, accept 1 client, decrypt frames, print counters.
+
 /// Receiver: bind, accept 1 client, decrypt frames, print counters, handle rekeys.
 pub async fn run_receiver(bind: &str) -> Result<()> {
    let listener = TcpListener::bind(bind).await.context("bind")?;
@@ -552,44 +1134,29 @@ pub async fn run_receiver(bind: &str) -> Result<()> {
    eprintln!("[recv] connection from {peer}");
 
 
-    let (_tx_unused, mut rx, shared32, _client_pub_len, _server_pub_len) = handshake_server(&mut sock).await.context("handshake_server")?;
-   eprintln!("[recv] handshake OK");
+    let mut sess = handshake_server(&mut sock, None, &OnceLock::new()).await.context("handshake_server")?;
+   eprintln!("[recv] handshake OK ({})", sess.kex.label());
 
 
    let mut frames = 0u64;
    loop {
-       let mut hdr = [0u8; 12];
-       if let Err(e) = sock.read_exact(&mut hdr).await {
-           eprintln!("[recv] closed: {e}");
-           break;
-       }
-       let seq = u64::from_be_bytes(hdr[..8].try_into().unwrap()) as u32; // low 32 used for nonce ctr
-       let ct_len = u32::from_be_bytes(hdr[8..12].try_into().unwrap()) as usize;
-       let mut ct = vec![0u8; ct_len];
-       read_exact(&mut sock, &mut ct).await?;
-
-
-       let aad = seq.to_be_bytes();
-       let pt = rx.decrypt(seq, &ct, &aad)?;   // decrypt first to authenticate
-
-
-       // Rekey control frame?
-       if seq == REKEY_SEQ {
-           if pt.len() != REKEY_PT_LEN {
-               return Err(anyhow!("bad rekey payload length"));
+       let (hdr, ct) = match read_frame(&mut sock).await {
+           Ok(v) => v,
+           Err(e) => {
+               eprintln!("[recv] closed: {e}");
+               break;
            }
-           let mut salt = [0u8; 16];    salt.copy_from_slice(&pt[0..16]);
-           let mut c2s_base = [0u8; 8]; c2s_base.copy_from_slice(&pt[16..24]);
-           let mut s2c_base = [0u8; 8]; s2c_base.copy_from_slice(&pt[24..32]);
-
-
-           // Server role: new RX is k_c2s with c2s_base
-           let (k_c2s, _k_s2c) = hkdf_pair_from_shared(&shared32, &salt);
-           rx = AesGcmCtx::new(k_c2s, c2s_base);
+       };
 
 
-           eprintln!("[recv] rekey applied");
-           continue;
+       // decrypt first to authenticate; control frames are applied inside
+       match sess.handle(&mut sock, &hdr, &ct).await? {
+           Received::Rekeyed { epoch, dh } => {
+               eprintln!("[recv] rekey applied (epoch {epoch}, dh={dh})");
+               continue;
+           }
+           Received::DhOffered => continue,
+           Received::Data(_) => {}
        }
 
 
@@ -603,18 +1170,16 @@ pub async fn run_receiver(bind: &str) -> Result<()> {
 }
 
 
-/// Sender: connect, handshake, send N encrypted dummy frames.
 /// Sender: connect, handshake, send N encrypted dummy frames; optional timed rekey.
 
 
-pub async fn run_sender(host: &str, n_frames: u32, rekey_every: Option<Duration>) -> anyhow::Result<()> {
-   eprintln!("[send] connecting to {host} (rekey_every={rekey_every:?})");
+pub async fn run_sender(host: &str, n_frames: u32, rekey_every: Option<Duration>, kex: KeyExchange) -> anyhow::Result<()> {
+   eprintln!("[send] connecting to {host} (rekey_every={rekey_every:?}, kex={})", kex.label());
    let mut sock = tokio::net::TcpStream::connect(host).await.context("connect")?;
    eprintln!("[send] connected to {host}");
 
 
-   // Handshake must return (tx, rx, shared32). We only use tx + shared32 here.
-    let (mut tx, _rx_unused, shared32, _client_pub_len, _server_pub_len) = handshake_client(&mut sock).await.context("handshake_client")?;
+    let mut sess = handshake_client(&mut sock, kex, None).await.context("handshake_client")?;
    eprintln!("[send] handshake OK");
 
 
@@ -622,87 +1187,336 @@ pub async fn run_sender(host: &str, n_frames: u32, rekey_every: Option<Duration>
    let mut next_rekey_at = rekey_every.map(|d| Instant::now() + d);
 
 
-   // Per-key sequence counter (used in the 12-byte nonce: base(8) || seq_be(4))
-   let mut seq: u32 = 0;
-
-
    for i in 0..n_frames {
-       // --- timed REKEY (control frame using reserved seq = u32::MAX) ---
+       // --- timed REKEY (symmetric ratchet step; old epoch key is wiped) ---
        if let Some(when) = next_rekey_at {
            if Instant::now() >= when {
-               // Fresh HKDF salt + fresh per-direction nonce bases
-               let mut salt = [0u8; 16];
-               let mut c2s_base = [0u8; 8];
-               let mut s2c_base = [0u8; 8];
-               rand::rngs::OsRng.fill_bytes(&mut salt);
-               rand::rngs::OsRng.fill_bytes(&mut c2s_base);
-               rand::rngs::OsRng.fill_bytes(&mut s2c_base);
+               sess.step(&mut sock, None).await?;
+               eprintln!("[send] rekey sent+applied (epoch {})", sess.tx.epoch());
+               next_rekey_at = rekey_every.map(|d| Instant::now() + d);
+               continue; // don’t count the control frame
+           }
+       }
 
 
-               // Payload: salt || c2s_base || s2c_base
-               let mut pt = [0u8; REKEY_PT_LEN];
-               pt[0..16].copy_from_slice(&salt);
-               pt[16..24].copy_from_slice(&c2s_base);
-               pt[24..32].copy_from_slice(&s2c_base);
+       // --- normal DATA frame (dummy 1KiB payload for now) ---
+       let mut pt = vec![0u8; 1024];
+       OsRng.fill_bytes(&mut pt);
+       let (hdr, ct) = sess.tx.seal(KIND_DATA, &pt)?;
+       write_frame(&mut sock, &hdr, &ct).await?;
 
 
-               // Encrypt under current key with reserved seq
-               let seq_ctl = REKEY_SEQ;
-               let aad = seq_ctl.to_be_bytes();
-               let ct = tx.encrypt(seq_ctl, &pt, &aad)?;
+       if i % 50 == 0 {
+           eprintln!("[send] sent seq={i} (epoch {}, seq {})", hdr.epoch, hdr.seq);
+       }
+   }
 
 
-               // Header: [u64 seq][u32 ct_len]
-               let mut hdr = [0u8; 12];
-               hdr[..8].copy_from_slice(&(seq_ctl as u64).to_be_bytes());
-               let ct_len_u32 = u32::try_from(ct.len()).expect("ct too large");
-               hdr[8..12].copy_from_slice(&ct_len_u32.to_be_bytes());
-               sock.write_all(&hdr).await?;
-               sock.write_all(&ct).await?;
+   eprintln!("[send] done");
+   Ok(())
+}
 
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   #[tokio::test]
+   async fn plain_and_dh_rekeys_keep_both_sides_in_step() {
+       let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+       let addr = listener.local_addr().unwrap();
+       let server = tokio::spawn(async move {
+           let (mut sock, _) = listener.accept().await.unwrap();
+           let mut sess = handshake_server(&mut sock, None, &OnceLock::new()).await.unwrap();
+           let mut got = Vec::new();
+           while let Ok((hdr, ct)) = read_frame(&mut sock).await {
+               if let Received::Data(pt) = sess.handle(&mut sock, &hdr, &ct).await.unwrap() {
+                   got.push((hdr.epoch, pt[8..].to_vec()));
+               }
+           }
+           (got, sess.rx.epoch())
+       });
+
+       let mut sock = TcpStream::connect(addr).await.unwrap();
+       let mut sess = handshake_client(&mut sock, KeyExchange::Ecdh, None).await.unwrap();
+       sess.send_data(&mut sock, b"e0").await.unwrap();
+       assert!(matches!(sess.start_rekey(&mut sock, Some(2)).await.unwrap(), RekeyStart::Advanced(_)));
+       sess.send_data(&mut sock, b"e1").await.unwrap();
+       assert!(matches!(sess.start_rekey(&mut sock, Some(2)).await.unwrap(), RekeyStart::Offered(_)));
+       sess.send_data(&mut sock, b"still e1").await.unwrap();
+       let (hdr, ct) = read_frame(&mut sock).await.unwrap();
+       assert!(matches!(sess.on_control(&mut sock, &hdr, &ct).await.unwrap(), Control::Rekeyed(_)));
+       sess.send_data(&mut sock, b"e2").await.unwrap();
+       assert_eq!(sess.tx.epoch(), 2);
+       drop(sock);
+
+       let (got, epoch) = server.await.unwrap();
+       assert_eq!(epoch, 2);
+       let want: Vec<(u32, Vec<u8>)> =
+           vec![(0, b"e0".to_vec()), (1, b"e1".to_vec()), (1, b"still e1".to_vec()), (2, b"e2".to_vec())];
+       assert_eq!(got, want);
+   }
 
-               // Locally rotate sender keys (client role)
-               let (k_c2s, _k_s2c) = hkdf_pair_from_shared(&shared32, &salt);
-               tx = aead::AesGcmCtx::new(k_c2s, c2s_base);
 
+   #[tokio::test]
+   async fn hybrid_handshake_agrees_and_reports_sizes() {
+       let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+       let addr = listener.local_addr().unwrap();
+       let server = tokio::spawn(async move {
+           let (mut sock, _) = listener.accept().await.unwrap();
+           let mut sess = handshake_server(&mut sock, None, &OnceLock::new()).await.unwrap();
+           let (hdr, ct) = read_frame(&mut sock).await.unwrap();
+           let Received::Data(pt) = sess.handle(&mut sock, &hdr, &ct).await.unwrap() else { panic!("expected data") };
+           (sess.kex, sess.hs_bytes_sent, sess.hs_bytes_received, pt[8..].to_vec())
+       });
+
+       let mut sock = TcpStream::connect(addr).await.unwrap();
+       let mut sess = handshake_client(&mut sock, KeyExchange::HybridMlKem768, None).await.unwrap();
+       sess.send_data(&mut sock, b"pq").await.unwrap();
+
+       let (kex, srv_sent, srv_recv, pt) = server.await.unwrap();
+       assert_eq!(kex, KeyExchange::HybridMlKem768);
+       assert_eq!(pt, b"pq");
+       // mech + (2 + 65 P-256) + (2 + 1184 ek) / (2 + 65) + (2 + no node key) + (2 + 1088 ct)
+       assert_eq!((sess.hs_bytes_sent, sess.hs_bytes_received), (1 + 67 + 1186, 67 + 2 + 1090));
+       assert_eq!((srv_recv, srv_sent), (sess.hs_bytes_sent, sess.hs_bytes_received));
+   }
 
-               // Reset per-key counter and schedule next rekey
-               seq = 0;
-               eprintln!("[send] rekey sent+applied");
-               next_rekey_at = rekey_every.map(|d| Instant::now() + d);
-               continue; // don’t count the control frame
+   #[tokio::test]
+   async fn discovered_peer_must_hold_the_advertised_node_key() {
+       let node = Arc::new(discovery::Identity::generate());
+       let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+       let addr = listener.local_addr().unwrap();
+       let id = node.clone();
+       let server = tokio::spawn(async move {
+           let mut got = Vec::new();
+           for present in [Some(&*id), Some(&*id), None] {
+               let (mut sock, _) = listener.accept().await.unwrap();
+               let Ok(mut sess) = handshake_server(&mut sock, present, &OnceLock::new()).await else { continue };
+               if let Ok((hdr, ct)) = read_frame(&mut sock).await {
+                   if let Ok(Received::Data(pt)) = sess.handle(&mut sock, &hdr, &ct).await {
+                       got.push(pt[8..].to_vec());
+                   }
+               }
            }
+           got
+       });
+
+       // Matching fingerprint: keys agree and data flows
+       let mut sock = TcpStream::connect(addr).await.unwrap();
+       let mut sess = handshake_client(&mut sock, KeyExchange::Ecdh, Some(&node.fingerprint())).await.unwrap();
+       sess.send_data(&mut sock, b"trusted").await.unwrap();
+       drop(sock);
+
+       // A beacon advertising some other key, and a receiver presenting none
+       for _ in 0..2 {
+           let mut sock = TcpStream::connect(addr).await.unwrap();
+           let other = discovery::Identity::generate().fingerprint();
+           assert!(handshake_client(&mut sock, KeyExchange::Ecdh, Some(&other)).await.is_err());
        }
 
+       assert_eq!(server.await.unwrap(), vec![b"trusted".to_vec()]);
+   }
 
-       // --- normal DATA frame (dummy 1KiB payload for now) ---
-       let mut pt = vec![0u8; 1024];
-       rand::rngs::OsRng.fill_bytes(&mut pt);
-       let aad = seq.to_be_bytes();
-       let ct = tx.encrypt(seq, &pt, &aad)?;
+   #[tokio::test]
+   async fn failed_handshakes_are_filed_under_the_requested_mechanism() {
+       let dir = std::env::temp_dir().join(format!("hs-metrics-{}", std::process::id()));
+       let metrics = metrics::Metrics::new(&dir).unwrap();
+       // a hybrid hello cut off after the mechanism byte, then a hello naming no mechanism
+       for hello in [&[KeyExchange::HybridMlKem768.id()][..], &[9u8][..]] {
+           let (mut client, server) = tokio::io::duplex(64);
+           client.write_all(hello).await.unwrap();
+           drop(client);
+           let (frame_tx, _frame_rx) = mpsc::channel(1);
+           receive_session(server, "peer".into(), None, frame_tx, Some(metrics.clone())).await;
+       }
+       let csv = std::fs::read_to_string(dir.join("handshake.csv")).unwrap();
+       let methods: Vec<&str> = csv.lines().skip(1).map(|l| l.split(',').nth(1).unwrap()).collect();
+       assert_eq!(methods, ["ECDH+ML-KEM-768", "unknown"]);
+       std::fs::remove_dir_all(&dir).unwrap();
+   }
 
+   #[tokio::test]
+   async fn oversized_frame_length_is_refused_before_allocating() {
+       let hdr = FrameHeader { kind: KIND_DATA, epoch: 0, seq: 0, ct_len: u32::MAX };
+       let (mut a, mut b) = tokio::io::duplex(64);
+       a.write_all(&hdr.encode()).await.unwrap();
+       let err = read_frame(&mut b).await.unwrap_err();
+       assert!(err.to_string().contains("exceeds"), "{err}");
+   }
 
-       let mut hdr = [0u8; 12];
-       hdr[..8].copy_from_slice(&(seq as u64).to_be_bytes());
-       let ct_len_u32 = u32::try_from(ct.len()).expect("ct too large");
-       hdr[8..12].copy_from_slice(&ct_len_u32.to_be_bytes());
-       sock.write_all(&hdr).await?;
-       sock.write_all(&ct).await?;
+   #[tokio::test]
+   async fn lost_and_forged_frames_trigger_keyframe_requests() {
+       let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+       let addr = listener.local_addr().unwrap();
+       let server = tokio::spawn(async move {
+           let (mut sock, _) = listener.accept().await.unwrap();
+           let mut sess = handshake_server(&mut sock, None, &OnceLock::new()).await.unwrap();
+           let mut got = Vec::new();
+           while let Ok((hdr, ct)) = read_frame(&mut sock).await {
+               if let Ok(Received::Data(pt)) = sess.handle(&mut sock, &hdr, &ct).await {
+                   got.push(pt[8..].to_vec());
+               }
+           }
+           (got, sess.take_lost(), sess.keyframe_requests)
+       });
+
+       let mut sock = TcpStream::connect(addr).await.unwrap();
+       let mut sess = handshake_client(&mut sock, KeyExchange::Ecdh, None).await.unwrap();
+       sess.send_data(&mut sock, b"a").await.unwrap();
+       // dropped in transit: sealed but never written
+       sess.tx.seal(KIND_DATA, b"lost").unwrap();
+       sess.send_data(&mut sock, b"b").await.unwrap();
+       let (hdr, ct) = read_frame(&mut sock).await.unwrap();
+       assert_eq!(hdr.kind, KIND_KEYFRAME_REQ);
+       assert!(matches!(sess.on_control(&mut sock, &hdr, &ct).await.unwrap(), Control::KeyframeRequested(KEYFRAME_GAP)));
+
+       // tampered on the wire: fails the tag, reported once the next frame opens
+       let (hdr, mut ct) = sess.tx.seal(KIND_DATA, &[0u8; 16]).unwrap();
+       ct[0] ^= 1;
+       write_frame(&mut sock, &hdr, &ct).await.unwrap();
+       sess.send_data(&mut sock, b"c").await.unwrap();
+       let (hdr, ct) = read_frame(&mut sock).await.unwrap();
+       assert!(matches!(sess.on_control(&mut sock, &hdr, &ct).await.unwrap(), Control::KeyframeRequested(KEYFRAME_AUTH_FAIL)));
+
+       // a replayed frame is refused without asking for anything
+       let (hdr, ct) = sess.tx.seal(KIND_DATA, b"00000000d").unwrap();
+       write_frame(&mut sock, &hdr, &ct).await.unwrap();
+       write_frame(&mut sock, &hdr, &ct).await.unwrap();
+       drop(sock);
+
+       let (got, lost, requests) = server.await.unwrap();
+       assert_eq!(got, vec![b"a".to_vec(), b"b".to_vec(), b"c".to_vec(), b"d".to_vec()]);
+       assert_eq!((lost, requests), (1, 2));
+   }
 
+   #[tokio::test]
+   async fn selective_frames_reach_the_receiver_intact() {
+       let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+       let addr = listener.local_addr().unwrap();
+       let server = tokio::spawn(async move {
+           let (mut sock, _) = listener.accept().await.unwrap();
+           let mut sess = handshake_server(&mut sock, None, &OnceLock::new()).await.unwrap();
+           let mut got = Vec::new();
+           while let Ok((hdr, ct)) = read_frame(&mut sock).await {
+               if let Received::Data(pt) = sess.handle(&mut sock, &hdr, &ct).await.unwrap() {
+                   got.push((hdr.kind, pt[8..].to_vec()));
+               }
+           }
+           got
+       });
+
+       // SEI + non-IDR slice: the slice body is encrypted, the rest only authenticated
+       let au = [0, 0, 0, 1, 0x06, 5, 1, 0xff, 0x80, 0, 0, 1, 0x41, 0x9a, 0x11, 0x22, 0x33];
+       let mut sock = TcpStream::connect(addr).await.unwrap();
+       let mut sess = handshake_client(&mut sock, KeyExchange::Ecdh, None).await.unwrap();
+       sess.set_protection(NalProtection::AllSlices);
+       sess.send_data(&mut sock, &au).await.unwrap();
+       sess.set_protection(NalProtection::Full);
+       sess.send_data(&mut sock, &au).await.unwrap();
+       drop(sock);
+
+       let got = server.await.unwrap();
+       assert_eq!(got, vec![(KIND_DATA_SELECTIVE, au.to_vec()), (KIND_DATA, au.to_vec())]);
+   }
 
-       if i % 50 == 0 {
-           eprintln!("[send] sent seq={i}");
+   #[tokio::test]
+   async fn rtp_mode_delivers_access_units_over_srtp() {
+       // free port for both TCP and UDP
+       let addr = TcpListener::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap().to_string();
+       let (au_tx, mut au_rx) = mpsc::channel(8);
+       let bind = addr.clone();
+       let receiver = tokio::spawn(async move { run_srtp_receiver_to_channel(&bind, au_tx, None).await });
+       tokio::time::sleep(Duration::from_millis(50)).await;
+
+       // SPS + PPS + a slice that needs FU-A, then a single-packet slice
+       let idr: Vec<u8> = [0x65].into_iter().chain((0..5000).map(|i| (i % 200) as u8 + 1)).collect();
+       let aus = vec![
+           [&[0, 0, 0, 1, 0x67, 0x42, 0, 0x1f, 0, 0, 0, 1, 0x68, 0xce, 0x3c, 0x80, 0, 0, 0, 1][..], &idr].concat(),
+           vec![0, 0, 0, 1, 0x41, 0x9a, 1, 2, 3],
+       ];
+       let key_log = std::env::temp_dir().join(format!("srtp-keys-{}.log", std::process::id()));
+       let _ = std::fs::remove_file(&key_log);
+       let (frame_tx, frame_rx) = mpsc::channel(8);
+       let opts = SenderOptions { srtp_key_log: Some(key_log.clone()), ..Default::default() };
+       let sender = tokio::spawn(async move { run_srtp_sender(&addr, opts, frame_rx, None).await });
+       for au in &aus {
+           frame_tx.send(au.clone()).await.unwrap();
        }
-       seq = seq.wrapping_add(1);
 
+       for au in &aus {
+           let got = tokio::time::timeout(Duration::from_secs(5), au_rx.recv()).await.unwrap().unwrap();
+           assert_eq!(&got, au);
+       }
+       let log = std::fs::read_to_string(&key_log).unwrap();
+       let fields: Vec<&str> = log.split_whitespace().collect();
+       assert_eq!((fields.len(), fields[0].len(), fields[1].len()), (2, 8, 2 * 28));
+       #[cfg(unix)]
+       {
+           use std::os::unix::fs::PermissionsExt;
+           assert_eq!(std::fs::metadata(&key_log).unwrap().permissions().mode() & 0o777, 0o600);
+       }
+
+       drop(frame_tx);
+       sender.await.unwrap().unwrap();
+       receiver.abort();
+       let _ = std::fs::remove_file(&key_log);
+   }
 
-       // Safety: you can force a rekey if seq gets big
-       if seq == u32::MAX / 2 {
-           eprintln!("[send] WARNING: nonce counter high; consider forcing rekey soon");
+   #[tokio::test]
+   async fn rtp_mode_refuses_rekey_and_nal_protection() {
+       // refused before connecting, so nothing needs to listen here
+       for opts in [
+           SenderOptions { rekey_every: Some(Duration::from_secs(1)), ..Default::default() },
+           SenderOptions { rekey_every: Some(Duration::from_secs(1)), dh_every: Some(4), ..Default::default() },
+           SenderOptions { protect: NalProtection::IdrSlices, ..Default::default() },
+       ] {
+           let (_frame_tx, frame_rx) = mpsc::channel(1);
+           let err = run_srtp_sender("127.0.0.1:9", opts, frame_rx, None).await.unwrap_err();
+           assert!(err.to_string().contains("not supported in RTP mode"), "{err:#}");
        }
    }
 
+   #[tokio::test]
+   async fn rtp_mode_refuses_a_taken_ssrc_and_foreign_packets() {
+       let addr = TcpListener::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap();
+       let (au_tx, mut au_rx) = mpsc::channel(8);
+       let receiver = tokio::spawn(async move { run_srtp_receiver_to_channel(&addr.to_string(), au_tx, None).await });
+       tokio::time::sleep(Duration::from_millis(50)).await;
+
+       let ssrc = 0x5eed_0001;
+       let mut first = tokio::net::TcpStream::connect(addr).await.unwrap();
+       let mut sess = handshake_client(&mut first, KeyExchange::Ecdh, None).await.unwrap();
+       sess.announce_rtp(&mut first, ssrc).await.unwrap();
+
+       // A second session announcing the same SSRC is refused and leaves the first alone
+       let mut second = tokio::net::TcpStream::connect(addr).await.unwrap();
+       let mut other = handshake_client(&mut second, KeyExchange::Ecdh, None).await.unwrap();
+       let err = other.announce_rtp(&mut second, ssrc).await.unwrap_err();
+       assert!(err.to_string().contains("refused ssrc"), "{err:#}");
+       drop(second);
+       tokio::time::sleep(Duration::from_millis(50)).await;
+
+       let mut ctx = srtp::Context::new(&srtp::MasterKey::from_chain(&sess.tx));
+       let mut pay = rtp::H264Packetizer::new(ssrc, rtp::DEFAULT_PAYLOAD_TYPE, rtp::DEFAULT_MAX_PAYLOAD);
+       let mut send_from = |ip: &str, au: &[u8], ts: u32| {
+           let pkts: Vec<Vec<u8>> = pay.packetize(au, ts).iter().map(|p| ctx.protect(p).unwrap()).collect();
+           let ip = ip.to_string();
+           async move {
+               let udp = UdpSocket::bind((ip.as_str(), 0)).await.unwrap();
+               for p in pkts {
+                   udp.send_to(&p, addr).await.unwrap();
+               }
+           }
+       };
+       let (real, spoofed, next) = (vec![0, 0, 0, 1, 0x41, 1], vec![0, 0, 0, 1, 0x41, 2], vec![0, 0, 0, 1, 0x41, 3]);
+       send_from("127.0.0.1", &real, 1).await;
+       // correctly keyed, but not from the host that announced the stream
+       send_from("127.0.0.2", &spoofed, 2).await;
+       send_from("127.0.0.1", &next, 3).await;
+       for want in [real, next] {
+           assert_eq!(tokio::time::timeout(Duration::from_secs(5), au_rx.recv()).await.unwrap().unwrap(), want);
+       }
 
-   eprintln!("[send] done");
-   Ok(())
+       receiver.abort();
+   }
 }
\ No newline at end of file
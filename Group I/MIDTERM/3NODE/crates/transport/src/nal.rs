@@ -0,0 +1,669 @@
+//! Selective encryption of H.264 access units.
+//!
+//! Instead of sealing the whole Annex-B access unit, only chosen NAL units (or
+//! parts of them) go through AES-GCM. Everything left in the clear — start codes,
+//! SPS/PPS/SEI, NAL headers and, in `SlicePayloads` mode, slice headers — is
+//! bound into the same tag as AAD, so tampering with it is still detected.
+//!
+//! Slice headers are found by parsing just enough of the SPS/PPS seen earlier in
+//! the stream. If that fails (unknown PPS, slice groups, truncated unit) the
+//! slice falls back to keeping only its one-byte NAL header in the clear.
+
+use crate::ratchet::{Chain, FrameHeader, KIND_DATA_SELECTIVE};
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+use std::ops::Range;
+
+/// GCM tag length
+const TAG_LEN: usize = 16;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NalProtection {
+    /// Whole access unit encrypted (KIND_DATA).
+    #[default]
+    Full,
+    /// IDR slices only; non-IDR slices are authenticated but readable.
+    IdrSlices,
+    /// Every slice NAL unit, NAL header byte left in the clear.
+    AllSlices,
+    /// Every slice, with NAL and slice headers left in the clear.
+    SlicePayloads,
+}
+
+impl NalProtection {
+    pub fn label(self) -> &'static str {
+        match self {
+            NalProtection::Full => "full",
+            NalProtection::IdrSlices => "idr",
+            NalProtection::AllSlices => "slices",
+            NalProtection::SlicePayloads => "payload",
+        }
+    }
+
+    /// Accepts the CLI spellings: full | idr | slices | payload
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "full" | "frame" => Some(NalProtection::Full),
+            "idr" => Some(NalProtection::IdrSlices),
+            "slices" | "slice" => Some(NalProtection::AllSlices),
+            "payload" | "payloads" => Some(NalProtection::SlicePayloads),
+            _ => None,
+        }
+    }
+}
+
+/// NAL units of an Annex-B access unit, as byte ranges starting at the NAL
+/// header (start codes and trailing zero bytes excluded).
+pub fn nal_units(au: &[u8]) -> Vec<Range<usize>> {
+    let mut starts = Vec::new();
+    let mut i = 0;
+    while i + 2 < au.len() {
+        if au[i] == 0 && au[i + 1] == 0 && au[i + 2] == 1 {
+            starts.push(i + 3);
+            i += 3;
+        } else {
+            i += 1;
+        }
+    }
+    let mut out = Vec::with_capacity(starts.len());
+    for (k, &s) in starts.iter().enumerate() {
+        let mut e = starts.get(k + 1).map_or(au.len(), |&n| n - 3);
+        while e > s && au[e - 1] == 0 {
+            e -= 1;
+        }
+        if e > s {
+            out.push(s..e);
+        }
+    }
+    out
+}
+
+/// Tracks SPS/PPS across access units and decides which bytes to encrypt.
+pub struct Selector {
+    mode: NalProtection,
+    sps: HashMap<u32, Sps>,
+    pps: HashMap<u32, Pps>,
+}
+
+impl Selector {
+    pub fn new(mode: NalProtection) -> Self {
+        Self { mode, sps: HashMap::new(), pps: HashMap::new() }
+    }
+
+    pub fn mode(&self) -> NalProtection {
+        self.mode
+    }
+
+    /// Byte ranges of `au` to encrypt, in order and non-overlapping.
+    pub fn ranges(&mut self, au: &[u8]) -> Vec<Range<usize>> {
+        if self.mode == NalProtection::Full {
+            return std::iter::once(0..au.len()).collect();
+        }
+        let mut out = Vec::new();
+        for nal in nal_units(au) {
+            let unit = &au[nal.clone()];
+            let ty = unit[0] & 0x1f;
+            let clear = match (ty, self.mode) {
+                (7, _) => {
+                    if let Some(sps) = Sps::parse(unit) {
+                        self.sps.insert(sps.id, sps);
+                    }
+                    continue;
+                }
+                (8, _) => {
+                    if let Some(pps) = Pps::parse(unit) {
+                        self.pps.insert(pps.id, pps);
+                    }
+                    continue;
+                }
+                (1..=4, NalProtection::IdrSlices) => continue,
+                (1..=5, NalProtection::SlicePayloads) => self.slice_header_len(unit).unwrap_or(1),
+                (1..=5, _) => 1,
+                _ => continue,
+            };
+            if unit.len() > clear {
+                out.push(nal.start + clear..nal.end);
+            }
+        }
+        out
+    }
+
+    /// Bytes from the NAL header through the last byte holding slice-header bits.
+    fn slice_header_len(&self, unit: &[u8]) -> Option<usize> {
+        let ty = unit[0] & 0x1f;
+        if ty != 1 && ty != 5 {
+            return None; // data partitions: no full slice header to keep
+        }
+        let mut r = BitReader::new(&unit[1..]);
+        skip_slice_header(&mut r, unit[0], &self.sps, &self.pps)?;
+        Some(1 + r.bytes_touched())
+    }
+}
+
+/// Seal `au` with only `ranges` encrypted.
+///
+/// Body: ts(u64 BE) || n(u16 BE) || n × [off u32 BE, len u32 BE] || AU' || tag,
+/// where AU' is the access unit with each range replaced by its ciphertext.
+/// AAD: frame header fields || ts || range table || the clear bytes of the AU.
+pub fn seal_selective(chain: &mut Chain, ts_ns: u64, au: &[u8], ranges: &[Range<usize>]) -> Result<(FrameHeader, Vec<u8>)> {
+    let n = u16::try_from(ranges.len()).map_err(|_| anyhow!("too many NAL ranges"))?;
+    let mut prefix = Vec::with_capacity(10 + 8 * ranges.len());
+    prefix.extend_from_slice(&ts_ns.to_be_bytes());
+    prefix.extend_from_slice(&n.to_be_bytes());
+    for r in ranges {
+        let off = u32::try_from(r.start).map_err(|_| anyhow!("access unit too large"))?;
+        prefix.extend_from_slice(&off.to_be_bytes());
+        prefix.extend_from_slice(&(r.len() as u32).to_be_bytes());
+    }
+    let (secret, clear) = split(au, ranges);
+    let aad = [&prefix[..], &clear].concat();
+    let (mut hdr, ct) = chain.seal_aad(KIND_DATA_SELECTIVE, &secret, &aad)?;
+
+    let mut body = prefix;
+    let base = body.len();
+    body.extend_from_slice(au);
+    let mut at = 0;
+    for r in ranges {
+        body[base + r.start..base + r.end].copy_from_slice(&ct[at..at + r.len()]);
+        at += r.len();
+    }
+    body.extend_from_slice(&ct[at..]);
+    hdr.ct_len = u32::try_from(body.len()).map_err(|_| anyhow!("frame too large"))?;
+    Ok((hdr, body))
+}
+
+/// Open a KIND_DATA_SELECTIVE body. Returns ts(8) || AU, like a DATA frame.
+pub fn open_selective(chain: &mut Chain, hdr: &FrameHeader, body: &[u8]) -> Result<Vec<u8>> {
+    let short = || anyhow!("selective frame truncated");
+    let n = u16::from_be_bytes(body.get(8..10).ok_or_else(short)?.try_into().unwrap()) as usize;
+    let table_end = 10 + 8 * n;
+    if body.len() < table_end + TAG_LEN {
+        return Err(short());
+    }
+    let (prefix, rest) = body.split_at(table_end);
+    let (au, tag) = rest.split_at(rest.len() - TAG_LEN);
+
+    let mut ranges = Vec::with_capacity(n);
+    let mut prev_end = 0;
+    for e in prefix[10..].chunks_exact(8) {
+        let off = u32::from_be_bytes(e[..4].try_into().unwrap()) as usize;
+        let len = u32::from_be_bytes(e[4..].try_into().unwrap()) as usize;
+        let end = off.checked_add(len).filter(|&end| off >= prev_end && end <= au.len());
+        let end = end.ok_or_else(|| anyhow!("bad NAL range {off}+{len}"))?;
+        ranges.push(off..end);
+        prev_end = end;
+    }
+    let (mut ct, clear) = split(au, &ranges);
+    ct.extend_from_slice(tag);
+    let aad = [prefix, &clear].concat();
+    let secret = chain.open_aad(hdr, &ct, &aad)?;
+
+    let mut pt = Vec::with_capacity(8 + au.len());
+    pt.extend_from_slice(&prefix[..8]);
+    pt.extend_from_slice(au);
+    let mut at = 0;
+    for r in &ranges {
+        pt[8 + r.start..8 + r.end].copy_from_slice(&secret[at..at + r.len()]);
+        at += r.len();
+    }
+    Ok(pt)
+}
+
+/// (bytes inside `ranges`, bytes outside), each concatenated in order.
+fn split(au: &[u8], ranges: &[Range<usize>]) -> (Vec<u8>, Vec<u8>) {
+    let inside: usize = ranges.iter().map(|r| r.len()).sum();
+    let mut secret = Vec::with_capacity(inside + TAG_LEN);
+    let mut clear = Vec::with_capacity(au.len() - inside);
+    let mut pos = 0;
+    for r in ranges {
+        clear.extend_from_slice(&au[pos..r.start]);
+        secret.extend_from_slice(&au[r.clone()]);
+        pos = r.end;
+    }
+    clear.extend_from_slice(&au[pos..]);
+    (secret, clear)
+}
+
+/// Exp-Golomb reader over escaped NAL bytes (emulation-prevention 0x03 skipped).
+struct BitReader<'a> {
+    data: &'a [u8],
+    /// next raw byte to load
+    pos: usize,
+    zeros: usize,
+    cur: u8,
+    bits_left: u8,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0, zeros: 0, cur: 0, bits_left: 0 }
+    }
+
+    fn bit(&mut self) -> Option<u32> {
+        if self.bits_left == 0 {
+            let mut b = *self.data.get(self.pos)?;
+            if self.zeros >= 2 && b == 3 {
+                self.pos += 1;
+                self.zeros = 0;
+                b = *self.data.get(self.pos)?;
+            }
+            self.zeros = if b == 0 { self.zeros + 1 } else { 0 };
+            self.pos += 1;
+            self.cur = b;
+            self.bits_left = 8;
+        }
+        self.bits_left -= 1;
+        Some(((self.cur >> self.bits_left) & 1) as u32)
+    }
+
+    fn flag(&mut self) -> Option<bool> {
+        Some(self.bit()? == 1)
+    }
+
+    fn u(&mut self, n: u32) -> Option<u32> {
+        let mut v = 0;
+        for _ in 0..n {
+            v = (v << 1) | self.bit()?;
+        }
+        Some(v)
+    }
+
+    fn ue(&mut self) -> Option<u32> {
+        let mut zeros = 0;
+        while self.bit()? == 0 {
+            zeros += 1;
+            if zeros > 31 {
+                return None;
+            }
+        }
+        Some(((1u64 << zeros) - 1 + self.u(zeros)? as u64) as u32)
+    }
+
+    fn se(&mut self) -> Option<i32> {
+        let k = self.ue()? as i64;
+        Some(if k % 2 == 1 { (k + 1) / 2 } else { -(k / 2) } as i32)
+    }
+
+    /// Raw bytes consumed so far, counting a partly read byte.
+    fn bytes_touched(&self) -> usize {
+        self.pos
+    }
+}
+
+/// The SPS fields a slice header depends on.
+struct Sps {
+    id: u32,
+    separate_colour_plane: bool,
+    chroma_array_type: u32,
+    log2_max_frame_num: u32,
+    poc_type: u32,
+    log2_max_poc_lsb: u32,
+    delta_pic_order_always_zero: bool,
+    frame_mbs_only: bool,
+}
+
+impl Sps {
+    fn parse(unit: &[u8]) -> Option<Self> {
+        let mut r = BitReader::new(unit.get(1..)?);
+        let profile = r.u(8)?;
+        r.u(16)?; // constraint flags, level
+        let id = r.ue()?;
+        let (mut chroma_format, mut separate_colour_plane) = (1, false);
+        if matches!(profile, 100 | 110 | 122 | 244 | 44 | 83 | 86 | 118 | 128 | 138 | 139 | 134 | 135) {
+            chroma_format = r.ue()?;
+            if chroma_format == 3 {
+                separate_colour_plane = r.flag()?;
+            }
+            r.ue()?; // bit_depth_luma_minus8
+            r.ue()?; // bit_depth_chroma_minus8
+            r.flag()?; // qpprime_y_zero_transform_bypass
+            if r.flag()? {
+                for i in 0..if chroma_format == 3 { 12 } else { 8 } {
+                    if r.flag()? {
+                        skip_scaling_list(&mut r, if i < 6 { 16 } else { 64 })?;
+                    }
+                }
+            }
+        }
+        let log2_max_frame_num = r.ue()? + 4;
+        let poc_type = r.ue()?;
+        let (mut log2_max_poc_lsb, mut delta_pic_order_always_zero) = (0, false);
+        match poc_type {
+            0 => log2_max_poc_lsb = r.ue()? + 4,
+            1 => {
+                delta_pic_order_always_zero = r.flag()?;
+                r.se()?; // offset_for_non_ref_pic
+                r.se()?; // offset_for_top_to_bottom_field
+                for _ in 0..r.ue()? {
+                    r.se()?;
+                }
+            }
+            _ => {}
+        }
+        r.ue()?; // max_num_ref_frames
+        r.flag()?; // gaps_in_frame_num_value_allowed
+        r.ue()?; // pic_width_in_mbs_minus1
+        r.ue()?; // pic_height_in_map_units_minus1
+        let frame_mbs_only = r.flag()?;
+        let chroma_array_type = if separate_colour_plane { 0 } else { chroma_format };
+        Some(Sps {
+            id,
+            separate_colour_plane,
+            chroma_array_type,
+            log2_max_frame_num,
+            poc_type,
+            log2_max_poc_lsb,
+            delta_pic_order_always_zero,
+            frame_mbs_only,
+        })
+    }
+}
+
+fn skip_scaling_list(r: &mut BitReader, size: usize) -> Option<()> {
+    let (mut last, mut next) = (8i32, 8i32);
+    for _ in 0..size {
+        if next != 0 {
+            next = (last + r.se()? + 256) % 256;
+        }
+        if next != 0 {
+            last = next;
+        }
+    }
+    Some(())
+}
+
+/// The PPS fields a slice header depends on.
+struct Pps {
+    id: u32,
+    sps_id: u32,
+    cabac: bool,
+    bottom_field_pic_order_present: bool,
+    num_ref_idx_l0_default: u32,
+    num_ref_idx_l1_default: u32,
+    weighted_pred: bool,
+    weighted_bipred_idc: u32,
+    deblocking_filter_control_present: bool,
+    redundant_pic_cnt_present: bool,
+}
+
+impl Pps {
+    fn parse(unit: &[u8]) -> Option<Self> {
+        let mut r = BitReader::new(unit.get(1..)?);
+        let id = r.ue()?;
+        let sps_id = r.ue()?;
+        let cabac = r.flag()?;
+        let bottom_field_pic_order_present = r.flag()?;
+        if r.ue()? != 0 {
+            return None; // slice groups (FMO) are not handled
+        }
+        let num_ref_idx_l0_default = r.ue()? + 1;
+        let num_ref_idx_l1_default = r.ue()? + 1;
+        let weighted_pred = r.flag()?;
+        let weighted_bipred_idc = r.u(2)?;
+        r.se()?; // pic_init_qp_minus26
+        r.se()?; // pic_init_qs_minus26
+        r.se()?; // chroma_qp_index_offset
+        let deblocking_filter_control_present = r.flag()?;
+        r.flag()?; // constrained_intra_pred
+        let redundant_pic_cnt_present = r.flag()?;
+        Some(Pps {
+            id,
+            sps_id,
+            cabac,
+            bottom_field_pic_order_present,
+            num_ref_idx_l0_default,
+            num_ref_idx_l1_default,
+            weighted_pred,
+            weighted_bipred_idc,
+            deblocking_filter_control_present,
+            redundant_pic_cnt_present,
+        })
+    }
+}
+
+/// Read past slice_header() (7.3.3), leaving `r` at its last bit.
+fn skip_slice_header(r: &mut BitReader, nal_header: u8, sps: &HashMap<u32, Sps>, pps: &HashMap<u32, Pps>) -> Option<()> {
+    let idr = nal_header & 0x1f == 5;
+    let nal_ref_idc = (nal_header >> 5) & 3;
+    r.ue()?; // first_mb_in_slice
+    let slice_type = r.ue()? % 5;
+    let (p, b, i, sp, si) = (slice_type == 0, slice_type == 1, slice_type == 2, slice_type == 3, slice_type == 4);
+    let pps = pps.get(&r.ue()?)?;
+    let sps = sps.get(&pps.sps_id)?;
+    if sps.separate_colour_plane {
+        r.u(2)?; // colour_plane_id
+    }
+    r.u(sps.log2_max_frame_num)?; // frame_num
+    let mut field_pic = false;
+    if !sps.frame_mbs_only {
+        field_pic = r.flag()?;
+        if field_pic {
+            r.flag()?; // bottom_field_flag
+        }
+    }
+    if idr {
+        r.ue()?; // idr_pic_id
+    }
+    if sps.poc_type == 0 {
+        r.u(sps.log2_max_poc_lsb)?;
+        if pps.bottom_field_pic_order_present && !field_pic {
+            r.se()?; // delta_pic_order_cnt_bottom
+        }
+    }
+    if sps.poc_type == 1 && !sps.delta_pic_order_always_zero {
+        r.se()?;
+        if pps.bottom_field_pic_order_present && !field_pic {
+            r.se()?;
+        }
+    }
+    if pps.redundant_pic_cnt_present {
+        r.ue()?;
+    }
+    if b {
+        r.flag()?; // direct_spatial_mv_pred
+    }
+    // field pictures default to twice the frame reference count
+    let scale = if field_pic { 2 } else { 1 };
+    let (mut l0, mut l1) = (pps.num_ref_idx_l0_default * scale, pps.num_ref_idx_l1_default * scale);
+    if (p || sp || b) && r.flag()? {
+        l0 = r.ue()? + 1;
+        if b {
+            l1 = r.ue()? + 1;
+        }
+    }
+    // ref_pic_list_modification()
+    if !i && !si {
+        for _ in 0..if b { 2 } else { 1 } {
+            if r.flag()? {
+                loop {
+                    match r.ue()? {
+                        0..=2 => {
+                            r.ue()?;
+                        }
+                        3 => break,
+                        _ => return None,
+                    }
+                }
+            }
+        }
+    }
+    if (pps.weighted_pred && (p || sp)) || (pps.weighted_bipred_idc == 1 && b) {
+        // pred_weight_table()
+        r.ue()?;
+        if sps.chroma_array_type != 0 {
+            r.ue()?;
+        }
+        for n in if b { [l0, l1] } else { [l0, 0] } {
+            for _ in 0..n {
+                if r.flag()? {
+                    r.se()?;
+                    r.se()?;
+                }
+                if sps.chroma_array_type != 0 && r.flag()? {
+                    for _ in 0..4 {
+                        r.se()?;
+                    }
+                }
+            }
+        }
+    }
+    if nal_ref_idc != 0 {
+        // dec_ref_pic_marking()
+        if idr {
+            r.flag()?;
+            r.flag()?;
+        } else if r.flag()? {
+            loop {
+                match r.ue()? {
+                    0 => break,
+                    1 | 2 | 6 => {
+                        r.ue()?;
+                    }
+                    3 => {
+                        r.ue()?;
+                        r.ue()?;
+                    }
+                    4 => {
+                        r.ue()?;
+                    }
+                    5 => {}
+                    _ => return None,
+                }
+            }
+        }
+    }
+    if pps.cabac && !i && !si {
+        r.ue()?; // cabac_init_idc
+    }
+    r.se()?; // slice_qp_delta
+    if sp || si {
+        if sp {
+            r.flag()?; // sp_for_switch_flag
+        }
+        r.se()?; // slice_qs_delta
+    }
+    if pps.deblocking_filter_control_present && r.ue()? != 1 {
+        r.se()?;
+        r.se()?;
+    }
+    Some(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct BitWriter {
+        bytes: Vec<u8>,
+        bits: u32,
+    }
+
+    impl BitWriter {
+        fn u(&mut self, n: u32, v: u32) -> &mut Self {
+            for i in (0..n).rev() {
+                if self.bits.is_multiple_of(8) {
+                    self.bytes.push(0);
+                }
+                *self.bytes.last_mut().unwrap() |= (((v >> i) & 1) as u8) << (7 - self.bits % 8);
+                self.bits += 1;
+            }
+            self
+        }
+        fn ue(&mut self, v: u32) -> &mut Self {
+            let n = 32 - (v + 1).leading_zeros();
+            self.u(n - 1, 0).u(n, v + 1)
+        }
+        /// rbsp_trailing_bits, then append to `au` behind a start code
+        fn nal(&mut self, header: u8, au: &mut Vec<u8>) {
+            self.u(1, 1);
+            au.extend_from_slice(&[0, 0, 0, 1, header]);
+            au.extend_from_slice(&self.bytes);
+        }
+    }
+
+    /// Baseline SPS/PPS, an IDR and a P slice (20- and 18-bit headers, 3 bytes each).
+    fn gop() -> (Vec<u8>, Vec<u8>) {
+        let mut idr = Vec::new();
+        BitWriter::default().u(8, 66).u(16, 30).ue(0).ue(0).ue(2).ue(1).u(1, 0).ue(19).ue(14).u(4, 0b1100).nal(0x67, &mut idr);
+        BitWriter::default().ue(0).ue(0).u(2, 0).ue(0).ue(0).ue(0).u(3, 0).ue(0).ue(0).ue(0).u(3, 0b100).nal(0x68, &mut idr);
+        let mut w = BitWriter::default();
+        w.ue(0).ue(7).ue(0).u(4, 0).ue(0).u(2, 0).ue(0).ue(0).ue(0).ue(0).u(4, 0b0101);
+        w.bytes.extend_from_slice(&[0xaa; 40]);
+        w.nal(0x65, &mut idr);
+        let mut p = Vec::new();
+        let mut w = BitWriter::default();
+        w.ue(0).ue(5).ue(0).u(4, 1).u(3, 0).ue(0).ue(1).u(6, 0b101010);
+        w.bytes.extend_from_slice(&[0x55; 20]);
+        w.nal(0x41, &mut p);
+        (idr, p)
+    }
+
+    #[test]
+    fn splits_annexb_with_both_start_code_lengths() {
+        let au = [0, 0, 0, 1, 0x67, 1, 2, 0, 0, 1, 0x68, 3, 0, 0, 0, 0, 1, 0x65, 4];
+        assert_eq!(nal_units(&au), vec![4..7, 10..12, 17..19]);
+        assert!(nal_units(&[0, 0, 1]).is_empty());
+    }
+
+    #[test]
+    fn reader_skips_emulation_prevention() {
+        let mut r = BitReader::new(&[0, 0, 3, 1, 0b0100_0000]);
+        assert_eq!(r.u(24), Some(1));
+        assert_eq!(r.bytes_touched(), 4);
+        assert_eq!(r.ue(), Some(1));
+        assert_eq!(r.bytes_touched(), 5);
+    }
+
+    #[test]
+    fn modes_select_the_documented_bytes() {
+        let (idr, p) = gop();
+        let slice_at = |au: &[u8]| nal_units(au).last().unwrap().clone();
+        let (s_idr, s_p) = (slice_at(&idr), slice_at(&p));
+
+        let mut sel = Selector::new(NalProtection::IdrSlices);
+        assert_eq!(sel.ranges(&idr), vec![s_idr.start + 1..s_idr.end]);
+        assert!(sel.ranges(&p).is_empty());
+
+        let mut sel = Selector::new(NalProtection::AllSlices);
+        sel.ranges(&idr);
+        assert_eq!(sel.ranges(&p), vec![s_p.start + 1..s_p.end]);
+
+        // NAL header + 3 slice-header bytes stay readable
+        let mut sel = Selector::new(NalProtection::SlicePayloads);
+        assert_eq!(sel.ranges(&p), vec![s_p.start + 1..s_p.end], "no PPS yet: only the NAL header is kept");
+        assert_eq!(sel.ranges(&idr), vec![s_idr.start + 4..s_idr.end]);
+        assert_eq!(sel.ranges(&p), vec![s_p.start + 4..s_p.end]);
+
+        assert_eq!(Selector::new(NalProtection::Full).ranges(&p), vec![0..p.len()]);
+    }
+
+    #[test]
+    fn selective_roundtrip_hides_ranges_and_authenticates_the_rest() {
+        let key = [9u8; 32];
+        let (mut tx, mut rx) = (Chain::new(key), Chain::new(key));
+        let (idr, _) = gop();
+        let mut sel = Selector::new(NalProtection::SlicePayloads);
+        let ranges = sel.ranges(&idr);
+        let (hdr, body) = seal_selective(&mut tx, 42, &idr, &ranges).unwrap();
+        assert_eq!(hdr.kind, KIND_DATA_SELECTIVE);
+        assert_eq!(hdr.ct_len as usize, body.len());
+
+        let base = 10 + 8 * ranges.len();
+        let r = &ranges[0];
+        assert_eq!(&body[base..base + r.start], &idr[..r.start], "SPS/PPS/headers in the clear");
+        assert_ne!(&body[base + r.start..base + r.end], &idr[r.clone()]);
+
+        let mut forged = body.clone();
+        forged[base + 5] ^= 0x01; // inside the SPS
+        assert!(open_selective(&mut Chain::new(key), &hdr, &forged).is_err());
+        let mut moved = body.clone();
+        moved[13] ^= 0x01; // range offset
+        assert!(open_selective(&mut Chain::new(key), &hdr, &moved).is_err());
+
+        let pt = open_selective(&mut rx, &hdr, &body).unwrap();
+        assert_eq!(&pt[..8], &42u64.to_be_bytes());
+        assert_eq!(&pt[8..], &idr[..]);
+    }
+}
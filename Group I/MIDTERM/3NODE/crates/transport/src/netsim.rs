@@ -0,0 +1,348 @@
+//! Deterministic network simulation of the TCP transports (turmoil).
+//!
+//! Sender, relay and receiver run as hosts of one seeded, simulated network,
+//! on the same code as `run_sender_from_channel`, `run_receiver_to_channel` and
+//! `run_multi_receiver_to_channel`; only the socket type differs. Every link
+//! has jittered latency, so segments arrive out of order and the simulated TCP
+//! puts them back in order. Partitions and resets are injected per test, and
+//! time is simulated, so timeouts and rekey intervals cost nothing to wait for.
+//!
+//! The relay logs the handshake keys and (kind, epoch, seq) of every frame it
+//! forwards. That is enough to check for key reuse: a connection's keys come from
+//! fresh ECDH key pairs, and within a connection each (epoch, seq) must be
+//! used once.
+
+use super::*;
+use rand::SeedableRng;
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use turmoil::net::{TcpListener as SimListener, TcpStream as SimStream};
+
+const PORT: u16 = 5000;
+const SEEDS: std::ops::Range<u64> = 0..4;
+
+impl Accept for SimListener {
+    type Stream = SimStream;
+    async fn accept(&self) -> std::io::Result<(SimStream, SocketAddr)> {
+        SimListener::accept(self).await
+    }
+}
+
+fn sim<'a>(seed: u64, max_latency: Duration) -> turmoil::Sim<'a> {
+    turmoil::Builder::new()
+        .simulation_duration(Duration::from_secs(120))
+        .min_message_latency(Duration::from_millis(1))
+        .max_message_latency(max_latency)
+        .build_with_rng(Box::new(rand::rngs::StdRng::seed_from_u64(seed)))
+}
+
+fn any_port() -> SocketAddr {
+    (Ipv4Addr::UNSPECIFIED, PORT).into()
+}
+
+/// Frame `i`: its number, padded to a typical P-frame size.
+fn frame(i: u32) -> Vec<u8> {
+    let mut f = i.to_be_bytes().to_vec();
+    f.resize(1200, i as u8);
+    f
+}
+
+fn frame_no(f: &[u8]) -> u32 {
+    u32::from_be_bytes(f[..4].try_into().unwrap())
+}
+
+/// `run_receiver_to_channel` as host "receiver"; frame numbers in delivery order.
+fn receiver_host(sim: &mut turmoil::Sim, delivered: Arc<Mutex<Vec<u32>>>) {
+    sim.host("receiver", move || {
+        let delivered = delivered.clone();
+        async move {
+            let listener = SimListener::bind(any_port()).await?;
+            let (tx, mut rx) = mpsc::channel::<Vec<u8>>(32);
+            tokio::spawn(async move {
+                while let Some(f) = rx.recv().await {
+                    delivered.lock().unwrap().push(frame_no(&f));
+                }
+            });
+            serve_receiver(listener, tx, None).await?;
+            Ok(())
+        }
+    });
+}
+
+/// The app's reconnecting sender (`spawn_peer_sender`): a fresh channel and
+/// connection to `to` per attempt, 2 s apart. Frames 0..n are produced at
+/// 30 fps; those produced while disconnected are lost. Returns each attempt's
+/// outcome once the last frame has had time to arrive.
+async fn send_frames(to: &'static str, opts: SenderOptions, n: u32) -> Vec<Result<()>> {
+    let slot: Arc<Mutex<Option<mpsc::Sender<Vec<u8>>>>> = Arc::default();
+    let producer = tokio::spawn({
+        let slot = slot.clone();
+        async move {
+            let mut tick = tokio::time::interval(Duration::from_millis(33));
+            for i in 0..n {
+                tick.tick().await;
+                if let Some(tx) = slot.lock().unwrap().as_ref() {
+                    let _ = tx.try_send(frame(i));
+                }
+            }
+            // closing the channel ends the sender cleanly
+            slot.lock().unwrap().take();
+        }
+    });
+
+    let mut outcomes = Vec::new();
+    loop {
+        let (tx, rx) = mpsc::channel(32);
+        {
+            let mut s = slot.lock().unwrap();
+            if producer.is_finished() {
+                break;
+            }
+            *s = Some(tx);
+        }
+        let res = async {
+            let sock = SimStream::connect((to, PORT)).await.context("connect")?;
+            run_sender_on(sock, to, opts.clone(), rx, None).await
+        }
+        .await;
+        let done = res.is_ok();
+        outcomes.push(res);
+        if done {
+            break;
+        }
+        tokio::time::sleep(Duration::from_secs(2)).await;
+    }
+    producer.await.unwrap();
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    outcomes
+}
+
+/// One relayed connection as seen on the wire.
+#[derive(Default)]
+struct Wire {
+    client_pub: Vec<u8>,
+    server_pub: Vec<u8>,
+    /// (kind, epoch, seq) per frame, in forwarding order
+    c2s: Vec<(u8, u32, u64)>,
+    s2c: Vec<(u8, u32, u64)>,
+}
+
+/// Host "relay": forwards each connection to the receiver, frame by frame
+/// (ECDH handshakes only). With `cut_after`, the first connection is reset right
+/// after a sender frame of that kind has been forwarded.
+fn relay_host(sim: &mut turmoil::Sim, wires: Arc<Mutex<Vec<Wire>>>, cut_after: Option<u8>) {
+    sim.host("relay", move || {
+        let wires = wires.clone();
+        async move {
+            let listener = SimListener::bind(any_port()).await?;
+            loop {
+                let (down, _) = listener.accept().await?;
+                let up = SimStream::connect(("receiver", PORT)).await?;
+                let conn = {
+                    let mut w = wires.lock().unwrap();
+                    w.push(Wire::default());
+                    w.len() - 1
+                };
+                let cut = cut_after.filter(|_| conn == 0);
+                tokio::spawn(relay_connection(down, up, wires.clone(), conn, cut));
+            }
+        }
+    });
+}
+
+async fn relay_connection(down: SimStream, up: SimStream, wires: Arc<Mutex<Vec<Wire>>>, conn: usize, cut: Option<u8>) {
+    let (mut dr, mut dw) = down.into_split();
+    let (mut ur, mut uw) = up.into_split();
+    let c2s = async {
+        let mut mech = [0u8; 1];
+        read_exact(&mut dr, &mut mech).await?;
+        write_all(&mut uw, &mech).await?;
+        let pk = read_blob(&mut dr).await?;
+        write_blob(&mut uw, &pk).await?;
+        wires.lock().unwrap()[conn].client_pub = pk;
+        forward_frames(&mut dr, &mut uw, |h| {
+            wires.lock().unwrap()[conn].c2s.push((h.kind, h.epoch, h.seq));
+            cut == Some(h.kind)
+        })
+        .await
+    };
+    let s2c = async {
+        let pk = read_blob(&mut ur).await?;
+        write_blob(&mut dw, &pk).await?;
+        wires.lock().unwrap()[conn].server_pub = pk;
+        // node key: empty, the receivers here run without an identity
+        let node = read_blob(&mut ur).await?;
+        write_blob(&mut dw, &node).await?;
+        forward_frames(&mut ur, &mut dw, |h| {
+            wires.lock().unwrap()[conn].s2c.push((h.kind, h.epoch, h.seq));
+            false
+        })
+        .await
+    };
+    // either direction ending drops all four halves
+    tokio::select! {
+        _ = c2s => {}
+        _ = s2c => {}
+    }
+}
+
+/// Copy frames from `r` to `w` until EOF, or until `seen` returns true.
+async fn forward_frames<R, W>(r: &mut R, w: &mut W, mut seen: impl FnMut(&FrameHeader) -> bool) -> Result<()>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    loop {
+        let (hdr, ct) = read_frame(r).await?;
+        write_frame(w, &hdr, &ct).await?;
+        if seen(&hdr) {
+            return Ok(());
+        }
+    }
+}
+
+/// Within one connection every (epoch, seq) is used once, in order: seq counts
+/// up from 0 in each epoch and the epoch only moves on after a REKEY frame.
+fn assert_nonces_fresh(frames: &[(u8, u32, u64)]) {
+    let mut want = (0u32, 0u64);
+    for &(kind, epoch, seq) in frames {
+        assert_eq!((epoch, seq), want, "frame kind {kind}");
+        want = if kind == KIND_REKEY { (epoch + 1, 0) } else { (epoch, seq + 1) };
+    }
+}
+
+/// Each connection ran its own ECDH, and no (key, nonce) pair repeats within one.
+fn assert_no_key_reuse(wires: &[Wire]) {
+    for (i, a) in wires.iter().enumerate() {
+        assert_eq!(a.client_pub.len(), 65);
+        assert_eq!(a.server_pub.len(), 65);
+        for b in &wires[i + 1..] {
+            assert_ne!(a.client_pub, b.client_pub);
+            assert_ne!(a.server_pub, b.server_pub);
+        }
+        assert_nonces_fresh(&a.c2s);
+        assert_nonces_fresh(&a.s2c);
+    }
+}
+
+fn rekeying() -> SenderOptions {
+    SenderOptions { rekey_every: Some(Duration::from_millis(200)), dh_every: Some(2), ..Default::default() }
+}
+
+#[test]
+fn handshake_times_out_on_partition_then_reconnects() {
+    for seed in SEEDS {
+        let mut sim = sim(seed, Duration::from_millis(25));
+        let delivered = Arc::new(Mutex::new(Vec::new()));
+        receiver_host(&mut sim, delivered.clone());
+        sim.client("sender", async move {
+            // the link fails right after the TCP connect, so the hello never arrives
+            let sock = SimStream::connect(("receiver", PORT)).await?;
+            turmoil::partition("sender", "receiver");
+            let started = Instant::now();
+            let (_frame_tx, frame_rx) = mpsc::channel(1);
+            let err = run_sender_on(sock, "receiver", SenderOptions::default(), frame_rx, None).await.unwrap_err();
+            assert!(format!("{err:#}").contains("handshake timed out"), "{err:#}");
+            assert_eq!(started.elapsed().as_secs(), HANDSHAKE_TIMEOUT.as_secs());
+
+            // the receiver's half-open session gave up as well
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            assert_eq!(turmoil::established_tcp_stream_count_on("receiver"), 0);
+
+            turmoil::repair("sender", "receiver");
+            let outcomes = send_frames("receiver", rekeying(), 60).await;
+            assert_eq!(outcomes.len(), 1);
+            assert!(outcomes[0].is_ok());
+            Ok(())
+        });
+        sim.run().unwrap();
+        assert_eq!(*delivered.lock().unwrap(), (0..60).collect::<Vec<u32>>(), "seed {seed}");
+    }
+}
+
+#[test]
+fn reset_mid_dh_rekey_reconnects_with_fresh_keys() {
+    for seed in SEEDS {
+        let mut sim = sim(seed, Duration::from_millis(25));
+        let delivered = Arc::new(Mutex::new(Vec::new()));
+        let wires = Arc::new(Mutex::new(Vec::new()));
+        receiver_host(&mut sim, delivered.clone());
+        // the DH offer reaches the receiver, its reply never reaches the sender
+        relay_host(&mut sim, wires.clone(), Some(KIND_DH_OFFER));
+        sim.client("sender", async move {
+            let outcomes = send_frames("relay", rekeying(), 150).await;
+            assert_eq!(outcomes.len(), 2, "one reconnect");
+            assert!(outcomes[0].is_err());
+            assert!(outcomes[1].is_ok());
+            Ok(())
+        });
+        sim.run().unwrap();
+
+        let wires = wires.lock().unwrap();
+        assert_eq!(wires.len(), 2, "seed {seed}");
+        assert_eq!(wires[0].c2s.last().map(|f| f.0), Some(KIND_DH_OFFER));
+        assert!(wires[0].s2c.iter().all(|f| f.0 != KIND_DH_REPLY));
+        // the second connection completes its own DH step and keeps rekeying
+        assert!(wires[1].s2c.iter().any(|f| f.0 == KIND_DH_REPLY));
+        assert!(wires[1].c2s.last().unwrap().1 >= 3);
+        assert_no_key_reuse(&wires);
+
+        // every data frame the relay forwarded was delivered once, in order
+        let delivered = delivered.lock().unwrap();
+        let forwarded = wires.iter().flat_map(|w| &w.c2s).filter(|f| f.0 == KIND_DATA).count();
+        assert_eq!(delivered.len(), forwarded, "seed {seed}");
+        assert!(delivered.windows(2).all(|p| p[0] < p[1]), "seed {seed}: {delivered:?}");
+        let first_conn = wires[0].c2s.iter().filter(|f| f.0 == KIND_DATA).count();
+        assert_eq!(delivered[..first_conn], (0..first_conn as u32).collect::<Vec<_>>()[..]);
+        assert_eq!(delivered.last(), Some(&149));
+    }
+}
+
+#[test]
+fn jittered_links_deliver_every_frame_through_relay_and_multi_receiver() {
+    for seed in SEEDS {
+        // up to 80 ms per segment: header and ciphertext writes overtake each other
+        let mut sim = sim(seed, Duration::from_millis(80));
+        let by_peer: Arc<Mutex<HashMap<String, Vec<u32>>>> = Arc::default();
+        let wires = Arc::new(Mutex::new(Vec::new()));
+        sim.host("receiver", {
+            let by_peer = by_peer.clone();
+            move || {
+                let by_peer = by_peer.clone();
+                async move {
+                    let listener = SimListener::bind(any_port()).await?;
+                    let handler = move |mut rx: mpsc::Receiver<Vec<u8>>, peer: String| {
+                        let by_peer = by_peer.clone();
+                        tokio::spawn(async move {
+                            while let Some(f) = rx.recv().await {
+                                by_peer.lock().unwrap().entry(peer.clone()).or_default().push(frame_no(&f));
+                            }
+                        })
+                    };
+                    serve_multi_receiver(listener, None, None, handler).await?;
+                    Ok(())
+                }
+            }
+        });
+        relay_host(&mut sim, wires.clone(), None);
+        for (name, to) in [("cam-a", "receiver"), ("cam-b", "relay")] {
+            sim.client(name, async move {
+                let outcomes = send_frames(to, rekeying(), 90).await;
+                assert!(outcomes.len() == 1 && outcomes[0].is_ok());
+                Ok(())
+            });
+        }
+        sim.run().unwrap();
+
+        let by_peer = by_peer.lock().unwrap();
+        assert_eq!(by_peer.len(), 2, "one playback channel per connection");
+        for (peer, got) in by_peer.iter() {
+            assert_eq!(*got, (0..90).collect::<Vec<u32>>(), "seed {seed}, {peer}");
+        }
+        let wires = wires.lock().unwrap();
+        assert_eq!(wires.len(), 1);
+        assert!(wires[0].c2s.last().unwrap().1 >= 10, "rekeys every 200 ms");
+        assert_no_key_reuse(&wires);
+    }
+}
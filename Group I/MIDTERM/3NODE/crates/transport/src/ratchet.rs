@@ -0,0 +1,266 @@
+//! Epoch-numbered symmetric ratchet for one direction of a session.
+//!
+//! Each direction starts from its own 32-byte chain key. Epoch `e` encrypts with
+//! a key/IV expanded from `ck_e`; moving to `e+1` replaces it with
+//! `ck_{e+1} = HKDF(ck_e)` (optionally mixed with a fresh ECDH secret) and the
+//! old chain key is zeroized, so leaking the current state exposes neither
+//! earlier epochs nor, after a DH step, later ones.
+
+use aead::AesGcmCtx;
+use anyhow::{anyhow, Result};
+use hkdf::Hkdf;
+use sha2::Sha256;
+use zeroize::Zeroize;
+
+pub const KIND_DATA: u8 = 0;
+/// Advance to the next epoch. Payload: [mix_dh u8].
+pub const KIND_REKEY: u8 = 1;
+/// Sender -> receiver: fresh ephemeral P-256 public key.
+pub const KIND_DH_OFFER: u8 = 2;
+/// Receiver -> sender: ephemeral public key answering a DH_OFFER.
+pub const KIND_DH_REPLY: u8 = 3;
+/// Receiver -> sender: a frame was lost or failed authentication; ask the
+/// encoder for an IDR. Payload: [reason u8] (see `KEYFRAME_*`).
+pub const KIND_KEYFRAME_REQ: u8 = 4;
+
+/// Sender -> receiver: H.264 access unit with only some NAL units encrypted;
+/// layout in `nal::seal_selective`.
+pub const KIND_DATA_SELECTIVE: u8 = 5;
+
+/// Sender -> receiver: media follows as SRTP over UDP, keyed by `Chain::export`
+/// of the c2s chain; the receiver echoes it once ready. Payload: [ssrc u32 BE].
+pub const KIND_RTP_STREAM: u8 = 6;
+
+/// KEYFRAME_REQ reasons
+pub const KEYFRAME_GAP: u8 = 1;
+pub const KEYFRAME_AUTH_FAIL: u8 = 2;
+
+/// Wire header: kind(1) || epoch(u32 BE) || seq(u64 BE) || ct_len(u32 BE)
+pub const HDR_LEN: usize = 1 + 4 + 8 + 4;
+/// Largest ciphertext a frame may carry. `ct_len` is read before anything is
+/// authenticated, so receivers refuse bigger lengths instead of allocating them.
+pub const MAX_CT_LEN: u32 = 16 << 20;
+const AAD_LEN: usize = 1 + 4 + 8;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FrameHeader {
+    pub kind: u8,
+    pub epoch: u32,
+    pub seq: u64,
+    pub ct_len: u32,
+}
+
+impl FrameHeader {
+    pub fn encode(&self) -> [u8; HDR_LEN] {
+        let mut h = [0u8; HDR_LEN];
+        h[0] = self.kind;
+        h[1..5].copy_from_slice(&self.epoch.to_be_bytes());
+        h[5..13].copy_from_slice(&self.seq.to_be_bytes());
+        h[13..17].copy_from_slice(&self.ct_len.to_be_bytes());
+        h
+    }
+
+    pub fn decode(h: &[u8; HDR_LEN]) -> Self {
+        Self {
+            kind: h[0],
+            epoch: u32::from_be_bytes(h[1..5].try_into().unwrap()),
+            seq: u64::from_be_bytes(h[5..13].try_into().unwrap()),
+            ct_len: u32::from_be_bytes(h[13..17].try_into().unwrap()),
+        }
+    }
+
+    /// kind || epoch || seq are authenticated; ct_len is implied by the tag check.
+    fn aad(&self) -> [u8; AAD_LEN] {
+        let mut a = [0u8; AAD_LEN];
+        a.copy_from_slice(&self.encode()[..AAD_LEN]);
+        a
+    }
+}
+
+/// Per-direction chain keys from the handshake secret.
+pub fn initial_chain_keys(shared: &[u8], salt: &[u8], info_c2s: &[u8], info_s2c: &[u8]) -> ([u8; 32], [u8; 32]) {
+    let hk = Hkdf::<Sha256>::new(Some(salt), shared);
+    let mut c2s = [0u8; 32];
+    let mut s2c = [0u8; 32];
+    hk.expand(info_c2s, &mut c2s).expect("HKDF expand c2s");
+    hk.expand(info_s2c, &mut s2c).expect("HKDF expand s2c");
+    (c2s, s2c)
+}
+
+pub struct Chain {
+    epoch: u32,
+    /// Next sequence to send, or lowest sequence still accepted when receiving.
+    seq: u64,
+    chain_key: [u8; 32],
+    ctx: AesGcmCtx,
+}
+
+impl Chain {
+    pub fn new(mut chain_key: [u8; 32]) -> Self {
+        let ctx = epoch_ctx(&chain_key);
+        let c = Self { epoch: 0, seq: 0, chain_key, ctx };
+        chain_key.zeroize();
+        c
+    }
+
+    pub fn epoch(&self) -> u32 {
+        self.epoch
+    }
+
+    pub fn seq(&self) -> u64 {
+        self.seq
+    }
+
+    /// Encrypt `pt` as the next frame of this epoch.
+    pub fn seal(&mut self, kind: u8, pt: &[u8]) -> Result<(FrameHeader, Vec<u8>)> {
+        self.seal_aad(kind, pt, &[])
+    }
+
+    /// `seal` with `extra` authenticated (but not encrypted) after the header fields.
+    pub fn seal_aad(&mut self, kind: u8, pt: &[u8], extra: &[u8]) -> Result<(FrameHeader, Vec<u8>)> {
+        if self.seq == u64::MAX {
+            return Err(anyhow!("sequence space exhausted in epoch {}; rekey required", self.epoch));
+        }
+        let mut hdr = FrameHeader { kind, epoch: self.epoch, seq: self.seq, ct_len: 0 };
+        let ct = self.ctx.encrypt(hdr.seq, pt, &[&hdr.aad()[..], extra].concat())?;
+        hdr.ct_len = u32::try_from(ct.len()).ok().filter(|&n| n <= MAX_CT_LEN).ok_or_else(|| anyhow!("frame too large"))?;
+        self.seq += 1;
+        Ok((hdr, ct))
+    }
+
+    /// Authenticate and decrypt a frame of the current epoch. Frames from another
+    /// epoch or with a sequence at or below one already accepted are refused.
+    pub fn open(&mut self, hdr: &FrameHeader, ct: &[u8]) -> Result<Vec<u8>> {
+        self.open_aad(hdr, ct, &[])
+    }
+
+    /// `open` for frames sealed with `seal_aad`.
+    pub fn open_aad(&mut self, hdr: &FrameHeader, ct: &[u8], extra: &[u8]) -> Result<Vec<u8>> {
+        if hdr.epoch != self.epoch {
+            return Err(anyhow!("frame for epoch {} while in epoch {}", hdr.epoch, self.epoch));
+        }
+        if hdr.seq < self.seq {
+            return Err(anyhow!("replayed or reordered seq {} (expected >= {})", hdr.seq, self.seq));
+        }
+        let pt = self.ctx.decrypt(hdr.seq, ct, &[&hdr.aad()[..], extra].concat())?;
+        self.seq = hdr.seq.saturating_add(1);
+        Ok(pt)
+    }
+
+    /// Key material for another protocol bound to this epoch (e.g. the SRTP
+    /// master key). Independent of the frame keys; both peers get the same bytes.
+    pub fn export(&self, label: &[u8], out: &mut [u8]) {
+        Hkdf::<Sha256>::from_prk(&self.chain_key)
+            .expect("32-byte chain key")
+            .expand(&[b"export:", label].concat(), out)
+            .expect("HKDF expand export");
+    }
+
+    /// Move to the next epoch: ck' = HKDF(ck) or, with a fresh ECDH secret,
+    /// ck' = HKDF(salt = ck, ikm = dh). The previous chain key is wiped.
+    pub fn advance(&mut self, dh: Option<&[u8]>) {
+        let mut next = [0u8; 32];
+        match dh {
+            Some(dh) => Hkdf::<Sha256>::new(Some(&self.chain_key), dh)
+                .expand(b"ratchet:dh", &mut next)
+                .expect("HKDF expand ratchet"),
+            None => Hkdf::<Sha256>::from_prk(&self.chain_key)
+                .expect("32-byte chain key")
+                .expand(b"ratchet:next", &mut next)
+                .expect("HKDF expand ratchet"),
+        }
+        self.chain_key.zeroize();
+        self.chain_key = next;
+        next.zeroize();
+        self.ctx = epoch_ctx(&self.chain_key);
+        self.epoch = self.epoch.wrapping_add(1);
+        self.seq = 0;
+    }
+}
+
+impl Drop for Chain {
+    fn drop(&mut self) {
+        self.chain_key.zeroize();
+    }
+}
+
+fn epoch_ctx(chain_key: &[u8; 32]) -> AesGcmCtx {
+    let hk = Hkdf::<Sha256>::from_prk(chain_key).expect("32-byte chain key");
+    let mut key = [0u8; 16];
+    let mut iv = [0u8; 12];
+    hk.expand(b"ctx:key", &mut key).expect("HKDF expand key");
+    hk.expand(b"ctx:iv", &mut iv).expect("HKDF expand iv");
+    let ctx = AesGcmCtx::new(key, iv);
+    key.zeroize();
+    ctx
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pair() -> (Chain, Chain) {
+        let (c2s, _) = initial_chain_keys(&[7u8; 32], b"salt", b"c2s", b"s2c");
+        (Chain::new(c2s), Chain::new(c2s))
+    }
+
+    #[test]
+    fn header_roundtrip_keeps_full_u64_seq() {
+        let h = FrameHeader { kind: KIND_DATA, epoch: 3, seq: u64::MAX - 1, ct_len: 1500 };
+        assert_eq!(FrameHeader::decode(&h.encode()), h);
+    }
+
+    #[test]
+    fn epochs_advance_in_lockstep() {
+        let (mut tx, mut rx) = pair();
+        for epoch in 0..4u32 {
+            for i in 0..3u8 {
+                let (h, ct) = tx.seal(KIND_DATA, &[i]).unwrap();
+                assert_eq!((h.epoch, h.seq), (epoch, i as u64));
+                assert_eq!(rx.open(&h, &ct).unwrap(), vec![i]);
+            }
+            let dh = (epoch % 2 == 1).then_some([epoch as u8; 32]);
+            tx.advance(dh.as_ref().map(|d| &d[..]));
+            rx.advance(dh.as_ref().map(|d| &d[..]));
+        }
+    }
+
+    #[test]
+    fn stale_epoch_and_replay_are_refused() {
+        let (mut tx, mut rx) = pair();
+        let (h0, ct0) = tx.seal(KIND_DATA, b"a").unwrap();
+        rx.open(&h0, &ct0).unwrap();
+        assert!(rx.open(&h0, &ct0).is_err(), "replay");
+
+        tx.advance(None);
+        rx.advance(None);
+        assert!(rx.open(&h0, &ct0).is_err(), "old epoch");
+
+        // Header fields are authenticated
+        let (mut h1, ct1) = tx.seal(KIND_DATA, b"b").unwrap();
+        h1.kind = KIND_REKEY;
+        assert!(rx.open(&h1, &ct1).is_err());
+    }
+
+    #[test]
+    fn old_epoch_keys_cannot_be_recomputed_from_current_state() {
+        let (mut tx, _) = pair();
+        let (h0, ct0) = tx.seal(KIND_DATA, b"epoch0").unwrap();
+        tx.advance(None);
+
+        // An attacker holding the current chain key can only ratchet forward
+        let mut stolen = Chain::new(tx.chain_key);
+        assert!(stolen.open(&h0, &ct0).is_err());
+        stolen.epoch = 0;
+        assert!(stolen.open(&h0, &ct0).is_err(), "epoch-0 key is not derivable from ck_1");
+    }
+
+    #[test]
+    fn dh_step_diverges_from_plain_ratchet() {
+        let (mut a, mut b) = pair();
+        a.advance(None);
+        b.advance(Some(&[9u8; 32]));
+        let (h, ct) = a.seal(KIND_DATA, b"x").unwrap();
+        assert!(b.open(&h, &ct).is_err(), "state without the DH secret cannot follow a DH step");
+    }
+}
@@ -0,0 +1,327 @@
+//! RTP (RFC 3550) framing and the H.264 payload format (RFC 6184).
+//!
+//! Access units are sent in packetization-mode 1: a NAL unit that fits the
+//! payload budget goes as a single-NAL packet, a larger one as FU-A fragments.
+//! All packets of an access unit share its 90 kHz timestamp and the last one
+//! carries the marker bit. The depacketizer also accepts STAP-A, which other
+//! senders (e.g. rtph264pay) use for SPS/PPS.
+
+use crate::nal::nal_units;
+use anyhow::{anyhow, Result};
+
+pub const HDR_LEN: usize = 12;
+pub const H264_CLOCK_RATE: u32 = 90_000;
+/// First dynamic payload type, the usual choice for H.264
+pub const DEFAULT_PAYLOAD_TYPE: u8 = 96;
+/// Payload bytes per packet: with the RTP header, GCM tag and UDP/IPv4 headers
+/// this stays well below a 1500-byte Ethernet MTU.
+pub const DEFAULT_MAX_PAYLOAD: usize = 1200;
+
+const NAL_STAP_A: u8 = 24;
+const NAL_FU_A: u8 = 28;
+const FU_START: u8 = 0x80;
+const FU_END: u8 = 0x40;
+
+/// Fixed RTP header fields we send (V=2, no padding, extension or CSRCs).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RtpHeader {
+    pub marker: bool,
+    pub payload_type: u8,
+    pub seq: u16,
+    pub timestamp: u32,
+    pub ssrc: u32,
+}
+
+impl RtpHeader {
+    pub fn encode(&self) -> [u8; HDR_LEN] {
+        let mut h = [0u8; HDR_LEN];
+        h[0] = 2 << 6;
+        h[1] = (self.marker as u8) << 7 | (self.payload_type & 0x7f);
+        h[2..4].copy_from_slice(&self.seq.to_be_bytes());
+        h[4..8].copy_from_slice(&self.timestamp.to_be_bytes());
+        h[8..12].copy_from_slice(&self.ssrc.to_be_bytes());
+        h
+    }
+
+    /// Header of `pkt` and its full length (CSRC list and extension included),
+    /// i.e. the offset of the payload.
+    pub fn parse(pkt: &[u8]) -> Result<(Self, usize)> {
+        if pkt.len() < HDR_LEN {
+            return Err(anyhow!("RTP packet too short ({} bytes)", pkt.len()));
+        }
+        if pkt[0] >> 6 != 2 {
+            return Err(anyhow!("not RTP version 2"));
+        }
+        let mut len = HDR_LEN + 4 * (pkt[0] & 0x0f) as usize;
+        if pkt[0] & 0x10 != 0 {
+            let ext = pkt.get(len + 2..len + 4).ok_or_else(|| anyhow!("truncated RTP header extension"))?;
+            len += 4 + 4 * u16::from_be_bytes([ext[0], ext[1]]) as usize;
+        }
+        if pkt.len() < len {
+            return Err(anyhow!("truncated RTP header"));
+        }
+        let hdr = RtpHeader {
+            marker: pkt[1] & 0x80 != 0,
+            payload_type: pkt[1] & 0x7f,
+            seq: u16::from_be_bytes([pkt[2], pkt[3]]),
+            timestamp: u32::from_be_bytes(pkt[4..8].try_into().unwrap()),
+            ssrc: u32::from_be_bytes(pkt[8..12].try_into().unwrap()),
+        };
+        Ok((hdr, len))
+    }
+
+    /// Header and payload of a (decrypted) packet, padding removed.
+    pub fn split(pkt: &[u8]) -> Result<(Self, &[u8])> {
+        let (hdr, len) = Self::parse(pkt)?;
+        let mut end = pkt.len();
+        if pkt[0] & 0x20 != 0 {
+            let pad = pkt[end - 1] as usize;
+            if pad == 0 || end - len < pad {
+                return Err(anyhow!("bad RTP padding"));
+            }
+            end -= pad;
+        }
+        Ok((hdr, &pkt[len..end]))
+    }
+}
+
+/// Turns Annex-B access units into RTP packets of one stream.
+pub struct H264Packetizer {
+    ssrc: u32,
+    payload_type: u8,
+    seq: u16,
+    max_payload: usize,
+}
+
+impl H264Packetizer {
+    /// The first sequence number is random, as RFC 3550 recommends.
+    pub fn new(ssrc: u32, payload_type: u8, max_payload: usize) -> Self {
+        Self { ssrc, payload_type, seq: rand::random(), max_payload: max_payload.max(3) }
+    }
+
+    pub fn ssrc(&self) -> u32 {
+        self.ssrc
+    }
+
+    /// RTP packets (header || payload) for one access unit.
+    pub fn packetize(&mut self, au: &[u8], timestamp: u32) -> Vec<Vec<u8>> {
+        let mut payloads = Vec::new();
+        for r in nal_units(au) {
+            let unit = &au[r];
+            if unit.len() <= self.max_payload {
+                payloads.push(unit.to_vec());
+                continue;
+            }
+            // FU-A: indicator keeps F/NRI, type 28; FU header carries S/E and the type
+            let indicator = (unit[0] & 0xe0) | NAL_FU_A;
+            let chunks: Vec<&[u8]> = unit[1..].chunks(self.max_payload - 2).collect();
+            for (i, chunk) in chunks.iter().enumerate() {
+                let mut fu = unit[0] & 0x1f;
+                if i == 0 {
+                    fu |= FU_START;
+                }
+                if i + 1 == chunks.len() {
+                    fu |= FU_END;
+                }
+                let mut p = Vec::with_capacity(2 + chunk.len());
+                p.extend_from_slice(&[indicator, fu]);
+                p.extend_from_slice(chunk);
+                payloads.push(p);
+            }
+        }
+
+        let n = payloads.len();
+        payloads
+            .into_iter()
+            .enumerate()
+            .map(|(i, payload)| {
+                let hdr = RtpHeader {
+                    marker: i + 1 == n,
+                    payload_type: self.payload_type,
+                    seq: self.seq,
+                    timestamp,
+                    ssrc: self.ssrc,
+                };
+                self.seq = self.seq.wrapping_add(1);
+                let mut pkt = Vec::with_capacity(HDR_LEN + payload.len());
+                pkt.extend_from_slice(&hdr.encode());
+                pkt.extend_from_slice(&payload);
+                pkt
+            })
+            .collect()
+    }
+}
+
+/// Reassembles Annex-B access units (4-byte start codes) from RTP payloads.
+/// An access unit ends at the marker bit or, if that packet was lost, when the
+/// timestamp changes. A fragmented NAL unit missing a piece is dropped whole;
+/// the rest of its access unit is still delivered.
+#[derive(Default)]
+pub struct H264Depacketizer {
+    au: Vec<u8>,
+    timestamp: Option<u32>,
+    /// FU-A reassembly (NAL header first); None when not inside a fragment
+    fu: Option<Vec<u8>>,
+    last_seq: Option<u16>,
+    lost: u64,
+}
+
+impl H264Depacketizer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feed one packet in arrival order; returns the access units it completed
+    /// (usually none or one). Duplicates and late packets are ignored.
+    pub fn push(&mut self, hdr: &RtpHeader, payload: &[u8]) -> Vec<Vec<u8>> {
+        if let Some(last) = self.last_seq {
+            let d = hdr.seq.wrapping_sub(last);
+            if d == 0 || d >= 0x8000 {
+                return Vec::new();
+            }
+            if d > 1 {
+                self.lost += (d - 1) as u64;
+                self.fu = None;
+            }
+        }
+        self.last_seq = Some(hdr.seq);
+
+        let mut done = Vec::new();
+        if self.timestamp.is_some_and(|t| t != hdr.timestamp) {
+            self.fu = None;
+            done.extend(self.take_au());
+        }
+        self.timestamp = Some(hdr.timestamp);
+
+        match payload.first().map(|b| b & 0x1f) {
+            Some(1..=23) => self.push_nal(payload),
+            Some(NAL_STAP_A) => {
+                let mut rest = &payload[1..];
+                while rest.len() >= 2 {
+                    let n = u16::from_be_bytes([rest[0], rest[1]]) as usize;
+                    let Some(unit) = rest.get(2..2 + n) else { break };
+                    self.push_nal(unit);
+                    rest = &rest[2 + n..];
+                }
+            }
+            Some(NAL_FU_A) if payload.len() > 2 => {
+                let (indicator, fu) = (payload[0], payload[1]);
+                if fu & FU_START != 0 {
+                    self.fu = Some(vec![(indicator & 0xe0) | (fu & 0x1f)]);
+                }
+                if let Some(unit) = self.fu.as_mut() {
+                    unit.extend_from_slice(&payload[2..]);
+                }
+                if fu & FU_END != 0 {
+                    if let Some(unit) = self.fu.take() {
+                        self.push_nal(&unit);
+                    }
+                }
+            }
+            // empty, reserved, or a mode-2 type (STAP-B, MTAP, FU-B)
+            _ => {}
+        }
+
+        if hdr.marker {
+            self.fu = None;
+            self.timestamp = None;
+            done.extend(self.take_au());
+        }
+        done
+    }
+
+    /// Packets missing from the sequence since the last call.
+    pub fn take_lost(&mut self) -> u64 {
+        std::mem::take(&mut self.lost)
+    }
+
+    fn push_nal(&mut self, unit: &[u8]) {
+        self.au.extend_from_slice(&[0, 0, 0, 1]);
+        self.au.extend_from_slice(unit);
+    }
+
+    fn take_au(&mut self) -> Option<Vec<u8>> {
+        (!self.au.is_empty()).then(|| std::mem::take(&mut self.au))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn au(units: &[&[u8]]) -> Vec<u8> {
+        units.iter().flat_map(|u| [&[0, 0, 0, 1][..], u].concat()).collect()
+    }
+
+    #[test]
+    fn header_roundtrip_and_extension_length() {
+        let h = RtpHeader { marker: true, payload_type: 96, seq: 0xf17b, timestamp: 0x8041f8d3, ssrc: 0x5501a0b2 };
+        let mut pkt = h.encode().to_vec();
+        assert_eq!(pkt, [0x80, 0xe0, 0xf1, 0x7b, 0x80, 0x41, 0xf8, 0xd3, 0x55, 0x01, 0xa0, 0xb2]);
+        assert_eq!(RtpHeader::parse(&pkt).unwrap(), (h, HDR_LEN));
+
+        // X bit + one-word extension, then padding of 2
+        pkt[0] |= 0x10 | 0x20;
+        pkt.extend_from_slice(&[0xbe, 0xde, 0, 1, 1, 2, 3, 4, 0x65, 0xaa, 0, 2]);
+        assert_eq!(RtpHeader::parse(&pkt).unwrap().1, HDR_LEN + 8);
+        assert_eq!(RtpHeader::split(&pkt).unwrap().1, [0x65, 0xaa]);
+    }
+
+    #[test]
+    fn large_units_are_fragmented_and_reassembled() {
+        let sps = [0x67, 0x42, 0x00, 0x1f];
+        let pps = [0x68, 0xce, 0x3c, 0x80];
+        let idr: Vec<u8> = [0x65].into_iter().chain((0..2500).map(|i| (i % 251) as u8 + 1)).collect();
+        let frame = au(&[&sps, &pps, &idr]);
+
+        let mut pay = H264Packetizer::new(7, DEFAULT_PAYLOAD_TYPE, 1000);
+        let pkts = pay.packetize(&frame, 3000);
+        // SPS, PPS, then 2499 bytes of slice in 998-byte fragments
+        assert_eq!(pkts.len(), 2 + 3);
+        let hdrs: Vec<RtpHeader> = pkts.iter().map(|p| RtpHeader::parse(p).unwrap().0).collect();
+        assert!(hdrs.iter().all(|h| h.timestamp == 3000 && h.ssrc == 7));
+        assert_eq!(hdrs.iter().filter(|h| h.marker).count(), 1);
+        assert!(hdrs[4].marker);
+        assert_eq!(&pkts[2][HDR_LEN..HDR_LEN + 2], [0x60 | NAL_FU_A, FU_START | 5]);
+        assert_eq!(&pkts[4][HDR_LEN..HDR_LEN + 2], [0x60 | NAL_FU_A, FU_END | 5]);
+        assert!(pkts.iter().all(|p| p.len() <= HDR_LEN + 1000));
+
+        let mut depay = H264Depacketizer::new();
+        let mut out = Vec::new();
+        for p in &pkts {
+            let (h, payload) = RtpHeader::split(p).unwrap();
+            out.extend(depay.push(&h, payload));
+        }
+        assert_eq!(out, vec![frame]);
+        assert_eq!(depay.take_lost(), 0);
+    }
+
+    #[test]
+    fn loss_drops_the_broken_fragment_and_stap_a_is_accepted() {
+        let mut pay = H264Packetizer::new(1, DEFAULT_PAYLOAD_TYPE, 100);
+        let big: Vec<u8> = [0x41].into_iter().chain([0x9a; 250]).collect();
+        let small = [0x41, 0x9b, 0x01];
+        let first = pay.packetize(&au(&[&big, &small]), 0);
+        let second = pay.packetize(&au(&[&small, &small]), 3000);
+        let third = pay.packetize(&au(&[&small]), 6000);
+        assert_eq!((first.len(), second.len(), third.len()), (4, 2, 1));
+
+        // a middle fragment of the first AU and the marker packet of the second never arrive
+        let mut depay = H264Depacketizer::new();
+        let mut out = Vec::new();
+        for p in [&first[0], &first[2], &first[3], &second[0], &third[0]] {
+            let (h, payload) = RtpHeader::split(p).unwrap();
+            out.extend(depay.push(&h, payload));
+        }
+        assert_eq!(out, vec![au(&[&small]); 3]);
+        assert_eq!(depay.take_lost(), 2);
+
+        // STAP-A carrying SPS + PPS, then a single-NAL slice closing the AU
+        let stap = [NAL_STAP_A | 0x60, 0, 2, 0x67, 0x42, 0, 2, 0x68, 0xce];
+        let h = RtpHeader { marker: false, payload_type: 96, seq: 100, timestamp: 9, ssrc: 1 };
+        let mut depay = H264Depacketizer::new();
+        assert!(depay.push(&h, &stap).is_empty());
+        let h = RtpHeader { marker: true, seq: 101, ..h };
+        assert_eq!(depay.push(&h, &small), vec![au(&[&[0x67, 0x42], &[0x68, 0xce], &small])]);
+    }
+}
@@ -0,0 +1,295 @@
+//! SRTP with AEAD_AES_128_GCM (RFC 7714) for one RTP stream.
+//!
+//! The session key and salt come from a 16-byte master key and 12-byte master
+//! salt through the AES-CM key derivation of RFC 3711 §4.3 (key derivation rate
+//! 0), which RFC 7714 keeps for GCM. The master salt is zero-padded to the
+//! 112 bits that KDF expects, as libsrtp (and so GStreamer's srtpenc/srtpdec)
+//! does. Each packet is sealed with IV = (0x0000 || SSRC || ROC || SEQ) XOR
+//! salt and the whole RTP header as AAD; the 16-byte tag follows the payload.
+//! No MKI and no SRTCP.
+
+use crate::ratchet::Chain;
+use crate::rtp::RtpHeader;
+use aes_gcm::aead::{Aead, Payload};
+use aes_gcm::aes::cipher::{BlockEncrypt, KeyInit};
+use aes_gcm::aes::Aes128;
+use aes_gcm::{Aes128Gcm, Nonce};
+use anyhow::{anyhow, Result};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+use zeroize::Zeroize;
+
+pub const MASTER_KEY_LEN: usize = 16;
+pub const MASTER_SALT_LEN: usize = 12;
+pub const TAG_LEN: usize = 16;
+
+/// KDF labels (RFC 3711 §4.3.2); only the RTP ones are used
+const LABEL_RTP_ENCRYPTION: u8 = 0x00;
+const LABEL_RTP_SALT: u8 = 0x02;
+
+/// Replay window, in packets
+const REPLAY_WINDOW: u64 = 64;
+
+/// Master key and salt of one SRTP stream.
+pub struct MasterKey {
+    pub key: [u8; MASTER_KEY_LEN],
+    pub salt: [u8; MASTER_SALT_LEN],
+}
+
+impl MasterKey {
+    /// Both peers export the same master from their view of the c2s chain.
+    pub fn from_chain(chain: &Chain) -> Self {
+        let mut m = Self { key: [0; MASTER_KEY_LEN], salt: [0; MASTER_SALT_LEN] };
+        chain.export(b"srtp master key", &mut m.key);
+        chain.export(b"srtp master salt", &mut m.salt);
+        m
+    }
+
+    /// key || salt, the layout srtpdec expects in its `srtp-key` caps field.
+    pub fn to_bytes(&self) -> [u8; MASTER_KEY_LEN + MASTER_SALT_LEN] {
+        let mut out = [0u8; MASTER_KEY_LEN + MASTER_SALT_LEN];
+        out[..MASTER_KEY_LEN].copy_from_slice(&self.key);
+        out[MASTER_KEY_LEN..].copy_from_slice(&self.salt);
+        out
+    }
+}
+
+impl Drop for MasterKey {
+    fn drop(&mut self) {
+        self.key.zeroize();
+        self.salt.zeroize();
+    }
+}
+
+/// Append `ssrc key||salt` in hex to a key log file, one stream per line. A
+/// new file is created readable by the owner only.
+pub fn append_key_log(path: &Path, ssrc: u32, master: &MasterKey) -> Result<()> {
+    let hex: String = master.to_bytes().iter().map(|b| format!("{b:02x}")).collect();
+    let mut opts = OpenOptions::new();
+    opts.create(true).append(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut opts, 0o600);
+    let mut f = opts.open(path)?;
+    writeln!(f, "{ssrc:08x} {hex}")?;
+    Ok(())
+}
+
+/// AES-CM PRF of RFC 3711 §4.3.3 with r = 0: the keystream of AES-CTR keyed by
+/// the master key, starting at IV = (salt XOR label << 48) || 0x0000.
+pub fn derive(master_key: &[u8; MASTER_KEY_LEN], master_salt: &[u8], label: u8, out: &mut [u8]) {
+    let aes = Aes128::new(master_key.into());
+    let mut x = [0u8; 16];
+    x[..master_salt.len().min(14)].copy_from_slice(&master_salt[..master_salt.len().min(14)]);
+    x[7] ^= label;
+    for (i, chunk) in out.chunks_mut(16).enumerate() {
+        let mut block = x;
+        block[14..].copy_from_slice(&(i as u16).to_be_bytes());
+        let mut block = block.into();
+        aes.encrypt_block(&mut block);
+        chunk.copy_from_slice(&block[..chunk.len()]);
+    }
+    x.zeroize();
+}
+
+/// Crypto context for one SSRC, either direction. The packet index
+/// (ROC << 16 | SEQ) is tracked as in RFC 3711 Appendix A; the same index is
+/// never sealed twice, and one already opened (or older than the replay
+/// window) is refused.
+pub struct Context {
+    aead: Aes128Gcm,
+    salt: [u8; 12],
+    ssrc: Option<u32>,
+    /// Highest index processed so far
+    top: Option<u64>,
+    /// Bit i set: index top - i was processed
+    window: u64,
+}
+
+impl Context {
+    pub fn new(master: &MasterKey) -> Self {
+        let mut key = [0u8; 16];
+        let mut salt = [0u8; 12];
+        derive(&master.key, &master.salt, LABEL_RTP_ENCRYPTION, &mut key);
+        derive(&master.key, &master.salt, LABEL_RTP_SALT, &mut salt);
+        let ctx = Self::with_session_keys(&key, salt);
+        key.zeroize();
+        ctx
+    }
+
+    fn with_session_keys(key: &[u8; 16], salt: [u8; 12]) -> Self {
+        Self { aead: Aes128Gcm::new(key.into()), salt, ssrc: None, top: None, window: 0 }
+    }
+
+    /// Rollover counter of the highest packet index seen.
+    pub fn roc(&self) -> u32 {
+        self.top.map_or(0, |t| (t >> 16) as u32)
+    }
+
+    /// RTP packet -> SRTP packet (header || ciphertext || tag).
+    pub fn protect(&mut self, pkt: &[u8]) -> Result<Vec<u8>> {
+        let (hdr, hlen) = RtpHeader::parse(pkt)?;
+        let index = self.accept(&hdr)?;
+        let mut out = Vec::with_capacity(pkt.len() + TAG_LEN);
+        out.extend_from_slice(&pkt[..hlen]);
+        let ct = self
+            .aead
+            .encrypt(&self.iv(hdr.ssrc, index), Payload { msg: &pkt[hlen..], aad: &pkt[..hlen] })
+            .map_err(|_| anyhow!("SRTP encrypt failed"))?;
+        out.extend_from_slice(&ct);
+        self.commit(hdr.ssrc, index);
+        Ok(out)
+    }
+
+    /// SRTP packet -> RTP packet. Fails on a bad tag, a foreign SSRC or a replay.
+    pub fn unprotect(&mut self, pkt: &[u8]) -> Result<Vec<u8>> {
+        let (hdr, hlen) = RtpHeader::parse(pkt)?;
+        if pkt.len() < hlen + TAG_LEN {
+            return Err(anyhow!("SRTP packet too short ({} bytes)", pkt.len()));
+        }
+        let index = self.accept(&hdr)?;
+        let pt = self
+            .aead
+            .decrypt(&self.iv(hdr.ssrc, index), Payload { msg: &pkt[hlen..], aad: &pkt[..hlen] })
+            .map_err(|_| anyhow!("SRTP authentication failed (seq {})", hdr.seq))?;
+        self.commit(hdr.ssrc, index);
+        let mut out = Vec::with_capacity(hlen + pt.len());
+        out.extend_from_slice(&pkt[..hlen]);
+        out.extend_from_slice(&pt);
+        Ok(out)
+    }
+
+    fn iv(&self, ssrc: u32, index: u64) -> Nonce<aes_gcm::aes::cipher::consts::U12> {
+        let mut iv = [0u8; 12];
+        iv[2..6].copy_from_slice(&ssrc.to_be_bytes());
+        iv[6..12].copy_from_slice(&index.to_be_bytes()[2..]);
+        for (b, s) in iv.iter_mut().zip(&self.salt) {
+            *b ^= s;
+        }
+        iv.into()
+    }
+
+    /// Index of `hdr` (guessing the ROC from the highest index seen), checked
+    /// against the SSRC and replay window.
+    fn accept(&self, hdr: &RtpHeader) -> Result<u64> {
+        if self.ssrc.is_some_and(|s| s != hdr.ssrc) {
+            return Err(anyhow!("SSRC {:08x} does not belong to this context", hdr.ssrc));
+        }
+        let Some(top) = self.top else { return Ok(hdr.seq as u64) };
+        let (roc, s_l, seq) = ((top >> 16) as i64, (top & 0xffff) as i64, hdr.seq as i64);
+        let v = if s_l < 0x8000 {
+            if seq - s_l > 0x8000 { roc - 1 } else { roc }
+        } else if s_l - 0x8000 > seq {
+            roc + 1
+        } else {
+            roc
+        };
+        if !(0..=u32::MAX as i64).contains(&v) {
+            return Err(anyhow!("seq {} outside the rollover counter range", hdr.seq));
+        }
+        let index = (v as u64) << 16 | hdr.seq as u64;
+        if index <= top {
+            let age = top - index;
+            if age >= REPLAY_WINDOW {
+                return Err(anyhow!("seq {} is older than the replay window", hdr.seq));
+            }
+            if self.window >> age & 1 == 1 {
+                return Err(anyhow!("replayed seq {}", hdr.seq));
+            }
+        }
+        Ok(index)
+    }
+
+    fn commit(&mut self, ssrc: u32, index: u64) {
+        self.ssrc = Some(ssrc);
+        match self.top {
+            Some(top) if index <= top => self.window |= 1 << (top - index),
+            Some(top) => {
+                let shift = index - top;
+                self.window = if shift >= REPLAY_WINDOW { 1 } else { self.window << shift | 1 };
+                self.top = Some(index);
+            }
+            None => {
+                self.window = 1;
+                self.top = Some(index);
+            }
+        }
+    }
+}
+
+impl Drop for Context {
+    fn drop(&mut self) {
+        self.salt.zeroize();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(s: &str) -> Vec<u8> {
+        let s: String = s.split_whitespace().collect();
+        (0..s.len()).step_by(2).map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap()).collect()
+    }
+
+    #[test]
+    fn key_derivation_matches_rfc3711_b3() {
+        let key: [u8; 16] = hex("E1F97A0D3E018BE0D64FA32C06DE4139").try_into().unwrap();
+        let salt = hex("0EC675AD498AFEEBB6960B3AABE6");
+        let mut cipher_key = [0u8; 16];
+        let mut cipher_salt = [0u8; 14];
+        derive(&key, &salt, LABEL_RTP_ENCRYPTION, &mut cipher_key);
+        derive(&key, &salt, LABEL_RTP_SALT, &mut cipher_salt);
+        assert_eq!(cipher_key.to_vec(), hex("C61E7A93744F39EE10734AFE3FF7A087"));
+        assert_eq!(cipher_salt.to_vec(), hex("30CBBC08863D8C85D49DB34A9AE1"));
+    }
+
+    #[test]
+    fn gcm_packet_matches_rfc7714_16_1() {
+        let key: [u8; 16] = hex("000102030405060708090a0b0c0d0e0f").try_into().unwrap();
+        let salt: [u8; 12] = hex("517569642070726f2071756f").try_into().unwrap();
+        let rtp = hex(
+            "8040f17b 8041f8d3 5501a0b2 47616c6c 69612065 7374206f 6d6e6973 20646976
+             69736120 696e2070 61727465 73207472 6573",
+        );
+        let srtp = hex(
+            "8040f17b 8041f8d3 5501a0b2 f24de3a3 fb34de6c acba861c 9d7e4bca be633bd5
+             0d294e6f 42a5f47a 51c7d19b 36de3adf 8833899d 7f27beb1 6a9152cf 765ee439 0cce",
+        );
+        let mut tx = Context::with_session_keys(&key, salt);
+        assert_eq!(tx.protect(&rtp).unwrap(), srtp);
+        let mut rx = Context::with_session_keys(&key, salt);
+        assert_eq!(rx.unprotect(&srtp).unwrap(), rtp);
+    }
+
+    fn packet(seq: u16, payload: &[u8]) -> Vec<u8> {
+        let mut p = RtpHeader { marker: false, payload_type: 96, seq, timestamp: 0, ssrc: 0x1234 }.encode().to_vec();
+        p.extend_from_slice(payload);
+        p
+    }
+
+    #[test]
+    fn rollover_replay_and_tampering() {
+        let master = MasterKey { key: [1; 16], salt: [2; 12] };
+        let (mut tx, mut rx) = (Context::new(&master), Context::new(&master));
+        let seqs = [0xfffe, 0xffff, 0, 1];
+        let sent: Vec<Vec<u8>> = seqs.iter().map(|&seq| tx.protect(&packet(seq, &[seq as u8])).unwrap()).collect();
+        assert_eq!(tx.roc(), 1);
+        assert!(tx.protect(&packet(1, b"again")).is_err(), "an index is never sealed twice");
+
+        // reordered across the wrap: 0 and 1 are ROC 1, 0xffff still ROC 0
+        for i in [0, 2, 1, 3] {
+            assert_eq!(rx.unprotect(&sent[i]).unwrap(), packet(seqs[i], &[seqs[i] as u8]));
+        }
+        assert_eq!(rx.roc(), 1);
+        assert!(rx.unprotect(&sent[2]).is_err(), "replay");
+
+        let mut forged = tx.protect(&packet(2, b"x")).unwrap();
+        forged[12] ^= 1;
+        assert!(rx.unprotect(&forged).is_err());
+        // the header is authenticated too
+        let mut forged = tx.protect(&packet(3, b"y")).unwrap();
+        forged[1] ^= 0x80;
+        assert!(rx.unprotect(&forged).is_err());
+    }
+}
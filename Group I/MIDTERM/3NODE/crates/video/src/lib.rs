@@ -1,12 +1,16 @@
 use anyhow::{anyhow, Context, Result};
-use std::{sync::Once, time::Duration};
+use std::sync::{Arc, Mutex, Once};
+use std::time::{Duration, Instant};
 
 use gstreamer as gst;
 use gstreamer_app as gst_app;
+use gstreamer_video as gst_video;
 use gst::prelude::*;
 use gstreamer_app::prelude::*;
 use tokio::sync::mpsc;
 
+pub mod mosaic;
+
 static GST_INIT: Once = Once::new();
 fn ensure_gst() {
     GST_INIT.call_once(|| gst::init().expect("gstreamer init"));
@@ -124,6 +128,58 @@ pub fn start_h264_capture_v4l2(
     setup_appsink_pipeline(&desc, "v4l2cap")
 }
 
+/// Default spacing between forced IDRs: a burst of loss reports inside this
+/// window collapses into a single keyframe instead of an IDR storm.
+pub const KEYFRAME_MIN_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Forces an IDR out of a running capture pipeline (libcamera, v4l2 or file)
+/// by sending a GstForceKeyUnit event from its appsink up to the encoder.
+#[derive(Clone)]
+pub struct KeyframeRequester {
+    sink: gst::Element,
+    min_interval: Duration,
+    last: Arc<Mutex<Option<Instant>>>,
+}
+
+impl KeyframeRequester {
+    /// `pipeline` is one returned by the `start_h264_*` capture functions.
+    pub fn new(pipeline: &gst::Pipeline, min_interval: Duration) -> Result<Self> {
+        let sink = pipeline.by_name("sink").ok_or_else(|| anyhow!("appsink not found"))?;
+        Ok(Self { sink, min_interval, last: Arc::new(Mutex::new(None)) })
+    }
+
+    /// Ask for a keyframe (with SPS/PPS). Returns false when rate-limited or
+    /// when nothing upstream handled the event.
+    pub fn request(&self) -> bool {
+        {
+            let mut last = self.last.lock().unwrap();
+            let now = Instant::now();
+            if last.is_some_and(|t| now.duration_since(t) < self.min_interval) {
+                return false;
+            }
+            *last = Some(now);
+        }
+        let ev = gst_video::UpstreamForceKeyUnitEvent::builder().all_headers(true).build();
+        self.sink.send_event(ev)
+    }
+}
+
+/// True if an Annex-B access unit carries an IDR slice (NAL type 5).
+pub fn is_keyframe(au: &[u8]) -> bool {
+    let mut i = 0;
+    while i + 3 < au.len() {
+        if au[i] == 0 && au[i + 1] == 0 && au[i + 2] == 1 {
+            if au[i + 3] & 0x1f == 5 {
+                return true;
+            }
+            i += 3;
+        } else {
+            i += 1;
+        }
+    }
+    false
+}
+
 /// Playback: appsrc (H.264 byte-stream, AU-aligned) → parse → decode → autovideosink.
 /// Returns a Sender<Vec<u8>> you feed with decrypted H.264 access units.
 pub fn start_h264_playback(fps: i32) -> Result<(mpsc::Sender<Vec<u8>>, gst::Pipeline)> {
@@ -188,3 +244,162 @@ pub fn start_h264_from_file(path: &str) -> Result<(mpsc::Receiver<Vec<u8>>, gst:
     );
     setup_appsink_pipeline(&desc, "filecap")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn idr_detection_follows_nal_type() {
+        // SPS, PPS, IDR slice with 4- and 3-byte start codes
+        let idr = [0, 0, 0, 1, 0x67, 0x42, 0, 0, 0, 1, 0x68, 0xce, 0, 0, 1, 0x65, 0x88, 0x84];
+        let p_slice = [0, 0, 0, 1, 0x41, 0x9a, 0x02];
+        assert!(is_keyframe(&idr));
+        assert!(!is_keyframe(&p_slice));
+        assert!(!is_keyframe(&[0, 0, 1]));
+        assert!(!is_keyframe(&[]));
+    }
+
+    fn require(elems: &[&str]) {
+        ensure_gst();
+        let missing: Vec<_> = elems.iter().filter(|e| gst::ElementFactory::find(e).is_none()).collect();
+        assert!(missing.is_empty(), "GStreamer elements not installed: {missing:?}");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    #[ignore = "needs GStreamer with x264, matroska and libav; run with --ignored"]
+    async fn forced_keyframe_after_drop_beats_the_gop() {
+        require(&["videotestsrc", "x264enc", "h264parse", "matroskamux", "decodebin", "avdec_h264", "filesink"]);
+        // 10 s clip; re-encoding it through start_h264_from_file gives an IDR every 30 frames
+        let path = std::env::temp_dir().join(format!("kf_recovery_{}.mkv", std::process::id()));
+        let path = path.to_string_lossy().into_owned();
+        let clip = gst::parse::launch(&format!(
+            "videotestsrc num-buffers=300 ! video/x-raw,width=320,height=240,framerate=30/1 ! \
+             x264enc speed-preset=ultrafast ! h264parse ! matroskamux ! filesink location=\"{}\"",
+            escape_path(&path)
+        ))
+        .unwrap();
+        clip.set_state(gst::State::Playing).unwrap();
+        let bus = clip.bus().unwrap();
+        let done = bus.timed_pop_filtered(gst::ClockTime::from_seconds(30), &[gst::MessageType::Eos, gst::MessageType::Error]);
+        assert!(matches!(done.as_ref().map(|m| m.type_()), Some(gst::MessageType::Eos)), "writing test clip failed");
+        clip.set_state(gst::State::Null).unwrap();
+
+        let (mut rx, pipe) = start_h264_from_file(&path).unwrap();
+        let kf = KeyframeRequester::new(&pipe, Duration::from_millis(200)).unwrap();
+        let mut next = || {
+            let au = rx.blocking_recv();
+            au.expect("file stream ended before recovery")
+        };
+        let (frames, recovery) = tokio::task::block_in_place(|| {
+            // Sync to a scheduled IDR and move a few frames past it
+            while !is_keyframe(&next()) {}
+            for _ in 0..5 {
+                next();
+            }
+            // Induced drop: this AU never reaches the decoder, which now needs an IDR
+            let _dropped = next();
+            let asked = Instant::now();
+            assert!(kf.request(), "encoder did not accept the force-key-unit event");
+            assert!(!kf.request(), "second request inside the interval must be rate-limited");
+            let mut frames = 1;
+            while !is_keyframe(&next()) {
+                frames += 1;
+            }
+            (frames, asked.elapsed())
+        });
+        eprintln!("keyframe recovery: {frames} frame(s), {recovery:?}");
+        let _ = pipe.set_state(gst::State::Null);
+        let _ = std::fs::remove_file(&path);
+        // The next scheduled IDR is still 24 frames away
+        assert!(frames < 24, "no forced keyframe: waited {frames} frames");
+    }
+
+    /// Push `bufs` into appsrc "src" of `desc` (`step` apart) and collect what appsink "sink" gets.
+    fn run_through(desc: &str, bufs: &[Vec<u8>], step: gst::ClockTime) -> Vec<Vec<u8>> {
+        let (mut rx, pipe) = setup_appsink_pipeline(desc, "rtp-test").unwrap();
+        let src = pipe.by_name("src").unwrap().downcast::<gst_app::AppSrc>().unwrap();
+        for (i, b) in bufs.iter().enumerate() {
+            let mut buf = gst::Buffer::from_slice(b.clone());
+            buf.get_mut().unwrap().set_pts(step * i as u64);
+            src.push_buffer(buf).unwrap();
+        }
+        src.end_of_stream().unwrap();
+        let mut out = Vec::new();
+        while let Some(b) = rx.blocking_recv() {
+            out.push(b);
+        }
+        let _ = pipe.set_state(gst::State::Null);
+        out
+    }
+
+    /// NAL units of a stream, access unit delimiters left out.
+    fn nals(aus: &[Vec<u8>]) -> Vec<Vec<u8>> {
+        aus.iter()
+            .flat_map(|au| transport::nal::nal_units(au).into_iter().map(move |r| au[r].to_vec()))
+            .filter(|n| n[0] & 0x1f != 9)
+            .collect()
+    }
+
+    #[test]
+    #[ignore = "needs GStreamer with x264 and the rtp plugins; run with --ignored"]
+    fn rtp_packets_interoperate_with_gstreamer_h264_payloader() {
+        use transport::rtp::{H264Depacketizer, H264Packetizer, RtpHeader, DEFAULT_MAX_PAYLOAD, DEFAULT_PAYLOAD_TYPE};
+        use transport::srtp::{Context, MasterKey};
+
+        require(&["videotestsrc", "x264enc", "h264parse", "appsrc", "appsink", "rtph264depay", "rtph264pay"]);
+        let (mut rx, pipe) = setup_appsink_pipeline(
+            "videotestsrc num-buffers=30 pattern=ball ! video/x-raw,width=640,height=480,framerate=30/1 ! \
+             x264enc speed-preset=ultrafast tune=zerolatency key-int-max=15 ! h264parse config-interval=-1 ! \
+             video/x-h264,stream-format=byte-stream,alignment=au ! appsink name=sink sync=false",
+            "rtp-clip",
+        )
+        .unwrap();
+        let mut aus = Vec::new();
+        while let Some(au) = rx.blocking_recv() {
+            aus.push(au);
+        }
+        let _ = pipe.set_state(gst::State::Null);
+        assert_eq!(aus.len(), 30);
+
+        // ours -> SRTP -> ours -> rtph264depay
+        let master = MasterKey { key: [7; 16], salt: [9; 12] };
+        let (mut protect, mut unprotect) = (Context::new(&master), Context::new(&master));
+        let mut pay = H264Packetizer::new(0x5eed, DEFAULT_PAYLOAD_TYPE, DEFAULT_MAX_PAYLOAD);
+        let mut packets = Vec::new();
+        for (i, au) in aus.iter().enumerate() {
+            for pkt in pay.packetize(au, i as u32 * 3000) {
+                let srtp = protect.protect(&pkt).unwrap();
+                assert_ne!(srtp[12..pkt.len()], pkt[12..], "payload must be encrypted");
+                packets.push(unprotect.unprotect(&srtp).unwrap());
+            }
+        }
+        assert!(packets.len() > aus.len(), "IDR frames should need FU-A");
+        let depayed = run_through(
+            "appsrc name=src format=time \
+             caps=application/x-rtp,media=video,clock-rate=90000,encoding-name=H264,payload=96 ! \
+             rtph264depay ! video/x-h264,stream-format=byte-stream,alignment=au ! appsink name=sink sync=false",
+            &packets,
+            gst::ClockTime::from_mseconds(1),
+        );
+        assert_eq!(depayed.len(), aus.len());
+        assert_eq!(nals(&depayed), nals(&aus));
+
+        // rtph264pay (FU-A, STAP-A) -> ours
+        let payed = run_through(
+            "appsrc name=src format=time caps=video/x-h264,stream-format=byte-stream,alignment=au,framerate=30/1 ! \
+             rtph264pay mtu=1200 pt=96 ! appsink name=sink sync=false",
+            &aus,
+            gst::ClockTime::from_mseconds(33),
+        );
+        let mut depay = H264Depacketizer::new();
+        let mut ours = Vec::new();
+        for pkt in &payed {
+            let (hdr, payload) = RtpHeader::split(pkt).unwrap();
+            ours.extend(depay.push(&hdr, payload));
+        }
+        assert_eq!(depay.take_lost(), 0);
+        assert_eq!(ours.len(), aus.len());
+        assert_eq!(nals(&ours), nals(&aus));
+    }
+}
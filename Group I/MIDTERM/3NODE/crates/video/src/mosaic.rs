@@ -0,0 +1,423 @@
+//! Mosaic playback: decode every incoming H.264 stream and tile them into one
+//! output with the GStreamer `compositor` element.
+//!
+//! Each source gets its own `appsrc ! h264parse ! avdec_h264 ! textoverlay` branch
+//! linked to a compositor request pad, plus a small live "STALLED" badge pad that
+//! is made visible when the source has not delivered a frame for `stall_after`.
+//! The grid is recomputed whenever a source joins or leaves.
+
+use anyhow::{anyhow, Context, Result};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
+
+use gstreamer as gst;
+use gstreamer_app as gst_app;
+use gst::prelude::*;
+use tokio::sync::mpsc;
+
+use crate::{attach_bus_log, ensure_gst, escape_path};
+
+const BADGE_W: i32 = 120;
+const BADGE_H: i32 = 28;
+
+/// Where the composited picture goes.
+#[derive(Clone, Debug)]
+pub enum MosaicSink {
+    /// On-screen window (autovideosink).
+    Display,
+    /// Discard frames; for headless runs and tests.
+    Fake,
+    /// Re-encode to H.264 in a Matroska file.
+    File(String),
+}
+
+#[derive(Clone, Debug)]
+pub struct MosaicOptions {
+    pub width: i32,
+    pub height: i32,
+    pub fps: i32,
+    pub sink: MosaicSink,
+    /// A source with no frame for this long is flagged as stalled.
+    pub stall_after: Duration,
+}
+
+impl Default for MosaicOptions {
+    fn default() -> Self {
+        Self { width: 1280, height: 720, fps: 30, sink: MosaicSink::Display, stall_after: Duration::from_secs(2) }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub w: i32,
+    pub h: i32,
+}
+
+/// Near-square grid: ceil(sqrt(n)) columns, as many rows as needed.
+pub fn grid_layout(n: usize, width: i32, height: i32) -> Vec<Rect> {
+    if n == 0 {
+        return Vec::new();
+    }
+    let cols = (1..=n).find(|c| c * c >= n).unwrap_or(n);
+    let rows = n.div_ceil(cols);
+    let (tw, th) = (width / cols as i32, height / rows as i32);
+    (0..n)
+        .map(|i| Rect { x: (i % cols) as i32 * tw, y: (i / cols) as i32 * th, w: tw, h: th })
+        .collect()
+}
+
+/// Snapshot of one tile, for status output and tests.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TileInfo {
+    pub label: String,
+    pub rect: Rect,
+    pub stalled: bool,
+}
+
+struct Tile {
+    id: u64,
+    label: String,
+    video: gst::Bin,
+    video_pad: gst::Pad,
+    badge: gst::Bin,
+    badge_pad: gst::Pad,
+    last_frame: Instant,
+    stalled: bool,
+    rect: Rect,
+}
+
+struct Inner {
+    pipeline: gst::Pipeline,
+    mixer: gst::Element,
+    opts: MosaicOptions,
+    tiles: Mutex<Vec<Tile>>,
+    next_id: AtomicU64,
+    frames_out: Arc<AtomicU64>,
+}
+
+/// Cheap to clone; all clones drive the same pipeline.
+#[derive(Clone)]
+pub struct Mosaic {
+    inner: Arc<Inner>,
+}
+
+impl Mosaic {
+    /// Build and start the compositor pipeline. Must be called inside a Tokio runtime.
+    pub fn new(opts: MosaicOptions) -> Result<Self> {
+        ensure_gst();
+        let (w, h, fps) = (opts.width, opts.height, opts.fps.max(1));
+        let sink = match &opts.sink {
+            MosaicSink::Display => "autovideosink sync=false".to_string(),
+            MosaicSink::Fake => "fakesink sync=false".to_string(),
+            MosaicSink::File(path) => format!(
+                "x264enc tune=zerolatency speed-preset=ultrafast ! h264parse ! matroskamux ! filesink location=\"{}\"",
+                escape_path(path)
+            ),
+        };
+        // A live black background keeps the output running (and sized) with zero sources.
+        let desc = format!(
+            "videotestsrc is-live=true pattern=black ! video/x-raw,width={w},height={h},framerate={fps}/1 ! \
+             compositor name=mix background=black ! video/x-raw,width={w},height={h} ! videoconvert ! {sink}"
+        );
+        let pipeline = gst::parse::launch(&desc)
+            .context("parse_launch mosaic pipeline")?
+            .downcast::<gst::Pipeline>()
+            .map_err(|_| anyhow!("not a pipeline"))?;
+        attach_bus_log(&pipeline, "mosaic");
+
+        let mixer = pipeline.by_name("mix").ok_or_else(|| anyhow!("compositor not found"))?;
+        let frames_out = Arc::new(AtomicU64::new(0));
+        let counter = frames_out.clone();
+        mixer
+            .static_pad("src")
+            .ok_or_else(|| anyhow!("compositor src pad not found"))?
+            .add_probe(gst::PadProbeType::BUFFER, move |_, _| {
+                counter.fetch_add(1, Ordering::Relaxed);
+                gst::PadProbeReturn::Ok
+            });
+
+        pipeline.set_state(gst::State::Playing).context("mosaic set Playing")?;
+
+        let inner = Arc::new(Inner {
+            pipeline,
+            mixer,
+            opts,
+            tiles: Mutex::new(Vec::new()),
+            next_id: AtomicU64::new(0),
+            frames_out,
+        });
+        spawn_stall_watchdog(Arc::downgrade(&inner));
+        Ok(Self { inner })
+    }
+
+    /// Add a tile labelled `label`. Feed decrypted H.264 access units into the
+    /// returned Sender; dropping it removes the tile and re-tiles the rest.
+    pub fn add_source(&self, label: &str) -> Result<mpsc::Sender<Vec<u8>>> {
+        let inner = &self.inner;
+        let id = inner.next_id.fetch_add(1, Ordering::Relaxed);
+
+        let video = gst::parse::bin_from_description(
+            "appsrc name=src is-live=true format=time do-timestamp=true \
+             caps=video/x-h264,stream-format=byte-stream,alignment=au \
+             ! h264parse ! avdec_h264 ! videoconvert \
+             ! textoverlay name=label valignment=top halignment=left shaded-background=true font-desc=\"Sans 18\" \
+             ! queue",
+            true,
+        )
+        .context("build mosaic source branch")?;
+        video
+            .by_name("label")
+            .ok_or_else(|| anyhow!("textoverlay not found"))?
+            .set_property("text", label);
+        let appsrc = video
+            .by_name("src")
+            .ok_or_else(|| anyhow!("appsrc not found"))?
+            .downcast::<gst_app::AppSrc>()
+            .map_err(|_| anyhow!("appsrc cast failed"))?;
+
+        let badge = gst::parse::bin_from_description(
+            &format!(
+                "videotestsrc is-live=true pattern=solid-color foreground-color=0xffcc0000 \
+                 ! video/x-raw,width={BADGE_W},height={BADGE_H},framerate=5/1 \
+                 ! textoverlay text=STALLED valignment=center halignment=center font-desc=\"Sans Bold 12\""
+            ),
+            true,
+        )
+        .context("build stall badge")?;
+
+        let video_pad = link_to_mixer(&inner.pipeline, &inner.mixer, &video)?;
+        let badge_pad = link_to_mixer(&inner.pipeline, &inner.mixer, &badge)?;
+        video_pad.set_property("zorder", 1u32);
+        badge_pad.set_property("zorder", 2u32);
+        badge_pad.set_property("alpha", 0.0f64);
+
+        {
+            let mut tiles = inner.tiles.lock().unwrap();
+            tiles.push(Tile {
+                id,
+                label: label.to_string(),
+                video,
+                video_pad,
+                badge,
+                badge_pad,
+                last_frame: Instant::now(),
+                stalled: false,
+                rect: Rect { x: 0, y: 0, w: 0, h: 0 },
+            });
+            inner.relayout(&mut tiles);
+        }
+        eprintln!("[mosaic] + {label}");
+
+        let (tx, mut rx) = mpsc::channel::<Vec<u8>>(64);
+        let weak = Arc::downgrade(inner);
+        tokio::spawn(async move {
+            while let Some(bytes) = rx.recv().await {
+                let Some(inner) = weak.upgrade() else { return };
+                if let Some(t) = inner.tiles.lock().unwrap().iter_mut().find(|t| t.id == id) {
+                    t.last_frame = Instant::now();
+                }
+                let _ = appsrc.push_buffer(gst::Buffer::from_slice(bytes));
+            }
+            if let Some(inner) = weak.upgrade() {
+                inner.remove(id);
+            }
+        });
+        Ok(tx)
+    }
+
+    pub fn tiles(&self) -> Vec<TileInfo> {
+        self.inner
+            .tiles
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|t| TileInfo { label: t.label.clone(), rect: t.rect, stalled: t.stalled })
+            .collect()
+    }
+
+    /// Composited frames produced so far.
+    pub fn frames_out(&self) -> u64 {
+        self.inner.frames_out.load(Ordering::Relaxed)
+    }
+
+    /// Send EOS (so a file sink is finalized) and stop the pipeline.
+    pub fn finish(&self) -> Result<()> {
+        let p = &self.inner.pipeline;
+        p.send_event(gst::event::Eos::new());
+        if let Some(bus) = p.bus() {
+            let _ = bus.timed_pop_filtered(gst::ClockTime::from_seconds(5), &[gst::MessageType::Eos, gst::MessageType::Error]);
+        }
+        p.set_state(gst::State::Null).context("mosaic set Null")?;
+        Ok(())
+    }
+}
+
+fn link_to_mixer(pipeline: &gst::Pipeline, mixer: &gst::Element, bin: &gst::Bin) -> Result<gst::Pad> {
+    pipeline.add(bin).context("add branch to mosaic")?;
+    let sink = mixer.request_pad_simple("sink_%u").ok_or_else(|| anyhow!("compositor pad request failed"))?;
+    bin.static_pad("src")
+        .ok_or_else(|| anyhow!("branch has no src pad"))?
+        .link(&sink)
+        .map_err(|e| anyhow!("link branch to compositor: {e:?}"))?;
+    bin.sync_state_with_parent().context("start mosaic branch")?;
+    Ok(sink)
+}
+
+impl Inner {
+    fn relayout(&self, tiles: &mut [Tile]) {
+        let rects = grid_layout(tiles.len(), self.opts.width, self.opts.height);
+        for (t, r) in tiles.iter_mut().zip(rects) {
+            t.rect = r;
+            t.video_pad.set_property("xpos", r.x);
+            t.video_pad.set_property("ypos", r.y);
+            t.video_pad.set_property("width", r.w);
+            t.video_pad.set_property("height", r.h);
+            t.badge_pad.set_property("xpos", r.x + r.w - BADGE_W);
+            t.badge_pad.set_property("ypos", r.y + r.h - BADGE_H);
+        }
+    }
+
+    fn remove(&self, id: u64) {
+        let mut tiles = self.tiles.lock().unwrap();
+        let Some(i) = tiles.iter().position(|t| t.id == id) else { return };
+        let t = tiles.remove(i);
+        detach_branch(&self.mixer, t.video, t.video_pad);
+        detach_branch(&self.mixer, t.badge, t.badge_pad);
+        self.relayout(&mut tiles);
+        eprintln!("[mosaic] - {}", t.label);
+    }
+}
+
+/// Unlink `bin` from the compositor while the pipeline keeps playing. The
+/// teardown runs from an IDLE probe on the branch's src pad, so no buffer is in
+/// flight to the request pad when it goes; the bin itself is stopped and removed
+/// off the streaming thread.
+fn detach_branch(mixer: &gst::Element, bin: gst::Bin, sink: gst::Pad) {
+    let Some(src) = bin.static_pad("src") else { return };
+    let pending = Mutex::new(Some((mixer.clone(), bin, sink)));
+    src.add_probe(gst::PadProbeType::IDLE, move |src, _| {
+        let Some((mixer, bin, sink)) = pending.lock().unwrap().take() else { return gst::PadProbeReturn::Remove };
+        let _ = src.unlink(&sink);
+        mixer.release_request_pad(&sink);
+        mixer.call_async(move |mixer| {
+            let _ = bin.set_state(gst::State::Null);
+            if let Some(pipeline) = mixer.parent().and_then(|p| p.downcast::<gst::Bin>().ok()) {
+                let _ = pipeline.remove(&bin);
+            }
+        });
+        gst::PadProbeReturn::Remove
+    });
+}
+
+/// Flip each tile's STALLED badge on/off as frames stop and resume.
+fn spawn_stall_watchdog(weak: Weak<Inner>) {
+    tokio::spawn(async move {
+        let mut tick = tokio::time::interval(Duration::from_millis(100));
+        loop {
+            tick.tick().await;
+            let Some(inner) = weak.upgrade() else { return };
+            for t in inner.tiles.lock().unwrap().iter_mut() {
+                let stalled = t.last_frame.elapsed() > inner.opts.stall_after;
+                if stalled != t.stalled {
+                    t.stalled = stalled;
+                    t.badge_pad.set_property("alpha", if stalled { 1.0f64 } else { 0.0f64 });
+                    eprintln!("[mosaic] {} {}", t.label, if stalled { "stalled" } else { "resumed" });
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grid_adapts_to_source_count() {
+        assert!(grid_layout(0, 1280, 720).is_empty());
+        assert_eq!(grid_layout(1, 1280, 720), vec![Rect { x: 0, y: 0, w: 1280, h: 720 }]);
+        let two = grid_layout(2, 1280, 720);
+        assert_eq!(two[1], Rect { x: 640, y: 0, w: 640, h: 720 });
+        let three = grid_layout(3, 1280, 720);
+        assert_eq!(three[2], Rect { x: 0, y: 360, w: 640, h: 360 });
+        assert_eq!(grid_layout(5, 1200, 720)[4], Rect { x: 400, y: 360, w: 400, h: 360 });
+    }
+
+    fn require(elems: &[&str]) {
+        ensure_gst();
+        let missing: Vec<_> = elems.iter().filter(|e| gst::ElementFactory::find(e).is_none()).collect();
+        assert!(missing.is_empty(), "GStreamer elements not installed: {missing:?}");
+    }
+
+    async fn wait_for(what: &str, mut cond: impl FnMut() -> bool) {
+        for _ in 0..100 {
+            if cond() {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        panic!("timed out waiting for {what}");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    #[ignore = "needs GStreamer with compositor, x264, libav and pango; run with --ignored"]
+    async fn headless_mosaic_tiles_flags_stall_and_retiles() {
+        require(&["compositor", "x264enc", "avdec_h264", "textoverlay", "videotestsrc"]);
+        let opts = MosaicOptions { width: 640, height: 360, fps: 15, sink: MosaicSink::Fake, stall_after: Duration::from_millis(400) };
+        let mosaic = Mosaic::new(opts).unwrap();
+
+        // Test encoder standing in for a remote camera
+        let (mut enc_rx, enc_pipe) = crate::setup_appsink_pipeline(
+            "videotestsrc is-live=true ! video/x-raw,width=320,height=240,framerate=15/1 ! \
+             x264enc tune=zerolatency speed-preset=ultrafast key-int-max=15 ! h264parse config-interval=1 ! \
+             video/x-h264,stream-format=byte-stream,alignment=au ! appsink name=sink sync=false",
+            "test-enc",
+        )
+        .unwrap();
+
+        let a = mosaic.add_source("node-a").unwrap();
+        let b = mosaic.add_source("node-b").unwrap();
+        let tiles = mosaic.tiles();
+        assert_eq!(tiles.len(), 2);
+        assert_eq!(tiles[1].rect, Rect { x: 320, y: 0, w: 320, h: 360 });
+
+        // Feed A continuously; B gets a second of frames and then goes quiet
+        let (stop_tx, mut stop_rx) = tokio::sync::oneshot::channel::<()>();
+        let feeder = tokio::spawn(async move {
+            let start = Instant::now();
+            let mut b = Some(b);
+            loop {
+                tokio::select! {
+                    _ = &mut stop_rx => break,
+                    au = enc_rx.recv() => {
+                        let Some(au) = au else { break };
+                        let _ = a.send(au.clone()).await;
+                        if start.elapsed() < Duration::from_secs(1) {
+                            if let Some(b) = &b { let _ = b.send(au).await; }
+                        } else if start.elapsed() > Duration::from_secs(2) {
+                            b.take(); // B leaves
+                        }
+                    }
+                }
+            }
+        });
+
+        let m = mosaic.clone();
+        wait_for("composited output", || m.frames_out() > 5).await;
+        let m = mosaic.clone();
+        wait_for("node-b stall flag", || m.tiles().iter().any(|t| t.label == "node-b" && t.stalled)).await;
+        assert!(!mosaic.tiles().iter().any(|t| t.label == "node-a" && t.stalled));
+
+        let m = mosaic.clone();
+        wait_for("node-b removal", || m.tiles().len() == 1).await;
+        assert_eq!(mosaic.tiles()[0], TileInfo { label: "node-a".into(), rect: Rect { x: 0, y: 0, w: 640, h: 360 }, stalled: false });
+
+        let _ = stop_tx.send(());
+        feeder.await.unwrap();
+        let _ = enc_pipe.set_state(gst::State::Null);
+        mosaic.finish().unwrap();
+    }
+}